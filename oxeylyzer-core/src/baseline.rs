@@ -0,0 +1,164 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::csv::stats_columns;
+use crate::error::RusylyzerError;
+use crate::generate::{LayoutGeneration, LayoutStats};
+use crate::layout::FastLayout;
+use crate::utility::TotalScore;
+
+/// Name of the file in every layout directory that holds the baseline of its language.
+pub const BASELINE_FILE: &str = ".baseline.json";
+
+/// Score and stats of a population of generated layouts, to tell how a layout compares to what
+/// generation finds with the same weights. Stats are named like the columns of
+/// [`stats_columns`], with the score as the `score` column.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Baseline {
+    /// `LayoutGeneration::baseline_key` of the generation the layouts come from.
+    key: u64,
+    columns: Vec<String>,
+    /// Every column sorted from low to high, in the order of `columns`.
+    values: Vec<Vec<f64>>,
+}
+
+impl Baseline {
+    /// Baseline of `layouts`, which should all be generated by `gen`.
+    pub fn new(gen: &LayoutGeneration, layouts: &[FastLayout]) -> Self {
+        let mut columns = vec!["score".to_string()];
+        let mut values = vec![Vec::with_capacity(layouts.len())];
+
+        for layout in layouts.iter() {
+            let stats = stats_columns(&gen.get_layout_stats(layout));
+            if columns.len() == 1 {
+                columns.extend(stats.iter().map(|(name, _)| name.clone()));
+                values.resize(columns.len(), Vec::with_capacity(layouts.len()));
+            }
+
+            values[0].push(gen.score(layout));
+            for (column, (_, value)) in values[1..].iter_mut().zip(stats) {
+                column.push(value);
+            }
+        }
+        for column in values.iter_mut() {
+            column.sort_by_key(|&value| TotalScore(value));
+        }
+
+        Self {
+            key: gen.baseline_key(),
+            columns,
+            values,
+        }
+    }
+
+    /// Reads the baseline at `path`, or `None` if it doesn't exist, can't be read, or wasn't made
+    /// with `key` and `amount` layouts.
+    pub fn read<P: AsRef<Path>>(path: P, key: u64, amount: usize) -> Option<Self> {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Self>(&s).ok())
+            .filter(|baseline| baseline.key == key && baseline.len() == amount)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), RusylyzerError> {
+        let path = path.as_ref();
+        let json = serde_json::to_string(self)
+            .map_err(|e| RusylyzerError::io(path, std::io::Error::from(e)))?;
+        std::fs::write(path, json).map_err(|e| RusylyzerError::io(path, e))
+    }
+
+    /// Amount of layouts in the baseline.
+    pub fn len(&self) -> usize {
+        self.values.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn column(&self, name: &str) -> Option<&[f64]> {
+        let i = self.columns.iter().position(|column| column == name)?;
+        Some(&self.values[i])
+    }
+
+    /// Percentage of layouts in the baseline with a lower `column` than `value`, where layouts
+    /// with the same value count for half. Whether that is good depends on the stat: a score in
+    /// the 90th percentile is better than most, but so is an sfb in the 10th.
+    pub fn percentile(&self, column: &str, value: f64) -> Option<f64> {
+        let values = self.column(column)?;
+        if values.is_empty() {
+            return None;
+        }
+        let below = values.partition_point(|&v| v < value);
+        let equal = values[below..].partition_point(|&v| v <= value);
+
+        Some((below as f64 + equal as f64 / 2.0) / values.len() as f64 * 100.0)
+    }
+
+    /// Median of `column` in the baseline.
+    pub fn median(&self, column: &str) -> Option<f64> {
+        let values = self.column(column)?;
+        match values.len() {
+            0 => None,
+            len if len % 2 == 0 => Some((values[len / 2 - 1] + values[len / 2]) / 2.0),
+            len => Some(values[len / 2]),
+        }
+    }
+
+    /// Percentile of the score and every stat of a layout, in the order of the columns.
+    pub fn percentiles(&self, score: f64, stats: &LayoutStats) -> Vec<(String, f64, f64)> {
+        std::iter::once(("score".to_string(), score))
+            .chain(stats_columns(stats))
+            .filter_map(|(column, value)| {
+                let percentile = self.percentile(&column, value)?;
+                Some((column, value, percentile))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Layout;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn percentiles() {
+        let layouts = (0..5)
+            .map(|_| {
+                let mut layout = FastLayout::random(GEN.chars_for_generation);
+                layout.score = GEN.score(&layout);
+                layout
+            })
+            .collect::<Vec<_>>();
+        let baseline = Baseline::new(&GEN, &layouts);
+        assert_eq!(baseline.len(), 5);
+
+        let mut scores = layouts.iter().map(|l| l.score).collect::<Vec<_>>();
+        scores.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(baseline.percentile("score", scores[0]), Some(10.0));
+        assert_eq!(baseline.percentile("score", scores[4] + 1.0), Some(100.0));
+        assert_eq!(baseline.percentile("score", scores[0] - 1.0), Some(0.0));
+        assert_eq!(baseline.median("score"), Some(scores[2]));
+        assert_eq!(baseline.percentile("sfr", 0.0), None);
+
+        let stats = GEN.get_layout_stats(&layouts[0]);
+        let percentiles = baseline.percentiles(layouts[0].score, &stats);
+        assert_eq!(percentiles.len(), stats_columns(&stats).len() + 1);
+        assert!(percentiles.iter().all(|&(_, _, p)| p > 0.0 && p < 100.0));
+
+        let path = std::env::temp_dir().join("rusylyzer_baseline_test.json");
+        baseline.write(&path).unwrap();
+        let key = GEN.baseline_key();
+        assert_eq!(Baseline::read(&path, key, 5), Some(baseline));
+        assert_eq!(Baseline::read(&path, key, 6), None);
+        assert_eq!(Baseline::read(&path, key + 1, 5), None);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use serde::Deserialize;
+
+use crate::trigram_patterns::{Finger, FingerKind};
+use crate::utility::{is_left_hand, FingerAssignment, PosPair};
+
+/// Rows the two keys of a [`BigramPenaltyRule`] are on, relative to each other.
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RowRelation {
+    /// Both keys are on the same row.
+    Same,
+    /// The keys are on neighbouring rows.
+    Adjacent,
+    /// One key is on the top row and the other on the bottom row.
+    Skip,
+    #[default]
+    Any,
+}
+
+impl RowRelation {
+    fn matches(self, row1: usize, row2: usize) -> bool {
+        match self {
+            Self::Same => row1 == row2,
+            Self::Adjacent => row1.abs_diff(row2) == 1,
+            Self::Skip => row1.abs_diff(row2) == 2,
+            Self::Any => true,
+        }
+    }
+}
+
+/// A set of bigrams that is penalized, as it is written in `[bigram_penalties.<name>]` of the
+/// config. The positions are those of `fingers` pressed by the same hand on rows that match
+/// `rows`, together with every position in `pairs`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BigramPenaltyRule {
+    pub weight: f64,
+    #[serde(default)]
+    pub fingers: Option<[FingerKind; 2]>,
+    #[serde(default)]
+    pub rows: RowRelation,
+    #[serde(default)]
+    pub pairs: Vec<[usize; 2]>,
+}
+
+/// A [`BigramPenaltyRule`] turned into the positions it penalizes, like `scissor_indices` is for
+/// scissors. Bigrams on these positions count in both directions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BigramPenalty {
+    pub name: String,
+    pub weight: f64,
+    pub pairs: Vec<PosPair>,
+}
+
+impl BigramPenaltyRule {
+    /// The positions of the rule for `fingers`, without duplicates.
+    pub fn resolve(&self, name: &str, fingers: &FingerAssignment) -> Result<BigramPenalty, String> {
+        if self.weight < 0.0 {
+            return Err(format!(
+                "weight is {}, but it can't be negative",
+                self.weight
+            ));
+        }
+        if self.fingers.is_none() && self.pairs.is_empty() {
+            return Err("a penalty needs fingers, pairs or both".to_string());
+        }
+
+        let mut pairs = Vec::new();
+        if let Some([k1, k2]) = self.fingers {
+            let kind = |i: usize| FingerKind::from(Finger::from_usize(fingers[i]));
+            for i1 in 0..30 {
+                for i2 in (i1 + 1)..30 {
+                    let same_hand = is_left_hand(fingers[i1]) == is_left_hand(fingers[i2]);
+                    let kinds = (kind(i1), kind(i2));
+                    if same_hand
+                        && (kinds == (k1, k2) || kinds == (k2, k1))
+                        && self.rows.matches(i1 / 10, i2 / 10)
+                    {
+                        pairs.push(PosPair(i1, i2));
+                    }
+                }
+            }
+        }
+        for &[i1, i2] in self.pairs.iter() {
+            if i1 >= 30 || i2 >= 30 || i1 == i2 {
+                return Err(format!(
+                    "[{i1}, {i2}] has to be two different positions from 0 to 29"
+                ));
+            }
+            let pair = PosPair(i1.min(i2), i1.max(i2));
+            if !pairs.contains(&pair) {
+                pairs.push(pair);
+            }
+        }
+
+        Ok(BigramPenalty {
+            name: name.to_string(),
+            weight: self.weight,
+            pairs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::I_TO_COL;
+
+    #[test]
+    fn resolve_rules() {
+        let rule = |toml: &str| toml::from_str::<BigramPenaltyRule>(toml).unwrap();
+
+        let ring_pinky = rule("weight = 2.0\nfingers = [\"ring\", \"pinky\"]\nrows = \"same\"");
+        let penalty = ring_pinky.resolve("ring_pinky", &I_TO_COL).unwrap();
+        assert_eq!(
+            penalty.pairs,
+            vec![
+                PosPair(0, 1),
+                PosPair(8, 9),
+                PosPair(10, 11),
+                PosPair(18, 19),
+                PosPair(20, 21),
+                PosPair(28, 29)
+            ]
+        );
+
+        let with_pairs = rule(concat!(
+            "weight = 1.0\nfingers = [\"pinky\", \"ring\"]\n",
+            "rows = \"skip\"\npairs = [[21, 0], [3, 4]]"
+        ));
+        let penalty = with_pairs.resolve("pinky_ring", &I_TO_COL).unwrap();
+        assert_eq!(
+            penalty.pairs,
+            vec![
+                PosPair(0, 21),
+                PosPair(1, 20),
+                PosPair(8, 29),
+                PosPair(9, 28),
+                PosPair(3, 4)
+            ]
+        );
+
+        assert!(rule("weight = 1.0").resolve("none", &I_TO_COL).is_err());
+        assert!(rule("weight = -1.0\npairs = [[0, 1]]")
+            .resolve("negative", &I_TO_COL)
+            .is_err());
+        assert!(rule("weight = 1.0\npairs = [[0, 30]]")
+            .resolve("outside", &I_TO_COL)
+            .is_err());
+        assert!(toml::from_str::<BigramPenaltyRule>("weight = 1.0\nrow = \"same\"").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use std::cmp::Reverse;
+
+use crate::language_data::LanguageData;
+use crate::utility::TotalScore;
+
+/// What the language data of a corpus is made of, to check that it looks like the text it should
+/// be before generating layouts with it. Frequencies are fractions of the corpus.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CorpusStats {
+    /// The most common characters, with their frequency and the frequency of them together with
+    /// every more common character.
+    pub chars: Vec<(char, f64, f64)>,
+    pub bigrams: Vec<(String, f64)>,
+    pub trigrams: Vec<(String, f64)>,
+    /// Mutual information in bits between characters 1, 2, 3 and 4 apart, from the bigrams and
+    /// skipgrams. It drops quickly with the distance for prose, and stays high for text that
+    /// repeats itself, like a corpus with the same file in it many times.
+    pub skipgram_decay: [f64; 4],
+    /// Entropy in bits of the characters, bigrams and trigrams.
+    pub entropy: [f64; 3],
+}
+
+impl CorpusStats {
+    /// Stats of `data`, with the `amount` most common characters, bigrams and trigrams.
+    pub fn new(data: &LanguageData, amount: usize) -> Self {
+        let con = &data.convert_u8;
+        let len = data.characters.len();
+
+        let mut chars = data
+            .characters
+            .iter()
+            .enumerate()
+            .map(|(c, &f)| (con.from_single(c as u8), f))
+            .collect::<Vec<_>>();
+        chars.sort_by_key(|&(_, f)| Reverse(TotalScore(f)));
+        let mut coverage = 0.0;
+        let chars = chars
+            .into_iter()
+            .take(amount)
+            .map(|(c, f)| {
+                coverage += f;
+                (c, f, coverage)
+            })
+            .collect();
+
+        let mut bigrams = data
+            .bigrams
+            .iter()
+            .enumerate()
+            .filter(|(_, &f)| f > 0.0)
+            .map(|(i, &f)| (con.as_str(&[(i / len) as u8, (i % len) as u8]), f))
+            .collect::<Vec<_>>();
+        bigrams.sort_by_key(|&(_, f)| Reverse(TotalScore(f)));
+        bigrams.truncate(amount);
+
+        let trigrams = data
+            .trigrams
+            .iter()
+            .take(amount)
+            .map(|(trigram, f)| (con.as_str(trigram), *f))
+            .collect();
+
+        let skipgram_decay = [
+            &data.bigrams,
+            &data.skipgrams,
+            &data.skipgrams2,
+            &data.skipgrams3,
+        ]
+        .map(|pairs| mutual_information(pairs, len));
+
+        let entropy = [
+            entropy(data.characters.iter().copied()),
+            entropy(data.bigrams.iter().copied()),
+            entropy(data.trigrams.iter().map(|&(_, f)| f)),
+        ];
+
+        Self {
+            chars,
+            bigrams,
+            trigrams,
+            skipgram_decay,
+            entropy,
+        }
+    }
+}
+
+/// Entropy in bits of the distribution of `freqs`, which don't have to add up to 1.
+fn entropy<I: IntoIterator<Item = f64>>(freqs: I) -> f64 {
+    let freqs = freqs.into_iter().filter(|&f| f > 0.0).collect::<Vec<_>>();
+    let total = freqs.iter().sum::<f64>();
+    if total <= 0.0 {
+        return 0.0;
+    }
+
+    -freqs
+        .into_iter()
+        .map(|f| f / total * (f / total).log2())
+        .sum::<f64>()
+}
+
+/// Mutual information in bits between the first and second character of `pairs`, a table of
+/// `len` by `len` frequencies like the bigrams. It is 0 when the characters are independent.
+fn mutual_information(pairs: &[f64], len: usize) -> f64 {
+    let total = pairs.iter().sum::<f64>();
+    if total <= 0.0 || len == 0 {
+        return 0.0;
+    }
+
+    let mut firsts = vec![0.0; len];
+    let mut seconds = vec![0.0; len];
+    for (i, &f) in pairs.iter().enumerate().take(len * len) {
+        firsts[i / len] += f;
+        seconds[i % len] += f;
+    }
+
+    pairs
+        .iter()
+        .enumerate()
+        .take(len * len)
+        .filter(|(_, &f)| f > 0.0)
+        .map(|(i, &f)| f / total * (f * total / (firsts[i / len] * seconds[i % len])).log2())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::ApproxEq;
+
+    #[test]
+    fn information() {
+        assert!(entropy([0.25; 4]).approx_eq_dbg(2.0, 9));
+        assert!(entropy([3.0, 3.0, 0.0]).approx_eq_dbg(1.0, 9));
+        assert_eq!(entropy([0.0; 0]), 0.0);
+
+        assert!(mutual_information(&[0.25; 4], 2).approx_eq_dbg(0.0, 9));
+        assert!(mutual_information(&[0.5, 0.0, 0.0, 0.5], 2).approx_eq_dbg(1.0, 9));
+        assert_eq!(mutual_information(&[0.0; 4], 2), 0.0);
+    }
+
+    #[test]
+    fn english_corpus() {
+        let data = LanguageData::from_file("static/language_data", "english").unwrap();
+        let stats = CorpusStats::new(&data, 30);
+
+        assert_eq!(stats.chars.len(), 30);
+        assert_eq!(stats.bigrams.len(), 30);
+        assert_eq!(stats.trigrams.len(), 30);
+        assert_eq!(stats.chars[0].0, 'e');
+        assert!(stats
+            .chars
+            .windows(2)
+            .all(|w| w[0].1 >= w[1].1 && w[0].2 < w[1].2));
+        assert!(stats.chars[29].2 <= 1.0 + 1e-9);
+        assert!(stats.bigrams.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        let [chars, bigrams, trigrams] = stats.entropy;
+        assert!(chars > 3.0 && chars < 5.0, "{chars}");
+        assert!(chars < bigrams && bigrams < trigrams);
+        assert!(stats.skipgram_decay[0] > stats.skipgram_decay[3]);
+    }
+}
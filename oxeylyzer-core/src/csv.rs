@@ -0,0 +1,156 @@
+use std::fmt::Write;
+
+use crate::generate::LayoutStats;
+
+const FINGERS: [&str; 8] = ["lp", "lr", "lm", "li", "ri", "rm", "rr", "rp"];
+const ROWS: [&str; 4] = ["top", "home", "bottom", "across"];
+const HANDS: [&str; 2] = ["left", "right"];
+
+/// Every stat as a named column. Names are the same as in the `stats` schema, where arrays get a
+/// column for every finger, hand or row, like `finger_speed_lp` or `inrolls_by_row_home`.
+pub fn stats_columns(stats: &LayoutStats) -> Vec<(String, f64)> {
+    let ts = &stats.trigram_stats;
+    let mut res = Vec::new();
+
+    let mut push = |name: &str, value: f64| res.push((name.to_string(), value));
+    push("sfb", stats.sfb);
+    push("sfb_distance", stats.sfb_distance);
+    push("dsfb", stats.dsfb);
+    push("dsfb2", stats.dsfb2);
+    push("dsfb3", stats.dsfb3);
+    push("scissors", stats.scissors);
+    push("lsbs", stats.lsbs);
+    push("row_skips", stats.row_skips);
+    push("fspeed", stats.fspeed);
+    push("onehand_rolls", stats.onehand_rolls);
+    push("travel", stats.travel);
+    push("hand_usage_left", stats.hand_usage[0]);
+    push("hand_usage_right", stats.hand_usage[1]);
+    push("row_usage_top", stats.row_usage[0]);
+    push("row_usage_home", stats.row_usage[1]);
+    push("row_usage_bottom", stats.row_usage[2]);
+    push("hand_alternation", stats.hand_alternation);
+    push("shortcut_reach", stats.shortcut_reach);
+
+    push("alternates", ts.alternates);
+    push("alternates_sfs", ts.alternates_sfs);
+    push("inrolls", ts.inrolls);
+    push("outrolls", ts.outrolls);
+    push("adjacent_rolls", ts.adjacent_rolls);
+    push("onehands", ts.onehands);
+    push("redirects", ts.redirects);
+    push("redirects_sfs", ts.redirects_sfs);
+    push("bad_redirects", ts.bad_redirects);
+    push("bad_redirects_sfs", ts.bad_redirects_sfs);
+    push("sfbs", ts.sfbs);
+    push("bad_sfbs", ts.bad_sfbs);
+    push("sfts", ts.sfts);
+    push("other", ts.other);
+    push("invalid", ts.invalid);
+
+    let per_finger = [
+        ("sfb_per_finger", &stats.sfb_per_finger),
+        ("sfb_distance_per_finger", &stats.sfb_distance_per_finger),
+        ("finger_speed", &stats.finger_speed),
+    ];
+    for (name, values) in per_finger {
+        for (finger, value) in FINGERS.iter().zip(values) {
+            res.push((format!("{name}_{finger}"), *value));
+        }
+    }
+    for (name, values) in [
+        ("inrolls_by_row", &ts.inrolls_by_row),
+        ("outrolls_by_row", &ts.outrolls_by_row),
+    ] {
+        for (row, value) in ROWS.iter().zip(values) {
+            res.push((format!("{name}_{row}"), *value));
+        }
+    }
+    for (name, values) in [
+        ("inrolls_by_hand", &ts.inrolls_by_hand),
+        ("outrolls_by_hand", &ts.outrolls_by_hand),
+    ] {
+        for (hand, value) in HANDS.iter().zip(values) {
+            res.push((format!("{name}_{hand}"), *value));
+        }
+    }
+    for (name, value) in stats.bigram_penalties.iter() {
+        res.push((format!("bigram_penalties_{name}"), *value));
+    }
+    res
+}
+
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// A csv file with a row for every layout, holding its name, score and every stat from
+/// [`stats_columns`]. Stats are fractions like in the `stats` schema, not percentages.
+pub fn stats_csv<'a, I>(layouts: I) -> String
+where
+    I: IntoIterator<Item = (&'a str, f64, &'a LayoutStats)>,
+{
+    let mut res = String::new();
+
+    for (i, (name, score, stats)) in layouts.into_iter().enumerate() {
+        let columns = stats_columns(stats);
+
+        if i == 0 {
+            res.push_str("name,score");
+            for (column, _) in columns.iter() {
+                write!(res, ",{column}").unwrap();
+            }
+            res.push('\n');
+        }
+
+        write!(res, "{},{score}", escape(name)).unwrap();
+        for (_, value) in columns {
+            write!(res, ",{value}").unwrap();
+        }
+        res.push('\n');
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::LayoutGeneration;
+    use crate::layout::FastLayout;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn escapes_fields() {
+        assert_eq!(escape("semimak"), "semimak");
+        assert_eq!(escape("a,b"), "\"a,b\"");
+        assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn csv_rows() {
+        let layout = FastLayout::with_fingers(GEN.chars_for_generation, &GEN.fingers);
+        let stats = GEN.get_layout_stats(&layout);
+        let csv = stats_csv([("first", 1.5, &stats), ("second, again", -2.0, &stats)]);
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("name,score,sfb,sfb_distance,"));
+        assert!(lines[0].contains(",outrolls_by_row_across,inrolls_by_hand_left,"));
+        assert!(lines[0].contains(",outrolls_by_hand_left,outrolls_by_hand_right"));
+        assert!(lines[1].starts_with(&format!("first,1.5,{},", stats.sfb)));
+        assert!(lines[2].starts_with("\"second, again\",-2,"));
+
+        let columns = lines[0].split(',').count();
+        assert_eq!(columns, stats_columns(&stats).len() + 2);
+        assert_eq!(lines[1].split(',').count(), columns);
+
+        assert_eq!(stats_csv(Vec::<(&str, f64, &LayoutStats)>::new()), "");
+    }
+}
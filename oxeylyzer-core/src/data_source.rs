@@ -0,0 +1,223 @@
+use std::path::{Path, PathBuf};
+
+use fxhash::FxHashMap;
+
+use crate::error::RusylyzerError;
+use crate::score_cache::{ScoreCache, SCORE_CACHE_FILE};
+use crate::trigram_cache::TRIGRAM_CACHE_EXTENSION;
+use crate::utility::{is_kb_file, layout_name};
+
+/// Where the config, language data and layouts are read from. `FileSource` reads them from disk the
+/// way the repl does, and `MemorySource` keeps them in memory for tests or when embedding the
+/// analyzer somewhere without a filesystem.
+pub trait DataSource {
+    /// Contents of `config.toml`.
+    fn config(&self) -> Result<String, RusylyzerError>;
+
+    /// Language data of `language` as json, in the format `load` writes it in.
+    fn language_data(&self, language: &str) -> Result<String, RusylyzerError>;
+
+    /// Name and contents of every layout of `language`, in the format of a `.kb` file.
+    fn layouts(&self, language: &str) -> Result<Vec<(String, String)>, RusylyzerError>;
+
+    /// Scores of the layouts of `language` that were cached before. Without a cache, every layout
+    /// is scored again.
+    fn read_score_cache(&self, _language: &str, key: u64) -> ScoreCache {
+        ScoreCache::new(key)
+    }
+
+    /// Stores the scores of the layouts of `language`, which does nothing without a cache.
+    fn write_score_cache(
+        &self,
+        _language: &str,
+        _cache: &ScoreCache,
+    ) -> Result<(), RusylyzerError> {
+        Ok(())
+    }
+
+    /// Trigrams of `language` in the binary format of `trigram_cache::encode`, if they were cached
+    /// before. Without a cache, trigrams are always read from the json.
+    fn read_trigram_cache(&self, _language: &str) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Stores the trigrams of `language`, which does nothing without a cache.
+    fn write_trigram_cache(&self, _language: &str, _cache: &[u8]) -> Result<(), RusylyzerError> {
+        Ok(())
+    }
+}
+
+/// Reads everything from disk: language data from `language_data/<language>.json` and layouts from
+/// `layouts/<language>/*.kb` in a base directory like `static`, and the config from `config.toml`.
+#[derive(Clone, Debug)]
+pub struct FileSource {
+    pub config_path: PathBuf,
+    pub language_data_dir: PathBuf,
+    pub layouts_dir: PathBuf,
+}
+
+impl Default for FileSource {
+    fn default() -> Self {
+        Self::new("static")
+    }
+}
+
+impl FileSource {
+    pub fn new<P: AsRef<Path>>(base_path: P) -> Self {
+        Self {
+            config_path: PathBuf::from("config.toml"),
+            language_data_dir: base_path.as_ref().join("language_data"),
+            layouts_dir: base_path.as_ref().join("layouts"),
+        }
+    }
+
+    fn score_cache_path(&self, language: &str) -> PathBuf {
+        self.layouts_dir.join(language).join(SCORE_CACHE_FILE)
+    }
+
+    fn trigram_cache_path(&self, language: &str) -> PathBuf {
+        let file = format!("{}.{TRIGRAM_CACHE_EXTENSION}", language.to_lowercase());
+        self.language_data_dir.join(file)
+    }
+}
+
+impl DataSource for FileSource {
+    fn config(&self) -> Result<String, RusylyzerError> {
+        std::fs::read_to_string(&self.config_path)
+            .map_err(|e| RusylyzerError::io(&self.config_path, e))
+    }
+
+    fn language_data(&self, language: &str) -> Result<String, RusylyzerError> {
+        let path = self
+            .language_data_dir
+            .join(language.to_lowercase() + ".json");
+        std::fs::read_to_string(&path).map_err(|e| RusylyzerError::io(path, e))
+    }
+
+    /// Reads every `.kb` file in the directory of `language`, which is created if it doesn't exist.
+    fn layouts(&self, language: &str) -> Result<Vec<(String, String)>, RusylyzerError> {
+        let dir = self.layouts_dir.join(language);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => {
+                std::fs::create_dir(&dir).map_err(|e| RusylyzerError::io(&dir, e))?;
+                return Ok(Vec::new());
+            }
+        };
+
+        let mut res = Vec::new();
+        for entry in entries.flatten().filter(is_kb_file) {
+            if let Some(name) = layout_name(&entry) {
+                let path = entry.path();
+                let content =
+                    std::fs::read_to_string(&path).map_err(|e| RusylyzerError::io(&path, e))?;
+                res.push((name, content));
+            }
+        }
+        Ok(res)
+    }
+
+    fn read_score_cache(&self, language: &str, key: u64) -> ScoreCache {
+        ScoreCache::read(self.score_cache_path(language), key)
+    }
+
+    fn write_score_cache(&self, language: &str, cache: &ScoreCache) -> Result<(), RusylyzerError> {
+        cache.write(self.score_cache_path(language))
+    }
+
+    fn read_trigram_cache(&self, language: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.trigram_cache_path(language)).ok()
+    }
+
+    fn write_trigram_cache(&self, language: &str, cache: &[u8]) -> Result<(), RusylyzerError> {
+        let path = self.trigram_cache_path(language);
+        std::fs::write(&path, cache).map_err(|e| RusylyzerError::io(path, e))
+    }
+}
+
+/// Keeps everything in memory. Languages and layouts that were never added don't exist.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySource {
+    pub config: String,
+    /// Language data json by language.
+    pub language_data: FxHashMap<String, String>,
+    /// Name and `.kb` contents of the layouts of every language.
+    pub layouts: FxHashMap<String, Vec<(String, String)>>,
+}
+
+impl MemorySource {
+    pub fn new(config: String) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_language_data(&mut self, language: &str, json: String) {
+        self.language_data.insert(language.to_lowercase(), json);
+    }
+
+    pub fn add_layout(&mut self, language: &str, name: &str, layout: String) {
+        self.layouts
+            .entry(language.to_string())
+            .or_default()
+            .push((name.to_string(), layout));
+    }
+
+    fn missing(what: String) -> RusylyzerError {
+        RusylyzerError::io(what, std::io::Error::from(std::io::ErrorKind::NotFound))
+    }
+}
+
+impl DataSource for MemorySource {
+    fn config(&self) -> Result<String, RusylyzerError> {
+        Ok(self.config.clone())
+    }
+
+    fn language_data(&self, language: &str) -> Result<String, RusylyzerError> {
+        self.language_data
+            .get(&language.to_lowercase())
+            .cloned()
+            .ok_or_else(|| Self::missing(format!("language data of {language}")))
+    }
+
+    fn layouts(&self, language: &str) -> Result<Vec<(String, String)>, RusylyzerError> {
+        Ok(self.layouts.get(language).cloned().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::LayoutGeneration;
+    use crate::weights::Config;
+
+    #[test]
+    fn memory_source() {
+        let files = FileSource::default();
+        let mut source = MemorySource::new(files.config().unwrap());
+        source.add_language_data("english", files.language_data("english").unwrap());
+        source.add_layout(
+            "english",
+            "qwerty",
+            "# author: someone\nq w e r t y u i o p\na s d f g h j k l ;\nz x c v b n m , . /\n"
+                .to_string(),
+        );
+
+        let mut gen = LayoutGeneration::from_source("english", &source, None).unwrap();
+        let layouts = gen.load_layouts_from(&source, "english").unwrap();
+        let from_files = LayoutGeneration::new("english", "static", None).unwrap();
+
+        let qwerty = &layouts["qwerty"];
+        assert_eq!(qwerty.score, from_files.score(qwerty));
+        assert_eq!(
+            gen.layout_metadata["qwerty"].author.as_deref(),
+            Some("someone")
+        );
+        assert!(gen.load_layouts_from(&source, "german").unwrap().is_empty());
+
+        assert!(LayoutGeneration::from_source("german", &source, None).is_err());
+        let invalid = MemorySource::new("[weights]\nfspeed = -1.0\n".to_string());
+        assert!(Config::from_source(&invalid, None).is_err());
+    }
+}
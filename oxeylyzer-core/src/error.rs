@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Everything that can go wrong while loading configs, corpora and layouts. The messages are
+/// meant to be shown to the user as is, so they say what to do about it where possible.
+#[derive(Debug, Error)]
+pub enum RusylyzerError {
+    #[error("could not read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("config.toml is invalid: {0}")]
+    Config(String),
+
+    #[error("no usable language data for '{language}' ({reason}). Try 'load {language}' first")]
+    LanguageData { language: String, reason: String },
+
+    #[error("layout could not be parsed: {0}")]
+    LayoutParse(String),
+
+    #[error("'{0}' is not a bigram type. Use one of sfbs, skipgrams, skipgrams2 or skipgrams3")]
+    BigramType(String),
+
+    #[error(
+        "{layout} scores NaN because its {component} score is NaN. Check that the language data \
+         isn't empty and that every weight is a number"
+    )]
+    NanScore {
+        layout: String,
+        component: &'static str,
+    },
+
+    #[error("the external metric '{command}' failed, because {reason}")]
+    ExternalMetric { command: String, reason: String },
+}
+
+impl RusylyzerError {
+    pub fn io<P: Into<PathBuf>>(path: P, source: std::io::Error) -> Self {
+        Self::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::Mutex;
+
+use fxhash::FxHashMap;
+use serde::Deserialize;
+
+use crate::error::RusylyzerError;
+
+/// Layouts the scores of an external metric are remembered for, after which they are forgotten
+/// so generating for a long time doesn't keep taking memory.
+const CACHE_SIZE: usize = 1 << 20;
+
+/// `[external_metric]` of `config.toml`: a program that scores layouts, and how much its score
+/// counts.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ExternalMetricConfig {
+    /// The program followed by its arguments, or nothing to not use an external metric.
+    pub command: Vec<String>,
+    /// What the number the program returns is multiplied by before it is added to the score.
+    /// Negative weights make it a penalty.
+    pub weight: f64,
+}
+
+struct Scorer {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Drop for Scorer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A metric computed by another program, to try out metrics like learned models without changing
+/// the analyzer. The program is started once and kept running. For every layout it gets a line
+/// with its 30 keys row by row, and answers with a line with a number.
+pub struct ExternalMetric {
+    pub config: ExternalMetricConfig,
+    scorer: Mutex<Option<Scorer>>,
+    cache: Mutex<FxHashMap<String, f64>>,
+}
+
+impl ExternalMetric {
+    pub fn new(config: ExternalMetricConfig) -> Self {
+        Self {
+            config,
+            scorer: Mutex::new(None),
+            cache: Mutex::new(FxHashMap::default()),
+        }
+    }
+
+    /// Whether there is a program with a weight, so the metric counts towards the score.
+    pub fn is_enabled(&self) -> bool {
+        !self.config.command.is_empty() && self.config.weight != 0.0
+    }
+
+    /// Weighted score of `layout`, which is 0 without a program and NaN when the program fails.
+    /// `value` says why it failed.
+    pub fn score(&self, layout: &str) -> f64 {
+        if !self.is_enabled() {
+            return 0.0;
+        }
+        self.value(layout)
+            .map_or(f64::NAN, |value| value * self.config.weight)
+    }
+
+    /// The number the program returns for `layout`, before it is weighted. A program that fails
+    /// is started again for the next layout.
+    pub fn value(&self, layout: &str) -> Result<f64, RusylyzerError> {
+        if let Some(&value) = self.cache.lock().unwrap().get(layout) {
+            return Ok(value);
+        }
+
+        let mut scorer = self.scorer.lock().unwrap();
+        if scorer.is_none() {
+            *scorer = Some(self.start()?);
+        }
+        let value = Self::ask(scorer.as_mut().unwrap(), layout);
+        if value.is_err() {
+            *scorer = None;
+        }
+        let value = value.map_err(|reason| self.error(reason))?;
+        drop(scorer);
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(layout.to_string(), value);
+        Ok(value)
+    }
+
+    fn start(&self) -> Result<Scorer, RusylyzerError> {
+        let (program, args) = self
+            .config
+            .command
+            .split_first()
+            .ok_or_else(|| self.error("there is no command".to_string()))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.error(format!("it could not be started: {e}")))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(Scorer {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn ask(scorer: &mut Scorer, layout: &str) -> Result<f64, String> {
+        writeln!(scorer.stdin, "{layout}")
+            .and_then(|_| scorer.stdin.flush())
+            .map_err(|e| format!("it stopped reading layouts: {e}"))?;
+
+        let mut line = String::new();
+        match scorer.stdout.read_line(&mut line) {
+            Ok(0) => return Err("it stopped before answering".to_string()),
+            Ok(_) => {}
+            Err(e) => return Err(format!("its answer could not be read: {e}")),
+        }
+        line.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| format!("it answered '{}' instead of a number", line.trim()))
+    }
+
+    fn error(&self, reason: String) -> RusylyzerError {
+        RusylyzerError::ExternalMetric {
+            command: self.config.command.join(" "),
+            reason,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn metric(script: &str, weight: f64) -> ExternalMetric {
+        ExternalMetric::new(ExternalMetricConfig {
+            command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            weight,
+        })
+    }
+
+    #[test]
+    fn scores_from_a_program() {
+        // answers with the position of the first e, so layouts get different values
+        let positions = metric(r#"while read l; do p="${l%%e*}"; echo "${#p}"; done"#, -0.5);
+        let qwerty = "qwertyuiopasdfghjkl;zxcvbnm,./";
+        assert_eq!(positions.value(qwerty).unwrap(), 2.0);
+        assert_eq!(positions.score(qwerty), -1.0);
+        assert_eq!(positions.score("eqwrtyuiopasdfghjkl;zxcvbnm,./"), 0.0);
+        assert_eq!(positions.score(qwerty), -1.0);
+
+        let unweighted = metric("while read l; do echo 1; done", 0.0);
+        assert!(!unweighted.is_enabled());
+        assert_eq!(unweighted.score(qwerty), 0.0);
+        assert_eq!(
+            ExternalMetric::new(ExternalMetricConfig::default()).score(qwerty),
+            0.0
+        );
+    }
+
+    #[test]
+    fn failing_programs() {
+        let qwerty = "qwertyuiopasdfghjkl;zxcvbnm,./";
+        let words = metric("while read l; do echo good; done", 1.0);
+        assert!(words.score(qwerty).is_nan());
+        let error = words.value(qwerty).unwrap_err().to_string();
+        assert!(error.contains("'good' instead of a number"), "{error}");
+
+        let quits = metric("exit 0", 1.0);
+        let error = quits.value(qwerty).unwrap_err().to_string();
+        assert!(error.contains("stopped"), "{error}");
+
+        let missing = ExternalMetric::new(ExternalMetricConfig {
+            command: vec!["./no-such-scorer".to_string()],
+            weight: 1.0,
+        });
+        let error = missing.value(qwerty).unwrap_err().to_string();
+        assert!(error.contains("could not be started"), "{error}");
+    }
+}
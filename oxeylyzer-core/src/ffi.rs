@@ -0,0 +1,264 @@
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::UnwindSafe;
+
+use serde_json::{json, Value};
+
+use crate::data_source::MemorySource;
+use crate::error::RusylyzerError;
+use crate::generate::LayoutGeneration;
+use crate::graphemes::{check_single_chars, nfc};
+use crate::layout::FastLayout;
+use crate::schema;
+use crate::weights::Config;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(error: Option<String>) {
+    let error = error.map(|e| CString::new(e.replace('\0', "")).unwrap_or_default());
+    LAST_ERROR.with(|last| *last.borrow_mut() = error);
+}
+
+/// Runs `f` and hands its string over to the caller, or returns null and remembers the error for
+/// `rusylyzer_last_error`. Panics are caught, as they can't unwind into the caller.
+fn respond<F>(f: F) -> *mut c_char
+where
+    F: FnOnce() -> Result<String, RusylyzerError> + UnwindSafe,
+{
+    let res = std::panic::catch_unwind(f)
+        .map_err(|_| "the analyzer panicked".to_string())
+        .and_then(|res| res.map_err(|e| e.to_string()))
+        .and_then(|s| CString::new(s).map_err(|e| e.to_string()));
+
+    match res {
+        Ok(s) => {
+            set_last_error(None);
+            s.into_raw()
+        }
+        Err(e) => {
+            set_last_error(Some(e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// The string `ptr` points to, or `None` when it is null.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a nul terminated string that outlives `'a`.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<Option<&'a str>, String> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(Some)
+        .map_err(|_| format!("{name} isn't valid utf-8"))
+}
+
+/// Like `str_arg`, for arguments that can't be null.
+///
+/// # Safety
+///
+/// The same as for `str_arg`.
+unsafe fn required<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, String> {
+    str_arg(ptr, name)?.ok_or_else(|| format!("{name} can't be null"))
+}
+
+/// A config of the defaults with the tables of `json` on top, like `{"weights": {"sfbs": 8}}`.
+/// It is read the same way `config.toml` is.
+fn config_from_json(json: Value) -> Result<Config, RusylyzerError> {
+    let error = |e: toml::ser::Error| RusylyzerError::Config(e.to_string());
+    let value = toml::Value::try_from(json).map_err(error)?;
+    let toml = toml::to_string(&value).map_err(error)?;
+    Config::from_source(&MemorySource::new(toml), None)
+}
+
+fn parse_json(json: Option<&str>) -> Result<Value, RusylyzerError> {
+    match json {
+        Some(json) => serde_json::from_str(json),
+        None => Ok(json!({})),
+    }
+    .map_err(|e| RusylyzerError::Config(e.to_string()))
+}
+
+/// The 30 keys of `keys`, row by row. Whitespace between them is ignored.
+fn parse_layout(gen: &LayoutGeneration, keys: &str) -> Result<FastLayout, RusylyzerError> {
+    let keys = nfc(keys).split_whitespace().collect::<String>();
+    check_single_chars(&keys)?;
+    let count = keys.chars().count();
+    if count != 30 {
+        let message = format!("a layout needs 30 keys, but '{keys}' has {count}");
+        return Err(RusylyzerError::LayoutParse(message));
+    }
+
+    let bytes = gen.convert_u8.to_lossy(keys.chars());
+    let mut layout = FastLayout::try_from(bytes.as_slice())?;
+    layout.set_fingers(&gen.fingers);
+    Ok(layout)
+}
+
+/// Scores the 30 keys of `layout` on `language`, with the weights of `weights_json` on top of the
+/// default ones. Returns json with the score, its parts and the stats of the layout, shaped like
+/// the `analyze --json` output of the repl. Language data is read from `static/` of the current
+/// directory. Returns null on failure, see `rusylyzer_last_error`.
+///
+/// # Safety
+///
+/// Every argument must be null or a nul terminated string. `weights_json` may be null to use the
+/// default weights. The result must be freed with `rusylyzer_free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn rusylyzer_score_layout(
+    language: *const c_char,
+    layout: *const c_char,
+    weights_json: *const c_char,
+) -> *mut c_char {
+    let args = (|| -> Result<_, String> {
+        Ok((
+            required(language, "language")?,
+            required(layout, "layout")?,
+            str_arg(weights_json, "weights_json")?,
+        ))
+    })();
+    let (language, layout, weights_json) = match args {
+        Ok(args) => args,
+        Err(e) => {
+            set_last_error(Some(e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    respond(move || {
+        let weights = parse_json(weights_json)?;
+        let config = config_from_json(json!({ "weights": weights }))?;
+        let gen = LayoutGeneration::new(language, "static", Some(config))?;
+        let keys = parse_layout(&gen, layout)?;
+
+        let score = gen.checked_score(layout, &keys)?;
+        let stats = gen.get_layout_stats(&keys);
+        let res = json!({
+            "schema_version": schema::SCHEMA_VERSION,
+            "layout": gen.convert_u8.as_str(&keys.matrix),
+            "score": score,
+            "score_components": gen.score_components(&keys, gen.trigram_precision),
+            "stats": stats,
+        });
+        Ok(res.to_string())
+    })
+}
+
+/// Generates a layout for `language` with the config of `config_json` on top of the default one,
+/// which has the same tables as `config.toml`. Returns the 30 keys of the layout row by row, or
+/// null on failure, see `rusylyzer_last_error`.
+///
+/// # Safety
+///
+/// Every argument must be null or a nul terminated string. `config_json` may be null to use the
+/// default config. The result must be freed with `rusylyzer_free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn rusylyzer_generate(
+    language: *const c_char,
+    config_json: *const c_char,
+) -> *mut c_char {
+    let args = (|| -> Result<_, String> {
+        Ok((
+            required(language, "language")?,
+            str_arg(config_json, "config_json")?,
+        ))
+    })();
+    let (language, config_json) = match args {
+        Ok(args) => args,
+        Err(e) => {
+            set_last_error(Some(e));
+            return std::ptr::null_mut();
+        }
+    };
+
+    respond(move || {
+        let config = config_from_json(parse_json(config_json)?)?;
+        let gen = LayoutGeneration::new(language, "static", Some(config))?;
+        let layout = gen.generate();
+        Ok(gen.convert_u8.as_str(&layout.matrix))
+    })
+}
+
+/// Frees a string returned by `rusylyzer_score_layout` or `rusylyzer_generate`.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that wasn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rusylyzer_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Why the last call on this thread returned null, or null if it didn't. The string belongs to
+/// the library and is valid until the next call on this thread, so it must not be freed.
+#[no_mangle]
+pub extern "C" fn rusylyzer_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |error| error.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn take(s: *mut c_char) -> Option<String> {
+        if s.is_null() {
+            return None;
+        }
+        let res = CStr::from_ptr(s).to_str().unwrap().to_string();
+        rusylyzer_free_string(s);
+        Some(res)
+    }
+
+    unsafe fn last_error() -> String {
+        CStr::from_ptr(rusylyzer_last_error())
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn c_interface() {
+        let english = CString::new("english").unwrap();
+        let qwerty = CString::new("qwertyuiop asdfghjkl; zxcvbnm,./").unwrap();
+        let weights = CString::new(r#"{"sfbs": 8.0}"#).unwrap();
+
+        unsafe {
+            let res = take(rusylyzer_score_layout(
+                english.as_ptr(),
+                qwerty.as_ptr(),
+                weights.as_ptr(),
+            ))
+            .unwrap();
+            assert!(rusylyzer_last_error().is_null());
+            let res = serde_json::from_str::<Value>(&res).unwrap();
+            assert_eq!(res["layout"], "qwertyuiopasdfghjkl;zxcvbnm,./");
+            assert!(res["score"].is_f64());
+            assert!(res["stats"].is_object());
+
+            let short = CString::new("qwerty").unwrap();
+            let res = rusylyzer_score_layout(english.as_ptr(), short.as_ptr(), std::ptr::null());
+            assert!(res.is_null());
+            assert!(last_error().contains("30 keys"));
+
+            let config = CString::new(r#"{"weights": {"sfbs": "many"}}"#).unwrap();
+            assert!(rusylyzer_generate(english.as_ptr(), config.as_ptr()).is_null());
+            assert!(last_error().starts_with("config.toml is invalid"));
+
+            let layout = take(rusylyzer_generate(english.as_ptr(), std::ptr::null())).unwrap();
+            assert_eq!(layout.chars().count(), 30);
+        }
+    }
+}
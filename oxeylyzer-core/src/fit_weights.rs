@@ -0,0 +1,174 @@
+use nanorand::{tls_rng, Rng};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::generate::LayoutGeneration;
+use crate::language_data::LanguageData;
+use crate::layout::FastLayout;
+use crate::weights::{Config, Weights};
+
+/// Weights that are tried at the same time. The step size changes after every batch.
+const BATCH: usize = 8;
+
+/// Weights found by [`fit_weights`], and how well they reproduce the ranking.
+#[derive(Debug, Clone)]
+pub struct WeightFit {
+    pub weights: Weights,
+    /// Pairs of layouts the fitted weights score in the wrong order.
+    pub wrong_pairs: usize,
+    /// Pairs of layouts the weights the search started from score in the wrong order.
+    pub start_wrong_pairs: usize,
+    /// Pairs of layouts in the ranking.
+    pub pairs: usize,
+    /// Amount of weights that were tried.
+    pub candidates: usize,
+}
+
+/// How far `scores` are from going from high to low: the pairs that are in the wrong order, and
+/// how far they are apart as a fraction of how far all pairs are apart. Pairs that score the same
+/// are in the wrong order, as they don't show a preference. Lower is better.
+fn ranking_loss(scores: &[f64]) -> (usize, f64) {
+    let mut wrong = 0;
+    let mut wrong_by = 0.0;
+    let mut total = 0.0;
+
+    for (i, s1) in scores.iter().enumerate() {
+        for s2 in scores[(i + 1)..].iter() {
+            if s2 >= s1 {
+                wrong += 1;
+                wrong_by += s2 - s1;
+            }
+            total += (s1 - s2).abs();
+        }
+    }
+    let fraction = if total > 0.0 { wrong_by / total } else { 1.0 };
+    (wrong, fraction)
+}
+
+/// `weights` with every weight that isn't 0 multiplied by a random factor between `e^-step` and
+/// `e^step`, rounded to 3 decimals so they are easy to read in `config.toml`.
+fn perturb(weights: &Weights, step: f64) -> Weights {
+    let mut rng = tls_rng();
+    let mut res = weights.clone();
+
+    for name in Weights::NAMES {
+        if let Some(weight) = res.weight_mut(name).filter(|weight| **weight != 0.0) {
+            let factor = (step * (rng.generate_range(0..=2000u32) as f64 / 1000.0 - 1.0)).exp();
+            *weight = ((*weight * factor * 1000.0).round() / 1000.0).max(0.001);
+        }
+    }
+    res.set_dsfb_ratios();
+    res
+}
+
+/// Searches for weights that score the layouts of `ranking` in order, from the one that should
+/// score the best to the one that should score the worst. Starting from the weights of `config`,
+/// batches of random variations are scored and the best one is kept when it ranks the layouts
+/// better. The variations get larger while that works and smaller while it doesn't. Weights that
+/// are 0 stay 0, so only the weights that are in use are tuned.
+///
+/// Stops once the ranking is reproduced or `max_candidates` weights were tried. `on_candidate` is
+/// called every time a weight is tried, like to show progress.
+pub fn fit_weights<F>(
+    chars_language: &str,
+    data: &LanguageData,
+    config: &Config,
+    ranking: &[&FastLayout],
+    max_candidates: usize,
+    on_candidate: F,
+) -> WeightFit
+where
+    F: Fn() + Sync,
+{
+    let loss = |weights: &Weights| {
+        let config = Config {
+            weights: weights.clone(),
+            ..config.clone()
+        };
+        let gen = LayoutGeneration::from_data(chars_language, data.clone(), config);
+        let scores = ranking
+            .iter()
+            .map(|layout| gen.score(layout))
+            .collect::<Vec<_>>();
+        ranking_loss(&scores)
+    };
+
+    let mut best_weights = config.weights.clone();
+    let mut best_loss = loss(&best_weights);
+    let start_wrong_pairs = best_loss.0;
+    let mut step = 0.5;
+    let mut candidates = 0;
+
+    while best_loss.0 > 0 && candidates < max_candidates {
+        let batch = BATCH.min(max_candidates - candidates);
+        let best = (0..batch)
+            .into_par_iter()
+            .map(|_| {
+                let weights = perturb(&best_weights, step);
+                let loss = loss(&weights);
+                on_candidate();
+                (weights, loss)
+            })
+            .min_by(|(_, l1), (_, l2)| l1.partial_cmp(l2).unwrap());
+        candidates += batch;
+
+        match best.filter(|(_, loss)| *loss < best_loss) {
+            Some((weights, loss)) => {
+                best_weights = weights;
+                best_loss = loss;
+                step = (step * 1.2f64).min(1.0);
+            }
+            None => step = (step * 0.8f64).max(0.05),
+        }
+    }
+
+    WeightFit {
+        weights: best_weights,
+        wrong_pairs: best_loss.0,
+        start_wrong_pairs,
+        pairs: ranking.len() * ranking.len().saturating_sub(1) / 2,
+        candidates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_ranking() {
+        assert_eq!(ranking_loss(&[3.0, 2.0, 1.0]), (0, 0.0));
+        assert_eq!(ranking_loss(&[1.0, 2.0]), (1, 1.0));
+        assert_eq!(ranking_loss(&[2.0, 2.0]), (1, 1.0));
+        assert_eq!(ranking_loss(&[3.0, 1.0, 2.0]), (1, 0.25));
+
+        let config = Config::default();
+        let mut weights = config.weights.clone();
+        let mut changed = perturb(&config.weights, 0.5);
+        for name in Weights::NAMES {
+            let (before, after) = (
+                *weights.weight_mut(name).unwrap(),
+                *changed.weight_mut(name).unwrap(),
+            );
+            assert_eq!(before == 0.0, after == 0.0, "{name}");
+            assert!(after <= before * 0.5f64.exp() + 1e-3, "{name}");
+        }
+
+        let data = LanguageData::from_file("static/language_data", "english").unwrap();
+        let gen = LayoutGeneration::from_data("english", data.clone(), config.clone());
+        let qwerty_bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let generated = gen.generate();
+
+        let in_order = fit_weights("english", &data, &config, &[&generated, &qwerty], 16, || ());
+        assert_eq!(in_order.wrong_pairs, 0);
+        assert_eq!(in_order.candidates, 0);
+        assert_eq!(in_order.pairs, 1);
+
+        let reversed = fit_weights("english", &data, &config, &[&qwerty, &generated], 16, || ());
+        assert_eq!(reversed.start_wrong_pairs, 1);
+        assert!(reversed.wrong_pairs <= 1);
+        assert!(reversed.candidates == 16 || reversed.wrong_pairs == 0);
+    }
+}
@@ -1,25 +1,44 @@
-use std::hash::BuildHasherDefault;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
 use std::hint::unreachable_unchecked;
 use std::path::Path;
+use std::time::Instant;
 
 use anyhow::Result;
 use fxhash::FxHashMap;
 use indexmap::IndexMap;
 use itertools::Itertools;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-
-use crate::language_data::{BigramData, LanguageData, TrigramData};
+use nanorand::{tls_rng, Rng};
+use once_cell::sync::OnceCell;
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+use serde::{Serialize, Serializer};
+
+use crate::bigram_penalties::BigramPenalty;
+use crate::data_source::{DataSource, FileSource};
+use crate::error::RusylyzerError;
+use crate::external_metric::ExternalMetric;
+use crate::graphemes::nfc;
+use crate::language_data::{BigramData, LanguageData, TrigramData, SPACE};
 use crate::layout::*;
-use crate::trigram_patterns::TrigramPattern;
+use crate::metadata::LayoutMetadata;
+use crate::score_cache::ScoreCache;
+use crate::trigram_patterns::{TrigramPattern, TrigramTable};
 use crate::utility::*;
-use crate::weights::{Config, Weights};
+use crate::weights::{
+    Config, Constraint, FingerSpeeds, GenerationOptions, HandDominance, SeedStrategy, SpaceThumb,
+    SwapPolicy, Weights,
+};
 
 #[cfg(test)]
 static PRUNED_COUNT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
 #[cfg(test)]
 static NOT_PRUNED: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
 
-#[derive(Clone, Default)]
+/// Share of the trigrams of every pattern. Serialized as `trigram_stats` in the `stats` schema.
+#[derive(Clone, Default, Serialize)]
 pub struct TrigramStats {
     pub alternates: f64,
     pub alternates_sfs: f64,
@@ -35,6 +54,105 @@ pub struct TrigramStats {
     pub sfts: f64,
     pub other: f64,
     pub invalid: f64,
+    /// Inrolls and outrolls split by where the two keys on the same hand are: both on the top,
+    /// home or bottom row, or on different rows. These add up to `inrolls` and `outrolls`.
+    pub inrolls_by_row: [f64; 4],
+    pub outrolls_by_row: [f64; 4],
+    /// Inrolls and outrolls on the left and right hand. These add up to `inrolls` and `outrolls`.
+    pub inrolls_by_hand: [f64; 2],
+    pub outrolls_by_hand: [f64; 2],
+    /// Rolls where the two keys on the same hand are on one row and use neighbouring fingers.
+    pub adjacent_rolls: f64,
+}
+
+impl TrigramStats {
+    pub fn home_row_rolls(&self) -> f64 {
+        self.inrolls_by_row[1] + self.outrolls_by_row[1]
+    }
+
+    /// Frequency of every trigram that was classified, including invalid ones.
+    pub fn total(&self) -> f64 {
+        self.alternates
+            + self.alternates_sfs
+            + self.inrolls
+            + self.outrolls
+            + self.onehands
+            + self.redirects
+            + self.redirects_sfs
+            + self.bad_redirects
+            + self.bad_redirects_sfs
+            + self.sfbs
+            + self.bad_sfbs
+            + self.sfts
+            + self.other
+            + self.invalid
+    }
+
+    /// Part of all trigrams that is invalid, from 0 to 1.
+    pub fn invalid_share(&self) -> f64 {
+        match self.total() {
+            total if total > 0.0 => self.invalid / total,
+            _ => 0.0,
+        }
+    }
+
+    /// The same stats with the invalid trigrams left out, and the others scaled up to make up for
+    /// them. This makes layouts that can't type all characters comparable to those that can.
+    pub fn without_invalid(&self) -> Self {
+        let valid = self.total() - self.invalid;
+        if valid <= 0.0 {
+            return Self::default();
+        }
+        let scale = self.total() / valid;
+
+        Self {
+            alternates: self.alternates * scale,
+            alternates_sfs: self.alternates_sfs * scale,
+            inrolls: self.inrolls * scale,
+            outrolls: self.outrolls * scale,
+            onehands: self.onehands * scale,
+            redirects: self.redirects * scale,
+            redirects_sfs: self.redirects_sfs * scale,
+            bad_redirects: self.bad_redirects * scale,
+            bad_redirects_sfs: self.bad_redirects_sfs * scale,
+            sfbs: self.sfbs * scale,
+            bad_sfbs: self.bad_sfbs * scale,
+            sfts: self.sfts * scale,
+            other: self.other * scale,
+            invalid: 0.0,
+            inrolls_by_row: self.inrolls_by_row.map(|freq| freq * scale),
+            outrolls_by_row: self.outrolls_by_row.map(|freq| freq * scale),
+            inrolls_by_hand: self.inrolls_by_hand.map(|freq| freq * scale),
+            outrolls_by_hand: self.outrolls_by_hand.map(|freq| freq * scale),
+            adjacent_rolls: self.adjacent_rolls * scale,
+        }
+    }
+
+    /// Rolls on the left (0) or right hand (1), inrolls and outrolls together.
+    pub fn rolls_by_hand(&self, hand: usize) -> f64 {
+        self.inrolls_by_hand[hand] + self.outrolls_by_hand[hand]
+    }
+
+    fn add_roll(
+        &mut self,
+        inroll: bool,
+        hand: usize,
+        (row, adjacent): (usize, bool),
+        freq: f64,
+    ) {
+        if inroll {
+            self.inrolls += freq;
+            self.inrolls_by_row[row] += freq;
+            self.inrolls_by_hand[hand] += freq;
+        } else {
+            self.outrolls += freq;
+            self.outrolls_by_row[row] += freq;
+            self.outrolls_by_hand[hand] += freq;
+        }
+        if adjacent {
+            self.adjacent_rolls += freq;
+        }
+    }
 }
 
 impl std::fmt::Display for TrigramStats {
@@ -44,6 +162,15 @@ impl std::fmt::Display for TrigramStats {
             "Inrolls: {:.3}%\n\
 			Outrolls: {:.3}%\n\
 			Total Rolls: {:.3}%\n\
+			Rolls by row (in, out):\n\
+			    Top: {:.3}%, {:.3}%\n\
+			    Home: {:.3}%, {:.3}%\n\
+			    Bottom: {:.3}%, {:.3}%\n\
+			    Across rows: {:.3}%, {:.3}%\n\
+			Rolls by hand (in, out):\n\
+			    Left: {:.3}%, {:.3}%\n\
+			    Right: {:.3}%, {:.3}%\n\
+			Adjacent Finger Rolls: {:.3}%\n\
 			Onehands: {:.3}%\n\n\
 			Alternates: {:.3}%\n\
 			Alternates (sfs): {:.3}%\n\
@@ -58,6 +185,19 @@ impl std::fmt::Display for TrigramStats {
             self.inrolls * 100.0,
             self.outrolls * 100.0,
             (self.inrolls + self.outrolls) * 100.0,
+            self.inrolls_by_row[0] * 100.0,
+            self.outrolls_by_row[0] * 100.0,
+            self.inrolls_by_row[1] * 100.0,
+            self.outrolls_by_row[1] * 100.0,
+            self.inrolls_by_row[2] * 100.0,
+            self.outrolls_by_row[2] * 100.0,
+            self.inrolls_by_row[3] * 100.0,
+            self.outrolls_by_row[3] * 100.0,
+            self.inrolls_by_hand[0] * 100.0,
+            self.outrolls_by_hand[0] * 100.0,
+            self.inrolls_by_hand[1] * 100.0,
+            self.outrolls_by_hand[1] * 100.0,
+            self.adjacent_rolls * 100.0,
             self.onehands * 100.0,
             self.alternates * 100.0,
             self.alternates_sfs * 100.0,
@@ -115,25 +255,131 @@ impl std::fmt::Debug for TrigramStats {
     }
 }
 
-fn format_fspeed(finger_speed: &[f64]) -> String {
-    let mut finger_speed_str: Vec<String> = Vec::new();
-    for v in finger_speed {
-        finger_speed_str.push(format!("{:.3}", v * 10.0))
+fn format_per_finger(values: &[f64], multiplier: f64) -> String {
+    let mut values_str: Vec<String> = Vec::new();
+    for v in values {
+        values_str.push(format!("{:.3}", v * multiplier))
+    }
+    values_str.join(", ")
+}
+
+#[derive(Clone, Default)]
+pub struct Coverage {
+    pub characters: f64,
+    pub bigrams: f64,
+    pub trigrams: f64,
+}
+
+impl std::fmt::Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Characters: {:.3}%\nBigrams: {:.3}%\nTrigrams: {:.3}%",
+            self.characters * 100.0,
+            self.bigrams * 100.0,
+            self.trigrams * 100.0
+        )
+    }
+}
+
+/// Writes stats by name as a map, the way the `stats` schema has them.
+pub(crate) fn serialize_named<S: Serializer>(values: &[(String, f64)], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_map(values.iter().map(|(name, value)| (name, value)))
+}
+
+/// Weighted parts of a score. The score is `trigrams`, `onehand_rolls`, `quadgrams` and
+/// `external_metric` minus the others, which are penalties. Serializes to the `score` schema, so
+/// fields are only renamed or removed along with a new `SCHEMA_VERSION`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ScoreComponents {
+    pub trigrams: f64,
+    pub onehand_rolls: f64,
+    /// Weighted quadgram patterns, which are 0 without quadgram data.
+    pub quadgrams: f64,
+    /// Heatmap effort, including travel from and back to the home keys.
+    pub effort: f64,
+    /// Finger speed together with the `max_finger_use` penalty.
+    pub fspeed_usage: f64,
+    pub scissors: f64,
+    pub lsbs: f64,
+    pub row_skips: f64,
+    pub bigram_penalties: f64,
+    pub hand_alternation: f64,
+    /// How far the rows are outside of the `row_usage` limits.
+    pub row_usage: f64,
+    /// Share of the shortcut keys out of reach of the left hand.
+    pub shortcuts: f64,
+    /// Weighted result of the program in `[external_metric]`.
+    pub external_metric: f64,
+    pub total: f64,
+}
+
+impl ScoreComponents {
+    /// Name of the first component that is NaN, which makes the total NaN as well.
+    pub fn nan_component(&self) -> Option<&'static str> {
+        [
+            ("trigrams", self.trigrams),
+            ("onehand_rolls", self.onehand_rolls),
+            ("quadgrams", self.quadgrams),
+            ("effort", self.effort),
+            ("fspeed_usage", self.fspeed_usage),
+            ("scissors", self.scissors),
+            ("lsbs", self.lsbs),
+            ("row_skips", self.row_skips),
+            ("bigram_penalties", self.bigram_penalties),
+            ("hand_alternation", self.hand_alternation),
+            ("row_usage", self.row_usage),
+            ("shortcuts", self.shortcuts),
+            ("external_metric", self.external_metric),
+            ("total", self.total),
+        ]
+        .into_iter()
+        .find(|(_, value)| value.is_nan())
+        .map(|(name, _)| name)
     }
-    finger_speed_str.join(", ")
 }
 
-#[derive(Clone)]
+/// Every stat `analyze` shows. Serializes to the `stats` schema, so fields are only renamed or
+/// removed along with a new `SCHEMA_VERSION`.
+#[derive(Clone, Default, Serialize)]
 pub struct LayoutStats {
     pub sfb: f64,
+    pub sfb_per_finger: [f64; 8],
+    pub sfb_distance: f64,
+    pub sfb_distance_per_finger: [f64; 8],
     pub dsfb: f64,
     pub dsfb2: f64,
     pub dsfb3: f64,
     pub scissors: f64,
     pub lsbs: f64,
+    /// Bigrams between the top and bottom row on the same or adjacent fingers.
+    pub row_skips: f64,
     pub trigram_stats: TrigramStats,
     pub fspeed: f64,
     pub finger_speed: [f64; 8],
+    pub hand_usage: [f64; 2],
+    /// Share of keypresses on the top, home and bottom row.
+    pub row_usage: [f64; 3],
+    /// Bigrams typed with one hand after the other.
+    pub hand_alternation: f64,
+    /// Share of the shortcut keys on the home and bottom row of the left hand.
+    pub shortcut_reach: f64,
+    pub onehand_rolls: f64,
+    pub travel: f64,
+    /// Bigrams of every penalty in `[bigram_penalties]`, by name.
+    #[serde(serialize_with = "serialize_named")]
+    pub bigram_penalties: Vec<(String, f64)>,
+}
+
+/// 2 keys typed with the same finger with other keys in between, which `LayoutStats` counts as
+/// dsfbs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SameFingerSkipgram {
+    /// The first and last key, in the order they are typed.
+    pub chars: [char; 2],
+    /// Keys typed in between, from 1 to 3. Dsfbs have 1, dsfb2s 2 and dsfb3s 3.
+    pub gap: usize,
+    pub freq: f64,
 }
 
 impl std::fmt::Display for LayoutStats {
@@ -141,27 +387,168 @@ impl std::fmt::Display for LayoutStats {
         write!(
             f,
             concat!(
-                "Sfb:  {:.3}%\nDsfb: {:.3}%\nFinger Speed: {:.3}\n",
-                "    [{}]\nScissors: {:.3}%\nLsbs: {:.3}%\n\n{}"
+                "Sfb:  {:.3}%\n    [{}]\nSfb Distance: {:.3}\n    [{}]\n",
+                "Dsfb: {:.3}%, Dsfb2: {:.3}%, Dsfb3: {:.3}%\nFinger Speed: {:.3}\n",
+                "    [{}]\nScissors: {:.3}%\nLsbs: {:.3}%\nRow Skips: {:.3}%\n",
+                "Hand Usage: {:.2}% left, {:.2}% right\n",
+                "Row Usage: {:.2}% top, {:.2}% home, {:.2}% bottom\n",
+                "Hand Alternation: {:.3}%\nShortcuts: {:.2}% within reach of the left hand\n\n{}",
+                "Onehand Rolls: {:.3}%\nTravel: {:.3}\n"
             ),
             self.sfb * 100.0,
+            format_per_finger(&self.sfb_per_finger, 100.0),
+            self.sfb_distance * 100.0,
+            format_per_finger(&self.sfb_distance_per_finger, 100.0),
             self.dsfb * 100.0,
+            self.dsfb2 * 100.0,
+            self.dsfb3 * 100.0,
             self.fspeed * 10.0,
-            format_fspeed(&self.finger_speed),
+            format_per_finger(&self.finger_speed, 10.0),
             self.scissors * 100.0,
             self.lsbs * 100.0,
-            self.trigram_stats
+            self.row_skips * 100.0,
+            self.hand_usage[0] * 100.0,
+            self.hand_usage[1] * 100.0,
+            self.row_usage[0] * 100.0,
+            self.row_usage[1] * 100.0,
+            self.row_usage[2] * 100.0,
+            self.hand_alternation * 100.0,
+            self.shortcut_reach * 100.0,
+            self.trigram_stats,
+            self.onehand_rolls * 100.0,
+            self.travel
+        )?;
+        for (name, value) in self.bigram_penalties.iter() {
+            writeln!(f, "{name}: {:.3}%", value * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl LayoutStats {
+    /// Looks up a stat by name, returning its display name and its value as shown when analyzing,
+    /// so in percent for everything except finger speed, sfb distance and travel.
+    pub fn metric(&self, name: &str) -> Option<(&'static str, f64)> {
+        let ts = &self.trigram_stats;
+
+        let (display_name, value) = match name.to_lowercase().as_str() {
+            "sfb" | "sfbs" => ("Sfb", self.sfb),
+            "sfb_distance" | "sfb_dist" => return Some(("Sfb Distance", self.sfb_distance * 100.0)),
+            "dsfb" | "dsfbs" => ("Dsfb", self.dsfb),
+            "dsfb2" | "dsfbs2" => ("Dsfb2", self.dsfb2),
+            "dsfb3" | "dsfbs3" => ("Dsfb3", self.dsfb3),
+            "fspeed" | "finger_speed" => return Some(("Finger Speed", self.fspeed * 10.0)),
+            "travel" | "distance" => return Some(("Travel", self.travel)),
+            "scissors" | "scissor" => ("Scissors", self.scissors),
+            "lsbs" | "lsb" => ("Lsbs", self.lsbs),
+            "row_skips" | "row_skip" => ("Row Skips", self.row_skips),
+            "hand_alternation" | "alternation" => ("Hand Alternation", self.hand_alternation),
+            "top_row" | "top_row_usage" => ("Top Row Usage", self.row_usage[0]),
+            "home_row" | "home_row_usage" => ("Home Row Usage", self.row_usage[1]),
+            "bottom_row" | "bottom_row_usage" => ("Bottom Row Usage", self.row_usage[2]),
+            "shortcuts" | "shortcut_reach" => ("Shortcut Reach", self.shortcut_reach),
+            "inrolls" | "inroll" => ("Inrolls", ts.inrolls),
+            "outrolls" | "outroll" => ("Outrolls", ts.outrolls),
+            "rolls" | "roll" => ("Total Rolls", ts.inrolls + ts.outrolls),
+            "home_rolls" | "home_row_rolls" => ("Home Row Rolls", ts.home_row_rolls()),
+            "adjacent_rolls" => ("Adjacent Finger Rolls", ts.adjacent_rolls),
+            "left_rolls" | "left_hand_rolls" => ("Left Hand Rolls", ts.rolls_by_hand(0)),
+            "right_rolls" | "right_hand_rolls" => ("Right Hand Rolls", ts.rolls_by_hand(1)),
+            "onehands" | "onehand" => ("Onehands", ts.onehands),
+            "alternates" | "alternate" | "alts" => {
+                ("Alternates", ts.alternates + ts.alternates_sfs)
+            }
+            "redirects" | "redirect" | "redirs" => (
+                "Redirects",
+                ts.redirects + ts.redirects_sfs + ts.bad_redirects + ts.bad_redirects_sfs,
+            ),
+            "bad_redirects" | "bad_redirs" => {
+                ("Bad Redirects", ts.bad_redirects + ts.bad_redirects_sfs)
+            }
+            "bad_sfbs" => ("Bad Sfbs", ts.bad_sfbs),
+            "sft" | "sfts" => ("Sft", ts.sfts),
+            "onehand_rolls" => ("Onehand Rolls", self.onehand_rolls),
+            _ => return None,
+        };
+        Some((display_name, value * 100.0))
+    }
+
+    /// Lists how much every stat changed compared to `parent`, leaving out the ones that changed
+    /// by less than what is shown when analyzing.
+    pub fn deltas(&self, parent: &LayoutStats) -> Vec<String> {
+        let (ts, pts) = (&self.trigram_stats, &parent.trigram_stats);
+        let redirects = |t: &TrigramStats| {
+            t.redirects + t.redirects_sfs + t.bad_redirects + t.bad_redirects_sfs
+        };
+
+        [
+            ("Sfb", self.sfb - parent.sfb),
+            ("Dsfb", self.dsfb - parent.dsfb),
+            ("Dsfb2", self.dsfb2 - parent.dsfb2),
+            ("Dsfb3", self.dsfb3 - parent.dsfb3),
+            ("Scissors", self.scissors - parent.scissors),
+            ("Lsbs", self.lsbs - parent.lsbs),
+            ("Row Skips", self.row_skips - parent.row_skips),
+            ("Hand Alternation", self.hand_alternation - parent.hand_alternation),
+            ("Top Row Usage", self.row_usage[0] - parent.row_usage[0]),
+            ("Home Row Usage", self.row_usage[1] - parent.row_usage[1]),
+            ("Bottom Row Usage", self.row_usage[2] - parent.row_usage[2]),
+            ("Shortcut Reach", self.shortcut_reach - parent.shortcut_reach),
+            ("Inrolls", ts.inrolls - pts.inrolls),
+            ("Outrolls", ts.outrolls - pts.outrolls),
+            ("Home Row Rolls", ts.home_row_rolls() - pts.home_row_rolls()),
+            ("Adjacent Finger Rolls", ts.adjacent_rolls - pts.adjacent_rolls),
+            ("Onehands", ts.onehands - pts.onehands),
+            ("Alternates", ts.alternates - pts.alternates),
+            ("Alternates Sfs", ts.alternates_sfs - pts.alternates_sfs),
+            ("Redirects", redirects(ts) - redirects(pts)),
+            ("Bad Sfbs", ts.bad_sfbs - pts.bad_sfbs),
+            ("Sft", ts.sfts - pts.sfts),
+        ]
+        .into_iter()
+        .chain(
+            self.bigram_penalties
+                .iter()
+                .zip(parent.bigram_penalties.iter())
+                .map(|((name, value), (_, parent))| (name.as_str(), value - parent)),
+        )
+        .map(|(name, delta)| (name, delta * 100.0))
+        .filter(|(_, delta)| delta.abs() >= 0.0005)
+        .map(|(name, delta)| format!("{name} {delta:+.3}%"))
+        .chain(
+            Some((self.fspeed - parent.fspeed) * 10.0)
+                .filter(|delta| delta.abs() >= 0.0005)
+                .map(|delta| format!("Finger Speed {delta:+.3}")),
+        )
+        .chain(
+            Some((self.sfb_distance - parent.sfb_distance) * 100.0)
+                .filter(|delta| delta.abs() >= 0.0005)
+                .map(|delta| format!("Sfb Distance {delta:+.3}")),
         )
+        .chain(
+            Some(self.travel - parent.travel)
+                .filter(|delta| delta.abs() >= 0.0005)
+                .map(|delta| format!("Travel {delta:+.3}")),
+        )
+        .collect()
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct LayoutCache {
     effort: [f64; 30],
     effort_total: f64,
 
     scissors: f64,
     lsbs: f64,
+    row_skips: f64,
+    bigram_penalties: f64,
+    hand_alternation: f64,
+    row_usage: f64,
+    shortcuts: f64,
+    /// Weighted onehand rolls and quadgrams, which every swap can change.
+    onehand_rolls: f64,
+    quadgrams: f64,
 
     usage: [f64; 8],
     usage_total: f64,
@@ -171,39 +558,206 @@ pub struct LayoutCache {
 
     // trigrams: FxHashMap<(char, Option<char>), f64>,
     trigrams_total: f64,
+    /// Trigrams less common than this are left out of `trigrams_total`, to optimize with a lower
+    /// precision than the generator's. See `LayoutGeneration::set_cache_precision`.
+    min_trigram_freq: f64,
 
     total_score: f64,
 }
 
 impl LayoutCache {
     pub fn total_score(&self) -> f64 {
-        self.trigrams_total
+        self.trigrams_total + self.onehand_rolls + self.quadgrams
             - self.scissors
             - self.lsbs
+            - self.row_skips
+            - self.bigram_penalties
+            - self.hand_alternation
+            - self.row_usage
+            - self.shortcuts
             - self.effort_total
             - self.usage_total
             - self.fspeed_total
     }
 }
 
-type PerCharTrigrams = FxHashMap<[u8; 2], TrigramData>;
+/// Trigrams containing either character of a pair of characters, at the `pair_index` of the pair.
+type PerCharTrigrams = Vec<TrigramData>;
+/// The third character and frequency of the trigrams starting with every 2 characters, at
+/// `c1 * len + c2`.
+type TrigramsByPrefix = Vec<Vec<(u8, f64)>>;
+
+/// The scored trigrams looked up by the characters in them, which are both built in a single pass
+/// over the trigrams.
+#[derive(Debug)]
+struct TrigramIndex {
+    per_pair: PerCharTrigrams,
+    by_prefix: TrigramsByPrefix,
+}
+
+impl TrigramIndex {
+    fn new(data: &LanguageData, trigram_precision: usize) -> Self {
+        let len = data.characters.len();
+        let mut per_pair = vec![TrigramData::new(); len * len];
+        let mut by_prefix = vec![Vec::new(); len * len];
+
+        for &(trigram @ [c1, c2, c3], freq) in data.trigrams.iter().take(trigram_precision) {
+            if (c1 as usize) < len && (c2 as usize) < len {
+                by_prefix[c1 as usize * len + c2 as usize].push((c3, freq));
+            }
+
+            // every pair with a character of the trigram in it, once even if it has two of them
+            for (i, &c) in trigram.iter().enumerate() {
+                if (c as usize) >= len || trigram[..i].contains(&c) {
+                    continue;
+                }
+                for other in (0..len as u8).filter(|other| !trigram[..i].contains(other)) {
+                    per_pair[pair_index(c, other, len).unwrap()].push((trigram, freq));
+                }
+            }
+        }
+        // most common first, so a lower precision only has to look at the start
+        for trigrams in per_pair.iter_mut() {
+            trigrams.sort_by_key(|&(_, freq)| Reverse(TotalScore(freq)));
+        }
+
+        Self {
+            per_pair,
+            by_prefix,
+        }
+    }
+}
+
+/// Where a pair of characters is in `PerCharTrigrams`. Both orders of a pair have the same
+/// trigrams, so they are only stored once.
+#[inline(always)]
+fn pair_index(c1: u8, c2: u8, len: usize) -> Option<usize> {
+    let (c1, c2) = (c1.min(c2) as usize, c1.max(c2) as usize);
+    (c2 < len).then(|| c1 * len + c2)
+}
 
 static COLS: [usize; 6] = [0, 1, 2, 7, 8, 9];
 
+/// Scores of swaps closer together than this count as the same.
+pub const SWAP_EPSILON: f64 = 1e-9;
+
+/// Whether `swap` scoring `score` should replace the best swap so far. Only a better score counts
+/// with `SwapPolicy::Shuffled`, but with `Deterministic` a tie within `SWAP_EPSILON` is won by the
+/// swap with the lowest positions, so the result doesn't depend on tiny floating point
+/// differences between platforms. Works for a `PosPair` as well as any other `Move`.
+pub(crate) fn beats_swap<T: Ord>(
+    policy: SwapPolicy,
+    (score, swap): (f64, &T),
+    (best_score, best): (f64, Option<&T>),
+) -> bool {
+    if score > best_score + SWAP_EPSILON {
+        return true;
+    }
+    match best {
+        Some(best) if policy == SwapPolicy::Deterministic => {
+            score >= best_score - SWAP_EPSILON && swap < best
+        }
+        _ => false,
+    }
+}
+
+/// Keys that stay in place when a layout that migrated to another island is optimized again.
+const MIGRANT_PINS: usize = 15;
+
+/// Random swaps `optimize_cached` tries with a `temperature` once no swap improves a layout
+/// anymore.
+const TEMPERATURE_ATTEMPTS: usize = 200;
+
 pub(crate) fn pinned_swaps(pins: &[usize]) -> Vec<PosPair> {
+    let mut res = Vec::new();
+    pinned_swaps_into(pins, &mut res);
+    res
+}
+
+pub(crate) fn pinned_swaps_into(pins: &[usize], res: &mut Vec<PosPair>) {
     let mut map = [true; 30];
     for i in 0..30 {
         if pins.contains(&i) {
             map[i] = false;
         }
     }
-    let mut res = Vec::new();
+    res.clear();
     for ps in POSSIBLE_SWAPS {
         if map[ps.0] && map[ps.1] {
             res.push(ps);
         }
     }
-    res
+}
+
+/// Buffers reused by every restart that runs on the same thread, so generating thousands of
+/// layouts doesn't set up a new layout, cache and swap list for each one of them.
+#[derive(Default)]
+struct GenerationScratch {
+    layout: FastLayout,
+    cache: LayoutCache,
+    swaps: Vec<PosPair>,
+}
+
+thread_local! {
+    static SCRATCH: RefCell<GenerationScratch> = RefCell::new(GenerationScratch::default());
+}
+
+fn with_scratch<R>(f: impl FnOnce(&mut GenerationScratch) -> R) -> R {
+    SCRATCH.with(|scratch| match scratch.try_borrow_mut() {
+        Ok(mut scratch) => f(&mut scratch),
+        // generation was entered again on this thread while the pooled buffers are in use
+        Err(_) => f(&mut GenerationScratch::default()),
+    })
+}
+
+/// Whether every constraint that was met `before` a change is still met `after` it.
+fn keeps_constraints(before: &[bool], after: &[bool]) -> bool {
+    before.iter().zip(after).all(|(&before, &after)| !before || after)
+}
+
+/// Row of every character on the 30 main keys of `layout`. Everything else, like thumb keys, gets
+/// `usize::MAX`.
+fn char_rows(layout: &FastLayout) -> [usize; MAX_CHARS] {
+    let mut rows = [usize::MAX; MAX_CHARS];
+    for (i, &c) in layout.matrix.iter().enumerate() {
+        if let Some(row) = rows.get_mut(c as usize) {
+            *row = i / 10;
+        }
+    }
+    rows
+}
+
+/// Hand a roll is on, 0 for left and 1 for right. The middle key of a roll is always one of the two
+/// keys on the same hand.
+fn roll_hand(layout: &FastLayout, trigram: &[u8; 3]) -> usize {
+    if is_left_hand(layout.char_to_finger[trigram[1] as usize]) {
+        0
+    } else {
+        1
+    }
+}
+
+/// Where the two keys on the same hand of a roll are: on the top (0), home (1) or bottom row (2),
+/// or on different rows (3). Also returns whether they use neighbouring fingers on the same row.
+fn roll_placement(
+    layout: &FastLayout,
+    rows: &[usize; MAX_CHARS],
+    trigram: &[u8; 3],
+) -> (usize, bool) {
+    let finger = |c: u8| layout.char_to_finger[c as usize];
+    let (c1, c2) = if is_left_hand(finger(trigram[0])) == is_left_hand(finger(trigram[1])) {
+        (trigram[0], trigram[1])
+    } else {
+        (trigram[1], trigram[2])
+    };
+    let (row1, row2) = (rows[c1 as usize], rows[c2 as usize]);
+
+    if row1 == row2 && row1 < 3 {
+        let (f1, f2) = (finger(c1), finger(c2));
+        (row1, f1 < 8 && f2 < 8 && f1.abs_diff(f2) == 1)
+    } else {
+        (3, false)
+    }
 }
 
 pub struct LayoutGeneration {
@@ -212,63 +766,366 @@ pub struct LayoutGeneration {
     pub convert_u8: ConvertU8,
     pub repeat_key: usize,
     pub chars_for_generation: [u8; 30],
+    /// Finger that presses every position, which every layout scored here has to use as well.
+    pub fingers: FingerAssignment,
 
-    fspeed_vals: [(PosPair, f64); 48],
+    fspeed_vals: Vec<(PosPair, f64)>,
+    finger_ranges: [(usize, usize); 8],
+    finger_keys: [Vec<usize>; 8],
     effort_map: [f64; 30],
+    home_distances: [f64; 30],
+    /// Distance between every 2 keys in key widths, used to estimate typing time.
+    key_distances: [[f64; 30]; 30],
+    sfb_travel: Vec<f64>,
     scissor_indices: [PosPair; 28],
     lsb_indices: [PosPair; 16],
-
-    weighted_bigrams: BigramData,
-    per_char_trigrams: PerCharTrigrams,
+    row_skip_indices: Vec<PosPair>,
+    bigram_penalties: Vec<BigramPenalty>,
+    /// Whether a position is in any of `bigram_penalties`, so swaps that don't touch them can use
+    /// the cached score.
+    penalty_positions: [bool; 30],
+    /// Positions in `[avoid_keys]`.
+    avoided_keys: [bool; 30],
+    /// Whether a character is too common to go on an avoided key, by character. Empty when any
+    /// character can.
+    banned_chars: Vec<bool>,
+
+    /// Only needed to generate layouts and for a few stats, so it is built the first time one of
+    /// them needs it.
+    trigram_index: OnceCell<TrigramIndex>,
+    trigram_table: TrigramTable,
 
     pub weights: Weights,
+    /// Amount of the most common trigrams that are scored, both during generation and after.
+    /// Change it with `rebuild_trigrams`, which also rebuilds what is made from the trigrams.
+    pub trigram_precision: usize,
+    /// Thumb space is on for `space_trigram_stats`.
+    pub space_thumb: SpaceThumb,
+    /// Stats generated layouts aren't allowed to go over once they are under them.
+    pub constraints: Vec<Constraint>,
+    pub seed_strategy: SeedStrategy,
+    pub swap_policy: SwapPolicy,
+    /// Whether every 3 keys are rotated once swaps don't improve a layout anymore, instead of only
+    /// the keys of a column.
+    pub rotations: bool,
+    /// Whether `generate` keeps the mirrored version of a layout when it scores better.
+    pub try_mirrored: bool,
+    /// How willing optimizing is to make a swap that lowers the score once no swap improves a
+    /// layout anymore. At 0 it never does.
+    pub temperature: f64,
+    /// Whether optimizing starts with fewer trigrams than `trigram_precision`, and only uses all
+    /// of them once the layout is close to done.
+    pub progressive_precision: bool,
+    /// Amount of populations `generate_islands` splits generation over.
+    pub islands: usize,
+    /// Layouts every island generates before the islands exchange their best ones.
+    pub migration_interval: usize,
+    /// Characters `punctuation_costs` reports on.
+    pub punctuation: Vec<u8>,
+    /// Keys of the shortcuts in `[weights.shortcuts]`.
+    pub shortcut_keys: Vec<u8>,
+    /// Whether `improve` can move punctuation, or keeps it where it is.
+    pub move_punctuation: bool,
+    /// Keys generation keeps in place, from `locked_hands` and `column_units`.
+    pub locks: Locks,
+    vowels: Vec<u8>,
+    consonants: Vec<u8>,
     pub layouts: IndexMap<String, FastLayout, BuildHasherDefault<fxhash::FxHasher>>,
+    /// Metadata of every layout `load_layouts` found, by name.
+    pub layout_metadata: FxHashMap<String, LayoutMetadata>,
+    /// Second layer of every layout `load_layouts` found that has one, by name.
+    pub layout_layers: FxHashMap<String, Layer>,
+    /// Magic key of every layout `load_layouts` found that has one, by name.
+    pub layout_magic: FxHashMap<String, MagicKey>,
+    /// Hash of everything scores depend on, which decides whether cached scores can be used.
+    score_key: u64,
+    /// Every language `combined` mixed, with its share and a generator for it alone. Empty for a
+    /// single language.
+    pub languages: Vec<(String, f64, LayoutGeneration)>,
+    pub external_metric: ExternalMetric,
 }
 
 impl LayoutGeneration {
-    pub fn new<P>(language: &str, base_path: P, config: Option<Config>) -> Result<Self>
+    /// Loads the data of `language` from `base_path/language_data`. Without a `config`, it is read
+    /// from `config.toml`.
+    pub fn new<P>(
+        language: &str,
+        base_path: P,
+        config: Option<Config>,
+    ) -> Result<Self, RusylyzerError>
     where
         P: AsRef<Path>,
     {
-        let config = config.unwrap_or_else(|| Config::new());
+        Self::from_source(language, &FileSource::new(base_path), config)
+    }
 
-        if let Ok(mut data) =
-            LanguageData::from_file(base_path.as_ref().join("language_data"), language)
-        {
-            let chars_fg = data.convert_u8.to(chars_for_generation(language));
-            let mut chars_for_generation: [u8; 30] = chars_fg.try_into().unwrap();
-            chars_for_generation.sort_by(|&a, &b| {
-                let a = data.characters.get(a as usize).unwrap_or(&0.0);
-                let b = data.characters.get(b as usize).unwrap_or(&0.0);
-                b.partial_cmp(a).unwrap()
-            });
+    /// Loads the data of `language` from `source`. Without a `config`, it is read from there too.
+    pub fn from_source(
+        language: &str,
+        source: &dyn DataSource,
+        config: Option<Config>,
+    ) -> Result<Self, RusylyzerError> {
+        let config = match config {
+            Some(config) => config,
+            None => Config::from_source(source, None)?,
+        };
 
-            Ok(Self {
+        let start = Instant::now();
+        let data = LanguageData::from_source(source, language).map_err(|e| {
+            RusylyzerError::LanguageData {
                 language: language.to_string(),
-                chars_for_generation,
-                weighted_bigrams: Self::weighted_bigrams(&data, &config.weights),
-                per_char_trigrams: Self::per_char_trigrams(
-                    &data.trigrams,
-                    data.characters.len() as u8,
-                    config.defaults.trigram_precision,
-                ),
-                convert_u8: data.convert_u8.clone(),
-                repeat_key: data.convert_u8.to_single('@') as usize,
-                data,
+                reason: e.to_string(),
+            }
+        })?;
+        log::info!("loaded the {language} data in {:.2?}", start.elapsed());
 
-                fspeed_vals: get_fspeed(config.weights.lateral_penalty),
-                effort_map: get_effort_map(config.weights.heatmap, config.defaults.keyboard_type),
-                scissor_indices: get_scissor_indices(),
-                lsb_indices: get_lsb_indices(),
+        Ok(Self::from_data(language, data, config))
+    }
 
-                weights: config.weights,
-                layouts: IndexMap::default(),
-            })
+    /// Creates a generator for several languages at once, where each makes up its share of the
+    /// corpus, like `[("english", 0.6), ("german", 0.4)]`. The characters used for generation are
+    /// those of the first language.
+    pub fn combined<P>(
+        languages: &[(&str, f64)],
+        base_path: P,
+        config: Option<Config>,
+    ) -> Result<Self, RusylyzerError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::combined_from_source(languages, &FileSource::new(base_path), config)
+    }
+
+    /// Same as `combined`, reading everything from `source`.
+    pub fn combined_from_source(
+        languages: &[(&str, f64)],
+        source: &dyn DataSource,
+        config: Option<Config>,
+    ) -> Result<Self, RusylyzerError> {
+        let config = match config {
+            Some(config) => config,
+            None => Config::from_source(source, None)?,
+        };
+
+        let data = LanguageData::combined_from_source(source, languages).map_err(|e| {
+            RusylyzerError::LanguageData {
+                language: languages.iter().map(|(l, _)| l).join("+"),
+                reason: e.to_string(),
+            }
+        })?;
+
+        let total = languages.iter().map(|(_, share)| share).sum::<f64>();
+        let mut per_language = Vec::with_capacity(languages.len());
+        for &(language, share) in languages {
+            let gen = Self::from_source(language, source, Some(config.clone()))?;
+            per_language.push((language.to_string(), share / total, gen));
+        }
+
+        let mut res = Self::from_data(languages[0].0, data, config);
+        res.languages = per_language;
+        Ok(res)
+    }
+
+    /// Creates a generator from language data that is already loaded. The characters used for
+    /// generation are those of `chars_language`.
+    pub(crate) fn from_data(chars_language: &str, mut data: LanguageData, config: Config) -> Self {
+        data.weigh_bigrams(&config.weights);
+        let chars_fg = data.convert_u8.to(chars_for_generation(chars_language));
+        let mut chars_for_generation: [u8; 30] = chars_fg.try_into().unwrap();
+        chars_for_generation.sort_by(|&a, &b| {
+            let a = data.characters.get(a as usize).unwrap_or(&0.0);
+            let b = data.characters.get(b as usize).unwrap_or(&0.0);
+            b.partial_cmp(a).unwrap()
+        });
+        let score_key = Self::score_key(&data, &config);
+        let fingers = config.fingers;
+        let (vowels, consonants) =
+            Self::seed_sets(&data.convert_u8, &chars_for_generation, &config.generation);
+        let punctuation = config
+            .generation
+            .punctuation
+            .chars()
+            .map(|c| data.convert_u8.to_single_lossy(c))
+            .filter(|&u| (u as usize) < data.characters.len())
+            .unique()
+            .collect();
+        let shortcut_keys = config
+            .weights
+            .shortcuts
+            .keys
+            .chars()
+            .map(|c| data.convert_u8.to_single_lossy(c))
+            .filter(|&u| (u as usize) < data.characters.len())
+            .unique()
+            .collect();
+        let home_distances = get_home_distances(&config.defaults.keyboard_type, &fingers);
+        let sfb_travel = get_sfb_travel(&config.defaults.keyboard_type, &fingers);
+        let key_distances = std::array::from_fn(|i1| {
+            std::array::from_fn(|i2| key_distance(i1, i2, &config.defaults.keyboard_type))
+        });
+        let mut avoided_keys = [false; 30];
+        for &(i, _) in config.avoid_keys.iter() {
+            avoided_keys[i] = true;
+        }
+        let ban = config.generation.avoided_key_ban;
+        let banned_chars = if ban > 0.0 && !config.avoid_keys.is_empty() {
+            data.characters.iter().map(|&f| f * 100.0 > ban).collect()
         } else {
-            anyhow::bail!("Getting language data failed")
+            Vec::new()
+        };
+
+        // placeholders need a code of their own, or every unknown character would be one
+        let mut convert_u8 = data.convert_u8.clone();
+        convert_u8.try_to_single(PLACEHOLDER);
+
+        Self {
+            language: data.language.clone(),
+            chars_for_generation,
+            trigram_index: OnceCell::new(),
+            trigram_table: config.trigram_rules.build_table(),
+            convert_u8,
+            repeat_key: data.convert_u8.to_single('@') as usize,
+            data,
+
+            fspeed_vals: Self::dominance_fspeed(
+                Self::finger_speed_fspeed(
+                    get_finger_fspeed(&fingers, config.weights.lateral_penalty),
+                    &config.weights.fingers,
+                    &fingers,
+                ),
+                &config.weights.hand_dominance,
+                &fingers,
+            ),
+            finger_ranges: get_finger_pairs(&fingers).1,
+            finger_keys: std::array::from_fn(|f| (0..30).filter(|&i| fingers[i] == f).collect()),
+            fingers,
+            effort_map: Self::avoided_effort(
+                Self::travel_effort(
+                    Self::dominance_effort(
+                        get_effort_map(config.weights.heatmap, config.defaults.keyboard_type),
+                        &config.weights.hand_dominance,
+                        &fingers,
+                    ),
+                    &home_distances,
+                    config.weights.travel,
+                ),
+                &config.avoid_keys,
+            ),
+            home_distances,
+            key_distances,
+            sfb_travel,
+            scissor_indices: get_scissor_indices(),
+            lsb_indices: get_lsb_indices(),
+            row_skip_indices: get_row_skip_indices(&fingers),
+            penalty_positions: std::array::from_fn(|i| {
+                config
+                    .bigram_penalties
+                    .iter()
+                    .any(|p| p.pairs.iter().any(|pair| pair.0 == i || pair.1 == i))
+            }),
+            bigram_penalties: config.bigram_penalties,
+            avoided_keys,
+            banned_chars,
+
+            weights: config.weights,
+            trigram_precision: config.defaults.trigram_precision,
+            space_thumb: config.defaults.space_thumb,
+            constraints: config.constraints,
+            seed_strategy: config.generation.seed_strategy,
+            swap_policy: config.generation.swap_policy,
+            rotations: config.generation.rotations,
+            try_mirrored: config.generation.try_mirrored,
+            temperature: config.generation.temperature,
+            progressive_precision: config.generation.progressive_precision,
+            islands: config.generation.islands,
+            migration_interval: config.generation.migration_interval,
+            punctuation,
+            shortcut_keys,
+            move_punctuation: config.generation.move_punctuation,
+            locks: Locks::new(&config.generation, &fingers),
+            vowels,
+            consonants,
+            layouts: IndexMap::default(),
+            layout_metadata: FxHashMap::default(),
+            layout_layers: FxHashMap::default(),
+            layout_magic: FxHashMap::default(),
+            score_key,
+            languages: Vec::new(),
+            external_metric: ExternalMetric::new(config.external_metric),
         }
     }
 
+    /// Scores the `trigram_precision` most common trigrams from now on, without loading everything
+    /// again. What is made from the trigrams is built again from `data` as it is now, so this also
+    /// picks up trigrams or characters that changed in `data`, like after merging corpora.
+    pub fn rebuild_trigrams(&mut self, trigram_precision: usize) {
+        for (_, _, gen) in self.languages.iter_mut() {
+            gen.rebuild_trigrams(trigram_precision);
+        }
+        self.trigram_precision = trigram_precision;
+        self.trigram_index = OnceCell::new();
+        log::info!("scoring the {trigram_precision} most common trigrams from now on");
+
+        // scores cached with the old trigrams can't be used anymore
+        let mut hasher = fxhash::FxHasher::default();
+        self.score_key.hash(&mut hasher);
+        trigram_precision.hash(&mut hasher);
+        for (trigram, freq) in self.data.trigrams.iter() {
+            trigram.hash(&mut hasher);
+            freq.to_bits().hash(&mut hasher);
+        }
+        self.score_key = hasher.finish();
+    }
+
+    /// Hashes the language data and every part of the config that changes how layouts score.
+    fn score_key(data: &LanguageData, config: &Config) -> u64 {
+        let mut hasher = fxhash::FxHasher::default();
+
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        format!(
+            "{:?} {:?} {:?} {:?} {:?} {} {:?} {:?}",
+            config.weights,
+            config.trigram_rules,
+            config.bigram_penalties,
+            config.fingers,
+            config.defaults.keyboard_type,
+            config.defaults.trigram_precision,
+            config.avoid_keys,
+            config.external_metric
+        )
+        .hash(&mut hasher);
+
+        data.language.hash(&mut hasher);
+        data.convert_u8
+            .as_str(&(0..data.convert_u8.len()).collect::<Vec<_>>())
+            .hash(&mut hasher);
+        let freqs = data
+            .characters
+            .iter()
+            .chain(data.bigrams.iter())
+            .chain(data.skipgrams.iter())
+            .chain(data.skipgrams2.iter())
+            .chain(data.skipgrams3.iter());
+        for freq in freqs {
+            freq.to_bits().hash(&mut hasher);
+        }
+        for (trigram, freq) in data.trigrams.iter() {
+            trigram.hash(&mut hasher);
+            freq.to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Hash of everything generated layouts depend on: what they are scored with and the keys
+    /// they are made of. A `Baseline` is only used while this stays the same.
+    pub fn baseline_key(&self) -> u64 {
+        let mut hasher = fxhash::FxHasher::default();
+
+        self.score_key.hash(&mut hasher);
+        self.chars_for_generation.hash(&mut hasher);
+        hasher.finish()
+    }
+
     pub fn load_layouts<P>(
         &mut self,
         base_directory: P,
@@ -277,145 +1134,699 @@ impl LayoutGeneration {
     where
         P: AsRef<Path>,
     {
-        let mut res: IndexMap<String, FastLayout> = IndexMap::new();
-        let language_dir_path = base_directory.as_ref().join(language);
-
-        if let Ok(paths) = std::fs::read_dir(&language_dir_path) {
-            let valid = paths
-                .flatten()
-                .filter(|p| is_kb_file(p))
-                .collect::<Vec<_>>();
-
-            // let stats_dir = base_directory.as_ref().join("stats").join(language);
-            // if let Ok(false) = std::fs::try_exists(stats_dir) {
-            // 	std::fs::create_dir_all(&stats_dir)?;
-            // }
+        let source = FileSource {
+            layouts_dir: base_directory.as_ref().to_path_buf(),
+            ..Default::default()
+        };
+        Ok(self.load_layouts_from(&source, language)?)
+    }
 
-            for entry in valid {
-                if let Some(name) = layout_name(&entry) {
-                    let content = std::fs::read_to_string(entry.path())?;
-                    let layout_str = format_layout_str(&content);
-                    let layout_bytes = self.convert_u8.to(layout_str.chars());
+    /// Parses and scores every layout of `language` in `source`, sorted by score. Layouts that
+    /// can't be parsed are left out.
+    pub fn load_layouts_from(
+        &mut self,
+        source: &dyn DataSource,
+        language: &str,
+    ) -> Result<IndexMap<String, FastLayout>, RusylyzerError> {
+        let mut res: IndexMap<String, FastLayout> = IndexMap::new();
+        self.layout_metadata.clear();
+        self.layout_layers.clear();
+        self.layout_magic.clear();
+
+        // the program of an external metric can change without anything here changing
+        let cached = match self.external_metric.is_enabled() {
+            false => source.read_score_cache(language, self.score_key),
+            true => ScoreCache::new(self.score_key),
+        };
+        let mut cache = ScoreCache::new(self.score_key);
 
-                    if let Ok(mut layout) = FastLayout::try_from(layout_bytes.as_slice()) {
-                        layout.score = self.score(&layout);
-                        res.insert(name, layout);
+        for (name, content) in source.layouts(language)? {
+            let content = nfc(&content);
+            let parsed = format_layout_str(&content).and_then(|layout_str| {
+                let layout_bytes = self.convert_u8.to(layout_str.chars());
+                FastLayout::try_from(layout_bytes.as_slice())
+            });
+            let parsed = parsed.and_then(|mut l| {
+                l.set_fingers(&self.fingers);
+                for (thumb, c) in format_thumbs_str(&content).into_iter().enumerate() {
+                    if let Some(c) = c {
+                        l.set_thumb(thumb, self.convert_u8.to_single(c))?;
+                    }
+                }
+                Ok(l)
+            });
+            let parsed =
+                parsed.and_then(|l| Ok((l, Layer::parse(&content, &mut self.convert_u8)?)));
+            let parsed = parsed.and_then(|(l, layer)| {
+                let magic = MagicKey::parse(&content)?;
+                if let Some(magic) = &magic {
+                    let key = self.convert_u8.to_single(magic.key);
+                    if !l.matrix.contains(&key) && !l.thumbs.contains(&key) {
+                        return Err(RusylyzerError::LayoutParse(format!(
+                            "the magic key '{}' isn't on the layout",
+                            magic.key
+                        )));
+                    }
+                }
+                Ok((l, layer, magic))
+            });
 
-                    // self.get_layout_stats(&layout);
-                    } else {
-                        println!("layout {} is not formatted correctly", name);
+            match parsed {
+                Ok((mut layout, layer, magic)) => {
+                    let key = layout.formatted_string(&self.convert_u8);
+                    layout.score = match cached.get(&name, &key) {
+                        Some(score) => score,
+                        None => {
+                            log::debug!("{name} isn't in the score cache, so it is scored again");
+                            self.checked_score(&name, &layout)?
+                        }
+                    };
+                    cache.insert(name.clone(), key, layout.score);
+
+                    let metadata = LayoutMetadata::parse(&content);
+                    if !metadata.is_empty() {
+                        self.layout_metadata.insert(name.clone(), metadata);
+                    }
+                    if let Some(layer) = layer {
+                        self.layout_layers.insert(name.clone(), layer);
                     }
+                    if let Some(magic) = magic {
+                        self.layout_magic.insert(name.clone(), magic);
+                    }
+                    res.insert(name, layout);
                 }
+                Err(e) => log::warn!("layout {name} is not formatted correctly: {e}"),
             }
+        }
+        log::info!("loaded {} {language} layouts", res.len());
 
-            res.sort_by(|_, a, _, b| a.score.partial_cmp(&b.score).unwrap());
-        } else {
-            std::fs::create_dir(language_dir_path)?;
+        if cache != cached {
+            if let Err(e) = source.write_score_cache(language, &cache) {
+                log::warn!("layout scores could not be cached: {e}");
+            }
         }
+        res.sort_by(|_, a, _, b| TotalScore(a.score).cmp(&TotalScore(b.score)));
 
         Ok(res)
     }
 
+    /// Every constraint together with the value of its stat on `layout` and whether it is met.
+    pub fn check_constraints(&self, layout: &FastLayout) -> Vec<(&Constraint, f64, bool)> {
+        if self.constraints.is_empty() {
+            return Vec::new();
+        }
+
+        let stats = self.get_layout_stats(layout);
+        self.constraints
+            .iter()
+            .map(|constraint| {
+                let value = constraint.value(&stats);
+                (constraint, value, value <= constraint.max)
+            })
+            .collect()
+    }
+
+    fn constraints_met(&self, layout: &FastLayout) -> Vec<bool> {
+        self.check_constraints(layout)
+            .into_iter()
+            .map(|(_, _, met)| met)
+            .collect()
+    }
+
     pub fn get_layout_stats(&self, layout: &FastLayout) -> LayoutStats {
-        let sfb = self.bigram_percent(layout, "sfbs");
-        let dsfb = self.bigram_percent(layout, "skipgrams");
-        let dsfb2 = self.bigram_percent(layout, "skipgrams2");
-        let dsfb3 = self.bigram_percent(layout, "skipgrams3");
+        let sfb = self.same_finger_percent(layout, &self.data.bigrams);
+        let sfb_per_finger = self.same_finger_per_finger(layout, &self.data.bigrams, false);
+        let sfb_distance_per_finger = self.same_finger_per_finger(layout, &self.data.bigrams, true);
+        let sfb_distance = sfb_distance_per_finger.iter().sum();
+        let dsfb = self.same_finger_percent(layout, &self.data.skipgrams);
+        let dsfb2 = self.same_finger_percent(layout, &self.data.skipgrams2);
+        let dsfb3 = self.same_finger_percent(layout, &self.data.skipgrams3);
         let cache = self.initialize_cache(layout);
         let fspeed = cache.fspeed_total;
         let finger_speed = cache.fspeed;
         let scissors = self.scissor_score(layout) / self.weights.scissors;
         let lsbs = self.lsb_score(layout) / self.weights.lsbs;
+        let row_skips = self.row_skip_percent(layout);
         let trigram_stats = self.trigram_stats(layout, usize::MAX);
+        let onehand_rolls = self.onehand_rolls(layout);
+        let hand_usage = self.hand_usage(layout);
+        let row_usage = self.row_usage(layout);
+        let hand_alternation = self.hand_alternation(layout);
+        let shortcut_reach = self.shortcut_reach(layout);
+        let travel = self.travel(layout);
+        let bigram_penalties = self
+            .bigram_penalties
+            .iter()
+            .map(|penalty| penalty.name.clone())
+            .zip(self.bigram_penalty_percents(layout))
+            .collect();
 
         LayoutStats {
             sfb,
+            sfb_per_finger,
+            sfb_distance,
+            sfb_distance_per_finger,
             dsfb,
             dsfb2,
             dsfb3,
             fspeed,
             finger_speed,
+            hand_usage,
+            row_usage,
+            hand_alternation,
+            shortcut_reach,
             scissors,
             lsbs,
+            row_skips,
             trigram_stats,
+            onehand_rolls,
+            travel,
+            bigram_penalties,
         }
     }
 
-    pub fn bigram_percent(&self, layout: &FastLayout, bigram_type: &str) -> f64 {
-        let data = match bigram_type {
-            "bigram" | "bigrams" | "sfb" | "sfbs" => &self.data.bigrams,
-            "skipgram" | "skipgrams" | "dsfb" | "dsfbs" => &self.data.skipgrams,
-            "skipgram2" | "skipgrams2" | "dsfb2" | "dsfbs2" => &self.data.skipgrams2,
-            "skipgram3" | "skipgrams3" | "dsfb3" | "dsfbs3" => &self.data.skipgrams3,
-            _ => panic!("bigram type {bigram_type} does not exist!"),
-        };
+    /// Share of the characters on the layout typed by the left and right hand respectively.
+    pub fn hand_usage(&self, layout: &FastLayout) -> [f64; 2] {
+        let mut res = [0.0; 2];
 
-        let mut res = 0.0;
-        let len = self.data.characters.len();
+        for (i, &c) in layout.matrix.iter().enumerate() {
+            let freq = *self.data.characters.get(c as usize).unwrap_or(&0.0);
+            res[!is_left_hand(self.fingers[i]) as usize] += freq;
+        }
 
-        for (PosPair(i1, i2), _) in self.fspeed_vals {
-            let c1 = unsafe { layout.cu(i1) } as usize;
-            let c2 = unsafe { layout.cu(i2) } as usize;
+        let total = res[0] + res[1];
+        if total > 0.0 {
+            res[0] /= total;
+            res[1] /= total;
+        }
+        res
+    }
 
-            // if c1 != self.repeat_key && c2 != self.repeat_key {
-            // 	res += data.get(c1 * len + c2).unwrap_or(&0.0);
-            // 	res += data.get(c2 * len + c1).unwrap_or(&0.0);
-            // } else {
-            // 	res += data.get(c1 * len + c2).unwrap_or(&0.0);
-            // 	res += data.get(c2 * len + c1).unwrap_or(&0.0);
-            // }
+    /// Share of the characters on the layout typed on the top, home and bottom row respectively.
+    pub fn row_usage(&self, layout: &FastLayout) -> [f64; 3] {
+        let mut res = [0.0; 3];
 
-            res += data.get(c1 * len + c2).unwrap_or(&0.0);
-            res += data.get(c2 * len + c1).unwrap_or(&0.0);
+        for (i, &c) in layout.matrix.iter().enumerate() {
+            res[i / 10] += *self.data.characters.get(c as usize).unwrap_or(&0.0);
+        }
+
+        let total = res.iter().sum::<f64>();
+        if total > 0.0 {
+            res.iter_mut().for_each(|usage| *usage /= total);
         }
         res
     }
 
-    pub fn sfbs(&self, layout: &FastLayout, top_n: usize) -> Vec<(String, f64)> {
-        self.fspeed_vals
-            .iter()
-            .map(|(p, _)| {
-                let u1 = layout.c(p.0);
-                let u2 = layout.c(p.1);
-                let bigram = self.convert_u8.as_str(&[u1, u2]);
+    /// Penalty for how far the rows of `layout` are outside of the `row_usage` limits.
+    fn row_usage_score(&self, layout: &FastLayout) -> f64 {
+        let row_usage = &self.weights.row_usage;
+        if row_usage.penalty == 0.0 {
+            return 0.0;
+        }
+        row_usage.penalty * row_usage.distance(&self.row_usage(layout))
+    }
 
-                let i = (u1 as usize) * self.data.characters.len() + (u2 as usize);
-                let freq = self.data.bigrams[i];
+    /// Whether shortcuts on position `i` can be typed with the left hand while its pinky holds
+    /// ctrl, which is on the home or bottom row.
+    fn reaches_shortcut(&self, i: usize) -> bool {
+        i >= 10 && is_left_hand(self.fingers[i])
+    }
 
-                (bigram, freq)
-            })
-            .sorted_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap())
-            .take(top_n)
-            .collect::<Vec<_>>()
+    /// Shortcut keys that aren't on the home and bottom row of the left hand of `layout`,
+    /// including the ones that aren't on it at all.
+    pub fn unreachable_shortcuts(&self, layout: &FastLayout) -> Vec<u8> {
+        self.shortcut_keys
+            .iter()
+            .copied()
+            .filter(|&c| !(10..30).any(|i| layout.matrix[i] == c && self.reaches_shortcut(i)))
+            .collect()
     }
 
-    pub fn trigram_stats(&self, layout: &FastLayout, trigram_precision: usize) -> TrigramStats {
-        use TrigramPattern::*;
+    /// Share of the shortcut keys on the home and bottom row of the left hand, or 1 without any.
+    pub fn shortcut_reach(&self, layout: &FastLayout) -> f64 {
+        if self.shortcut_keys.is_empty() {
+            return 1.0;
+        }
+        let unreachable = self.unreachable_shortcuts(layout).len();
+        1.0 - unreachable as f64 / self.shortcut_keys.len() as f64
+    }
 
-        let mut freqs = TrigramStats::default();
+    /// Penalty for the share of the shortcut keys out of reach of the left hand.
+    fn shortcut_score(&self, layout: &FastLayout) -> f64 {
+        let penalty = self.weights.shortcuts.penalty;
+        if penalty == 0.0 {
+            return 0.0;
+        }
+        penalty * (1.0 - self.shortcut_reach(layout))
+    }
 
-        for (trigram, freq) in self.data.trigrams.iter().take(trigram_precision) {
-            match layout.get_trigram_pattern(trigram) {
-                Alternate => freqs.alternates += freq,
-                AlternateSfs => freqs.alternates_sfs += freq,
-                Inroll => freqs.inrolls += freq,
-                Outroll => freqs.outrolls += freq,
-                Onehand => freqs.onehands += freq,
-                Redirect => freqs.redirects += freq,
-                RedirectSfs => freqs.redirects_sfs += freq,
-                BadRedirect => freqs.bad_redirects += freq,
-                BadRedirectSfs => freqs.bad_redirects_sfs += freq,
-                Sfb => freqs.sfbs += freq,
-                BadSfb => freqs.bad_sfbs += freq,
-                Sft => freqs.sfts += freq,
-                Other => freqs.other += freq,
-                Invalid => freqs.invalid += freq,
+    /// Frequency of the bigrams on `layout` typed with one hand after the other.
+    pub fn hand_alternation(&self, layout: &FastLayout) -> f64 {
+        let mut res = 0.0;
+        let len = self.data.characters.len();
+        let on_left = |i: &usize| is_left_hand(self.fingers[*i]);
+
+        for i1 in (0..30).filter(on_left) {
+            for i2 in (0..30).filter(|i| !on_left(i)) {
+                let c1 = unsafe { layout.cu(i1) } as usize;
+                let c2 = unsafe { layout.cu(i2) } as usize;
+                res += self.data.bigrams.get(c1 * len + c2).unwrap_or(&0.0);
+                res += self.data.bigrams.get(c2 * len + c1).unwrap_or(&0.0);
             }
         }
-        freqs
+
+        res
     }
 
+    /// Penalty for how far the hand alternation of `layout` is from its target.
+    fn hand_alternation_score(&self, layout: &FastLayout) -> f64 {
+        let alternation = &self.weights.hand_alternation;
+        if alternation.penalty == 0.0 {
+            return 0.0;
+        }
+        alternation.penalty * alternation.distance(self.hand_alternation(layout))
+    }
+
+    /// Average distance fingers travel per keystroke in key widths, assuming every finger moves
+    /// back to its home key after pressing something, unless it presses the next key as well.
+    pub fn travel(&self, layout: &FastLayout) -> f64 {
+        let home = layout
+            .matrix
+            .iter()
+            .zip(self.home_distances)
+            .map(|(&c, dist)| self.data.characters.get(c as usize).unwrap_or(&0.0) * dist * 2.0)
+            .sum::<f64>();
+
+        let len = self.data.characters.len();
+        let sfb = self
+            .fspeed_vals
+            .iter()
+            .zip(self.sfb_travel.iter())
+            .map(|((PosPair(i1, i2), _), travel)| {
+                let c1 = layout.c(*i1) as usize;
+                let c2 = layout.c(*i2) as usize;
+                let freq = self.data.bigrams.get(c1 * len + c2).unwrap_or(&0.0)
+                    + self.data.bigrams.get(c2 * len + c1).unwrap_or(&0.0);
+                freq * travel
+            })
+            .sum::<f64>();
+
+        home + sfb
+    }
+
+    fn bigram_data(&self, bigram_type: &str) -> Result<&BigramData, RusylyzerError> {
+        match bigram_type {
+            "bigram" | "bigrams" | "sfb" | "sfbs" => Ok(&self.data.bigrams),
+            "skipgram" | "skipgrams" | "dsfb" | "dsfbs" => Ok(&self.data.skipgrams),
+            "skipgram2" | "skipgrams2" | "dsfb2" | "dsfbs2" => Ok(&self.data.skipgrams2),
+            "skipgram3" | "skipgrams3" | "dsfb3" | "dsfbs3" => Ok(&self.data.skipgrams3),
+            _ => Err(RusylyzerError::BigramType(bigram_type.to_string())),
+        }
+    }
+
+    pub fn bigram_percent(
+        &self,
+        layout: &FastLayout,
+        bigram_type: &str,
+    ) -> Result<f64, RusylyzerError> {
+        let data = self.bigram_data(bigram_type)?;
+        Ok(self.same_finger_percent(layout, data))
+    }
+
+    /// Like [`bigram_percent`](Self::bigram_percent), but every bigram is multiplied by the
+    /// distance between its keys as used for finger speed, so far sfbs count for more.
+    pub fn bigram_percent_weighted(
+        &self,
+        layout: &FastLayout,
+        bigram_type: &str,
+    ) -> Result<f64, RusylyzerError> {
+        let per_finger = self.bigram_percent_per_finger(layout, bigram_type, true)?;
+        Ok(per_finger.iter().sum())
+    }
+
+    /// Same finger bigrams of `bigram_type` on every finger, from left pinky to right pinky,
+    /// weighted by distance when `weighted` is set.
+    pub fn bigram_percent_per_finger(
+        &self,
+        layout: &FastLayout,
+        bigram_type: &str,
+        weighted: bool,
+    ) -> Result<[f64; 8], RusylyzerError> {
+        let data = self.bigram_data(bigram_type)?;
+        Ok(self.same_finger_per_finger(layout, data, weighted))
+    }
+
+    fn same_finger_per_finger(
+        &self,
+        layout: &FastLayout,
+        data: &BigramData,
+        weighted: bool,
+    ) -> [f64; 8] {
+        // without hand dominance and finger speeds, which fspeed_vals has applied
+        let distances = get_finger_fspeed(&self.fingers, self.weights.lateral_penalty);
+        let len = self.data.characters.len();
+        let mut res = [0.0; 8];
+
+        for (col, finger) in res.iter_mut().enumerate() {
+            let (start, count) = self.col_to_start_len(col);
+
+            for (PosPair(i1, i2), dist) in &distances[start..(start + count)] {
+                let c1 = layout.c(*i1) as usize;
+                let c2 = layout.c(*i2) as usize;
+                let freq = data.get(c1 * len + c2).unwrap_or(&0.0)
+                    + data.get(c2 * len + c1).unwrap_or(&0.0);
+
+                *finger += if weighted { freq * dist } else { freq };
+            }
+        }
+        res
+    }
+
+    /// Share of the bigrams in `data` that are typed with the same finger on `layout`.
+    fn same_finger_percent(&self, layout: &FastLayout, data: &BigramData) -> f64 {
+        let mut res = 0.0;
+        let len = self.data.characters.len();
+
+        for &(PosPair(i1, i2), _) in self.fspeed_vals.iter() {
+            let c1 = unsafe { layout.cu(i1) } as usize;
+            let c2 = unsafe { layout.cu(i2) } as usize;
+
+            // if c1 != self.repeat_key && c2 != self.repeat_key {
+            // 	res += data.get(c1 * len + c2).unwrap_or(&0.0);
+            // 	res += data.get(c2 * len + c1).unwrap_or(&0.0);
+            // } else {
+            // 	res += data.get(c1 * len + c2).unwrap_or(&0.0);
+            // 	res += data.get(c2 * len + c1).unwrap_or(&0.0);
+            // }
+
+            res += data.get(c1 * len + c2).unwrap_or(&0.0);
+            res += data.get(c2 * len + c1).unwrap_or(&0.0);
+        }
+        res
+    }
+
+    pub fn sfbs(&self, layout: &FastLayout, top_n: usize) -> Vec<(String, f64)> {
+        self.fspeed_vals
+            .iter()
+            .map(|(p, _)| {
+                let u1 = layout.c(p.0);
+                let u2 = layout.c(p.1);
+                let bigram = self.convert_u8.as_str(&[u1, u2]);
+
+                let i = (u1 as usize) * self.data.characters.len() + (u2 as usize);
+                let freq = self.data.bigrams[i];
+
+                (bigram, freq)
+            })
+            .sorted_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap())
+            .take(top_n)
+            .collect::<Vec<_>>()
+    }
+
+    /// The `top_n` most common same finger skipgrams of `layout` with 1, 2 or 3 keys in between,
+    /// most common first. Both orders of a pair of keys are a skipgram of their own.
+    pub fn sfs(&self, layout: &FastLayout, top_n: usize) -> Vec<SameFingerSkipgram> {
+        let len = self.data.characters.len();
+        let data = [
+            &self.data.skipgrams,
+            &self.data.skipgrams2,
+            &self.data.skipgrams3,
+        ];
+        let mut res = Vec::new();
+
+        for &(PosPair(i1, i2), _) in self.fspeed_vals.iter() {
+            let (u1, u2) = (layout.c(i1), layout.c(i2));
+            for (c1, c2) in [(u1, u2), (u2, u1)] {
+                for (gap, data) in data.iter().enumerate() {
+                    let freq = *data.get(c1 as usize * len + c2 as usize).unwrap_or(&0.0);
+                    if freq > 0.0 {
+                        res.push(SameFingerSkipgram {
+                            chars: [
+                                self.convert_u8.from_single(c1),
+                                self.convert_u8.from_single(c2),
+                            ],
+                            gap: gap + 1,
+                            freq,
+                        });
+                    }
+                }
+            }
+        }
+        res.sort_by_key(|s| Reverse(TotalScore(s.freq)));
+        res.truncate(top_n);
+        res
+    }
+
+    pub fn trigram_stats(&self, layout: &FastLayout, trigram_precision: usize) -> TrigramStats {
+        let trigrams = self.data.trigrams.iter().take(trigram_precision).copied();
+        self.classify_trigrams(layout, trigrams)
+    }
+
+    /// Trigram stats of the trigrams with a space in them, like the end of one word and the start
+    /// of the next, with space on the `space_thumb` instead of what was there. `None` if there is
+    /// no space thumb, or the language data was made before it had these trigrams.
+    pub fn space_trigram_stats(&self, layout: &FastLayout) -> Option<TrigramStats> {
+        let thumb = self.space_thumb.thumb()?;
+        if self.data.space_trigrams.is_empty() {
+            return None;
+        }
+
+        // space needs a character of its own, which can be any that isn't on the layout
+        let space = layout
+            .char_to_finger
+            .iter()
+            .position(|&f| f == usize::MAX)? as u8;
+        let mut with_space = layout.clone();
+        with_space.set_thumb(thumb, space).ok()?;
+
+        // checked on the layout without space, as a character of the corpus can have its code
+        let on_layout = |u: u8| {
+            layout
+                .char_to_finger
+                .get(u as usize)
+                .is_some_and(|&f| f != usize::MAX)
+        };
+        let trigrams = self.data.space_trigrams.iter().map(|&(trigram, freq)| {
+            let trigram = trigram.map(|u| match u {
+                SPACE => space,
+                u if on_layout(u) => u,
+                // anything else is invalid, so it can't be mistaken for space
+                _ => SPACE,
+            });
+            (trigram, freq)
+        });
+        Some(self.classify_trigrams(&with_space, trigrams))
+    }
+
+    fn classify_trigrams<I>(&self, layout: &FastLayout, trigrams: I) -> TrigramStats
+    where
+        I: IntoIterator<Item = ([u8; 3], f64)>,
+    {
+        use TrigramPattern::*;
+
+        let mut freqs = TrigramStats::default();
+        let rows = char_rows(layout);
+
+        for (trigram, freq) in trigrams {
+            let trigram = &trigram;
+            match layout.get_trigram_pattern_in(&self.trigram_table, trigram) {
+                Alternate => freqs.alternates += freq,
+                AlternateSfs => freqs.alternates_sfs += freq,
+                pattern @ (Inroll | Outroll) => freqs.add_roll(
+                    pattern == Inroll,
+                    roll_hand(layout, trigram),
+                    roll_placement(layout, &rows, trigram),
+                    freq,
+                ),
+                Onehand => freqs.onehands += freq,
+                Redirect => freqs.redirects += freq,
+                RedirectSfs => freqs.redirects_sfs += freq,
+                BadRedirect => freqs.bad_redirects += freq,
+                BadRedirectSfs => freqs.bad_redirects_sfs += freq,
+                Sfb => freqs.sfbs += freq,
+                BadSfb => freqs.bad_sfbs += freq,
+                Sft => freqs.sfts += freq,
+                Other => freqs.other += freq,
+                Invalid => freqs.invalid += freq,
+            }
+        }
+        freqs
+    }
+
+    /// Fraction of the corpus' characters, bigrams and trigrams that can be typed using only the
+    /// characters on `layout`. Everything else ends up as `TrigramPattern::Invalid` or is ignored.
+    pub fn coverage(&self, layout: &FastLayout) -> Coverage {
+        let len = self.data.characters.len();
+        let on_layout = |c: usize| matches!(layout.char_to_finger.get(c), Some(&f) if f != usize::MAX);
+        let fraction = |covered: f64, total: f64| if total > 0.0 { covered / total } else { 0.0 };
+
+        let mut chars_total = 0.0;
+        let mut chars_covered = 0.0;
+        for (c, freq) in self.data.characters.iter().enumerate() {
+            chars_total += freq;
+            if on_layout(c) {
+                chars_covered += freq;
+            }
+        }
+
+        let mut bigrams_total = 0.0;
+        let mut bigrams_covered = 0.0;
+        for (i, freq) in self.data.bigrams.iter().enumerate() {
+            bigrams_total += freq;
+            if on_layout(i / len) && on_layout(i % len) {
+                bigrams_covered += freq;
+            }
+        }
+
+        let mut trigrams_total = 0.0;
+        let mut trigrams_covered = 0.0;
+        for (trigram, freq) in self.data.trigrams.iter() {
+            trigrams_total += freq;
+            let pattern = layout.get_trigram_pattern_in(&self.trigram_table, trigram);
+            if pattern != TrigramPattern::Invalid {
+                trigrams_covered += freq;
+            }
+        }
+
+        Coverage {
+            characters: fraction(chars_covered, chars_total),
+            bigrams: fraction(bigrams_covered, bigrams_total),
+            trigrams: fraction(trigrams_covered, trigrams_total),
+        }
+    }
+
+    /// Characters that make trigrams invalid on `layout` because they aren't on it, with the
+    /// frequency of the trigrams they are in, most frequent first.
+    pub fn invalid_trigram_chars(&self, layout: &FastLayout) -> Vec<(char, f64)> {
+        let on_layout =
+            |c: u8| matches!(layout.char_to_finger.get(c as usize), Some(&f) if f != usize::MAX);
+        let mut freqs = FxHashMap::<u8, f64>::default();
+
+        for (trigram, freq) in self.data.trigrams.iter() {
+            let pattern = layout.get_trigram_pattern_in(&self.trigram_table, trigram);
+            if pattern != TrigramPattern::Invalid {
+                continue;
+            }
+            for c in trigram.iter().copied().filter(|&c| !on_layout(c)).unique() {
+                *freqs.entry(c).or_default() += freq;
+            }
+        }
+
+        let mut res = freqs
+            .into_iter()
+            .map(|(c, freq)| (self.convert_u8.from_single(c), freq))
+            .collect::<Vec<_>>();
+        res.sort_by(|(c1, f1), (c2, f2)| f2.partial_cmp(f1).unwrap().then(c1.cmp(c2)));
+        res
+    }
+
+    /// Characters of the language that generation never puts on the 30 keys, with their frequency,
+    /// most frequent first. Characters that didn't fit in the language data are included as well.
+    pub fn excluded_chars(&self) -> Vec<(char, f64)> {
+        let mut res = self
+            .data
+            .characters
+            .iter()
+            .enumerate()
+            .filter(|&(c, &freq)| freq > 0.0 && !self.chars_for_generation.contains(&(c as u8)))
+            .map(|(c, &freq)| (self.convert_u8.from_single(c as u8), freq))
+            .chain(self.data.dropped_chars.iter().copied())
+            .collect::<Vec<_>>();
+
+        res.sort_by(|(_, f1), (_, f2)| f2.partial_cmp(f1).unwrap());
+        res
+    }
+
+    /// Estimates how often three keys rolled on one hand are followed by a key on the other hand.
+    /// Not all language data has quadgrams, so overlapping trigrams are chained as
+    /// `P(abcd) = P(abc) * P(bcd) / P(bc)`. With quadgrams, `quadgram_stats` counts these exactly
+    /// as quad rolls.
+    pub fn onehand_rolls(&self, layout: &FastLayout) -> f64 {
+        let len = self.data.characters.len();
+        let by_prefix = self.trigrams_by_prefix();
+        let mut res = 0.0;
+
+        for (ab, continuations) in by_prefix.iter().enumerate() {
+            let (a, b) = ((ab / len) as u8, (ab % len) as u8);
+            for &(c, freq) in continuations {
+                if c as usize >= len {
+                    continue;
+                }
+                let pattern = layout.get_trigram_pattern_in(&self.trigram_table, &[a, b, c]);
+                if pattern != TrigramPattern::Onehand {
+                    continue;
+                }
+                let bc = *self.data.bigrams.get(b as usize * len + c as usize).unwrap_or(&0.0);
+                if bc <= 0.0 {
+                    continue;
+                }
+                if let Some(next) = by_prefix.get(b as usize * len + c as usize) {
+                    let c_is_left = is_left_hand(layout.char_to_finger[c as usize]);
+
+                    for &(d, next_freq) in next {
+                        match layout.char_to_finger.get(d as usize) {
+                            Some(&f) if f != usize::MAX && is_left_hand(f) != c_is_left => {
+                                res += freq * next_freq / bc
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Weighted onehand rolls on `layout`, which are only estimated when they are weighted.
+    fn onehand_roll_score(&self, layout: &FastLayout) -> f64 {
+        if self.weights.onehand_rolls != 0.0 {
+            self.weights.onehand_rolls * self.onehand_rolls(layout)
+        } else {
+            0.0
+        }
+    }
+
+    /// Score of `layout` using the most common `trigram_precision` trigrams, which is what
+    /// generation optimizes for.
     pub fn score(&self, layout: &FastLayout) -> f64 {
+        self.score_with_precision(layout, self.trigram_precision)
+    }
+
+    /// Score of `layout` using every trigram in the language data.
+    pub fn score_full(&self, layout: &FastLayout) -> f64 {
+        self.score_with_precision(layout, usize::MAX)
+    }
+
+    pub fn score_with_precision(&self, layout: &FastLayout, trigram_precision: usize) -> f64 {
+        self.score_components(layout, trigram_precision).total
+    }
+
+    /// Score of `layout`, or an error naming `name` and the part of the score that is NaN. NaN
+    /// scores come from language data without frequencies, or weights that aren't numbers.
+    pub fn checked_score(&self, name: &str, layout: &FastLayout) -> Result<f64, RusylyzerError> {
+        let components = self.score_components(layout, self.trigram_precision);
+        match components.nan_component() {
+            None => Ok(components.total),
+            // asked again for the reason, and the program may have recovered by now
+            Some("external_metric") => {
+                let keys = self.convert_u8.as_str(&layout.matrix);
+                self.external_metric.value(&keys)?;
+                Ok(self.score(layout))
+            }
+            Some(component) => Err(RusylyzerError::NanScore {
+                layout: name.to_string(),
+                component,
+            }),
+        }
+    }
+
+    /// The parts the score of `layout` is made of, scoring the `trigram_precision` most common
+    /// trigrams.
+    pub fn score_components(
+        &self,
+        layout: &FastLayout,
+        trigram_precision: usize,
+    ) -> ScoreComponents {
         let effort = (0..layout.matrix.len())
             .into_iter()
             .map(|i| self.char_effort(layout, i))
@@ -428,68 +1839,162 @@ impl LayoutGeneration {
 
         let scissors = self.scissor_score(layout);
         let lsbs = self.lsb_score(layout);
-        let trigram_score = self.trigram_score_iter(layout, &self.data.trigrams);
+        let row_skips = self.row_skip_score(layout);
+        let bigram_penalties = self.bigram_penalty_score(layout);
+        let hand_alternation = self.hand_alternation_score(layout);
+        let row_usage = self.row_usage_score(layout);
+        let shortcuts = self.shortcut_score(layout);
+        let trigram_iter = self.data.trigrams.iter().take(trigram_precision);
+        let trigram_score = self.trigram_score_iter(layout, trigram_iter);
+        let onehand_rolls = self.onehand_roll_score(layout);
+        let quadgrams = self.quadgram_score(layout, trigram_precision);
+
+        // far too slow to ask for every swap, so generation optimizes without it, and it only
+        // counts for the final scores
+        let external_metric = if self.external_metric.is_enabled() {
+            let keys = self.convert_u8.as_str(&layout.matrix);
+            self.external_metric.score(&keys)
+        } else {
+            0.0
+        };
 
-        trigram_score - effort - fspeed_usage - scissors - lsbs
+        let total = trigram_score + onehand_rolls + quadgrams + external_metric
+            - effort
+            - fspeed_usage
+            - scissors
+            - lsbs
+            - row_skips
+            - bigram_penalties
+            - hand_alternation
+            - row_usage
+            - shortcuts;
+
+        ScoreComponents {
+            trigrams: trigram_score,
+            onehand_rolls,
+            quadgrams,
+            effort,
+            fspeed_usage,
+            scissors,
+            lsbs,
+            row_skips,
+            bigram_penalties,
+            hand_alternation,
+            row_usage,
+            shortcuts,
+            external_metric,
+            total,
+        }
     }
 
-    fn weighted_bigrams(data: &LanguageData, weights: &Weights) -> BigramData {
-        let len = data.characters.len();
-        let chars = 0..len;
+    fn dominance_effort(
+        mut effort_map: [f64; 30],
+        dominance: &HandDominance,
+        fingers: &FingerAssignment,
+    ) -> [f64; 30] {
+        for (effort, &finger) in effort_map.iter_mut().zip(fingers) {
+            *effort *= dominance.for_finger(finger);
+        }
+        effort_map
+    }
 
-        chars
-            .clone()
-            .cartesian_product(chars)
-            .map(|(c1, c2)| {
-                let bigram = c1 * len + c2;
-                let sfb = data.bigrams.get(bigram).unwrap_or(&0.0);
-                let dsfb = data.skipgrams.get(bigram).unwrap_or(&0.0) * weights.dsfb_ratio;
-                let dsfb2 = data.skipgrams2.get(bigram).unwrap_or(&0.0) * weights.dsfb_ratio2;
-                let dsfb3 = data.skipgrams3.get(bigram).unwrap_or(&0.0) * weights.dsfb_ratio3;
-                (sfb + dsfb + dsfb2 + dsfb3) * weights.fspeed
-            })
-            .collect()
+    /// Moving to a key and back to the home key only depends on the key, so that part of the
+    /// travel can be added to the effort of every position.
+    fn travel_effort(
+        mut effort_map: [f64; 30],
+        home_distances: &[f64; 30],
+        travel_weight: f64,
+    ) -> [f64; 30] {
+        for (effort, dist) in effort_map.iter_mut().zip(home_distances) {
+            *effort += dist * 2.0 * travel_weight;
+        }
+        effort_map
     }
 
-    fn per_char_trigrams(
-        trigrams: &TrigramData,
-        highest: u8,
-        trigram_precision: usize,
-    ) -> PerCharTrigrams {
-        let mut n_trigrams = trigrams.clone();
-        n_trigrams.truncate(trigram_precision);
+    /// Keys in `[avoid_keys]` cost their multiplier times as much effort.
+    fn avoided_effort(mut effort_map: [f64; 30], avoid_keys: &[(usize, f64)]) -> [f64; 30] {
+        for &(i, multiplier) in avoid_keys {
+            effort_map[i] *= multiplier;
+        }
+        effort_map
+    }
 
-        let thingy: Vec<([u8; 2], Vec<([u8; 3], f64)>)> = (0..highest)
-            .into_iter()
-            .cartesian_product(0..highest)
-            .map(|(c1, c2)| {
-                let v1 = n_trigrams
-                    .iter()
-                    .map(|(t, f)| (t.clone(), f.clone()))
-                    .filter(|(t, _)| t.contains(&c1))
-                    .collect::<Vec<_>>();
+    /// Finger speed of every pair times the multiplier in `[weights.fingers]` of the finger that
+    /// presses it.
+    fn finger_speed_fspeed(
+        mut fspeed_vals: Vec<(PosPair, f64)>,
+        speeds: &FingerSpeeds,
+        fingers: &FingerAssignment,
+    ) -> Vec<(PosPair, f64)> {
+        for (pair, dist) in fspeed_vals.iter_mut() {
+            *dist *= speeds.for_finger(fingers[pair.0]);
+        }
+        fspeed_vals
+    }
 
-                let v2 = n_trigrams
-                    .iter()
-                    .map(|(t, f)| (t.clone(), f.clone()))
-                    .filter(|(t, _)| t.contains(&c2))
-                    .collect::<Vec<_>>();
+    fn dominance_fspeed(
+        mut fspeed_vals: Vec<(PosPair, f64)>,
+        dominance: &HandDominance,
+        fingers: &FingerAssignment,
+    ) -> Vec<(PosPair, f64)> {
+        for (pair, dist) in fspeed_vals.iter_mut() {
+            *dist *= dominance.for_finger(fingers[pair.0]);
+        }
+        fspeed_vals
+    }
 
-                let (big, small, c) = if v1.len() >= v2.len() {
-                    (v1, v2, &c1)
-                } else {
-                    (v2, v1, &c2)
-                };
+    /// The vowels and consonants for `vowel_split` seeding that are among `chars`, without the
+    /// duplicates. Consonants that aren't configured are the letters of `chars` that are left.
+    fn seed_sets(
+        con: &ConvertU8,
+        chars: &[u8; 30],
+        options: &GenerationOptions,
+    ) -> (Vec<u8>, Vec<u8>) {
+        let mut vowels = Vec::new();
+        for u in options.vowels.chars().map(|c| con.to_single_lossy(c)) {
+            if chars.contains(&u) && !vowels.contains(&u) {
+                vowels.push(u);
+            }
+        }
 
-                let per_char = big
-                    .into_iter()
-                    .chain(small.into_iter().filter(|(t, _)| !t.contains(c)))
-                    .collect::<Vec<_>>();
-                ([c1, c2], per_char)
-            })
-            .collect();
+        let candidates = match &options.consonants {
+            Some(consonants) => consonants
+                .chars()
+                .map(|c| con.to_single_lossy(c))
+                .collect::<Vec<_>>(),
+            None => chars
+                .iter()
+                .copied()
+                .filter(|&u| con.from_single(u).is_alphabetic())
+                .collect(),
+        };
+        let mut consonants = Vec::new();
+        for u in candidates {
+            if chars.contains(&u) && !vowels.contains(&u) && !consonants.contains(&u) {
+                consonants.push(u);
+            }
+        }
+        (vowels, consonants)
+    }
 
-        PerCharTrigrams::from_iter(thingy)
+    /// Trigrams by the characters in them, built the first time they're needed.
+    fn trigram_index(&self) -> &TrigramIndex {
+        self.trigram_index.get_or_init(|| {
+            let start = Instant::now();
+            let res = TrigramIndex::new(&self.data, self.trigram_precision);
+            log::info!("built the trigrams of every pair of characters in {:.2?}", start.elapsed());
+            res
+        })
+    }
+
+    /// Trigrams containing either character of every pair.
+    fn char_trigrams(&self) -> &PerCharTrigrams {
+        &self.trigram_index().per_pair
+    }
+
+    /// Trigrams starting with every 2 characters.
+    fn trigrams_by_prefix(&self) -> &TrigramsByPrefix {
+        &self.trigram_index().by_prefix
     }
 
     #[inline]
@@ -500,13 +2005,26 @@ impl LayoutGeneration {
         use TrigramPattern::*;
 
         let mut freqs = TrigramStats::default();
+        // finding out where a roll is takes a bit of work, so it's only done when it's weighted
+        let hands = &self.weights.roll_hands;
+        let rows = (self.weights.home_row_rolls != 0.0
+            || self.weights.adjacent_rolls != 0.0
+            || !hands.is_neutral())
+        .then(|| char_rows(layout));
 
         for (trigram, freq) in trigrams {
-            match layout.get_trigram_pattern(trigram) {
+            match layout.get_trigram_pattern_in(&self.trigram_table, trigram) {
                 Alternate => freqs.alternates += freq,
                 AlternateSfs => freqs.alternates_sfs += freq,
-                Inroll => freqs.inrolls += freq,
-                Outroll => freqs.outrolls += freq,
+                pattern @ (Inroll | Outroll) => match &rows {
+                    Some(rows) => {
+                        let placement = roll_placement(layout, rows, trigram);
+                        let hand = roll_hand(layout, trigram);
+                        freqs.add_roll(pattern == Inroll, hand, placement, *freq)
+                    }
+                    None if pattern == Inroll => freqs.inrolls += freq,
+                    None => freqs.outrolls += freq,
+                },
                 Onehand => freqs.onehands += freq,
                 Redirect => freqs.redirects += freq,
                 RedirectSfs => freqs.redirects += freq,
@@ -516,9 +2034,20 @@ impl LayoutGeneration {
             }
         }
 
+        let (inrolls, outrolls) = if hands.is_neutral() {
+            (freqs.inrolls, freqs.outrolls)
+        } else {
+            (
+                hands.apply(freqs.inrolls_by_hand),
+                hands.apply(freqs.outrolls_by_hand),
+            )
+        };
+
         let mut score = 0.0;
-        score += self.weights.inrolls * freqs.inrolls;
-        score += self.weights.outrolls * freqs.outrolls;
+        score += self.weights.inrolls * inrolls;
+        score += self.weights.outrolls * outrolls;
+        score += self.weights.home_row_rolls * freqs.home_row_rolls();
+        score += self.weights.adjacent_rolls * freqs.adjacent_rolls;
         score += self.weights.onehands * freqs.onehands;
         score += self.weights.alternates * freqs.alternates;
         score += self.weights.alternates_sfs * freqs.alternates_sfs;
@@ -529,14 +2058,17 @@ impl LayoutGeneration {
         score
     }
 
-    fn trigram_char_score(&self, layout: &FastLayout, pos: &PosPair) -> f64 {
+    fn trigram_char_score(&self, layout: &FastLayout, pos: &PosPair, min_freq: f64) -> f64 {
         let c1 = unsafe { layout.cu(pos.0) };
         let c2 = unsafe { layout.cu(pos.1) };
 
-        if let Some(t_vec) = self.per_char_trigrams.get(&[c1, c2]) {
-            self.trigram_score_iter(layout, t_vec)
-        } else {
-            0.0
+        let len = self.data.characters.len();
+        match pair_index(c1, c2, len).and_then(|i| self.char_trigrams().get(i)) {
+            Some(t_vec) => {
+                let t_vec = t_vec.iter().take_while(|(_, freq)| *freq >= min_freq);
+                self.trigram_score_iter(layout, t_vec)
+            }
+            None => 0.0,
         }
     }
 
@@ -554,6 +2086,36 @@ impl LayoutGeneration {
         res * self.weights.scissors
     }
 
+    /// Frequency of the bigrams of every penalty in `[bigram_penalties]` on `layout`, in the order
+    /// of the config.
+    pub fn bigram_penalty_percents(&self, layout: &FastLayout) -> Vec<f64> {
+        self.bigram_penalties
+            .iter()
+            .map(|penalty| self.pairs_percent(layout, &penalty.pairs))
+            .collect()
+    }
+
+    fn pairs_percent(&self, layout: &FastLayout, pairs: &[PosPair]) -> f64 {
+        let mut res = 0.0;
+        let len = self.data.characters.len();
+
+        for &PosPair(i1, i2) in pairs.iter() {
+            let c1 = unsafe { layout.cu(i1) } as usize;
+            let c2 = unsafe { layout.cu(i2) } as usize;
+            res += self.data.bigrams.get(c1 * len + c2).unwrap_or(&0.0);
+            res += self.data.bigrams.get(c2 * len + c1).unwrap_or(&0.0);
+        }
+
+        res
+    }
+
+    fn bigram_penalty_score(&self, layout: &FastLayout) -> f64 {
+        self.bigram_penalties
+            .iter()
+            .map(|penalty| penalty.weight * self.pairs_percent(layout, &penalty.pairs))
+            .sum()
+    }
+
     fn lsb_score(&self, layout: &FastLayout) -> f64 {
         let mut res = 0.0;
         let len = self.data.characters.len();
@@ -568,46 +2130,64 @@ impl LayoutGeneration {
         res * self.weights.lsbs
     }
 
+    fn row_skip_percent(&self, layout: &FastLayout) -> f64 {
+        let mut res = 0.0;
+        let len = self.data.characters.len();
+
+        for &PosPair(i1, i2) in self.row_skip_indices.iter() {
+            let c1 = unsafe { layout.cu(i1) } as usize;
+            let c2 = unsafe { layout.cu(i2) } as usize;
+            res += self.data.bigrams.get(c1 * len + c2).unwrap_or(&0.0);
+            res += self.data.bigrams.get(c2 * len + c1).unwrap_or(&0.0);
+        }
+
+        res
+    }
+
+    fn row_skip_score(&self, layout: &FastLayout) -> f64 {
+        self.row_skip_percent(layout) * self.weights.row_skips
+    }
+
     fn col_usage(&self, layout: &FastLayout, col: usize) -> f64 {
         let mut res = 0.0;
-        match col {
-            0 | 1 | 2 => {
-                for c in unsafe { [layout.cu(col), layout.cu(col + 10), layout.cu(col + 20)] } {
-                    res += *self.data.characters.get(c as usize).unwrap_or(&0.0);
-                }
-            }
-            3 | 4 => {
-                let col = (col - 3) * 2 + 3;
-                for c in unsafe {
-                    [
-                        layout.cu(col),
-                        layout.cu(col + 10),
-                        layout.cu(col + 20),
-                        layout.cu(col + 1),
-                        layout.cu(col + 11),
-                        layout.cu(col + 21),
-                    ]
-                } {
-                    res += *self.data.characters.get(c as usize).unwrap_or(&0.0);
-                }
-            }
-            5 | 6 | 7 => {
-                let col = col + 2;
-                for c in unsafe { [layout.cu(col), layout.cu(col + 10), layout.cu(col + 20)] } {
-                    res += *self.data.characters.get(c as usize).unwrap_or(&0.0);
-                }
-            }
+        for &i in unsafe { self.finger_keys.get_unchecked(col) } {
+            let c = unsafe { layout.cu(i) };
+            res += *self.data.characters.get(c as usize).unwrap_or(&0.0);
+        }
+
+        let overuse = match col {
+            0 | 7 => (res - self.weights.max_finger_use.pinky).max(0.0),
+            1 | 6 => (res - self.weights.max_finger_use.ring).max(0.0),
+            2 | 5 => (res - self.weights.max_finger_use.middle).max(0.0),
+            3 | 4 => (res - self.weights.max_finger_use.index).max(0.0),
             _ => unsafe { unreachable_unchecked() },
         };
 
-        self.weights.max_finger_use.penalty
-            * match col {
-                0 | 7 => (res - self.weights.max_finger_use.pinky).max(0.0),
-                1 | 6 => (res - self.weights.max_finger_use.ring).max(0.0),
-                2 | 5 => (res - self.weights.max_finger_use.middle).max(0.0),
-                3 | 4 => (res - self.weights.max_finger_use.index).max(0.0),
-                _ => unsafe { unreachable_unchecked() },
-            }
+        self.weights.max_finger_use.penalty * overuse + self.col_travel(layout, col)
+    }
+
+    /// The part of the travel that depends on which keys are pressed in a row by the finger of
+    /// `col`. It's cached together with the usage, as both only change when that finger does.
+    #[inline]
+    fn col_travel(&self, layout: &FastLayout, col: usize) -> f64 {
+        if self.weights.travel == 0.0 {
+            return 0.0;
+        }
+
+        let (start, len) = self.col_to_start_len(col);
+        let char_count = self.data.characters.len();
+        let mut res = 0.0;
+
+        for i in start..(start + len) {
+            let (PosPair(i1, i2), _) = unsafe { self.fspeed_vals.get_unchecked(i) };
+            let c1 = unsafe { layout.cu(*i1) } as usize;
+            let c2 = unsafe { layout.cu(*i2) } as usize;
+            let freq = self.data.bigrams.get(c1 * char_count + c2).unwrap_or(&0.0)
+                + self.data.bigrams.get(c2 * char_count + c1).unwrap_or(&0.0);
+
+            res += freq * unsafe { self.sfb_travel.get_unchecked(i) };
+        }
+        res * self.weights.travel
     }
 
     #[inline]
@@ -629,32 +2209,20 @@ impl LayoutGeneration {
         // 	res += self.weighted_bigrams.get(c2 * len + c1).unwrap_or(&0.0) * dist * 0.5;
         // 	res
         // }
-        let mut res = 0.0;
-
         let len = self.data.characters.len();
-        res += self.weighted_bigrams.get(c1 * len + c2).unwrap_or(&0.0) * dist;
-        res += self.weighted_bigrams.get(c2 * len + c1).unwrap_or(&0.0) * dist;
-        res
+        self.data.weighted_bigrams.get(c1 * len + c2).unwrap_or(&0.0) * dist
     }
 
+    /// Where the same finger pairs of the finger `col` start in `fspeed_vals`, and how many there
+    /// are.
     #[inline(always)]
-    pub unsafe fn col_to_start_len(col: usize) -> (usize, usize) {
-        *[
-            (0, 3),
-            (3, 3),
-            (6, 3),
-            (18, 15),
-            (33, 15),
-            (9, 3),
-            (12, 3),
-            (15, 3),
-        ]
-        .get_unchecked(col)
+    pub fn col_to_start_len(&self, col: usize) -> (usize, usize) {
+        self.finger_ranges[col]
     }
 
     #[inline]
     fn col_fspeed(&self, layout: &FastLayout, col: usize) -> f64 {
-        let (start, len) = unsafe { Self::col_to_start_len(col) };
+        let (start, len) = self.col_to_start_len(col);
         let mut res = 0.0;
 
         for i in start..(start + len) {
@@ -675,7 +2243,12 @@ impl LayoutGeneration {
 
     pub fn initialize_cache(&self, layout: &FastLayout) -> LayoutCache {
         let mut res = LayoutCache::default();
+        self.fill_cache(layout, &mut res);
+        res
+    }
 
+    /// Recalculates every value of `res` for `layout`, which lets an existing cache be reused.
+    pub fn fill_cache(&self, layout: &FastLayout, res: &mut LayoutCache) {
         for i in 0..layout.matrix.len() {
             res.effort[i] = self.char_effort(layout, i);
         }
@@ -692,11 +2265,84 @@ impl LayoutGeneration {
 
 		res.lsbs = self.lsb_score(layout);
 
-        res.trigrams_total = self.trigram_score_iter(layout, self.data.trigrams.iter().take(1000));
+        res.row_skips = self.row_skip_score(layout);
+
+        res.bigram_penalties = self.bigram_penalty_score(layout);
+
+        res.hand_alternation = self.hand_alternation_score(layout);
+
+        res.row_usage = self.row_usage_score(layout);
+
+        res.shortcuts = self.shortcut_score(layout);
+
+        res.onehand_rolls = self.onehand_roll_score(layout);
+        res.quadgrams = self.quadgram_score(layout, self.trigram_precision);
+
+        let min_freq = res.min_trigram_freq;
+        res.trigrams_total = self.trigram_score_iter(
+            layout,
+            self.data
+                .trigrams
+                .iter()
+                .take(self.trigram_precision)
+                .take_while(|(_, freq)| *freq >= min_freq),
+        );
 
         res.total_score = res.total_score();
+    }
 
-        res
+    /// Makes `cache` score only the `trigram_precision` most common trigrams, which is quicker but
+    /// less accurate when it is lower than the generator's own precision, and recalculates it for
+    /// `layout`. Trigrams as common as the least common one that is scored are scored as well.
+    pub fn set_cache_precision(
+        &self,
+        layout: &FastLayout,
+        cache: &mut LayoutCache,
+        trigram_precision: usize,
+    ) {
+        cache.min_trigram_freq = match trigram_precision {
+            0 => f64::INFINITY,
+            p if p < self.trigram_precision.min(self.data.trigrams.len()) => {
+                self.data.trigrams[p - 1].1
+            }
+            _ => 0.0,
+        };
+        self.fill_cache(layout, cache);
+    }
+
+    /// Precisions `optimize` goes through before it uses the full `trigram_precision`, from a
+    /// sixteenth to a quarter of it, when `progressive_precision` is on.
+    fn coarse_precisions(&self) -> Vec<usize> {
+        if !self.progressive_precision {
+            return Vec::new();
+        }
+        let precision = self.trigram_precision.min(self.data.trigrams.len());
+        [precision / 16, precision / 4]
+            .into_iter()
+            .filter(|&p| p > 0)
+            .dedup()
+            .collect()
+    }
+
+    /// Optimizes `layout` with every coarse precision in turn, each one starting from where the
+    /// one before it converged, and leaves `cache` at the full precision. Optimizing with fewer
+    /// trigrams is a lot quicker and gets most keys in place, so the full precision only has a
+    /// few swaps left to make.
+    fn refine_precision(
+        &self,
+        layout: &mut FastLayout,
+        cache: &mut LayoutCache,
+        possible_swaps: &[PosPair],
+    ) {
+        let coarse = self.coarse_precisions();
+        if coarse.is_empty() {
+            return;
+        }
+        for precision in coarse {
+            self.set_cache_precision(layout, cache, precision);
+            self.optimize_cached(layout, cache, possible_swaps);
+        }
+        self.set_cache_precision(layout, cache, self.trigram_precision);
     }
 
     pub fn score_swap_cached(
@@ -709,8 +2355,8 @@ impl LayoutGeneration {
 
         let PosPair(i1, i2) = *swap;
 
-        let col1 = I_TO_COL[i1];
-        let col2 = I_TO_COL[i2];
+        let col1 = self.fingers[i1];
+        let col2 = self.fingers[i2];
 
         let fspeed_score = if col1 == col2 {
             let fspeed = self.col_fspeed(layout, col1);
@@ -749,13 +2395,51 @@ impl LayoutGeneration {
             cache.lsbs
         };
 
+        // row skips are only on the rows scissors are on as well
+        let row_skips_score = if swap.affects_scissor() {
+            self.row_skip_score(layout)
+        } else {
+            cache.row_skips
+        };
+
+        let penalties_score = if self.penalty_positions[i1] || self.penalty_positions[i2] {
+            self.bigram_penalty_score(layout)
+        } else {
+            cache.bigram_penalties
+        };
+
+        // only swaps between the hands change which bigrams alternate
+        let alternation_score = if is_left_hand(col1) != is_left_hand(col2) {
+            self.hand_alternation_score(layout)
+        } else {
+            cache.hand_alternation
+        };
+
+        // only swaps between rows change how much every row is used
+        let row_usage_score = if i1 / 10 != i2 / 10 {
+            self.row_usage_score(layout)
+        } else {
+            cache.row_usage
+        };
+
+        // only swaps in and out of reach of the left hand change which shortcut keys are in reach
+        let shortcuts_score = if self.reaches_shortcut(i1) != self.reaches_shortcut(i2) {
+            self.shortcut_score(layout)
+        } else {
+            cache.shortcuts
+        };
+
+        // every swap can change these, but they're only scored when they are weighted
+        let onehand_rolls_score = self.onehand_roll_score(layout);
+        let quadgrams_score = self.quadgram_score(layout, self.trigram_precision);
+
         // let _new_heur = cache.trigrams_total - scissors_score - effort_score - usage_score - fspeed_score;
 
         let trigrams_score = if cache.total_score < (f64::MAX) {
             //new_heur + new_heur.abs() * 0.0) {
-            let trigrams_end = self.trigram_char_score(layout, swap);
+            let trigrams_end = self.trigram_char_score(layout, swap, cache.min_trigram_freq);
             unsafe { layout.swap_no_bounds(swap) };
-            let trigrams_start = self.trigram_char_score(layout, swap);
+            let trigrams_start = self.trigram_char_score(layout, swap, cache.min_trigram_freq);
 
             #[cfg(test)]
             NOT_PRUNED.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
@@ -769,18 +2453,60 @@ impl LayoutGeneration {
             return f64::MIN + 1000.0;
         };
 
-        trigrams_score - scissors_score - lsbs_score - effort_score - usage_score - fspeed_score
+        trigrams_score + onehand_rolls_score + quadgrams_score
+            - scissors_score
+            - lsbs_score
+            - row_skips_score
+            - penalties_score
+            - alternation_score
+            - row_usage_score
+            - shortcuts_score
+            - effort_score
+            - usage_score
+            - fspeed_score
     }
 
-    pub fn accept_swap(&self, layout: &mut FastLayout, swap: &PosPair, cache: &mut LayoutCache) {
-        let trigrams_start = self.trigram_char_score(layout, swap);
+    /// Score `layout` would have after `swap`, using `cache` to only score what changes. Rotations
+    /// are scored as their first swap followed by their second.
+    pub fn score_move_cached(
+        &self,
+        layout: &mut FastLayout,
+        swap: &Move,
+        cache: &LayoutCache,
+    ) -> f64 {
+        match *swap {
+            Move::Swap(swap) => self.score_swap_cached(layout, &swap, cache),
+            Move::Rotate([i1, i2, i3]) => {
+                let first = PosPair(i1, i2);
+                let mut after_first = cache.clone();
+                self.accept_pair(layout, &first, &mut after_first);
+                let score = self.score_swap_cached(layout, &PosPair(i1, i3), &after_first);
+                unsafe { layout.swap_no_bounds(&first) };
+                score
+            }
+        }
+    }
+
+    /// Makes `swap` on `layout`, and updates `cache` to match.
+    pub fn accept_swap(&self, layout: &mut FastLayout, swap: &Move, cache: &mut LayoutCache) {
+        match *swap {
+            Move::Swap(swap) => self.accept_pair(layout, &swap, cache),
+            Move::Rotate([i1, i2, i3]) => {
+                self.accept_pair(layout, &PosPair(i1, i2), cache);
+                self.accept_pair(layout, &PosPair(i1, i3), cache);
+            }
+        }
+    }
+
+    fn accept_pair(&self, layout: &mut FastLayout, swap: &PosPair, cache: &mut LayoutCache) {
+        let trigrams_start = self.trigram_char_score(layout, swap, cache.min_trigram_freq);
 
         unsafe { layout.swap_no_bounds(swap) };
 
         let PosPair(i1, i2) = *swap;
 
-        let col1 = I_TO_COL[i1];
-        let col2 = I_TO_COL[i2];
+        let col1 = self.fingers[i1];
+        let col2 = self.fingers[i2];
 
         cache.fspeed_total = if col1 == col2 {
             let fspeed = self.col_fspeed(layout, col1);
@@ -827,17 +2553,37 @@ impl LayoutGeneration {
         cache.effort[i1] = effort1;
         cache.effort[i2] = effort2;
 
-        let trigrams_end = self.trigram_char_score(layout, &swap);
+        let trigrams_end = self.trigram_char_score(layout, swap, cache.min_trigram_freq);
         cache.trigrams_total = cache.trigrams_total - trigrams_start + trigrams_end;
 
         if swap.affects_scissor() {
             cache.scissors = self.scissor_score(layout);
+            cache.row_skips = self.row_skip_score(layout);
         }
 
         if swap.affects_lsb() {
             cache.lsbs = self.lsb_score(layout);
         }
 
+        if self.penalty_positions[i1] || self.penalty_positions[i2] {
+            cache.bigram_penalties = self.bigram_penalty_score(layout);
+        }
+
+        if is_left_hand(col1) != is_left_hand(col2) {
+            cache.hand_alternation = self.hand_alternation_score(layout);
+        }
+
+        if i1 / 10 != i2 / 10 {
+            cache.row_usage = self.row_usage_score(layout);
+        }
+
+        if self.reaches_shortcut(i1) != self.reaches_shortcut(i2) {
+            cache.shortcuts = self.shortcut_score(layout);
+        }
+
+        cache.onehand_rolls = self.onehand_roll_score(layout);
+        cache.quadgrams = self.quadgram_score(layout, self.trigram_precision);
+
         cache.total_score = cache.total_score();
     }
 
@@ -846,15 +2592,31 @@ impl LayoutGeneration {
         layout: &mut FastLayout,
         cache: &LayoutCache,
         current_best_score: Option<f64>,
-        possible_swaps: &[PosPair],
-    ) -> (Option<PosPair>, f64) {
+        possible_swaps: &[Move],
+    ) -> (Option<Move>, f64) {
         let mut best_score = current_best_score.unwrap_or_else(|| f64::MIN / 2.0);
-        let mut best_swap: Option<PosPair> = None;
+        let mut best_swap: Option<Move> = None;
+
+        let shuffled;
+        let possible_swaps = match self.swap_policy {
+            SwapPolicy::Deterministic => possible_swaps,
+            SwapPolicy::Shuffled => {
+                shuffled = {
+                    let mut swaps = possible_swaps.to_vec();
+                    tls_rng().shuffle(&mut swaps);
+                    swaps
+                };
+                shuffled.as_slice()
+            }
+        };
 
         for swap in possible_swaps {
-            let score = self.score_swap_cached(layout, swap, cache);
+            if self.breaks_ban(layout, swap) {
+                continue;
+            }
+            let score = self.score_move_cached(layout, swap, cache);
 
-            if score > best_score {
+            if beats_swap(self.swap_policy, (score, swap), (best_score, best_swap.as_ref())) {
                 best_score = score;
                 best_swap = Some(*swap);
             }
@@ -863,31 +2625,231 @@ impl LayoutGeneration {
         (best_swap, best_score)
     }
 
+    /// Whether `c` is too common to go on position `i`, which is avoided.
+    fn is_banned(&self, c: u8, i: usize) -> bool {
+        self.avoided_keys[i] && self.banned_chars.get(c as usize).copied().unwrap_or(false)
+    }
+
+    /// Whether `mv` puts a character that is more common than `avoided_key_ban` on an avoided key.
+    fn breaks_ban(&self, layout: &FastLayout, mv: &Move) -> bool {
+        if self.banned_chars.is_empty() {
+            return false;
+        }
+        match *mv {
+            Move::Swap(PosPair(i1, i2)) => {
+                self.is_banned(layout.c(i1), i2) || self.is_banned(layout.c(i2), i1)
+            }
+            Move::Rotate([i1, i2, i3]) => {
+                self.is_banned(layout.c(i1), i2)
+                    || self.is_banned(layout.c(i2), i3)
+                    || self.is_banned(layout.c(i3), i1)
+            }
+        }
+    }
+
+    /// Whether any character of `layout` is on an avoided key it is too common for.
+    fn has_banned_keys(&self, layout: &FastLayout) -> bool {
+        !self.banned_chars.is_empty() && (0..30).any(|i| self.is_banned(layout.c(i), i))
+    }
+
+    /// Swaps every character that is on an avoided key it is too common for with one that isn't,
+    /// leaving `pins` where they are. Generation never makes such a swap, so it has to start
+    /// without them.
+    fn lift_bans(&self, layout: &mut FastLayout, pins: &[usize]) {
+        if self.banned_chars.is_empty() {
+            return;
+        }
+        for i in (0..30).filter(|i| !pins.contains(i)) {
+            if !self.is_banned(layout.c(i), i) {
+                continue;
+            }
+            let free = (0..30).find(|&j| {
+                !pins.contains(&j) && !self.avoided_keys[j] && !self.is_banned(layout.c(j), i)
+            });
+            if let Some(j) = free {
+                layout.swap(i, j);
+            }
+        }
+    }
+
+    /// Rotations of 3 positions that `possible_swaps` can all swap, which are tried once no swap
+    /// improves a layout anymore. These are the 3 keys of every column, or every 3 keys with
+    /// `rotations`.
+    fn possible_rotations(&self, possible_swaps: &[PosPair]) -> Vec<Move> {
+        let mut free = [false; 30];
+        for &PosPair(i1, i2) in possible_swaps {
+            free[i1] = true;
+            free[i2] = true;
+        }
+
+        let mut res = Vec::new();
+        for i1 in 0..30 {
+            for i2 in (i1 + 1)..30 {
+                for i3 in (i2 + 1)..30 {
+                    let same_col = i1 % 10 == i2 % 10 && i2 % 10 == i3 % 10;
+                    if free[i1] && free[i2] && free[i3] && (self.rotations || same_col) {
+                        res.push(Move::Rotate([i1, i2, i3]));
+                        res.push(Move::Rotate([i1, i3, i2]));
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// The best of `swaps`, or of `rotations` when none of the swaps beat `current_best_score`.
+    fn best_move_cached(
+        &self,
+        layout: &mut FastLayout,
+        cache: &LayoutCache,
+        current_best_score: f64,
+        swaps: &[Move],
+        rotations: &[Move],
+    ) -> (Option<Move>, f64) {
+        match self.best_swap_cached(layout, cache, Some(current_best_score), swaps) {
+            (None, _) => self.best_swap_cached(layout, cache, Some(current_best_score), rotations),
+            best => best,
+        }
+    }
+
     fn optimize_cached(
         &self,
         layout: &mut FastLayout,
         cache: &mut LayoutCache,
         possible_swaps: &[PosPair],
+    ) -> f64 {
+        if !self.constraints.is_empty() {
+            return self.optimize_constrained(layout, cache, possible_swaps);
+        }
+        let swaps = possible_swaps.iter().map(|&swap| Move::Swap(swap)).collect::<Vec<_>>();
+        let rotations = self.possible_rotations(possible_swaps);
+
+        let score = self.descend(layout, cache, f64::MIN / 2.0, &swaps, &rotations);
+        if self.temperature > 0.0 {
+            return self.escape_plateaus(layout, cache, score, &swaps, &rotations);
+        }
+        score
+    }
+
+    /// Makes the best of `swaps` or `rotations` until none of them beats `score` anymore.
+    fn descend(
+        &self,
+        layout: &mut FastLayout,
+        cache: &mut LayoutCache,
+        mut score: f64,
+        swaps: &[Move],
+        rotations: &[Move],
+    ) -> f64 {
+        while let (Some(best_swap), new_score) =
+            self.best_move_cached(layout, cache, score, swaps, rotations)
+        {
+            log::debug!("{best_swap:?} improves the score to {new_score:.5}");
+            score = new_score;
+            self.accept_swap(layout, &best_swap, cache);
+        }
+        score
+    }
+
+    /// Makes random swaps of a layout no swap improves anymore, where one that lowers the score
+    /// by `d` is made with a chance of `e^(-d / temperature)`, and optimizes again after each of
+    /// them. With heavy pins there are so few swaps that optimizing gets stuck right away, and
+    /// this gets it off that plateau. `layout` ends up as the best layout that came by, so it is
+    /// never worse than it was.
+    fn escape_plateaus(
+        &self,
+        layout: &mut FastLayout,
+        cache: &mut LayoutCache,
+        score: f64,
+        swaps: &[Move],
+        rotations: &[Move],
+    ) -> f64 {
+        if swaps.is_empty() {
+            return score;
+        }
+        let mut rng = tls_rng();
+        let mut current = score;
+        let mut best = (score, layout.clone(), cache.clone());
+
+        for _ in 0..TEMPERATURE_ATTEMPTS {
+            let swap = swaps[rng.generate_range(0..swaps.len())];
+            if self.breaks_ban(layout, &swap) {
+                continue;
+            }
+            let change = self.score_move_cached(layout, &swap, cache) - current;
+            if change < 0.0 && rng.generate::<f64>() >= (change / self.temperature).exp() {
+                continue;
+            }
+            log::debug!("{swap:?} changes the score by {change:.5} at the temperature");
+            self.accept_swap(layout, &swap, cache);
+            current = self.descend(layout, cache, current + change, swaps, rotations);
+
+            if current > best.0 + SWAP_EPSILON {
+                best = (current, layout.clone(), cache.clone());
+            }
+        }
+
+        let (best_score, best_layout, best_cache) = best;
+        *layout = best_layout;
+        *cache = best_cache;
+        best_score
+    }
+
+    /// Same as `optimize_cached`, but a move that takes the layout over a constraint it was under
+    /// is undone, and left out until another move has been made.
+    fn optimize_constrained(
+        &self,
+        layout: &mut FastLayout,
+        cache: &mut LayoutCache,
+        possible_swaps: &[PosPair],
     ) -> f64 {
         let mut current_best_score = f64::MIN / 2.0;
+        let mut met = self.constraints_met(layout);
+        let all_swaps = possible_swaps.iter().map(|&swap| Move::Swap(swap)).collect::<Vec<_>>();
+        let all_rotations = self.possible_rotations(possible_swaps);
+        let mut swaps = all_swaps.clone();
+        let mut rotations = all_rotations.clone();
 
         while let (Some(best_swap), new_score) =
-            self.best_swap_cached(layout, &cache, Some(current_best_score), possible_swaps)
+            self.best_move_cached(layout, cache, current_best_score, &swaps, &rotations)
         {
-            current_best_score = new_score;
             self.accept_swap(layout, &best_swap, cache);
+            let new_met = self.constraints_met(layout);
+
+            if keeps_constraints(&met, &new_met) {
+                log::debug!("{best_swap:?} improves the score to {new_score:.5}");
+                current_best_score = new_score;
+                met = new_met;
+                swaps.clone_from(&all_swaps);
+                rotations.clone_from(&all_rotations);
+            } else {
+                log::debug!("{best_swap:?} is undone, as it goes over a constraint");
+                self.accept_swap(layout, &best_swap.inverse(), cache);
+                swaps.retain(|swap| *swap != best_swap);
+                rotations.retain(|swap| *swap != best_swap);
+            }
         }
         current_best_score
     }
 
-    fn optimize_cols(&self, layout: &mut FastLayout, cache: &mut LayoutCache, score: Option<f64>) {
+    /// Tries every order of the outer columns, and the same with the index columns swapped, leaving
+    /// the columns with `pins` or keys on a locked hand where they are.
+    fn optimize_cols(
+        &self,
+        layout: &mut FastLayout,
+        cache: &mut LayoutCache,
+        score: Option<f64>,
+        pins: &[usize],
+    ) {
         let mut best_score = score.unwrap_or_else(|| cache.total_score);
+        let met = self.constraints_met(layout);
+        let cols = self.movable_cols(pins);
 
         let mut best = layout.clone();
-        self.col_perms(layout, &mut best, cache, &mut best_score, 6);
-        layout.swap_indexes();
-
-        self.col_perms(layout, &mut best, cache, &mut best_score, 6);
+        self.col_perms(layout, &mut best, cache, &mut best_score, &met, &cols);
+        if self.can_swap_indexes(pins) {
+            layout.swap_indexes();
+            self.col_perms(layout, &mut best, cache, &mut best_score, &met, &cols);
+        }
         *layout = best;
         layout.score = best_score;
     }
@@ -898,33 +2860,118 @@ impl LayoutGeneration {
         best: &mut FastLayout,
         cache: &mut LayoutCache,
         best_score: &mut f64,
-        k: usize,
+        met: &[bool],
+        cols: &[usize],
     ) {
+        let k = cols.len();
         if k == 1 {
             let new_score = cache.total_score;
-            if new_score > *best_score {
+            if new_score > *best_score
+                && keeps_constraints(met, &self.constraints_met(layout))
+                && !self.has_banned_keys(layout)
+            {
                 *best_score = new_score;
                 *best = layout.clone();
             }
             return;
         }
         for i in 0..k {
-            self.col_perms(layout, best, cache, best_score, k - 1);
+            self.col_perms(layout, best, cache, best_score, met, &cols[..k - 1]);
             if k % 2 == 0 {
-                self.accept_swap(layout, &PosPair(COLS[i], COLS[k - 1]), cache);
+                self.accept_col_swap(layout, cols[i], cols[k - 1], cache);
             } else {
-                self.accept_swap(layout, &PosPair(COLS[0], COLS[k - 1]), cache);
+                self.accept_col_swap(layout, cols[0], cols[k - 1], cache);
+            }
+        }
+    }
+
+    /// Swaps every key of `col1` with the one on the same row of `col2`, so a column unit stays
+    /// together.
+    fn accept_col_swap(
+        &self,
+        layout: &mut FastLayout,
+        col1: usize,
+        col2: usize,
+        cache: &mut LayoutCache,
+    ) {
+        for row in 0..3 {
+            self.accept_pair(layout, &PosPair(row * 10 + col1, row * 10 + col2), cache);
+        }
+    }
+
+    /// The characters generation starts swapping from, placed according to `seed_strategy`.
+    pub fn seed(&self) -> [u8; 30] {
+        let mut chars = self.chars_for_generation;
+
+        match self.seed_strategy {
+            SeedStrategy::Random => shuffle_pins::<30, u8>(&mut chars, &[]),
+            SeedStrategy::VowelSplit => {
+                let mut rng = tls_rng();
+                let (mut vowel_side, mut consonant_side): (Vec<usize>, Vec<usize>) =
+                    (0..30).partition(|&i| is_left_hand(self.fingers[i]));
+                if rng.generate::<bool>() {
+                    std::mem::swap(&mut vowel_side, &mut consonant_side);
+                }
+
+                let vowels = &self.vowels[..self.vowels.len().min(vowel_side.len())];
+                let consonants =
+                    &self.consonants[..self.consonants.len().min(consonant_side.len())];
+                let mut rest = self
+                    .chars_for_generation
+                    .into_iter()
+                    .filter(|c| !vowels.contains(c) && !consonants.contains(c))
+                    .collect::<Vec<_>>();
+                rng.shuffle(&mut rest);
+
+                let mut rest = rest.into_iter();
+                for (side, placed) in [(vowel_side, vowels), (consonant_side, consonants)] {
+                    let mut side_chars = placed.to_vec();
+                    side_chars.extend(rest.by_ref().take(side.len() - placed.len()));
+                    rng.shuffle(&mut side_chars);
+
+                    for (i, c) in side.into_iter().zip(side_chars) {
+                        chars[i] = c;
+                    }
+                }
             }
         }
+        chars
     }
 
     pub fn generate(&self) -> FastLayout {
-        let layout = FastLayout::random(self.chars_for_generation);
-        let mut cache = self.initialize_cache(&layout);
+        let start = Instant::now();
+        let res = with_scratch(|scratch| {
+            scratch.layout.thumbs = [NO_THUMB; 2];
+            scratch.layout.fingers = self.fingers;
+            scratch.layout.set_matrix(self.seed());
+            self.lift_bans(&mut scratch.layout, &self.locks.positions);
+            self.fill_cache(&scratch.layout, &mut scratch.cache);
+
+            self.optimize_mut(&mut scratch.layout, &mut scratch.cache, &self.locks.swaps);
+
+            // mirroring would move locked keys
+            if self.try_mirrored && self.locks.is_empty() {
+                let mut mirrored = scratch.layout.mirrored();
+                self.lift_bans(&mut mirrored, &[]);
+                if self.score(&mirrored) > self.score(&scratch.layout) {
+                    // the mirrored layout usually isn't fully optimized yet
+                    scratch.layout = mirrored;
+                    self.fill_cache(&scratch.layout, &mut scratch.cache);
+                    self.optimize_mut(&mut scratch.layout, &mut scratch.cache, &self.locks.swaps);
+                }
+            }
 
-        let mut layout = self.optimize(layout, &mut cache, &POSSIBLE_SWAPS);
-        layout.score = self.score(&layout);
-        layout
+            let mut layout = scratch.layout.clone();
+            layout.score = self.score(&layout);
+            layout
+        });
+        log::debug!(
+            "generated {} in {:.2?}, scoring {:.5}",
+            self.convert_u8.as_str(&res.matrix),
+            start.elapsed(),
+            res.score
+        );
+        res
     }
 
     pub fn optimize(
@@ -935,10 +2982,11 @@ impl LayoutGeneration {
     ) -> FastLayout {
         let mut with_col_score = f64::MIN;
         let mut optimized_score = f64::MIN / 2.0;
+        self.refine_precision(&mut layout, cache, possible_swaps);
 
         while with_col_score < optimized_score {
             optimized_score = self.optimize_cached(&mut layout, cache, possible_swaps);
-            self.optimize_cols(&mut layout, cache, Some(optimized_score));
+            self.optimize_cols(&mut layout, cache, Some(optimized_score), &[]);
             with_col_score = layout.score;
         }
 
@@ -951,13 +2999,26 @@ impl LayoutGeneration {
         layout: &mut FastLayout,
         cache: &mut LayoutCache,
         possible_swaps: &[PosPair],
+    ) {
+        self.refine_precision(layout, cache, possible_swaps);
+        self.optimize_with_cols(layout, cache, possible_swaps, &[]);
+    }
+
+    /// Makes swaps and then permutes columns until neither improves `layout` anymore. Columns with
+    /// `pins` stay where they are.
+    fn optimize_with_cols(
+        &self,
+        layout: &mut FastLayout,
+        cache: &mut LayoutCache,
+        possible_swaps: &[PosPair],
+        pins: &[usize],
     ) {
         let mut with_col_score = f64::MIN;
         let mut optimized_score = f64::MIN / 2.0;
 
         while with_col_score < optimized_score {
             optimized_score = self.optimize_cached(layout, cache, possible_swaps);
-            self.optimize_cols(layout, cache, Some(optimized_score));
+            self.optimize_cols(layout, cache, Some(optimized_score), pins);
             with_col_score = layout.score;
         }
 
@@ -975,7 +3036,7 @@ impl LayoutGeneration {
         based_on: FastLayout,
         pins: &'a [usize],
     ) -> impl ParallelIterator<Item = FastLayout> + '_ {
-        let possible_swaps = pinned_swaps(pins);
+        let possible_swaps = pinned_swaps(&self.locks.with_pins(pins));
 
         let x = (0..amount)
             .into_par_iter()
@@ -983,27 +3044,151 @@ impl LayoutGeneration {
         x
     }
 
+    /// Generates `amount` layouts split over `islands` populations, which are generated in
+    /// parallel. Every `migration_interval` layouts, an island stops starting from scratch and
+    /// takes the best layout of the island before it and its own best layout instead. Half of
+    /// their keys stay in place while the rest is shuffled and optimized again, which spends more
+    /// time around good layouts than fully independent restarts do. `on_layout` is called every
+    /// time a layout is done, like to show progress.
+    pub fn generate_islands<F>(&self, amount: usize, on_layout: F) -> Vec<FastLayout>
+    where
+        F: Fn() + Sync,
+    {
+        let islands = self.islands.clamp(1, amount.max(1));
+        let interval = self.migration_interval.max(1);
+        let share = |i: usize| amount / islands + usize::from(i < amount % islands);
+        let mut populations = vec![Vec::new(); islands];
+
+        while (0..islands).any(|i| populations[i].len() < share(i)) {
+            let bests = populations
+                .iter()
+                .map(|population: &Vec<FastLayout>| {
+                    population
+                        .iter()
+                        .max_by_key(|layout| TotalScore(layout.score))
+                        .cloned()
+                })
+                .collect::<Vec<_>>();
+
+            populations
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(i, population)| {
+                    let count = interval.min(share(i) - population.len());
+                    let immigrant = bests[(i + islands - 1) % islands].as_ref();
+                    let own = bests[i].as_ref();
+                    if let Some(own) = own {
+                        log::debug!(
+                            "island {i} has {} layouts, the best scoring {:.5}",
+                            population.len(),
+                            own.score
+                        );
+                    }
+
+                    let new = (0..count)
+                        .into_par_iter()
+                        .map(|j| {
+                            let layout = match (immigrant, own) {
+                                (Some(immigrant), _) if j % 2 == 0 => self.reoptimize(immigrant),
+                                (_, Some(own)) => self.reoptimize(own),
+                                _ => self.generate(),
+                            };
+                            on_layout();
+                            layout
+                        })
+                        .collect::<Vec<_>>();
+                    population.extend(new);
+                });
+        }
+        populations.into_iter().flatten().collect()
+    }
+
+    /// `layout` with `MIGRANT_PINS` random keys kept in place and the rest shuffled and optimized
+    /// again.
+    fn reoptimize(&self, layout: &FastLayout) -> FastLayout {
+        let mut pins = (0..30).collect::<Vec<_>>();
+        tls_rng().shuffle(&mut pins);
+        pins.truncate(MIGRANT_PINS);
+
+        let mut res = self.generate_with_pins(layout, &pins, None);
+        let mut cache = self.initialize_cache(&res);
+        self.optimize_mut(&mut res, &mut cache, &self.locks.swaps);
+        res.score = self.score(&res);
+        res
+    }
+
     pub fn generate_with_pins(
         &self,
         based_on: &FastLayout,
         pins: &[usize],
         possible_swaps: Option<&[PosPair]>,
-    ) -> FastLayout {
-        let mut layout = FastLayout::random_pins(based_on.matrix, pins);
-        let mut cache = self.initialize_cache(&layout);
-
-        if let Some(ps) = possible_swaps {
-            self.optimize_cached(&mut layout, &mut cache, ps)
-        } else {
-            self.optimize_cached(&mut layout, &mut cache, &pinned_swaps(pins))
-        };
+    ) -> FastLayout {
+        with_scratch(|scratch| {
+            let GenerationScratch {
+                layout,
+                cache,
+                swaps,
+            } = scratch;
+
+            let locked = self.locks.with_pins(pins);
+            layout.thumbs = based_on.thumbs;
+            layout.fingers = self.fingers;
+            layout.randomize(based_on.matrix, &locked);
+            self.lift_bans(layout, &locked);
+            self.fill_cache(layout, cache);
+
+            let possible_swaps = match possible_swaps {
+                Some(ps) => ps,
+                None => {
+                    pinned_swaps_into(&locked, swaps);
+                    swaps.as_slice()
+                }
+            };
+            self.refine_precision(layout, cache, possible_swaps);
+            if self.locks.column_units.is_empty() {
+                self.optimize_cached(layout, cache, possible_swaps);
+            } else {
+                // column units can't be swapped, so they only move with the columns
+                self.optimize_with_cols(layout, cache, possible_swaps, pins);
+            }
 
-        layout.score = self.score(&layout);
-        layout
+            let mut res = layout.clone();
+            res.score = self.score(&res);
+            res
+        })
     }
 }
 
+mod calibration;
+mod comparison;
+mod key_costs;
+mod languages;
+mod layers;
+mod locks;
+mod magic;
 mod obsolete;
+mod placeholders;
+mod punctuation;
+mod quadgrams;
+mod retraining;
+mod stopping;
+mod suggest;
+mod typing_time;
+
+pub use calibration::PrecisionCalibration;
+pub use comparison::Comparison;
+pub use key_costs::{HeatmapMode, KeyCost};
+pub use languages::LanguageResult;
+pub use layers::{Layer, LayerHold, LayerStats};
+pub use locks::Locks;
+pub use magic::MagicKey;
+pub use placeholders::PLACEHOLDER;
+pub use punctuation::PunctuationCost;
+pub use quadgrams::QuadgramStats;
+pub use retraining::RetrainingCost;
+pub use stopping::{GenerationProgress, StopCriteria, StopReason};
+pub use suggest::SwapSuggestion;
+pub use typing_time::{wpm, TypingTimeModel};
 // mod iterative;
 
 #[cfg(test)]
@@ -1019,7 +3204,7 @@ mod tests {
 
     #[allow(dead_code)]
     fn fspeed_per_pair() {
-        for (pair, dist) in GEN.fspeed_vals {
+        for (pair, dist) in GEN.fspeed_vals.iter() {
             println!(
                 "({}, {}) <-> ({}, {}): {dist}",
                 pair.0 % 10,
@@ -1034,6 +3219,7 @@ mod tests {
     fn prune_heuristic_correctness() {
         //has been tested with 10000 runs
         let runs = 200;
+        let swaps = POSSIBLE_SWAPS.map(Move::Swap);
 
         for _ in 0..runs {
             let mut layout = FastLayout::random(GEN.chars_for_generation);
@@ -1043,10 +3229,10 @@ mod tests {
 				GEN.best_swap(&mut layout, None, &POSSIBLE_SWAPS) {
                 
                 if let (Some(best_swap_cached), best_score_cached) =
-                    GEN.best_swap_cached(&mut layout, &cache, None, &POSSIBLE_SWAPS) {
+                    GEN.best_swap_cached(&mut layout, &cache, None, &swaps) {
     
                     if best_score_normal.approx_eq_dbg(best_score_cached, 7) {
-                        assert_eq!(best_swap_normal, best_swap_cached);
+                        assert_eq!(Move::Swap(best_swap_normal), best_swap_cached);
                     }
                 }
             }
@@ -1073,7 +3259,7 @@ mod tests {
             .map(|_| &POSSIBLE_SWAPS[rng.generate_range(0..435)])
             .take(10000)
         {
-            GEN.accept_swap(&mut qwerty, swap, &mut cache);
+            GEN.accept_swap(&mut qwerty, &Move::Swap(*swap), &mut cache);
 
             assert!(cache.scissors.approx_eq_dbg(GEN.scissor_score(&qwerty), 7));
             assert!(cache
@@ -1084,16 +3270,288 @@ mod tests {
                 .fspeed_total
                 .approx_eq_dbg(GEN.fspeed_score(&qwerty), 7));
             assert!(cache.trigrams_total.approx_eq_dbg(
-                GEN.trigram_score_iter(&qwerty, GEN.data.trigrams.iter().take(1000)),
+                GEN.trigram_score_iter(
+                    &qwerty,
+                    GEN.data.trigrams.iter().take(GEN.trigram_precision)
+                ),
                 7
             ));
             assert!(cache.lsbs.approx_eq_dbg(GEN.lsb_score(&qwerty), 7));
+            assert!(cache
+                .row_skips
+                .approx_eq_dbg(GEN.row_skip_score(&qwerty), 7));
             assert!(cache
                 .total_score
-                .approx_eq_dbg(GEN.score_with_precision(&qwerty, 1000), 7));
+                .approx_eq_dbg(GEN.score(&qwerty), 7));
+        }
+    }
+
+    #[test]
+    fn bigram_penalties() {
+        let mut config = Config::default();
+        config.bigram_penalties.push(BigramPenalty {
+            name: "Ring Pinky".to_string(),
+            weight: 3.0,
+            pairs: vec![PosPair(0, 1), PosPair(10, 11), PosPair(18, 19)],
+        });
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let qwerty_bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let mut qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+
+        let stats = gen.get_layout_stats(&qwerty);
+        assert_eq!(stats.bigram_penalties.len(), 1);
+        assert!(stats.bigram_penalties[0].1 > 0.0);
+        assert!(stats.to_string().contains("Ring Pinky: "));
+        let plain = LayoutGeneration::new("english", "static", Some(Config::default())).unwrap();
+        let penalty = plain.score(&qwerty) - gen.score(&qwerty);
+        assert!(penalty.approx_eq_dbg(3.0 * stats.bigram_penalties[0].1, 7));
+
+        let mut cache = gen.initialize_cache(&qwerty);
+        for swap in [PosPair(0, 12), PosPair(5, 6), PosPair(11, 29)] {
+            let score = gen.score_swap_cached(&mut qwerty, &swap, &cache);
+            gen.accept_swap(&mut qwerty, &swap.into(), &mut cache);
+            assert!(score.approx_eq_dbg(gen.score(&qwerty), 7));
+            assert!(cache.total_score.approx_eq_dbg(gen.score(&qwerty), 7));
+        }
+    }
+
+    #[test]
+    fn hand_alternation() {
+        let mut config = Config::default();
+        config.weights.hand_alternation.penalty = 5.0;
+        config.weights.hand_alternation.target = 0.9;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let qwerty_bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let mut qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+
+        let alternation = gen.hand_alternation(&qwerty);
+        assert!(alternation > 0.0 && alternation < 0.9);
+        assert_eq!(gen.get_layout_stats(&qwerty).hand_alternation, alternation);
+        let plain = LayoutGeneration::new("english", "static", Some(Config::default())).unwrap();
+        let penalty = plain.score(&qwerty) - gen.score(&qwerty);
+        assert!(penalty.approx_eq_dbg(5.0 * (0.9 - alternation), 7));
+
+        let mut cache = gen.initialize_cache(&qwerty);
+        for swap in [PosPair(0, 9), PosPair(1, 2), PosPair(13, 26)] {
+            let score = gen.score_swap_cached(&mut qwerty, &swap, &cache);
+            gen.accept_swap(&mut qwerty, &swap.into(), &mut cache);
+            assert!(score.approx_eq_dbg(gen.score(&qwerty), 7));
+            assert!(cache.total_score.approx_eq_dbg(gen.score(&qwerty), 7));
+        }
+    }
+
+    #[test]
+    fn row_usage() {
+        let mut config = Config::default();
+        config.weights.row_usage.penalty = 5.0;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let qwerty_bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let mut qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+
+        let usage = gen.row_usage(&qwerty);
+        assert!((usage.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(usage[1] < gen.weights.row_usage.home);
+        assert_eq!(gen.get_layout_stats(&qwerty).row_usage, usage);
+        let plain = LayoutGeneration::new("english", "static", Some(Config::default())).unwrap();
+        let penalty = plain.score(&qwerty) - gen.score(&qwerty);
+        let distance = gen.weights.row_usage.distance(&usage);
+        assert!(distance > 0.0 && penalty.approx_eq_dbg(5.0 * distance, 7));
+
+        let mut cache = gen.initialize_cache(&qwerty);
+        for swap in [PosPair(0, 10), PosPair(1, 2), PosPair(13, 26)] {
+            let score = gen.score_swap_cached(&mut qwerty, &swap, &cache);
+            gen.accept_swap(&mut qwerty, &swap.into(), &mut cache);
+            assert!(score.approx_eq_dbg(gen.score(&qwerty), 7));
+            assert!(cache.total_score.approx_eq_dbg(gen.score(&qwerty), 7));
+        }
+    }
+
+    #[test]
+    fn shortcuts() {
+        let mut config = Config::default();
+        config.weights.shortcuts.penalty = 5.0;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let layout = |keys: &str| {
+            let bytes = gen.convert_u8.to_lossy(keys.chars());
+            let mut layout = FastLayout::try_from(bytes.as_slice()).unwrap();
+            layout.set_fingers(&gen.fingers);
+            layout
+        };
+        let qwerty = layout("qwertyuiopasdfghjkl;zxcvbnm,./");
+        let mut dvorak = layout("',.pyfgcrlaoeuidhtns;qjkxbmwvz");
+
+        assert!(gen.shortcut_reach(&qwerty).approx_eq_dbg(6.0 / 7.0, 7));
+        assert_eq!(gen.get_layout_stats(&qwerty).shortcut_reach, gen.shortcut_reach(&qwerty));
+        assert!(gen.shortcut_reach(&dvorak).approx_eq_dbg(2.0 / 7.0, 7));
+        let unreachable = gen.convert_u8.as_str(&gen.unreachable_shortcuts(&dvorak));
+        assert_eq!(unreachable, "zcvfw");
+        let plain = LayoutGeneration::new("english", "static", Some(Config::default())).unwrap();
+        let penalty = plain.score(&dvorak) - gen.score(&dvorak);
+        assert!(penalty.approx_eq_dbg(5.0 * 5.0 / 7.0, 7));
+
+        let mut cache = gen.initialize_cache(&dvorak);
+        for swap in [PosPair(0, 10), PosPair(24, 29), PosPair(13, 26)] {
+            let score = gen.score_swap_cached(&mut dvorak, &swap, &cache);
+            gen.accept_swap(&mut dvorak, &swap.into(), &mut cache);
+            assert!(score.approx_eq_dbg(gen.score(&dvorak), 7));
+            assert!(cache.total_score.approx_eq_dbg(gen.score(&dvorak), 7));
+        }
+    }
+
+    #[test]
+    fn trigram_precision() {
+        let mut config = Config::default();
+        config.defaults.trigram_precision = 100;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let qwerty_bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let cache = gen.initialize_cache(&qwerty);
+
+        assert!(cache.total_score.approx_eq_dbg(gen.score(&qwerty), 7));
+        assert!(gen
+            .score(&qwerty)
+            .approx_eq_dbg(gen.score_with_precision(&qwerty, 100), 7));
+        assert!(gen
+            .score_full(&qwerty)
+            .approx_eq_dbg(gen.score_with_precision(&qwerty, gen.data.trigrams.len()), 7));
+        assert_ne!(gen.score(&qwerty), gen.score_full(&qwerty));
+    }
+
+    #[test]
+    fn trigram_index() {
+        let gen = LayoutGeneration::new("test", "static", None).unwrap();
+        let len = gen.data.characters.len();
+        let trigrams = &gen.data.trigrams[..gen.data.trigrams.len().min(gen.trigram_precision)];
+
+        for (c1, c2) in (0..len as u8).cartesian_product(0..len as u8) {
+            let mut expected = trigrams
+                .iter()
+                .filter(|(t, _)| t.contains(&c1) || t.contains(&c2))
+                .copied()
+                .collect::<TrigramData>();
+            expected.sort_by_key(|&(_, freq)| Reverse(TotalScore(freq)));
+
+            let i = pair_index(c1, c2, len).unwrap();
+            assert_eq!(gen.char_trigrams()[i], expected, "pair {c1} {c2}");
+
+            let expected = trigrams
+                .iter()
+                .filter(|([t1, t2, _], _)| (*t1, *t2) == (c1, c2))
+                .map(|&([_, _, t3], freq)| (t3, freq))
+                .collect::<Vec<_>>();
+            let by_prefix = &gen.trigrams_by_prefix()[c1 as usize * len + c2 as usize];
+            assert_eq!(*by_prefix, expected, "prefix {c1} {c2}");
         }
     }
 
+    #[test]
+    fn rebuild_trigrams() {
+        let mut config = Config::default();
+        let mut gen = LayoutGeneration::new("english", "static", Some(config.clone())).unwrap();
+        config.defaults.trigram_precision = 100;
+        let expected = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let qwerty_bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+
+        let full = gen.score(&qwerty);
+        let key = gen.score_key;
+        gen.char_trigrams();
+        gen.rebuild_trigrams(100);
+
+        assert_eq!(gen.trigram_precision, 100);
+        assert_ne!(gen.score_key, key);
+        assert_eq!(gen.char_trigrams(), expected.char_trigrams());
+        assert_eq!(gen.trigrams_by_prefix(), expected.trigrams_by_prefix());
+        assert!(gen.score(&qwerty).approx_eq_dbg(expected.score(&qwerty), 7));
+        assert!(gen
+            .initialize_cache(&qwerty)
+            .total_score
+            .approx_eq_dbg(expected.score(&qwerty), 7));
+
+        gen.rebuild_trigrams(expected.data.trigrams.len());
+        assert!(gen.score(&qwerty).approx_eq_dbg(gen.score_full(&qwerty), 7));
+        assert_ne!(gen.score(&qwerty), full);
+    }
+
+    #[test]
+    fn space_trigram_stats() {
+        let mut config = Config::default();
+        config.defaults.space_thumb = SpaceThumb::Right;
+        let mut gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let qwerty_bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        assert!(gen.space_trigram_stats(&qwerty).is_none());
+
+        let [e, t, h, quote] = [
+            gen.convert_u8.to_single_lossy('e'),
+            gen.convert_u8.to_single_lossy('t'),
+            gen.convert_u8.to_single_lossy('h'),
+            gen.convert_u8.to_single_lossy('\''),
+        ];
+        // the code space borrows, which a character that isn't on the layout has as well
+        let borrowed = qwerty
+            .char_to_finger
+            .iter()
+            .position(|&f| f == usize::MAX)
+            .unwrap() as u8;
+        gen.data.space_trigrams = vec![
+            ([e, SPACE, t], 0.5),
+            ([SPACE, h, e], 0.25),
+            ([quote, SPACE, t], 0.125),
+            ([borrowed, SPACE, t], 0.125),
+        ];
+        let stats = gen.space_trigram_stats(&qwerty).unwrap();
+        assert_eq!(stats.alternates, 0.5);
+        assert_eq!(stats.inrolls + stats.outrolls, 0.25);
+        assert_eq!(stats.invalid, 0.25);
+        assert_eq!(stats.rolls_by_hand(1), 0.25);
+
+        gen.space_thumb = SpaceThumb::None;
+        assert!(gen.space_trigram_stats(&qwerty).is_none());
+    }
+
+    #[test]
+    fn progressive_precision() {
+        let mut config = Config::default();
+        config.generation.progressive_precision = true;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        assert_eq!(gen.coarse_precisions(), vec![62, 250]);
+        assert!(GEN.coarse_precisions().is_empty());
+
+        let layout = gen.generate();
+        assert!(layout.score.approx_eq_dbg(gen.score(&layout), 7));
+
+        let qwerty_bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let mut qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let mut cache = gen.initialize_cache(&qwerty);
+        let full = cache.total_score;
+
+        // swaps keep a cache with a lower precision up to date as well
+        gen.set_cache_precision(&qwerty, &mut cache, 100);
+        assert_ne!(cache.total_score, full);
+        gen.accept_swap(&mut qwerty, &PosPair(0, 12).into(), &mut cache);
+        let mut fresh = LayoutCache::default();
+        gen.set_cache_precision(&qwerty, &mut fresh, 100);
+        assert!(cache.total_score.approx_eq_dbg(fresh.total_score, 7));
+
+        gen.set_cache_precision(&qwerty, &mut cache, gen.trigram_precision);
+        assert!(cache.total_score.approx_eq_dbg(gen.score(&qwerty), 7));
+    }
+
     #[test]
     fn best_found_swap() {
         let qwerty_bytes = GEN
@@ -1106,10 +3564,10 @@ mod tests {
 			GEN.best_swap(&mut qwerty, None, &POSSIBLE_SWAPS) {
             
             if let (Some(best_swap_cached), best_score_cached) =
-                GEN.best_swap_cached(&mut qwerty, &cache, None, &POSSIBLE_SWAPS) {
+                GEN.best_swap_cached(&mut qwerty, &cache, None, &POSSIBLE_SWAPS.map(Move::Swap)) {
     
                 if best_score_normal.approx_eq_dbg(best_score_cached, 7) {
-                    assert_eq!(best_swap_normal, best_swap_cached);
+                    assert_eq!(Move::Swap(best_swap_normal), best_swap_cached);
                 } else {
                     println!("scores not the same")
                 }
@@ -1148,7 +3606,7 @@ mod tests {
         let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
 
         let optimized_normal = GEN.optimize_normal_no_cols(qwerty.clone(), &POSSIBLE_SWAPS);
-        let normal_score = GEN.score_with_precision(&optimized_normal, 1000);
+        let normal_score = GEN.score(&optimized_normal);
 
         let mut qwerty_for_cached = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
         let mut cache = GEN.initialize_cache(&qwerty_for_cached);
@@ -1164,6 +3622,704 @@ mod tests {
         // println!("{qwerty_for_cached}");
     }
 
+    #[test]
+    fn coverage_of_generated_chars() {
+        let layout = FastLayout::with_fingers(GEN.chars_for_generation, &GEN.fingers);
+        let coverage = GEN.coverage(&layout);
+
+        assert!(coverage.characters > 0.0 && coverage.characters <= 1.0);
+        assert!(coverage.bigrams > 0.0 && coverage.bigrams <= 1.0);
+        assert!(coverage.trigrams > 0.0 && coverage.trigrams <= 1.0);
+
+        let trigram_stats = GEN.trigram_stats(&layout, usize::MAX);
+        let total_trigrams = GEN.data.trigrams.iter().map(|(_, f)| f).sum::<f64>();
+        assert!((1.0 - coverage.trigrams).approx_eq_dbg(trigram_stats.invalid / total_trigrams, 7));
+        assert!(trigram_stats
+            .invalid_share()
+            .approx_eq_dbg(trigram_stats.invalid / total_trigrams, 7));
+
+        let renormalized = trigram_stats.without_invalid();
+        assert_eq!(renormalized.invalid, 0.0);
+        assert!(renormalized.total().approx_eq_dbg(trigram_stats.total(), 7));
+        assert!(renormalized.inrolls > trigram_stats.inrolls);
+
+        let invalid_chars = GEN.invalid_trigram_chars(&layout);
+        assert!(invalid_chars.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert!(invalid_chars.iter().all(|(_, freq)| *freq <= trigram_stats.invalid + 1e-9));
+        let counted = invalid_chars.iter().map(|(_, freq)| freq).sum::<f64>();
+        assert!(counted >= trigram_stats.invalid - 1e-9);
+    }
+
+    #[test]
+    fn hand_dominance() {
+        let dominance = HandDominance {
+            left: 1.0,
+            right: 0.5,
+        };
+        let effort = LayoutGeneration::dominance_effort([1.0; 30], &dominance, &I_TO_COL);
+        let fspeed =
+            LayoutGeneration::dominance_fspeed(get_fspeed(1.0).to_vec(), &dominance, &I_TO_COL);
+
+        assert_eq!(effort[4], 1.0);
+        assert_eq!(effort[5], 0.5);
+        assert_eq!(effort[29], 0.5);
+
+        // the hand of a key is the one of the finger that presses it, not the side it is on
+        let mut fingers = I_TO_COL;
+        fingers[4] = 4;
+        let effort = LayoutGeneration::dominance_effort([1.0; 30], &dominance, &fingers);
+        assert_eq!(effort[4], 0.5);
+
+        for ((pair, dist), (_, base)) in fspeed.iter().zip(get_fspeed(1.0)) {
+            if is_left_hand(I_TO_COL[pair.0]) {
+                assert_eq!(*dist, base);
+            } else {
+                assert_eq!(*dist, base * 0.5);
+            }
+        }
+
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let [left, right] = GEN.hand_usage(&qwerty);
+        assert!((left + right).approx_eq_dbg(1.0, 7));
+        assert!(left > right);
+    }
+
+    #[test]
+    fn finger_speeds() {
+        let mut config = Config::default();
+        config.weights.fingers.pinky = 2.0;
+        config.weights.fingers.index = 0.5;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let plain = LayoutGeneration::new("english", "static", Some(Config::default())).unwrap();
+        let qwerty_bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+
+        let stats = gen.get_layout_stats(&qwerty);
+        let plain_stats = plain.get_layout_stats(&qwerty);
+        for (finger, multiplier) in [(0, 2.0), (1, 1.0), (2, 1.0), (3, 0.5), (4, 0.5), (7, 2.0)] {
+            let expected = plain_stats.finger_speed[finger] * multiplier;
+            assert!(stats.finger_speed[finger].approx_eq_dbg(expected, 9));
+        }
+        assert_eq!(stats.sfb_distance, plain_stats.sfb_distance);
+    }
+
+    #[test]
+    fn stat_deltas() {
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let stats = GEN.get_layout_stats(&qwerty);
+
+        assert!(stats.deltas(&stats).is_empty());
+
+        let mut swapped = qwerty.clone();
+        swapped.swap(10, 12);
+        let deltas = GEN.get_layout_stats(&swapped).deltas(&stats);
+
+        assert!(deltas.iter().any(|d| d.starts_with("Sfb ")));
+    }
+
+    #[test]
+    fn stat_metrics() {
+        let layout = FastLayout::with_fingers(GEN.chars_for_generation, &GEN.fingers);
+        let stats = GEN.get_layout_stats(&layout);
+
+        assert_eq!(stats.metric("SFB"), Some(("Sfb", stats.sfb * 100.0)));
+        assert_eq!(stats.metric("fspeed"), Some(("Finger Speed", stats.fspeed * 10.0)));
+        assert_eq!(stats.metric("sfr"), None);
+        assert_eq!(stats.metric("dsfb3"), Some(("Dsfb3", stats.dsfb3 * 100.0)));
+    }
+
+    #[test]
+    fn trigrams_per_pair() {
+        let len = GEN.data.characters.len();
+        let e = GEN.convert_u8.to_single_lossy('e');
+        let t = GEN.convert_u8.to_single_lossy('t');
+        let i = pair_index(e, t, len).unwrap();
+        assert_eq!(pair_index(t, e, len), Some(i));
+        assert_eq!(pair_index(e, u8::MAX, len), None);
+
+        let trigrams = &GEN.char_trigrams()[i];
+        let expected = GEN
+            .data
+            .trigrams
+            .iter()
+            .take(GEN.trigram_precision)
+            .filter(|(trigram, _)| trigram.contains(&e) || trigram.contains(&t))
+            .count();
+        assert_eq!(trigrams.len(), expected);
+        assert!(trigrams.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+
+    #[test]
+    fn same_finger_skipgrams() {
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let stats = GEN.get_layout_stats(&qwerty);
+
+        let all = GEN.sfs(&qwerty, usize::MAX);
+        let total = |gap: usize| {
+            all.iter()
+                .filter(|s| s.gap == gap)
+                .map(|s| s.freq)
+                .sum::<f64>()
+        };
+        assert!((total(1) - stats.dsfb).abs() < 1e-9);
+        assert!((total(2) - stats.dsfb2).abs() < 1e-9);
+        assert!((total(3) - stats.dsfb3).abs() < 1e-9);
+
+        let top = GEN.sfs(&qwerty, 10);
+        assert_eq!(top.len(), 10);
+        assert!(top.windows(2).all(|w| w[0].freq >= w[1].freq));
+        assert_eq!(top[..], all[..10]);
+    }
+
+    #[test]
+    fn loading_errors() {
+        let missing = LayoutGeneration::new("klingon", "static", Some(Config::default()));
+        assert!(matches!(
+            missing,
+            Err(RusylyzerError::LanguageData { language, .. }) if language == "klingon"
+        ));
+
+        let layout = FastLayout::with_fingers(GEN.chars_for_generation, &GEN.fingers);
+        assert!(matches!(
+            GEN.bigram_percent(&layout, "trigrams"),
+            Err(RusylyzerError::BigramType(_))
+        ));
+        assert_eq!(
+            GEN.bigram_percent(&layout, "dsfb").unwrap(),
+            GEN.get_layout_stats(&layout).dsfb
+        );
+
+        assert_eq!(GEN.checked_score("layout", &layout).unwrap(), GEN.score(&layout));
+        let mut config = Config::default();
+        config.weights.scissors = f64::NAN;
+        let nan = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        assert!(matches!(
+            nan.checked_score("layout", &layout),
+            Err(RusylyzerError::NanScore { component: "scissors", .. })
+        ));
+    }
+
+    #[test]
+    fn sfb_per_finger() {
+        let layout = FastLayout::with_fingers(GEN.chars_for_generation, &GEN.fingers);
+        let stats = GEN.get_layout_stats(&layout);
+
+        let total = stats.sfb_per_finger.iter().sum::<f64>();
+        assert!(total.approx_eq_dbg(stats.sfb, 7));
+        assert_eq!(
+            GEN.bigram_percent_weighted(&layout, "sfb").unwrap(),
+            stats.sfb_distance
+        );
+
+        let weighted_dsfb = GEN.bigram_percent_per_finger(&layout, "dsfb", true).unwrap();
+        let dsfb = GEN.bigram_percent_per_finger(&layout, "dsfb", false).unwrap();
+        // pinky distances are scaled up for finger strength
+        for pinky in [0, 7] {
+            assert!(weighted_dsfb[pinky] >= dsfb[pinky]);
+        }
+    }
+
+    #[test]
+    fn travel_in_score() {
+        let mut config = Config::default();
+        config.weights.travel = 0.5;
+        let with_travel = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let without = LayoutGeneration::new("english", "static", Some(Config::default())).unwrap();
+
+        let layout =
+            FastLayout::with_fingers(with_travel.chars_for_generation, &with_travel.fingers);
+        let travel = with_travel.travel(&layout);
+
+        assert!(travel > 0.0);
+        let difference = without.score(&layout) - with_travel.score(&layout);
+        assert!(difference.approx_eq_dbg(travel * 0.5, 7));
+        assert_eq!(with_travel.get_layout_stats(&layout).travel, travel);
+    }
+
+    #[test]
+    fn avoided_keys() {
+        let mut config = Config::default();
+        config.avoid_keys = vec![(0, 2.0), (20, 3.0)];
+        config.generation.avoided_key_ban = 2.0;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let base = LayoutGeneration::new("english", "static", Some(Config::default())).unwrap();
+
+        assert!(gen.effort_map[20].approx_eq_dbg(base.effort_map[20] * 3.0, 9));
+        assert!(gen.effort_map[0].approx_eq_dbg(base.effort_map[0] * 2.0, 9));
+        assert_eq!(gen.effort_map[10], base.effort_map[10]);
+
+        // most common characters first, so the first one is on an avoided key
+        let mut layout = FastLayout::with_fingers(gen.chars_for_generation, &gen.fingers);
+        assert!(gen.has_banned_keys(&layout));
+        gen.lift_bans(&mut layout, &[0]);
+        assert!(gen.has_banned_keys(&layout));
+        gen.lift_bans(&mut layout, &[]);
+        assert!(!gen.has_banned_keys(&layout));
+
+        let generated = gen.generate();
+        assert!(!gen.has_banned_keys(&generated));
+        for i in [0, 20] {
+            assert!(gen.data.characters[generated.c(i) as usize] <= 0.02);
+        }
+        let layout = FastLayout::with_fingers(base.chars_for_generation, &base.fingers);
+        assert!(!base.has_banned_keys(&layout));
+    }
+
+    #[test]
+    fn rolls_by_row() {
+        let layout = FastLayout::with_fingers(GEN.chars_for_generation, &GEN.fingers);
+        let rows = char_rows(&layout);
+        let placement = |i1: usize, i2: usize, i3: usize| {
+            roll_placement(&layout, &rows, &[layout.c(i1), layout.c(i2), layout.c(i3)])
+        };
+
+        assert_eq!(placement(11, 12, 17), (1, true));
+        assert_eq!(placement(17, 10, 12), (1, false));
+        assert_eq!(placement(1, 12, 17), (3, false));
+        assert_eq!(placement(2, 26, 27), (2, true));
+
+        let stats = GEN.trigram_stats(&layout, usize::MAX);
+        assert!(stats.inrolls_by_row.iter().sum::<f64>().approx_eq_dbg(stats.inrolls, 7));
+        assert!(stats.outrolls_by_row.iter().sum::<f64>().approx_eq_dbg(stats.outrolls, 7));
+        assert!(stats.adjacent_rolls > 0.0);
+        assert!(stats.adjacent_rolls < stats.inrolls + stats.outrolls);
+        assert_eq!(roll_hand(&layout, &[layout.c(17), layout.c(10), layout.c(12)]), 0);
+        assert_eq!(roll_hand(&layout, &[layout.c(1), layout.c(16), layout.c(17)]), 1);
+        assert!(stats.inrolls_by_hand.iter().sum::<f64>().approx_eq_dbg(stats.inrolls, 7));
+        assert!(stats.outrolls_by_hand.iter().sum::<f64>().approx_eq_dbg(stats.outrolls, 7));
+
+        let mut config = Config::default();
+        config.weights.home_row_rolls = 0.5;
+        let with_home_rolls = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let without = LayoutGeneration::new("english", "static", Some(Config::default())).unwrap();
+
+        let precision = without.trigram_precision;
+        let home_row_rolls = without.trigram_stats(&layout, precision).home_row_rolls();
+        let difference = with_home_rolls.score(&layout) - without.score(&layout);
+        assert!(difference.approx_eq_dbg(home_row_rolls * 0.5, 7));
+
+        let mut config = Config::default();
+        config.weights.roll_hands.right = 0.5;
+        let left_rolls = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+
+        let stats = without.trigram_stats(&layout, precision);
+        let weights = &without.weights;
+        let expected = -0.5
+            * (weights.inrolls * stats.inrolls_by_hand[1]
+                + weights.outrolls * stats.outrolls_by_hand[1]);
+        let difference = left_rolls.score(&layout) - without.score(&layout);
+        assert!(difference.approx_eq_dbg(expected, 7));
+    }
+
+    #[test]
+    fn finger_assignment() {
+        let mut config = Config::default();
+        config.fingers[23] = 2;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+
+        let mut layout = FastLayout::with_fingers(gen.chars_for_generation, &gen.fingers);
+        let base = layout.clone();
+        let cache = gen.initialize_cache(&layout);
+
+        assert_eq!(gen.fspeed_vals.len(), 46);
+        assert_eq!(gen.col_to_start_len(2), (6, 6));
+
+        for swap in [PosPair(3, 23), PosPair(22, 23), PosPair(0, 23), PosPair(12, 17)] {
+            let score_normal = gen.score_swap(&mut layout, &swap);
+            let score_cached = gen.score_swap_cached(&mut layout, &swap, &cache);
+
+            assert_eq!(base, layout);
+            assert!(score_normal.approx_eq_dbg(score_cached, 7), "failed on {swap}");
+        }
+
+        let sfb = gen.same_finger_percent(&layout, &gen.data.bigrams);
+        let per_finger = gen.same_finger_per_finger(&layout, &gen.data.bigrams, false);
+        assert!(sfb.approx_eq_dbg(per_finger.iter().sum(), 7));
+        assert_ne!(sfb, GEN.same_finger_percent(&layout, &GEN.data.bigrams));
+
+        assert_eq!(gen.generate().fingers, gen.fingers);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_metric() {
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+
+        let with_command = |script: &str| {
+            let mut config = Config::default();
+            config.external_metric.command = ["sh", "-c", script].map(str::to_string).to_vec();
+            config.external_metric.weight = -0.5;
+            LayoutGeneration::new("english", "static", Some(config)).unwrap()
+        };
+
+        let without = LayoutGeneration::new("english", "static", Some(Config::default())).unwrap();
+        let gen = with_command("while read l; do echo 3; done");
+        let components = gen.score_components(&qwerty, gen.trigram_precision);
+        assert_eq!(components.external_metric, -1.5);
+        assert!(gen.score(&qwerty).approx_eq_dbg(without.score(&qwerty) - 1.5, 7));
+        assert_ne!(gen.score_key, without.score_key);
+
+        let gen = with_command("while read l; do echo none; done");
+        assert!(gen.score(&qwerty).is_nan());
+        let error = gen.checked_score("qwerty", &qwerty).unwrap_err().to_string();
+        assert!(error.contains("instead of a number"), "{error}");
+    }
+
+    #[test]
+    fn score_key_follows_config() {
+        let key = LayoutGeneration::score_key(&GEN.data, &Config::default());
+        assert_eq!(key, LayoutGeneration::score_key(&GEN.data, &Config::default()));
+
+        let mut config = Config::default();
+        config.weights.fspeed += 1.0;
+        assert_ne!(key, LayoutGeneration::score_key(&GEN.data, &config));
+
+        let mut config = Config::default();
+        config.fingers[23] = 2;
+        assert_ne!(key, LayoutGeneration::score_key(&GEN.data, &config));
+    }
+
+    #[test]
+    fn mirrored_generation() {
+        let mut config = Config::default();
+        config.generation.try_mirrored = true;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let layout = gen.generate();
+
+        assert!(!GEN.try_mirrored);
+        assert!(layout.score.approx_eq_dbg(gen.score(&layout), 7));
+
+        let mirrored = layout.mirrored();
+        let stats = gen.get_layout_stats(&layout);
+        let mirrored_stats = gen.get_layout_stats(&mirrored);
+        assert!(stats.sfb.approx_eq_dbg(mirrored_stats.sfb, 7));
+        assert!(stats.dsfb.approx_eq_dbg(mirrored_stats.dsfb, 7));
+    }
+
+    #[test]
+    fn swap_tie_breaking() {
+        let a = PosPair(0, 1);
+        let b = PosPair(2, 3);
+        let det = SwapPolicy::Deterministic;
+
+        assert!(beats_swap(det, (1.0, &b), (0.0, None)));
+        assert!(!beats_swap(det, (SWAP_EPSILON / 2.0, &b), (0.0, None)));
+        assert!(beats_swap(det, (1.0, &a), (1.0 + SWAP_EPSILON / 2.0, Some(&b))));
+        assert!(!beats_swap(det, (1.0 + SWAP_EPSILON / 2.0, &b), (1.0, Some(&a))));
+        assert!(!beats_swap(SwapPolicy::Shuffled, (1.0, &a), (1.0, Some(&b))));
+
+        let layout = FastLayout::random(GEN.chars_for_generation);
+        let cache = GEN.initialize_cache(&layout);
+        let swaps = POSSIBLE_SWAPS.map(Move::Swap);
+        let mut reversed = swaps.to_vec();
+        reversed.reverse();
+        assert_eq!(
+            GEN.best_swap_cached(&mut layout.clone(), &cache, None, &swaps),
+            GEN.best_swap_cached(&mut layout.clone(), &cache, None, &reversed)
+        );
+    }
+
+    #[test]
+    fn rotations() {
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let mut layout = qwerty.clone();
+        let mut cache = GEN.initialize_cache(&layout);
+
+        GEN.accept_swap(&mut layout, &Move::Rotate([0, 10, 20]), &mut cache);
+        assert_eq!(
+            [layout.c(0), layout.c(10), layout.c(20)],
+            [qwerty.c(20), qwerty.c(0), qwerty.c(10)]
+        );
+        GEN.accept_swap(&mut layout, &Move::Rotate([0, 10, 20]).inverse(), &mut cache);
+        assert_eq!(layout, qwerty);
+
+        for rotation in [
+            Move::Rotate([3, 17, 25]),
+            Move::Rotate([29, 1, 14]),
+            Move::Rotate([5, 15, 25]),
+        ] {
+            let before = layout.clone();
+            let score = GEN.score_move_cached(&mut layout, &rotation, &cache);
+            assert_eq!(layout, before);
+
+            GEN.accept_swap(&mut layout, &rotation, &mut cache);
+            assert!(score.approx_eq_dbg(GEN.score(&layout), 7));
+            assert!(cache.total_score.approx_eq_dbg(GEN.score(&layout), 7));
+        }
+
+        assert!(!GEN.rotations);
+        assert_eq!(GEN.possible_rotations(&POSSIBLE_SWAPS).len(), 20);
+        assert_eq!(GEN.possible_rotations(&pinned_swaps(&[0])).len(), 18);
+
+        let mut config = Config::default();
+        config.generation.rotations = true;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        assert_eq!(gen.possible_rotations(&POSSIBLE_SWAPS).len(), 30 * 29 * 28 / 3);
+
+        // optimizing only stops once no rotation improves the layout either
+        let mut layout = FastLayout::random(GEN.chars_for_generation);
+        let mut cache = GEN.initialize_cache(&layout);
+        let score = GEN.optimize_cached(&mut layout, &mut cache, &POSSIBLE_SWAPS);
+        let rotations = GEN.possible_rotations(&POSSIBLE_SWAPS);
+        let (rotation, _) = GEN.best_swap_cached(&mut layout, &cache, Some(score), &rotations);
+        assert_eq!(rotation, None);
+    }
+
+    #[test]
+    fn swap_policies() {
+        let start = FastLayout::random(GEN.chars_for_generation);
+        let optimize = |gen: &LayoutGeneration| {
+            let mut layout = start.clone();
+            let mut cache = gen.initialize_cache(&layout);
+            let score = gen.optimize_cached(&mut layout, &mut cache, &POSSIBLE_SWAPS);
+            (layout, cache, score)
+        };
+
+        assert_eq!(GEN.swap_policy, SwapPolicy::Deterministic);
+        assert_eq!(optimize(&GEN).0.matrix, optimize(&GEN).0.matrix);
+
+        let mut config = Config::default();
+        config.generation.swap_policy = SwapPolicy::Shuffled;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let (mut layout, cache, score) = optimize(&gen);
+        let swaps = POSSIBLE_SWAPS.map(Move::Swap);
+        let (swap, _) = gen.best_swap_cached(&mut layout, &cache, Some(score), &swaps);
+        assert_eq!(swap, None);
+    }
+
+    #[test]
+    fn temperature() {
+        let start = FastLayout::random(GEN.chars_for_generation);
+        // only the bottom row can move, like when improving a layout with a lot of pins
+        let swaps = pinned_swaps(&(0..20).collect::<Vec<_>>());
+        let optimize = |gen: &LayoutGeneration| {
+            let mut layout = start.clone();
+            let mut cache = gen.initialize_cache(&layout);
+            let score = gen.optimize_cached(&mut layout, &mut cache, &swaps);
+            (layout, cache, score)
+        };
+
+        let mut config = Config::default();
+        config.generation.temperature = 0.05;
+        let mut gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        assert_eq!(gen.temperature, 0.05);
+        let (layout, cache, score) = optimize(&gen);
+        gen.temperature = 0.0;
+        let (_, _, greedy_score) = optimize(&gen);
+
+        assert!(score >= greedy_score - SWAP_EPSILON);
+        assert!(score.approx_eq_dbg(gen.score(&layout), 7));
+        assert!(cache.total_score.approx_eq_dbg(score, 7));
+        assert_eq!(layout.matrix[..20], start.matrix[..20]);
+    }
+
+    #[test]
+    fn excluded_chars() {
+        let excluded = GEN.excluded_chars();
+        let on_board = GEN
+            .chars_for_generation
+            .iter()
+            .map(|&c| GEN.data.characters[c as usize])
+            .sum::<f64>();
+        let total = GEN.data.characters.iter().sum::<f64>();
+
+        assert!(excluded.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert!(excluded.iter().all(|&(c, _)| {
+            !GEN.chars_for_generation
+                .contains(&GEN.convert_u8.to_single_lossy(c))
+        }));
+        assert!((on_board + excluded.iter().map(|(_, f)| f).sum::<f64>())
+            .approx_eq_dbg(total, 7));
+    }
+
+    #[test]
+    fn island_generation() {
+        let mut config = Config::default();
+        config.generation.islands = 3;
+        config.generation.migration_interval = 2;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let done = std::sync::atomic::AtomicUsize::new(0);
+
+        let layouts = gen.generate_islands(8, || {
+            done.fetch_add(1, Ordering::Relaxed);
+        });
+
+        assert_eq!(layouts.len(), 8);
+        assert_eq!(done.load(Ordering::Relaxed), 8);
+        for layout in layouts.iter() {
+            assert!(layout.score.approx_eq_dbg(gen.score(layout), 7));
+
+            let mut chars = layout.matrix;
+            let mut expected = gen.chars_for_generation;
+            chars.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(chars, expected);
+        }
+
+        assert!(gen.generate_islands(0, || {}).is_empty());
+        assert_eq!(GEN.generate_islands(2, || {}).len(), 2);
+    }
+
+    #[test]
+    fn vowel_split_seed() {
+        let mut config = Config::default();
+        config.generation.seed_strategy = SeedStrategy::VowelSplit;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let vowels = gen.convert_u8.to_lossy("aeiou".chars());
+
+        assert_eq!(GEN.seed_strategy, SeedStrategy::Random);
+        assert_eq!(gen.vowels, vowels);
+        assert!(gen.consonants.iter().all(|c| !vowels.contains(c)));
+
+        let mut expected = gen.chars_for_generation;
+        expected.sort_unstable();
+
+        for _ in 0..20 {
+            let seed = gen.seed();
+            let mut sorted = seed;
+            sorted.sort_unstable();
+            assert_eq!(sorted, expected);
+
+            let pos = |c: &u8| seed.iter().position(|k| k == c).unwrap();
+            let is_left = |c: &u8| is_left_hand(gen.fingers[pos(c)]);
+            let vowel_hand = is_left(&vowels[0]);
+            assert!(vowels.iter().all(|v| is_left(v) == vowel_hand));
+            assert!(gen.consonants[..10].iter().all(|c| is_left(c) != vowel_hand));
+        }
+    }
+
+    #[test]
+    fn constraints_stay_met() {
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let inrolls = GEN.get_layout_stats(&qwerty).metric("inrolls").unwrap().1;
+
+        let mut config = Config::default();
+        config.constraints = vec![Constraint {
+            metric: "inrolls".to_string(),
+            max: inrolls,
+        }];
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        assert!(GEN.check_constraints(&qwerty).is_empty());
+
+        let mut layout = qwerty.clone();
+        let mut cache = gen.initialize_cache(&layout);
+        gen.optimize_mut(&mut layout, &mut cache, &POSSIBLE_SWAPS);
+
+        let checked = gen.check_constraints(&layout);
+        assert_eq!(checked.len(), 1);
+        assert!(checked[0].1 <= inrolls);
+        assert!(checked[0].2);
+        assert_ne!(layout.matrix, qwerty.matrix);
+
+        assert!(keeps_constraints(&[true, false], &[true, true]));
+        assert!(keeps_constraints(&[false], &[false]));
+        assert!(!keeps_constraints(&[true, true], &[true, false]));
+    }
+
+    #[test]
+    fn reused_cache_matches_new() {
+        let mut cache = LayoutCache::default();
+
+        for _ in 0..10 {
+            let layout = FastLayout::random(GEN.chars_for_generation);
+            GEN.fill_cache(&layout, &mut cache);
+            let new = GEN.initialize_cache(&layout);
+
+            assert!(cache.total_score.approx_eq_dbg(new.total_score, 7));
+            assert_eq!(cache.effort, new.effort);
+            assert_eq!(cache.fspeed, new.fspeed);
+        }
+    }
+
+    #[test]
+    fn combined_languages() {
+        let test = LayoutGeneration::new("test", "static", None).unwrap();
+        let mixed =
+            LayoutGeneration::combined(&[("english", 3.0), ("test", 1.0)], "static", None).unwrap();
+
+        let bigram = |gen: &LayoutGeneration, bigram: &str| {
+            let len = gen.data.characters.len();
+            let chars = bigram
+                .chars()
+                .map(|c| gen.convert_u8.to_single_lossy(c) as usize)
+                .collect::<Vec<_>>();
+            match chars[..] {
+                [c1, c2] if c1 < len && c2 < len => gen.data.bigrams[c1 * len + c2],
+                _ => 0.0,
+            }
+        };
+
+        assert_eq!(mixed.language, "english+test");
+        for b in ["tr", "re", "th"] {
+            let expected = 0.75 * bigram(&GEN, b) + 0.25 * bigram(&test, b);
+            assert!(bigram(&mixed, b).approx_eq_dbg(expected, 7));
+        }
+        assert!(bigram(&test, "tr") > 0.0);
+    }
+
+    #[test]
+    fn generate_with_pins_keeps_pins() {
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let pins = [0, 1, 2, 10, 11, 12];
+
+        for _ in 0..3 {
+            let layout = GEN.generate_with_pins(&qwerty, &pins, None);
+            for &p in pins.iter() {
+                assert_eq!(layout.c(p), qwerty.c(p));
+            }
+        }
+    }
+
+    #[test]
+    fn onehand_rolls() {
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let mut qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let rolls = GEN.onehand_rolls(&qwerty);
+
+        assert!(rolls > 0.0 && rolls < 1.0);
+
+        let stats = GEN.get_layout_stats(&qwerty);
+        assert!(stats.onehand_rolls.approx_eq_dbg(rolls, 7));
+
+        // weighted, they are part of the cached score as well
+        let mut config = Config::default();
+        config.weights.onehand_rolls = 0.5;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let mut cache = gen.initialize_cache(&qwerty);
+        assert!(cache.total_score.approx_eq_dbg(gen.score(&qwerty), 7));
+
+        let swap = PosPair(10, 13);
+        let swapped = gen.score_swap_cached(&mut qwerty, &swap, &cache);
+        gen.accept_swap(&mut qwerty, &swap.into(), &mut cache);
+        assert!(swapped.approx_eq_dbg(gen.score(&qwerty), 7));
+        assert!(cache.total_score.approx_eq_dbg(gen.score(&qwerty), 7));
+    }
+
     #[test]
     fn optimize_random_layouts() {
         for _ in 0..5 {
@@ -1171,7 +4327,7 @@ mod tests {
             let mut layout_for_cached = layout.clone();
 
             let optimized_normal = GEN.optimize_normal_no_cols(layout, &POSSIBLE_SWAPS);
-            let normal_score = GEN.score_with_precision(&optimized_normal, 1000);
+            let normal_score = GEN.score(&optimized_normal);
 
             let mut cache = GEN.initialize_cache(&layout_for_cached);
             let best_cached_score =
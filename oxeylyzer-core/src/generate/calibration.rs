@@ -0,0 +1,130 @@
+use crate::generate::LayoutGeneration;
+use crate::layout::*;
+
+/// Smallest trigram precision that ranks a set of layouts close enough to how every trigram
+/// ranks them, found by `calibrate_precision`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrecisionCalibration {
+    pub precision: usize,
+    /// Share of the pairs of layouts `precision` ranks in the same order as every trigram does,
+    /// from 0 to 1.
+    pub agreement: f64,
+    /// Every precision that was tried with its agreement, from the most trigrams to the fewest.
+    pub tried: Vec<(usize, f64)>,
+}
+
+impl LayoutGeneration {
+    /// Precisions `calibrate_precision` tries, 100, 200, 500, 1000 and so on up to the amount of
+    /// trigrams there are, which is always tried as well.
+    fn calibration_precisions(&self) -> Vec<usize> {
+        let len = self.data.trigrams.len();
+        let mut res = (2..)
+            .map(|exp| 10usize.pow(exp))
+            .flat_map(|p| [p, p * 2, p * 5])
+            .take_while(|&p| p < len)
+            .collect::<Vec<_>>();
+        res.push(len);
+        res
+    }
+
+    /// The smallest precision that ranks `layouts` the same as scoring every trigram does, except
+    /// for at most a `tolerance` share of the pairs of layouts, which is from 0 to 1. Every larger
+    /// precision that was tried is within the tolerance as well, so a precision that is only
+    /// accurate by chance isn't picked.
+    pub fn calibrate_precision(
+        &self,
+        layouts: &[FastLayout],
+        tolerance: f64,
+    ) -> PrecisionCalibration {
+        let full = self.scores_with_precision(layouts, usize::MAX);
+        let mut res = PrecisionCalibration {
+            precision: self.data.trigrams.len(),
+            agreement: 1.0,
+            tried: Vec::new(),
+        };
+
+        for precision in self.calibration_precisions().into_iter().rev() {
+            let scores = self.scores_with_precision(layouts, precision);
+            let agreement = rank_agreement(&full, &scores);
+            res.tried.push((precision, agreement));
+            if agreement < 1.0 - tolerance {
+                break;
+            }
+            res.precision = precision;
+            res.agreement = agreement;
+        }
+        res
+    }
+
+    fn scores_with_precision(&self, layouts: &[FastLayout], precision: usize) -> Vec<f64> {
+        layouts
+            .iter()
+            .map(|layout| self.score_with_precision(layout, precision))
+            .collect()
+    }
+}
+
+/// Share of the pairs of scores that are in the same order in `a` and `b`, or 1 with fewer than
+/// 2 scores. Pairs that are tied in either count as the same order.
+fn rank_agreement(a: &[f64], b: &[f64]) -> f64 {
+    let scores = a.iter().zip(b).collect::<Vec<_>>();
+    let (mut same, mut pairs) = (0usize, 0usize);
+    for (i, (a1, b1)) in scores.iter().enumerate() {
+        for (a2, b2) in &scores[i + 1..] {
+            pairs += 1;
+            if (*a1 - *a2) * (*b1 - *b2) >= 0.0 {
+                same += 1;
+            }
+        }
+    }
+    if pairs == 0 {
+        return 1.0;
+    }
+    same as f64 / pairs as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calibration() {
+        assert_eq!(rank_agreement(&[1.0, 2.0, 3.0], &[1.0, 2.0, 3.0]), 1.0);
+        assert_eq!(rank_agreement(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]), 0.0);
+        assert_eq!(
+            rank_agreement(&[1.0, 2.0, 3.0], &[2.0, 1.0, 3.0]),
+            2.0 / 3.0
+        );
+        assert_eq!(rank_agreement(&[1.0], &[2.0]), 1.0);
+
+        let gen = LayoutGeneration::new("english", "static", None).unwrap();
+        let layouts = [
+            "qwertyuiopasdfghjkl;zxcvbnm,./",
+            "',.pyfgcrlaoeuidhtns;qjkxbmwvz",
+            "qwfpgjluy;arstdhneiozxcvbkm,./",
+            "qdrwbjfup;ashtgyneoizxmcvkl,./",
+            "flhvz'wuoysrntkcdeaix/jbmqpg,.",
+            "bldcvjfou,nrtsgyhaeizqxmwkp';.",
+        ]
+        .map(|keys| {
+            let bytes = gen.convert_u8.to_lossy(keys.chars());
+            FastLayout::try_from(bytes.as_slice()).unwrap()
+        });
+        let len = gen.data.trigrams.len();
+
+        let exact = gen.calibrate_precision(&layouts, 0.0);
+        assert!(exact.precision <= len);
+        assert_eq!(exact.agreement, 1.0);
+        assert_eq!(exact.tried[0], (len, 1.0));
+        let kept = exact
+            .tried
+            .iter()
+            .take_while(|(_, agreement)| *agreement == 1.0);
+        assert_eq!(kept.last().unwrap().0, exact.precision);
+
+        let loose = gen.calibrate_precision(&layouts, 1.0);
+        assert_eq!(loose.precision, 100.min(len));
+        assert!(loose.precision <= exact.precision);
+        assert_eq!(loose.tried.len(), gen.calibration_precisions().len());
+    }
+}
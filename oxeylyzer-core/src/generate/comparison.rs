@@ -0,0 +1,73 @@
+use crate::generate::{LayoutGeneration, LayoutStats, RetrainingCost};
+use crate::layout::*;
+
+/// Two layouts next to each other, which is what `compare` shows.
+#[derive(Clone)]
+pub struct Comparison {
+    pub scores: [f64; 2],
+    pub stats: [LayoutStats; 2],
+    /// What has to be relearned to switch from the first layout to the second.
+    pub retraining: RetrainingCost,
+}
+
+impl Comparison {
+    /// How much the score goes up by switching from the first layout to the second.
+    pub fn score_gain(&self) -> f64 {
+        self.scores[1] - self.scores[0]
+    }
+
+    /// How much every stat changes by switching from the first layout to the second, like
+    /// `LayoutStats::deltas`.
+    pub fn deltas(&self) -> Vec<String> {
+        self.stats[1].deltas(&self.stats[0])
+    }
+}
+
+impl LayoutGeneration {
+    /// Scores, stats and the cost of relearning of going from `from` to `to`.
+    pub fn compare(&self, from: &FastLayout, to: &FastLayout) -> Comparison {
+        Comparison {
+            scores: [self.score(from), self.score(to)],
+            stats: [self.get_layout_stats(from), self.get_layout_stats(to)],
+            retraining: self.retraining_cost(from, to),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn compares() {
+        let layout = |keys: &str| {
+            let bytes = GEN.convert_u8.to_lossy(keys.chars());
+            let mut layout = FastLayout::try_from(bytes.as_slice()).unwrap();
+            layout.set_fingers(&GEN.fingers);
+            layout
+        };
+        let qwerty = layout("qwertyuiopasdfghjkl;zxcvbnm,./");
+        let colemak = layout("qwfpgjluy;arstdhneiozxcvbkm,./");
+
+        let comparison = GEN.compare(&qwerty, &colemak);
+        assert_eq!(comparison.scores, [GEN.score(&qwerty), GEN.score(&colemak)]);
+        assert!(comparison.score_gain() > 0.0);
+        assert!(comparison.stats[1].sfb < comparison.stats[0].sfb);
+        assert_eq!(
+            comparison.retraining,
+            GEN.retraining_cost(&qwerty, &colemak)
+        );
+        assert!(comparison
+            .deltas()
+            .iter()
+            .any(|delta| delta.starts_with("Sfb ")));
+
+        let same = GEN.compare(&qwerty, &qwerty);
+        assert_eq!(same.score_gain(), 0.0);
+        assert!(same.deltas().is_empty());
+    }
+}
@@ -0,0 +1,288 @@
+use crate::generate::{pinned_swaps, LayoutGeneration};
+use crate::layout::*;
+use crate::utility::*;
+
+/// What the character on a single position of a layout costs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyCost {
+    pub position: usize,
+    pub c: char,
+    /// Frequency of the character.
+    pub freq: f64,
+    /// Effort of the position, from the heatmap with travel and hand dominance applied.
+    pub effort: f64,
+    /// Frequency times effort, which is what the key adds to the effort part of the score.
+    pub wasted_effort: f64,
+    /// Finger speed of the same finger bigrams with this key, where every bigram counts for half
+    /// on both of its keys.
+    pub fspeed: f64,
+    /// Part of the finger speed of its finger that comes from this key, from 0 to 1.
+    pub fspeed_share: f64,
+}
+
+/// What a heatmap colors the keys of a layout by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapMode {
+    /// How often the character on the key is typed.
+    Frequency,
+    /// Same finger bigrams with the key.
+    Sfb,
+    /// Finger speed of the same finger bigrams with the key, which also counts skipgrams and how
+    /// far the finger moves.
+    Fspeed,
+    /// Scissors with the key.
+    Scissors,
+    /// How much the score goes up with the best single swap that moves the key, which shows where
+    /// the weak spots of a layout are.
+    SwapGain,
+}
+
+impl TryFrom<&str> for HeatmapMode {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, &'static str> {
+        match value.to_lowercase().as_str() {
+            "freq" | "frequency" => Ok(Self::Frequency),
+            "sfb" | "sfbs" => Ok(Self::Sfb),
+            "fspeed" | "finger_speed" => Ok(Self::Fspeed),
+            "scissors" | "scissor" => Ok(Self::Scissors),
+            "gain" | "swap_gain" => Ok(Self::SwapGain),
+            _ => Err("heatmap modes are freq, sfb, fspeed, scissors and gain"),
+        }
+    }
+}
+
+impl LayoutGeneration {
+    /// What every key of `layout` contributes to the total of `mode`. Bigrams count for half on
+    /// both of their keys, so the contributions add up to the total. `SwapGain` has no total, so
+    /// it is `swap_gains` instead.
+    pub fn key_contributions(&self, layout: &FastLayout, mode: HeatmapMode) -> [f64; 30] {
+        let len = self.data.characters.len();
+        let bigram = |i1: usize, i2: usize| {
+            let (c1, c2) = (layout.matrix[i1] as usize, layout.matrix[i2] as usize);
+            self.data.bigrams.get(c1 * len + c2).unwrap_or(&0.0)
+                + self.data.bigrams.get(c2 * len + c1).unwrap_or(&0.0)
+        };
+        let mut res = [0.0; 30];
+
+        match mode {
+            HeatmapMode::Frequency => {
+                for (i, heat) in res.iter_mut().enumerate() {
+                    let c = layout.matrix[i] as usize;
+                    *heat = *self.data.characters.get(c).unwrap_or(&0.0);
+                }
+            }
+            HeatmapMode::Sfb | HeatmapMode::Fspeed => {
+                for &(pair, dist) in self.fspeed_vals.iter() {
+                    let value = match mode {
+                        HeatmapMode::Sfb => bigram(pair.0, pair.1),
+                        _ => self.pair_fspeed(layout, &pair, dist),
+                    };
+                    res[pair.0] += value / 2.0;
+                    res[pair.1] += value / 2.0;
+                }
+            }
+            HeatmapMode::Scissors => {
+                for PosPair(i1, i2) in self.scissor_indices {
+                    let value = bigram(i1, i2);
+                    res[i1] += value / 2.0;
+                    res[i2] += value / 2.0;
+                }
+            }
+            HeatmapMode::SwapGain => return self.swap_gains(layout),
+        }
+        res
+    }
+
+    /// How much the score of `layout` goes up with the best swap of every key with any other key,
+    /// or 0 when no swap with it improves the layout. This approximates how much better off the
+    /// character would be in the best spot for it, ignoring pins and locks. Like while optimizing,
+    /// the external metric isn't part of the score for this.
+    pub fn swap_gains(&self, layout: &FastLayout) -> [f64; 30] {
+        let mut layout = layout.clone();
+        let cache = self.initialize_cache(&layout);
+        let mut res = [0.0f64; 30];
+
+        for swap in pinned_swaps(&[]) {
+            let gain = self.score_swap_cached(&mut layout, &swap, &cache) - cache.total_score;
+            res[swap.0] = res[swap.0].max(gain);
+            res[swap.1] = res[swap.1].max(gain);
+        }
+        res
+    }
+
+    /// How hot every key of `layout` is in a heatmap of `mode`, from 0 to 1. Frequencies are on a
+    /// fixed scale where a key with 12.5% of all keystrokes is as hot as it gets, so heatmaps of
+    /// different layouts can be compared. Other modes are relative to the hottest key.
+    pub fn key_heat(&self, layout: &FastLayout, mode: HeatmapMode) -> [f64; 30] {
+        let contributions = self.key_contributions(layout, mode);
+        let scale = match mode {
+            HeatmapMode::Frequency => 8.0,
+            _ => match contributions.iter().copied().fold(0.0, f64::max) {
+                max if max > 0.0 => 1.0 / max,
+                _ => 0.0,
+            },
+        };
+        contributions.map(|value| (value * scale).min(1.0))
+    }
+
+    /// Effort of every position as the score uses it: the heatmap of the keyboard type times the
+    /// `heatmap` weight, with hand dominance, travel and `[avoid_keys]` applied.
+    pub fn effort_map(&self) -> [f64; 30] {
+        self.effort_map
+    }
+
+    /// Average distance of the same finger pairs every position is part of, as finger speed
+    /// weighs them: with the lateral penalty, finger multipliers and hand dominance applied.
+    pub fn key_fspeed_distances(&self) -> [f64; 30] {
+        let mut total = [0.0; 30];
+        let mut count = [0; 30];
+        for &(PosPair(i1, i2), dist) in self.fspeed_vals.iter() {
+            for i in [i1, i2] {
+                total[i] += dist;
+                count[i] += 1;
+            }
+        }
+        std::array::from_fn(|i| match count[i] {
+            0 => 0.0,
+            count => total[i] / count as f64,
+        })
+    }
+
+    /// Costs of every position on `layout`, sorted by wasted effort from high to low. A common
+    /// character on a key with a high effort is often a misplaced key that the total score hides.
+    pub fn key_costs(&self, layout: &FastLayout) -> Vec<KeyCost> {
+        let mut fspeed = [0.0; 30];
+        let mut col_fspeed = [0.0; 8];
+
+        for (col, total) in col_fspeed.iter_mut().enumerate() {
+            let (start, len) = self.col_to_start_len(col);
+            for (pair, dist) in self.fspeed_vals[start..(start + len)].iter() {
+                let pair_fspeed = self.pair_fspeed(layout, pair, *dist);
+                fspeed[pair.0] += pair_fspeed / 2.0;
+                fspeed[pair.1] += pair_fspeed / 2.0;
+                *total += pair_fspeed;
+            }
+        }
+
+        let mut res = (0..30)
+            .map(|i| {
+                let c = layout.matrix[i];
+                let freq = *self.data.characters.get(c as usize).unwrap_or(&0.0);
+                let col_fspeed = col_fspeed[self.fingers[i]];
+
+                KeyCost {
+                    position: i,
+                    c: self.convert_u8.from_single(c),
+                    freq,
+                    effort: self.effort_map[i],
+                    wasted_effort: freq * self.effort_map[i],
+                    fspeed: fspeed[i],
+                    fspeed_share: if col_fspeed > 0.0 {
+                        fspeed[i] / col_fspeed
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        // stable, so keys with the same cost keep the order of their positions
+        res.sort_by(|k1, k2| k2.wasted_effort.partial_cmp(&k1.wasted_effort).unwrap());
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn key_costs() {
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let costs = GEN.key_costs(&qwerty);
+
+        assert_eq!(costs.len(), 30);
+        assert!(costs
+            .windows(2)
+            .all(|w| w[0].wasted_effort >= w[1].wasted_effort));
+
+        let effort = costs.iter().map(|k| k.wasted_effort).sum::<f64>();
+        let expected = (0..30).map(|i| GEN.char_effort(&qwerty, i)).sum::<f64>();
+        assert!((effort - expected).abs() < 1e-9);
+
+        let fspeed = costs.iter().map(|k| k.fspeed).sum::<f64>();
+        let expected = (0..8).map(|col| GEN.col_fspeed(&qwerty, col)).sum::<f64>();
+        assert!((fspeed - expected).abs() < 1e-9);
+
+        // the shares of every finger add up to 1
+        for finger in 0..8 {
+            let share = costs
+                .iter()
+                .filter(|k| GEN.fingers[k.position] == finger)
+                .map(|k| k.fspeed_share)
+                .sum::<f64>();
+            assert!((share - 1.0).abs() < 1e-9);
+        }
+
+        let sfb = GEN.key_contributions(&qwerty, HeatmapMode::Sfb);
+        let expected = GEN.bigram_percent(&qwerty, "sfb").unwrap();
+        assert!((sfb.iter().sum::<f64>() - expected).abs() < 1e-9);
+        let fspeed_heat = GEN.key_contributions(&qwerty, HeatmapMode::Fspeed);
+        assert!((fspeed_heat.iter().sum::<f64>() - fspeed).abs() < 1e-9);
+
+        let heat = GEN.key_heat(&qwerty, HeatmapMode::Scissors);
+        assert!(heat.iter().all(|&h| (0.0..=1.0).contains(&h)));
+        assert!(heat.contains(&1.0));
+        assert_eq!(HeatmapMode::try_from("SFB"), Ok(HeatmapMode::Sfb));
+        assert_eq!(HeatmapMode::try_from("gain"), Ok(HeatmapMode::SwapGain));
+        assert!(HeatmapMode::try_from("lsb").is_err());
+
+        let e = costs.iter().find(|k| k.c == 'e').unwrap();
+        assert_eq!(e.position, 2);
+        assert_eq!(e.freq, GEN.data.characters[qwerty.matrix[2] as usize]);
+        assert_eq!(e.effort, GEN.effort_map()[2]);
+    }
+
+    #[test]
+    fn swap_gains() {
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let gains = GEN.swap_gains(&qwerty);
+        assert!(gains.iter().all(|&gain| gain >= 0.0));
+
+        // both keys of the best swap gain the most there is to gain
+        let best = &GEN.suggest_swaps(&qwerty, &[], 1)[0];
+        let max = gains.iter().copied().fold(0.0, f64::max);
+        assert!((gains[best.swap.0] - best.score_delta).abs() < 1e-9);
+        assert!((gains[best.swap.1] - best.score_delta).abs() < 1e-9);
+        assert!((max - best.score_delta).abs() < 1e-9);
+
+        let heat = GEN.key_heat(&qwerty, HeatmapMode::SwapGain);
+        assert!((heat[best.swap.0] - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fspeed_distances() {
+        let distances = GEN.key_fspeed_distances();
+        assert!(distances.iter().all(|&d| d > 0.0));
+
+        // the left pinky only has the 3 keys of its column, so every key is in 2 pairs
+        let pairs = GEN
+            .fspeed_vals
+            .iter()
+            .filter(|(pair, _)| pair.0 == 10 || pair.1 == 10)
+            .collect::<Vec<_>>();
+        assert_eq!(pairs.len(), 2);
+        let mean = pairs.iter().map(|(_, dist)| dist).sum::<f64>() / 2.0;
+        assert!((distances[10] - mean).abs() < 1e-9);
+    }
+}
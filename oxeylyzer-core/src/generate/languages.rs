@@ -0,0 +1,127 @@
+use crate::generate::{LayoutGeneration, LayoutStats};
+use crate::layout::*;
+use crate::utility::*;
+
+/// How a layout generated for a mix of languages does on one of them.
+#[derive(Clone)]
+pub struct LanguageResult {
+    pub language: String,
+    /// Share of the language in the mix, from 0 to 1.
+    pub share: f64,
+    /// Score with the language on its own.
+    pub score: f64,
+    /// Rank of the score among the layouts that were compared, where 1 is the best.
+    pub rank: usize,
+    pub stats: LayoutStats,
+    /// Whether the layout is in the best third of the layouts that were compared for the whole
+    /// mix, but in the worst third for this language.
+    pub lopsided: bool,
+}
+
+impl LayoutGeneration {
+    /// `layout` with the characters of `other`, so `other` can score it. Characters `other`
+    /// doesn't know are left empty.
+    pub fn convert_layout(&self, other: &LayoutGeneration, layout: &FastLayout) -> FastLayout {
+        let chars = layout.matrix.map(|c| {
+            other
+                .convert_u8
+                .to_single_lossy(self.convert_u8.from_single(c))
+        });
+        FastLayout::with_fingers(chars, &other.fingers)
+    }
+
+    /// How every layout of `layouts` does on every language of a `combined` generator, in the
+    /// order of `languages`. `layouts` go from best to worst for the whole mix, so a layout that
+    /// is near the front while it ranks near the back for a language is `lopsided`. Empty for a
+    /// single language.
+    pub fn language_results(&self, layouts: &[FastLayout]) -> Vec<Vec<LanguageResult>> {
+        let count = layouts.len();
+        let mut res = vec![Vec::with_capacity(self.languages.len()); count];
+
+        for (language, share, gen) in self.languages.iter() {
+            let scored = layouts
+                .iter()
+                .map(|layout| {
+                    let converted = self.convert_layout(gen, layout);
+                    (gen.score(&converted), gen.get_layout_stats(&converted))
+                })
+                .collect::<Vec<_>>();
+
+            for (i, (score, stats)) in scored.iter().enumerate() {
+                let rank = 1 + scored
+                    .iter()
+                    .filter(|(other, _)| TotalScore(*other) > TotalScore(*score))
+                    .count();
+                res[i].push(LanguageResult {
+                    language: language.clone(),
+                    share: *share,
+                    score: *score,
+                    rank,
+                    stats: stats.clone(),
+                    lopsided: (i + 1) * 3 <= count && rank * 3 > count * 2,
+                });
+            }
+        }
+        res
+    }
+
+    /// How `layouts[index]` does on every language of a `combined` generator compared to the rest
+    /// of `layouts`, from the language it ranks best on to the one it ranks worst on. `layouts`
+    /// go from best to worst for the whole mix, like for `language_results`.
+    pub fn language_standing(&self, layouts: &[FastLayout], index: usize) -> Vec<LanguageResult> {
+        let mut res = self.language_results(layouts).swap_remove(index);
+        res.sort_by_key(|r| r.rank);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_per_language() {
+        let mixed =
+            LayoutGeneration::combined(&[("english", 3.0), ("test", 1.0)], "static", None).unwrap();
+        assert_eq!(mixed.languages.len(), 2);
+        assert_eq!(mixed.languages[0].0, "english");
+        assert_eq!(mixed.languages[1].1, 0.25);
+
+        let layout = |keys: &str| {
+            let bytes = mixed.convert_u8.to_lossy(keys.chars());
+            let mut layout = FastLayout::try_from(bytes.as_slice()).unwrap();
+            layout.score = mixed.score(&layout);
+            layout
+        };
+        let layouts = [
+            layout("qwertyuiopasdfghjkl;zxcvbnm,./"),
+            layout("',.pyfgcrlaoeuidhtns;qjkxbmwvz"),
+            layout("qwfpbjluy;arstgmneiozxcdvkh,./"),
+        ];
+        let results = mixed.language_results(&layouts);
+        assert_eq!(results.len(), 3);
+
+        let (_, _, english) = &mixed.languages[0];
+        let qwerty = mixed.convert_layout(english, &layouts[0]);
+        assert_eq!(results[0][0].score, english.score(&qwerty));
+        assert_eq!(results[0][1].language, "test");
+        let mut ranks = results.iter().map(|r| r[0].rank).collect::<Vec<_>>();
+        ranks.sort();
+        assert_eq!(ranks, vec![1, 2, 3]);
+        assert!(results.iter().flatten().all(|r| (1..=3).contains(&r.rank)));
+        // qwerty is the worst for english, so it is lopsided as the first layout
+        assert!(results[0][0].rank == 3 && results[0][0].lopsided);
+        assert!(results[1].iter().all(|r| !r.lopsided));
+
+        let standing = mixed.language_standing(&layouts, 2);
+        assert!(standing.windows(2).all(|w| w[0].rank <= w[1].rank));
+        for r in standing.iter() {
+            let language = mixed.languages.iter().position(|(l, ..)| *l == r.language);
+            assert_eq!(r.rank, results[2][language.unwrap()].rank);
+        }
+
+        let single = LayoutGeneration::new("english", "static", None).unwrap();
+        assert!(single.languages.is_empty());
+        assert!(single.language_results(&layouts).iter().all(Vec::is_empty));
+    }
+}
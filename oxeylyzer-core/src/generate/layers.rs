@@ -0,0 +1,224 @@
+use crate::error::RusylyzerError;
+use crate::generate::LayoutGeneration;
+use crate::layout::*;
+use crate::utility::*;
+
+/// The key that has to be held to type on a [`Layer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerHold {
+    /// One of the 30 main keys.
+    Key(usize),
+    /// The left (0) or right (1) thumb.
+    Thumb(usize),
+}
+
+impl TryFrom<&str> for LayerHold {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, &'static str> {
+        match value.to_lowercase().as_str() {
+            "left" | "left thumb" | "lt" => Ok(Self::Thumb(0)),
+            "right" | "right thumb" | "rt" => Ok(Self::Thumb(1)),
+            value => match value.parse::<usize>() {
+                Ok(i) if i < 30 => Ok(Self::Key(i)),
+                _ => Err("a layer is held with left, right or the position of a key from 0 to 29"),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for LayerHold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Key(i) => write!(f, "{i}"),
+            Self::Thumb(0) => write!(f, "left"),
+            Self::Thumb(_) => write!(f, "right"),
+        }
+    }
+}
+
+/// A second layer of 30 keys on top of a layout, typed while `hold` is held down. Like thumb keys
+/// it is never changed by generation, it is only analyzed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    /// Keys in the same positions as `FastLayout::matrix`, so they are pressed by the same
+    /// fingers. Empty keys are `None`.
+    pub matrix: [Option<u8>; 30],
+    pub hold: LayerHold,
+}
+
+/// How a [`Layer`] adds to a layout, as a fraction of all characters or bigrams.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayerStats {
+    /// Frequency of the characters on the layer.
+    pub usage: f64,
+    /// Bigrams with exactly one character on the layer, where the hold key is pressed or released.
+    pub switches: f64,
+    /// Same finger bigrams with at least one character on the layer, including the ones between
+    /// both layers.
+    pub sfb: f64,
+    /// Characters on the layer that are pressed by the finger holding the layer.
+    pub hold_conflicts: f64,
+}
+
+impl std::fmt::Display for LayerStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            concat!(
+                "Layer Usage: {:.3}%\nLayer Switches: {:.3}%\n",
+                "Layer Sfb: {:.3}%\nHold Conflicts: {:.3}%\n"
+            ),
+            self.usage * 100.0,
+            self.switches * 100.0,
+            self.sfb * 100.0,
+            self.hold_conflicts * 100.0
+        )
+    }
+}
+
+impl Layer {
+    /// The second layer of a layout file, if it has one. See `format_layer_str` for the format.
+    pub fn parse(layout_str: &str, con: &mut ConvertU8) -> Result<Option<Self>, RusylyzerError> {
+        let (hold, keys) = match format_layer_str(layout_str) {
+            Some(layer) => layer,
+            None => return Ok(None),
+        };
+        let hold = LayerHold::try_from(hold)
+            .map_err(|e| RusylyzerError::LayoutParse(format!("{e}, not '{hold}'")))?;
+        if keys.len() != 30 {
+            return Err(RusylyzerError::LayoutParse(format!(
+                "a layer needs 30 keys, but {} were provided",
+                keys.len()
+            )));
+        }
+
+        let mut matrix = [None; 30];
+        for (key, c) in matrix.iter_mut().zip(keys) {
+            *key = (c != '_').then(|| con.to_single(c));
+        }
+        Ok(Some(Self { matrix, hold }))
+    }
+
+    /// The layer in the format of a layout file, starting with its `layer <hold>` line.
+    pub fn formatted_string(&self, con: &ConvertU8) -> String {
+        let mut res = format!("layer {}", self.hold);
+
+        for (i, key) in self.matrix.iter().enumerate() {
+            if i % 10 == 0 {
+                res.push('\n');
+            }
+            if (i + 5) % 10 == 0 {
+                res.push(' ');
+            }
+            res.push(key.map_or('_', |u| con.from_single(u)));
+            res.push(' ');
+        }
+        res
+    }
+}
+
+impl LayoutGeneration {
+    /// Stats of `layer` on top of `layout`. Characters that are on the main layer as well are
+    /// typed there, so they don't count for the layer.
+    pub fn layer_stats(&self, layout: &FastLayout, layer: &Layer) -> LayerStats {
+        let len = self.data.characters.len();
+        let bigram = |c1: usize, c2: usize| {
+            if c1 < len && c2 < len {
+                self.data.bigrams.get(c1 * len + c2).copied().unwrap_or(0.0)
+            } else {
+                0.0
+            }
+        };
+
+        // finger of every placed character, and whether it is on the layer
+        let mut placed = vec![None; u8::MAX as usize + 1];
+        for (i, &c) in layout.matrix.iter().enumerate() {
+            placed[c as usize] = Some((layout.fingers[i], false));
+        }
+        for (&c, finger) in layout.thumbs.iter().zip(THUMB_FINGERS) {
+            if c != NO_THUMB {
+                placed[c as usize] = Some((finger, false));
+            }
+        }
+        for (i, key) in layer.matrix.iter().enumerate() {
+            if let Some(c) = key.filter(|&c| placed[c as usize].is_none()) {
+                placed[c as usize] = Some((layout.fingers[i], true));
+            }
+        }
+        let on_layer = |c: usize| matches!(placed[c], Some((_, true)));
+        let hold_finger = match layer.hold {
+            LayerHold::Key(i) => layout.fingers[i],
+            LayerHold::Thumb(thumb) => THUMB_FINGERS[thumb],
+        };
+
+        let mut res = LayerStats::default();
+        for c1 in (0..placed.len()).filter(|&c| on_layer(c)) {
+            let freq = *self.data.characters.get(c1).unwrap_or(&0.0);
+            res.usage += freq;
+            if placed[c1] == Some((hold_finger, true)) {
+                res.hold_conflicts += freq;
+            }
+
+            for c2 in (0..len).filter(|&c| !on_layer(c)) {
+                res.switches += bigram(c1, c2) + bigram(c2, c1);
+            }
+            for (c2, &other) in placed.iter().enumerate() {
+                let same_finger =
+                    matches!((placed[c1], other), (Some((f1, _)), Some((f2, _))) if f1 == f2);
+                if c1 != c2 && same_finger {
+                    // sfbs within the layer are seen from both characters, the others only once
+                    res.sfb += bigram(c1, c2);
+                    if !on_layer(c2) {
+                        res.sfb += bigram(c2, c1);
+                    }
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn layer_stats() {
+        let mut con = GEN.data.convert_u8.clone();
+        let qwerty_bytes = con.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+
+        let layer_str = concat!(
+            "a\nb\nc\nlayer right\n",
+            "_ _ _ _ _  _ _ _ _ _\n_ _ _ _ _  _ ' - _ _\n_ _ _ _ _  _ _ _ _ ."
+        );
+        let layer = Layer::parse(layer_str, &mut con).unwrap().unwrap();
+        assert_eq!(layer.hold, LayerHold::Thumb(1));
+        assert!(layer
+            .formatted_string(&con)
+            .starts_with("layer right\n_ _ "));
+        assert!(Layer::parse("a\nb\nc\nlayer 30\n_", &mut con).is_err());
+        assert_eq!(Layer::parse("a\nb\nc", &mut con).unwrap(), None);
+
+        let stats = GEN.layer_stats(&qwerty, &layer);
+        let quote = GEN.data.characters[con.to_single('\'') as usize];
+        let dash = GEN.data.characters[con.to_single('-') as usize];
+        // '.' is on the main layer already
+        assert!((stats.usage - quote - dash).abs() < 1e-9);
+        assert!(stats.switches > 0.0 && stats.switches <= 2.0 * (quote + dash));
+        assert!(stats.sfb > 0.0);
+        assert_eq!(stats.hold_conflicts, 0.0);
+
+        let index_hold = Layer {
+            hold: LayerHold::Key(16),
+            ..layer
+        };
+        let stats = GEN.layer_stats(&qwerty, &index_hold);
+        assert!((stats.hold_conflicts - quote).abs() < 1e-9);
+    }
+}
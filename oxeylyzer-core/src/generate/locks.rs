@@ -0,0 +1,122 @@
+use crate::generate::{pinned_swaps, LayoutGeneration, COLS};
+use crate::utility::*;
+use crate::weights::GenerationOptions;
+
+/// What `locked_hands` and `column_units` of `[generation]` keep in place while generating.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locks {
+    /// Whether the keys of the left and the right hand never move.
+    pub hands: [bool; 2],
+    /// Columns that only move as a whole.
+    pub column_units: Vec<usize>,
+    /// Positions no swap touches: every key of a locked hand or a column unit.
+    pub positions: Vec<usize>,
+    /// Swaps that don't move any of `positions`.
+    pub swaps: Vec<PosPair>,
+}
+
+impl Locks {
+    pub fn new(options: &GenerationOptions, fingers: &[usize; 30]) -> Self {
+        let mut hands = [false; 2];
+        for hand in options.locked_hands.iter() {
+            hands[*hand as usize] = true;
+        }
+        let positions = (0..30)
+            .filter(|&i| {
+                hands[!is_left_hand(fingers[i]) as usize]
+                    || options.column_units.contains(&(i % 10))
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            hands,
+            column_units: options.column_units.clone(),
+            swaps: pinned_swaps(&positions),
+            positions,
+        }
+    }
+
+    /// Whether generation can move every key.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Whether keys can move from one hand to the other, which they can't when a hand is locked.
+    pub fn can_cross_hands(&self) -> bool {
+        self.hands == [false; 2]
+    }
+
+    /// `pins` together with every locked position.
+    pub fn with_pins(&self, pins: &[usize]) -> Vec<usize> {
+        let mut res = pins.to_vec();
+        res.extend(self.positions.iter().copied());
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+}
+
+impl LayoutGeneration {
+    /// Columns that the column permutations of `optimize_cols` can move, which are the outer
+    /// columns without any keys on a locked hand or in `pins`.
+    pub(crate) fn movable_cols(&self, pins: &[usize]) -> Vec<usize> {
+        COLS.into_iter()
+            .filter(|&col| {
+                (0..3).map(|row| row * 10 + col).all(|i| {
+                    let hand = !is_left_hand(self.fingers[i]) as usize;
+                    !pins.contains(&i) && !self.locks.hands[hand]
+                })
+            })
+            .collect()
+    }
+
+    /// Whether `optimize_cols` can swap the index columns of both hands.
+    pub(crate) fn can_swap_indexes(&self, pins: &[usize]) -> bool {
+        self.locks.can_cross_hands() && pins.iter().all(|&i| !(3..7).contains(&(i % 10)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::*;
+    use crate::weights::{Config, Hand};
+    use itertools::Itertools;
+
+    #[test]
+    fn locked_keys_stay() {
+        let mut config = Config::default();
+        config.generation.locked_hands = vec![Hand::Right];
+        config.generation.column_units = vec![0];
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+
+        let right_hand = (0..30).filter(|i| i % 10 >= 5);
+        let locked = right_hand.chain([0, 10, 20]).sorted().collect::<Vec<_>>();
+        assert_eq!(gen.locks.positions, locked);
+        assert!(gen
+            .locks
+            .swaps
+            .iter()
+            .all(|PosPair(i1, i2)| !locked.contains(i1) && !locked.contains(i2)));
+        assert_eq!(gen.movable_cols(&[]), vec![0, 1, 2]);
+        assert_eq!(gen.movable_cols(&[11]), vec![0, 2]);
+        assert!(!gen.can_swap_indexes(&[]));
+
+        let bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(bytes.as_slice()).unwrap();
+        for _ in 0..5 {
+            let layout = gen.generate_with_pins(&qwerty, &[], None);
+            for i in (0..30).filter(|i| i % 10 >= 5) {
+                assert_eq!(layout.matrix[i], qwerty.matrix[i]);
+            }
+            // the column of q, a and z can only move as a whole
+            let col = (0..3)
+                .find(|&col| layout.matrix[col] == qwerty.matrix[0])
+                .unwrap();
+            assert_eq!(layout.matrix[10 + col], qwerty.matrix[10]);
+            assert_eq!(layout.matrix[20 + col], qwerty.matrix[20]);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use itertools::Itertools;
+
+use crate::data_source::FileSource;
+use crate::error::RusylyzerError;
+use crate::generate::LayoutGeneration;
+use crate::language_data::LanguageData;
+use crate::layout::*;
+use crate::utility::*;
+use crate::weights::Config;
+
+/// A key that types a character depending on the one before it. Without a rule for that
+/// character it is a repeat key, which types it again, like the second `l` of `ll`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MagicKey {
+    /// Character that stands for the key on the layout.
+    pub key: char,
+    /// What the key types after a character, instead of repeating it.
+    pub rules: Vec<(char, char)>,
+}
+
+impl MagicKey {
+    /// The magic key of a layout file, if it has one. It is the last line of the file, like
+    /// `magic @` for a repeat key on `@`, followed by a rule for every character the key types
+    /// something else after, like `magic @ ou eu` to type `u` after `o` and `e`.
+    pub fn parse(layout_str: &str) -> Result<Option<Self>, RusylyzerError> {
+        let error = |message: String| Err(RusylyzerError::LayoutParse(message));
+        let mut words = match format_magic_str(layout_str) {
+            Some(magic) => magic.split_whitespace(),
+            None => return Ok(None),
+        };
+
+        let key = match words.next().map(|key| key.chars().collect::<Vec<_>>()) {
+            Some(key) if key.len() == 1 => key[0],
+            _ => return error("a magic key needs the character it has on the layout".into()),
+        };
+        let mut rules = Vec::new();
+        for rule in words {
+            match rule.chars().collect::<Vec<_>>()[..] {
+                [before, typed] if before != key && typed != key => rules.push((before, typed)),
+                _ => {
+                    return error(format!(
+                        "a magic key rule is the character before the key and the one it types \
+                         after it, like 'ou', not '{rule}'"
+                    ))
+                }
+            }
+        }
+        Ok(Some(Self { key, rules }))
+    }
+
+    /// What the key types after `before`.
+    pub fn output(&self, before: char) -> char {
+        self.rules
+            .iter()
+            .find(|(c, _)| *c == before)
+            .map_or(before, |&(_, typed)| typed)
+    }
+
+    /// `ngram` as it is typed on a layout with the key, where every character the key types after
+    /// the one before it is typed with the key. The first character stays the same, as the one
+    /// before it isn't known.
+    pub fn retype(&self, ngram: &str) -> String {
+        let mut before = None;
+        ngram
+            .chars()
+            .map(|c| match before.replace(c) {
+                Some(b) if !c.is_whitespace() && self.output(b) == c => self.key,
+                _ => c,
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for MagicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "magic {}", self.key)?;
+        for (before, typed) in self.rules.iter() {
+            write!(f, " {before}{typed}")?;
+        }
+        Ok(())
+    }
+}
+
+impl LayoutGeneration {
+    /// A generator for layouts with `magic` on them, which scores the data of `languages`
+    /// combined like `combined` does, as it is typed with the magic key. The key counts for
+    /// everything it types, and the characters it types count for less.
+    pub fn with_magic<P>(
+        languages: &[(&str, f64)],
+        base_path: P,
+        magic: &MagicKey,
+        config: Config,
+    ) -> Result<Self, RusylyzerError>
+    where
+        P: AsRef<Path>,
+    {
+        let source = FileSource::new(base_path);
+        let retype = |ngram: &str| magic.retype(ngram);
+        let data = LanguageData::retyped_from_source(&source, languages, &retype).map_err(|e| {
+            RusylyzerError::LanguageData {
+                language: languages.iter().map(|(l, _)| l).join("+"),
+                reason: e.to_string(),
+            }
+        })?;
+        Ok(Self::from_data(languages[0].0, data, config))
+    }
+
+    /// `layout` of the generator `from` with the characters of this one like `convert_layout`,
+    /// along with its thumb keys, so a magic key on a thumb is typed as well. Thumb keys this one
+    /// doesn't know are left empty.
+    pub fn convert_with_thumbs(
+        &self,
+        from: &LayoutGeneration,
+        layout: &FastLayout,
+    ) -> Result<FastLayout, RusylyzerError> {
+        let mut res = from.convert_layout(self, layout);
+        for (thumb, &c) in layout.thumbs.iter().enumerate() {
+            if c == NO_THUMB {
+                continue;
+            }
+            let c = self
+                .convert_u8
+                .to_single_lossy(from.convert_u8.from_single(c));
+            if c < self.convert_u8.len() {
+                res.set_thumb(thumb, c)?;
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_key() {
+        assert_eq!(MagicKey::parse("a\nb\nc").unwrap(), None);
+        let repeat = MagicKey::parse("a\nb\nc\n_ @\nmagic @").unwrap().unwrap();
+        assert_eq!(repeat.retype("all"), "al@");
+        assert_eq!(repeat.retype("lll"), "l@@");
+        assert_eq!(repeat.retype("l  l"), "l  l");
+        let magic = MagicKey::parse("a\nb\nc\nmagic @ ou").unwrap().unwrap();
+        assert_eq!(magic.retype("you"), "yo@");
+        assert_eq!(magic.retype("oo"), "oo");
+        assert_eq!(magic.to_string(), "magic @ ou");
+        assert!(MagicKey::parse("a\nb\nc\nmagic").is_err());
+        assert!(MagicKey::parse("a\nb\nc\nmagic @ oua").is_err());
+
+        let mut gen = LayoutGeneration::new("english", "static", None).unwrap();
+        let config = Config::default();
+        let magic_gen =
+            LayoutGeneration::with_magic(&[("english", 1.0)], "static", &repeat, config).unwrap();
+        let keys = "qwertyuiopasdfghjkl;zxcvbnm,./";
+        let mut qwerty = FastLayout::try_from(gen.convert_u8.to(keys.chars()).as_slice()).unwrap();
+        qwerty.set_fingers(&gen.fingers);
+        qwerty.set_thumb(1, gen.convert_u8.to_single('@')).unwrap();
+
+        let magic_qwerty = magic_gen.convert_with_thumbs(&gen, &qwerty).unwrap();
+        assert_eq!(magic_gen.convert_u8.as_str(&magic_qwerty.matrix), keys);
+        let key = magic_gen.convert_u8.to_single_lossy('@');
+        assert_eq!(magic_qwerty.thumbs, [NO_THUMB, key]);
+        assert!(magic_gen.data.characters[key as usize] > 0.0);
+        assert!(magic_gen.score(&magic_qwerty).is_finite());
+    }
+}
@@ -1,34 +1,13 @@
-use crate::{generate::LayoutGeneration, layout::*, utility::*};
+use crate::{
+    generate::{beats_swap, LayoutGeneration},
+    layout::*,
+    utility::*,
+};
 
 impl LayoutGeneration {
-    #[allow(dead_code)]
-    pub(crate) fn score_with_precision(
-        &self,
-        layout: &FastLayout,
-        trigram_precision: usize,
-    ) -> f64 {
-        let effort = (0..layout.matrix.len())
-            .into_iter()
-            .map(|i| self.char_effort(layout, i))
-            .sum::<f64>();
-
-        let fspeed_usage = (0..8)
-            .into_iter()
-            .map(|col| self.col_usage(layout, col) + self.col_fspeed(layout, col))
-            .sum::<f64>();
-
-        let scissors = self.scissor_score(layout);
-        let lsbs = self.lsb_score(layout);
-
-        let trigram_iter = self.data.trigrams.iter().take(trigram_precision);
-        let trigram_score = self.trigram_score_iter(layout, trigram_iter);
-
-        trigram_score - effort - fspeed_usage - scissors - lsbs
-    }
-
     #[allow(dead_code)]
     fn col_fspeed_before(&self, layout: &FastLayout, col: usize) -> f64 {
-        let (start, len) = unsafe { Self::col_to_start_len(col) };
+        let (start, len) = self.col_to_start_len(col);
 
         let mut res = 0.0;
         let dsfb_ratio = self.weights.dsfb_ratio;
@@ -62,7 +41,7 @@ impl LayoutGeneration {
     #[allow(dead_code)]
     pub(crate) fn score_swap(&self, layout: &mut FastLayout, swap: &PosPair) -> f64 {
         unsafe { layout.swap_no_bounds(swap) };
-        let score = self.score_with_precision(&layout, 1000);
+        let score = self.score(layout);
         unsafe { layout.swap_no_bounds(swap) };
         score
     }
@@ -80,7 +59,7 @@ impl LayoutGeneration {
         for swap in possible_swaps.iter() {
             let current = self.score_swap(layout, swap);
 
-            if current > best_score {
+            if beats_swap(self.swap_policy, (current, swap), (best_score, best_swap.as_ref())) {
                 best_score = current;
                 best_swap = Some(*swap);
             }
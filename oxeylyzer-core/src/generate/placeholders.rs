@@ -0,0 +1,113 @@
+use crate::error::RusylyzerError;
+use crate::generate::LayoutGeneration;
+use crate::layout::*;
+
+/// Key of a layout that isn't decided yet, so a layout can be designed a few keys at a time. It
+/// is only a placeholder in languages that don't have it as a character, which some do.
+pub const PLACEHOLDER: char = '*';
+
+impl LayoutGeneration {
+    /// What placeholders are converted to, or `None` when they are a character of the language.
+    /// The generator gives them a code of their own, unless there was no room for it.
+    fn placeholder(&self) -> Option<u8> {
+        let u = self.convert_u8.to_single_lossy(PLACEHOLDER);
+        if u == self.convert_u8.len() {
+            return None;
+        }
+        let freq = self
+            .data
+            .characters
+            .get(u as usize)
+            .copied()
+            .unwrap_or_default();
+        (freq == 0.0).then_some(u)
+    }
+
+    /// Positions of `layout` that hold a placeholder. They are never typed, so they add nothing
+    /// to the score or the stats of the layout.
+    pub fn undecided_keys(&self, layout: &FastLayout) -> Vec<usize> {
+        match self.placeholder() {
+            Some(placeholder) => (0..30)
+                .filter(|&i| layout.matrix[i] == placeholder)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// `layout` with its placeholders replaced by the most common characters to generate with
+    /// that aren't on it yet, and the positions of every key that was decided. Improving the
+    /// result with those positions pinned finds the best spots for the new keys, so it is only
+    /// useful when there are undecided keys.
+    pub fn fill_placeholders(
+        &self,
+        layout: &FastLayout,
+    ) -> Result<(FastLayout, Vec<usize>), RusylyzerError> {
+        let undecided = self.undecided_keys(layout);
+        let missing = self
+            .chars_for_generation
+            .iter()
+            .copied()
+            .filter(|c| !layout.matrix.contains(c))
+            .collect::<Vec<_>>();
+        if missing.len() < undecided.len() {
+            return Err(RusylyzerError::LayoutParse(format!(
+                "{} keys are undecided, but only {} of the characters to generate with aren't on \
+                 the layout",
+                undecided.len(),
+                missing.len()
+            )));
+        }
+
+        let mut matrix = layout.matrix;
+        for (&i, c) in undecided.iter().zip(missing) {
+            matrix[i] = c;
+        }
+        let mut res = layout.clone();
+        res.set_matrix(matrix);
+        let decided = (0..30).filter(|i| !undecided.contains(i)).collect();
+        Ok((res, decided))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders() {
+        let gen = LayoutGeneration::new("english", "static", None).unwrap();
+        let layout = |keys: &str| {
+            let bytes = gen.convert_u8.to_lossy(keys.chars());
+            let mut layout = FastLayout::try_from(bytes.as_slice()).unwrap();
+            layout.set_fingers(&gen.fingers);
+            layout
+        };
+        let qwerty = layout("qwertyuiopasdfghjkl;zxcvbnm,./");
+        let partial = layout("qwertyuiopasdfghjkl;zxcvb***./");
+        // a character the language doesn't have isn't a placeholder
+        let unknown = layout("qwertyuiopasdfghjkl;zxcvbnm,.§");
+
+        assert!(gen.undecided_keys(&qwerty).is_empty());
+        assert!(gen.undecided_keys(&unknown).is_empty());
+        assert_eq!(gen.undecided_keys(&partial), vec![25, 26, 27]);
+        assert!(gen.score(&partial).is_finite());
+        let stats = gen.get_layout_stats(&partial);
+        assert!(stats.sfb <= gen.get_layout_stats(&qwerty).sfb);
+
+        let (filled, decided) = gen.fill_placeholders(&partial).unwrap();
+        assert!(gen.undecided_keys(&filled).is_empty());
+        assert_eq!(decided.len(), 27);
+        assert_eq!(filled.matrix[..25], partial.matrix[..25]);
+        for c in &filled.matrix[25..28] {
+            assert!(!partial.matrix.contains(c));
+        }
+        let improved = gen.generate_with_pins(&filled, &decided, None);
+        assert_eq!(improved.matrix[..25], partial.matrix[..25]);
+
+        let empty = layout(&"*".repeat(30));
+        assert_eq!(
+            gen.fill_placeholders(&empty).unwrap().1,
+            Vec::<usize>::new()
+        );
+    }
+}
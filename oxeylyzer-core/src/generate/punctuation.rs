@@ -0,0 +1,121 @@
+use crate::generate::LayoutGeneration;
+use crate::language_data::BigramData;
+use crate::layout::*;
+use crate::utility::*;
+
+/// What a punctuation key on a layout costs in the bigrams it makes with the other keys.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PunctuationCost {
+    pub c: char,
+    /// Position of the key, or `None` when it isn't on the layout.
+    pub position: Option<usize>,
+    /// Frequency of the character itself.
+    pub freq: f64,
+    /// Same finger bigrams with the key, both ways around.
+    pub sfb: f64,
+    pub dsfb: f64,
+    pub scissors: f64,
+    pub lsbs: f64,
+}
+
+impl LayoutGeneration {
+    /// Costs of every `punctuation` character of the config on `layout`, in the order they were
+    /// configured. Punctuation that isn't on the layout only has a frequency.
+    pub fn punctuation_costs(&self, layout: &FastLayout) -> Vec<PunctuationCost> {
+        let len = self.data.characters.len();
+        let both_ways = |data: &BigramData, c1: u8, c2: u8| {
+            let (c1, c2) = (c1 as usize, c2 as usize);
+            data.get(c1 * len + c2).unwrap_or(&0.0) + data.get(c2 * len + c1).unwrap_or(&0.0)
+        };
+
+        self.punctuation
+            .iter()
+            .map(|&c| {
+                let mut res = PunctuationCost {
+                    c: self.convert_u8.from_single(c),
+                    freq: *self.data.characters.get(c as usize).unwrap_or(&0.0),
+                    ..Default::default()
+                };
+                let pos = match layout.matrix.iter().position(|&k| k == c) {
+                    Some(pos) => pos,
+                    None => return res,
+                };
+                res.position = Some(pos);
+
+                for &other in self.finger_keys[self.fingers[pos]].iter() {
+                    if other != pos {
+                        res.sfb += both_ways(&self.data.bigrams, c, layout.matrix[other]);
+                        res.dsfb += both_ways(&self.data.skipgrams, c, layout.matrix[other]);
+                    }
+                }
+                for (pairs, total) in [
+                    (&self.scissor_indices[..], &mut res.scissors),
+                    (&self.lsb_indices[..], &mut res.lsbs),
+                ] {
+                    for &PosPair(i1, i2) in pairs.iter().filter(|p| p.0 == pos || p.1 == pos) {
+                        *total +=
+                            both_ways(&self.data.bigrams, layout.matrix[i1], layout.matrix[i2]);
+                    }
+                }
+                res
+            })
+            .collect()
+    }
+
+    /// Positions of the punctuation on `layout` when punctuation shouldn't be moved, which `improve`
+    /// adds to its pins. Empty when it can be moved.
+    pub fn punctuation_pins(&self, layout: &FastLayout) -> Vec<usize> {
+        if self.move_punctuation {
+            return Vec::new();
+        }
+        (0..30)
+            .filter(|&i| self.punctuation.contains(&layout.matrix[i]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weights::Config;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn punctuation_costs() {
+        let mut config = Config::default();
+        config.generation.punctuation = ",.'~".to_string();
+        config.generation.move_punctuation = false;
+        let gen = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let con = &gen.convert_u8;
+        let qwerty_bytes = con.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,.'".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+
+        let costs = gen.punctuation_costs(&qwerty);
+        assert_eq!(costs.iter().map(|cost| cost.c).collect::<String>(), ",.'~");
+        assert_eq!(costs[0].position, Some(27));
+        assert_eq!(costs[3].position, None);
+        assert_eq!(costs[3].sfb, 0.0);
+
+        // ',' shares the right middle finger with 'i' and 'k'
+        let comma = con.to_single_lossy(',');
+        let expected = ['i', 'k']
+            .into_iter()
+            .map(|c| {
+                let c = con.to_single_lossy(c) as usize;
+                let len = gen.data.characters.len();
+                gen.data.bigrams[comma as usize * len + c]
+                    + gen.data.bigrams[c * len + comma as usize]
+            })
+            .sum::<f64>();
+        assert!(costs[0].sfb.approx_eq_dbg(expected, 9));
+        assert!(costs
+            .iter()
+            .all(|cost| cost.scissors >= 0.0 && cost.lsbs >= 0.0));
+
+        assert_eq!(gen.punctuation_pins(&qwerty), vec![27, 28, 29]);
+        assert!(GEN.punctuation_pins(&qwerty).is_empty());
+    }
+}
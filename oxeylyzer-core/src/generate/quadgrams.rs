@@ -0,0 +1,146 @@
+use crate::generate::LayoutGeneration;
+use crate::layout::*;
+use crate::trigram_patterns::TrigramPattern::{self, *};
+
+/// Patterns of 4 keys in a row that the 2 trigrams they are made of can't tell apart, like
+/// whether three keys rolled on one hand end on the other hand, keep going or turn around.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QuadgramStats {
+    /// Three keys rolled in one direction on one hand, followed by a key on the other hand, like
+    /// qwerty `sdfj`.
+    pub quad_rolls: f64,
+    /// Four keys rolled in one direction on one hand, like qwerty `asdf`.
+    pub long_onehands: f64,
+    /// Three keys rolled in one direction on one hand, followed by a key on the same hand that
+    /// goes back, like qwerty `sdfa`.
+    pub onehand_redirects: f64,
+    /// Four keys on one hand that change direction twice, like qwerty `dsfa`.
+    pub double_redirects: f64,
+}
+
+fn is_redirect(pattern: TrigramPattern) -> bool {
+    matches!(
+        pattern,
+        Redirect | RedirectSfs | BadRedirect | BadRedirectSfs
+    )
+}
+
+impl QuadgramStats {
+    /// Adds a quadgram made of a trigram with the `first` pattern followed by one with `second`.
+    fn add(&mut self, first: TrigramPattern, second: TrigramPattern, freq: f64) {
+        match (first, second) {
+            (Onehand, Inroll | Outroll) => self.quad_rolls += freq,
+            (Onehand, Onehand) => self.long_onehands += freq,
+            (Onehand, second) if is_redirect(second) => self.onehand_redirects += freq,
+            (first, second) if is_redirect(first) && is_redirect(second) => {
+                self.double_redirects += freq
+            }
+            _ => {}
+        }
+    }
+}
+
+impl LayoutGeneration {
+    /// Quadgram stats of the `precision` most common quadgrams on `layout`. `None` if the
+    /// language data was made before it had quadgrams.
+    pub fn quadgram_stats(&self, layout: &FastLayout, precision: usize) -> Option<QuadgramStats> {
+        if self.data.quadgrams.is_empty() {
+            return None;
+        }
+
+        let mut res = QuadgramStats::default();
+        for &([u1, u2, u3, u4], freq) in self.data.quadgrams.iter().take(precision) {
+            let first = layout.get_trigram_pattern_in(&self.trigram_table, &[u1, u2, u3]);
+            if first != Onehand && !is_redirect(first) {
+                continue;
+            }
+            let second = layout.get_trigram_pattern_in(&self.trigram_table, &[u2, u3, u4]);
+            res.add(first, second, freq);
+        }
+        Some(res)
+    }
+
+    /// Weighted quadgram stats, where rolls and onehands are added and redirects subtracted. They
+    /// are only counted when one of them is weighted, as every swap while optimizing scores them.
+    pub(crate) fn quadgram_score(&self, layout: &FastLayout, precision: usize) -> f64 {
+        let w = &self.weights;
+        let weights = [
+            w.quad_rolls,
+            w.long_onehands,
+            w.onehand_redirects,
+            w.double_redirects,
+        ];
+        if weights.iter().all(|&weight| weight == 0.0) {
+            return 0.0;
+        }
+
+        match self.quadgram_stats(layout, precision) {
+            Some(stats) => {
+                stats.quad_rolls * w.quad_rolls + stats.long_onehands * w.long_onehands
+                    - stats.onehand_redirects * w.onehand_redirects
+                    - stats.double_redirects * w.double_redirects
+            }
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::{ApproxEq, PosPair};
+
+    #[test]
+    fn quadgram_stats() {
+        let mut gen = LayoutGeneration::new("english", "static", None).unwrap();
+        let qwerty_bytes = gen
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let mut qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        qwerty.set_fingers(&gen.fingers);
+
+        gen.data.quadgrams = Vec::new();
+        assert!(gen.quadgram_stats(&qwerty, usize::MAX).is_none());
+        gen.weights.quad_rolls = 1.0;
+        assert_eq!(gen.quadgram_score(&qwerty, usize::MAX), 0.0);
+
+        let quadgram = |s: &str| {
+            let u = gen.convert_u8.to_lossy(s.chars());
+            [u[0], u[1], u[2], u[3]]
+        };
+        gen.data.quadgrams = vec![
+            (quadgram("sdfj"), 0.4),
+            (quadgram("asdf"), 0.3),
+            (quadgram("sdfa"), 0.15),
+            (quadgram("dsfa"), 0.1),
+            (quadgram("jsdf"), 0.05),
+        ];
+        let stats = gen.quadgram_stats(&qwerty, usize::MAX).unwrap();
+        assert_eq!(
+            stats,
+            QuadgramStats {
+                quad_rolls: 0.4,
+                long_onehands: 0.3,
+                onehand_redirects: 0.15,
+                double_redirects: 0.1,
+            }
+        );
+        assert_eq!(gen.quadgram_stats(&qwerty, 1).unwrap().long_onehands, 0.0);
+
+        gen.weights.double_redirects = 2.0;
+        let score = gen.quadgram_score(&qwerty, usize::MAX);
+        assert!(score.approx_eq_dbg(0.4 - 0.2, 9));
+        let components = gen.score_components(&qwerty, usize::MAX);
+        assert_eq!(components.quadgrams, score);
+
+        // weighted, they are part of the cached score as well
+        let mut cache = gen.initialize_cache(&qwerty);
+        assert!(cache.total_score().approx_eq_dbg(gen.score(&qwerty), 7));
+
+        let swap = PosPair(11, 16);
+        let swapped = gen.score_swap_cached(&mut qwerty, &swap, &cache);
+        gen.accept_swap(&mut qwerty, &swap.into(), &mut cache);
+        assert!(swapped.approx_eq_dbg(gen.score(&qwerty), 7));
+        assert!(cache.total_score().approx_eq_dbg(gen.score(&qwerty), 7));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::generate::LayoutGeneration;
+use crate::layout::*;
+use crate::utility::*;
+
+/// How much a key typed with the same finger as before costs to relearn, compared to one that is
+/// typed with the other hand.
+const SAME_FINGER: f64 = 0.5;
+/// How much a key typed with another finger of the same hand costs to relearn.
+const SAME_HAND: f64 = 0.75;
+
+/// Estimate of how much has to be relearned to switch from one layout to another.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetrainingCost {
+    /// Keys of the new layout that are in another position on the old one, or not on it at all.
+    pub moved: usize,
+    /// Moved keys that are still typed with the same finger.
+    pub same_finger: usize,
+    /// Moved keys that are typed with another finger of the same hand.
+    pub same_hand: usize,
+    /// Keystrokes that go to another position, weighted by how different that position is, as a
+    /// fraction of all keystrokes on the layout. 0 is nothing to relearn, 1 is every keystroke
+    /// moving to the other hand.
+    pub cost: f64,
+}
+
+impl LayoutGeneration {
+    /// How much has to be relearned to switch from `from` to `to`. Every key that moved counts
+    /// with its frequency, and keys that stay on the same finger or hand count for less.
+    pub fn retraining_cost(&self, from: &FastLayout, to: &FastLayout) -> RetrainingCost {
+        let mut res = RetrainingCost::default();
+        let (mut weighted, mut total) = (0.0, 0.0);
+
+        for (i, &c) in to.matrix.iter().enumerate() {
+            let freq = self.data.characters.get(c as usize).copied().unwrap_or(0.0);
+            total += freq;
+            if from.matrix[i] == c {
+                continue;
+            }
+
+            res.moved += 1;
+            let finger = to.fingers[i];
+            let old_finger = from
+                .matrix
+                .iter()
+                .position(|&k| k == c)
+                .map(|j| from.fingers[j]);
+            let factor = match old_finger {
+                Some(old) if old == finger => {
+                    res.same_finger += 1;
+                    SAME_FINGER
+                }
+                Some(old) if is_left_hand(old) == is_left_hand(finger) => {
+                    res.same_hand += 1;
+                    SAME_HAND
+                }
+                _ => 1.0,
+            };
+            weighted += freq * factor;
+        }
+
+        if total > 0.0 {
+            res.cost = weighted / total;
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn retraining() {
+        let layout = |keys: &str| {
+            let bytes = GEN.convert_u8.to_lossy(keys.chars());
+            let mut layout = FastLayout::try_from(bytes.as_slice()).unwrap();
+            layout.set_fingers(&GEN.fingers);
+            layout
+        };
+        let qwerty = layout("qwertyuiopasdfghjkl;zxcvbnm,./");
+        assert_eq!(
+            GEN.retraining_cost(&qwerty, &qwerty),
+            RetrainingCost::default()
+        );
+
+        // e and d are both on the left middle finger
+        let swapped = layout("qwdrtyuiopasefghjkl;zxcvbnm,./");
+        let cost = GEN.retraining_cost(&qwerty, &swapped);
+        assert_eq!((cost.moved, cost.same_finger, cost.same_hand), (2, 2, 0));
+        let freq = |c: char| GEN.data.characters[GEN.convert_u8.to_single_lossy(c) as usize];
+        let total = qwerty
+            .matrix
+            .iter()
+            .map(|&c| GEN.data.characters[c as usize])
+            .sum::<f64>();
+        let expected = SAME_FINGER * (freq('e') + freq('d')) / total;
+        assert!((cost.cost - expected).abs() < 1e-9);
+
+        let colemak = layout("qwfpgjluy;arstdhneiozxcvbkm,./");
+        let cost = GEN.retraining_cost(&qwerty, &colemak);
+        assert_eq!(cost.moved, 17);
+        assert!(cost.same_finger + cost.same_hand < cost.moved);
+        assert!(cost.cost > expected && cost.cost < 1.0);
+    }
+}
@@ -0,0 +1,207 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::generate::LayoutGeneration;
+use crate::layout::*;
+
+/// When generating stops before every layout that was asked for is done. Layouts that are being
+/// optimized when it stops are finished, so it can take a little longer than `max_time`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StopCriteria {
+    /// How long generating may take. No new layouts are started after it.
+    pub max_time: Option<Duration>,
+    /// A score that is good enough, so generating stops once a layout gets it.
+    pub target_score: Option<f64>,
+    /// Layouts in a row that don't beat the best score so far, after which it is unlikely to get
+    /// better.
+    pub patience: Option<usize>,
+}
+
+impl StopCriteria {
+    /// Whether nothing stops generating before all layouts are done.
+    pub fn is_empty(&self) -> bool {
+        self.max_time.is_none() && self.target_score.is_none() && self.patience.is_none()
+    }
+}
+
+/// Why generating stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Every layout that was asked for is done.
+    Amount,
+    Time,
+    TargetScore,
+    /// `patience` layouts in a row didn't beat the best score.
+    Converged,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Self::Amount => "every layout is done",
+            Self::Time => "the time ran out",
+            Self::TargetScore => "a layout reached the target score",
+            Self::Converged => "the best score stopped improving",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+#[derive(Default)]
+struct ProgressState {
+    done: usize,
+    best_score: Option<f64>,
+    since_best: usize,
+    stopped: Option<StopReason>,
+}
+
+/// How far generating with `StopCriteria` is, shared by the threads that generate.
+pub struct GenerationProgress {
+    pub criteria: StopCriteria,
+    start: Instant,
+    state: Mutex<ProgressState>,
+}
+
+impl GenerationProgress {
+    pub fn new(criteria: StopCriteria) -> Self {
+        Self {
+            criteria,
+            start: Instant::now(),
+            state: Mutex::new(ProgressState::default()),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Time left of `max_time`, if there is one.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.criteria
+            .max_time
+            .map(|max_time| max_time.saturating_sub(self.elapsed()))
+    }
+
+    /// Layouts that are done.
+    pub fn done(&self) -> usize {
+        self.state.lock().unwrap().done
+    }
+
+    pub fn best_score(&self) -> Option<f64> {
+        self.state.lock().unwrap().best_score
+    }
+
+    /// Why generating stopped or should stop, or `None` while it should go on.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        let mut state = self.state.lock().unwrap();
+        if state.stopped.is_none() && self.remaining() == Some(Duration::ZERO) {
+            log::info!("stopping after {} layouts, as the time ran out", state.done);
+            state.stopped = Some(StopReason::Time);
+        }
+        state.stopped
+    }
+
+    fn add(&self, score: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.done += 1;
+        match state.best_score {
+            Some(best) if best >= score => state.since_best += 1,
+            _ => {
+                state.best_score = Some(score);
+                state.since_best = 0;
+            }
+        }
+
+        if state.stopped.is_some() {
+            return;
+        }
+        if matches!(self.criteria.target_score, Some(target) if score >= target) {
+            state.stopped = Some(StopReason::TargetScore);
+        } else if matches!(self.criteria.patience, Some(patience) if state.since_best >= patience) {
+            state.stopped = Some(StopReason::Converged);
+        }
+        if let Some(reason) = state.stopped {
+            log::info!("stopping after {} layouts, as {reason}", state.done);
+        }
+    }
+}
+
+impl LayoutGeneration {
+    /// Generates up to `amount` layouts in parallel, until one of the criteria of `progress` is
+    /// met. `on_layout` is called every time a layout is done, like to show how much time is
+    /// left. Every layout starts from scratch, so `islands` isn't used.
+    pub fn generate_until<F>(
+        &self,
+        amount: usize,
+        progress: &GenerationProgress,
+        on_layout: F,
+    ) -> Vec<FastLayout>
+    where
+        F: Fn() + Sync,
+    {
+        (0..amount)
+            .into_par_iter()
+            .map(|_| {
+                if progress.stop_reason().is_some() {
+                    return None;
+                }
+                let layout = self.generate();
+                progress.add(layout.score);
+                on_layout();
+                Some(layout)
+            })
+            .while_some()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn stops_early() {
+        let progress = GenerationProgress::new(StopCriteria::default());
+        assert_eq!(GEN.generate_until(3, &progress, || ()).len(), 3);
+        assert_eq!(progress.stop_reason(), None);
+        assert_eq!(progress.done(), 3);
+
+        let progress = GenerationProgress::new(StopCriteria {
+            max_time: Some(Duration::ZERO),
+            ..Default::default()
+        });
+        assert!(GEN.generate_until(1000, &progress, || ()).is_empty());
+        assert_eq!(progress.stop_reason(), Some(StopReason::Time));
+
+        let progress = GenerationProgress::new(StopCriteria {
+            target_score: Some(f64::MIN),
+            ..Default::default()
+        });
+        let layouts = GEN.generate_until(1000, &progress, || ());
+        assert!(!layouts.is_empty() && layouts.len() < 1000);
+        assert_eq!(progress.stop_reason(), Some(StopReason::TargetScore));
+        assert!(progress.best_score() >= layouts.first().map(|layout| layout.score));
+    }
+
+    #[test]
+    fn converges() {
+        let progress = GenerationProgress::new(StopCriteria {
+            patience: Some(2),
+            ..Default::default()
+        });
+        for score in [1.0, 3.0, 2.0] {
+            progress.add(score);
+            assert_eq!(progress.stop_reason(), None);
+        }
+        progress.add(3.0);
+        assert_eq!(progress.stop_reason(), Some(StopReason::Converged));
+        assert_eq!(progress.best_score(), Some(3.0));
+        assert_eq!(progress.done(), 4);
+    }
+}
@@ -0,0 +1,101 @@
+use std::cmp::Reverse;
+
+use crate::generate::{pinned_swaps, LayoutGeneration, LayoutStats};
+use crate::layout::*;
+use crate::utility::*;
+
+/// A swap of two keys and what it would change on a layout, without making it.
+#[derive(Clone)]
+pub struct SwapSuggestion {
+    pub swap: PosPair,
+    /// Characters on the two keys, in the order of `swap`.
+    pub chars: (char, char),
+    /// Score of the layout after the swap minus its score before.
+    pub score_delta: f64,
+    /// Stats of the layout after the swap, which can be compared with its stats before using
+    /// `LayoutStats::deltas`.
+    pub stats: LayoutStats,
+}
+
+impl LayoutGeneration {
+    /// The `count` single swaps that raise the score of `layout` the most, best first. When no swap
+    /// raises it, these are the ones that lower it the least. Swaps that move a key in `pins` are
+    /// left out, just like when improving a layout, and so are swaps of locked keys.
+    pub fn suggest_swaps(
+        &self,
+        layout: &FastLayout,
+        pins: &[usize],
+        count: usize,
+    ) -> Vec<SwapSuggestion> {
+        let mut layout = layout.clone();
+        let cache = self.initialize_cache(&layout);
+
+        let mut scored = pinned_swaps(&self.locks.with_pins(pins))
+            .into_iter()
+            .map(|swap| (swap, self.score_swap_cached(&mut layout, &swap, &cache)))
+            .collect::<Vec<_>>();
+        // stable, so ties keep the order of the positions
+        scored.sort_by_key(|&(_, s)| Reverse(TotalScore(s)));
+
+        scored
+            .into_iter()
+            .take(count)
+            .map(|(swap, score)| {
+                let mut swapped = layout.clone();
+                swapped.swap_pair(&swap);
+
+                SwapSuggestion {
+                    swap,
+                    chars: (
+                        self.convert_u8.from_single(layout.matrix[swap.0]),
+                        self.convert_u8.from_single(layout.matrix[swap.1]),
+                    ),
+                    score_delta: score - cache.total_score,
+                    stats: self.get_layout_stats(&swapped),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn suggest_swaps() {
+        let qwerty_bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let score = GEN.score(&qwerty);
+
+        let suggestions = GEN.suggest_swaps(&qwerty, &[], 5);
+        assert_eq!(suggestions.len(), 5);
+        assert!(suggestions
+            .windows(2)
+            .all(|w| w[0].score_delta >= w[1].score_delta));
+        assert!(suggestions[0].score_delta > 0.0);
+
+        let best = &suggestions[0];
+        let mut swapped = qwerty.clone();
+        swapped.swap_pair(&best.swap);
+        assert!((GEN.score(&swapped) - score - best.score_delta).abs() < 1e-7);
+        assert_eq!(
+            best.chars.0,
+            GEN.convert_u8.from_single(qwerty.matrix[best.swap.0])
+        );
+        assert!(!best.stats.deltas(&GEN.get_layout_stats(&qwerty)).is_empty());
+
+        let pins = (0..29).collect::<Vec<_>>();
+        assert!(GEN.suggest_swaps(&qwerty, &pins, 5).is_empty());
+        let pins = (0..28).collect::<Vec<_>>();
+        let suggestions = GEN.suggest_swaps(&qwerty, &pins, 5);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].swap, PosPair(28, 29));
+    }
+}
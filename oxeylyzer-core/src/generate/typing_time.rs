@@ -0,0 +1,117 @@
+use crate::generate::LayoutGeneration;
+use crate::layout::*;
+use crate::utility::*;
+
+/// A simple model of how long every keystroke takes, in milliseconds, as an alternative to the
+/// score. A keystroke takes longer the further the finger has to move, a bit like Fitts's law,
+/// and depends on which finger pressed the key before it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypingTimeModel {
+    /// Time of a keystroke where the finger doesn't have to move.
+    pub base: f64,
+    /// Time added for the distance the finger moves, multiplied by `log2(1 + distance)` with the
+    /// distance in key widths.
+    pub movement: f64,
+    /// Time added when the same finger pressed the key before, so it can't start moving early.
+    pub same_finger: f64,
+    /// Time saved when the other hand pressed the key before, so this hand could get ready.
+    pub alternation: f64,
+    /// Time of pressing the same key again.
+    pub repeat: f64,
+}
+
+impl Default for TypingTimeModel {
+    fn default() -> Self {
+        Self {
+            base: 150.0,
+            movement: 60.0,
+            same_finger: 90.0,
+            alternation: 40.0,
+            repeat: 140.0,
+        }
+    }
+}
+
+/// Words per minute when every keystroke takes `ms_per_keystroke`, counting 5 keystrokes a word.
+pub fn wpm(ms_per_keystroke: f64) -> f64 {
+    60_000.0 / (ms_per_keystroke * 5.0)
+}
+
+impl LayoutGeneration {
+    /// Time of typing `i2` right after `i1`. Unless the same finger presses both, the finger
+    /// pressing `i2` starts on its home key.
+    fn keystroke_time(&self, model: &TypingTimeModel, i1: usize, i2: usize) -> f64 {
+        let (f1, f2) = (self.fingers[i1], self.fingers[i2]);
+        let movement = |distance: f64| model.movement * (1.0 + distance).log2();
+
+        if i1 == i2 {
+            model.repeat
+        } else if f1 == f2 {
+            model.base + model.same_finger + movement(self.key_distances[i1][i2])
+        } else if is_left_hand(f1) == is_left_hand(f2) {
+            model.base + movement(self.home_distances[i2])
+        } else {
+            model.base - model.alternation + movement(self.home_distances[i2])
+        }
+    }
+
+    /// Average time per keystroke on `layout` in milliseconds according to `model`, from every
+    /// bigram with both keys on the 30 main keys. Lower is faster, see [`wpm`] to compare it with
+    /// typing speeds.
+    pub fn typing_time(&self, layout: &FastLayout, model: &TypingTimeModel) -> f64 {
+        let len = self.data.characters.len();
+        let (mut time, mut total) = (0.0, 0.0);
+
+        for i1 in 0..30 {
+            for i2 in 0..30 {
+                let (c1, c2) = (layout.matrix[i1] as usize, layout.matrix[i2] as usize);
+                let freq = *self.data.bigrams.get(c1 * len + c2).unwrap_or(&0.0);
+                if freq > 0.0 {
+                    time += freq * self.keystroke_time(model, i1, i2);
+                    total += freq;
+                }
+            }
+        }
+        if total > 0.0 {
+            time / total
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn typing_time() {
+        let layout = |keys: &str| {
+            let bytes = GEN.convert_u8.to_lossy(keys.chars());
+            FastLayout::try_from(bytes.as_slice()).unwrap()
+        };
+        let qwerty = layout("qwertyuiopasdfghjkl;zxcvbnm,./");
+        let semimak = layout("flhvzqwuoysrntkcdeaix'bmjpg,./");
+        let model = TypingTimeModel::default();
+
+        let qwerty_time = GEN.typing_time(&qwerty, &model);
+        assert!(qwerty_time > model.base - model.alternation);
+        assert!(qwerty_time < model.base + model.same_finger + model.movement * 2.0);
+        assert!(GEN.typing_time(&semimak, &model) < qwerty_time);
+
+        let slow_sfbs = TypingTimeModel {
+            same_finger: 200.0,
+            ..Default::default()
+        };
+        assert!(GEN.typing_time(&qwerty, &slow_sfbs) > qwerty_time);
+
+        assert_eq!(GEN.keystroke_time(&model, 11, 11), model.repeat);
+        assert!(GEN.keystroke_time(&model, 11, 1) > GEN.keystroke_time(&model, 12, 1));
+        assert!(GEN.keystroke_time(&model, 16, 1) < GEN.keystroke_time(&model, 12, 1));
+        assert_eq!(wpm(200.0), 60.0);
+    }
+}
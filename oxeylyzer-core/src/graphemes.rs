@@ -0,0 +1,159 @@
+use std::borrow::Cow;
+
+use fxhash::FxHashMap;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+use crate::error::RusylyzerError;
+use crate::utility::TotalScore;
+
+const ZERO_WIDTH_JOINER: char = '\u{200d}';
+
+/// `s` in Unicode normalization form C, where a letter followed by a combining accent becomes the
+/// single character with that accent wherever there is one. Corpora, layout files, the config and
+/// the repl all go through this, so `é` is the same key however it was typed.
+pub fn nfc(s: &str) -> Cow<'_, str> {
+    match is_nfc(s) {
+        true => Cow::Borrowed(s),
+        false => Cow::Owned(s.nfc().collect()),
+    }
+}
+
+/// Graphemes of `s` made of several characters, like a letter with an accent that has no composed
+/// form, or emoji joined by a zero width joiner. `s` should be in NFC, or accents that do have a
+/// composed form are found as well.
+pub fn multi_char_graphemes(s: &str) -> impl Iterator<Item = &str> {
+    let mut chars = s.char_indices().peekable();
+
+    std::iter::from_fn(move || loop {
+        let (start, _) = chars.next()?;
+        let mut len = 1;
+        let mut joined = false;
+        while let Some(&(_, c)) = chars.peek() {
+            if !joined && c != ZERO_WIDTH_JOINER && !is_combining_mark(c) {
+                break;
+            }
+            joined = c == ZERO_WIDTH_JOINER;
+            chars.next();
+            len += 1;
+        }
+        if len > 1 {
+            let end = chars.peek().map_or(s.len(), |&(i, _)| i);
+            return Some(&s[start..end]);
+        }
+    })
+}
+
+/// Errors on the first grapheme of the keys of a layout that is several characters, which would
+/// otherwise be spread over several keys and shift every key after it.
+pub fn check_single_chars(keys: &str) -> Result<(), RusylyzerError> {
+    match multi_char_graphemes(keys).next() {
+        Some(grapheme) => Err(RusylyzerError::LayoutParse(format!(
+            "'{grapheme}' ({}) is several characters, which can't be a single key",
+            code_points(grapheme)
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// The code points of `s` written like `U+0078 U+0303`.
+pub fn code_points(s: &str) -> String {
+    s.chars()
+        .map(|c| format!("U+{:04X}", c as u32))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// How often every grapheme of several characters is in a corpus. Language data only has single
+/// characters, so these can't be put on a key as they are.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct GraphemeCounts {
+    pub counts: FxHashMap<String, u64>,
+    /// Characters that were counted in, to turn the counts into frequencies.
+    pub total: u64,
+}
+
+impl GraphemeCounts {
+    pub fn add(&mut self, text: &str) {
+        self.total += text.chars().count() as u64;
+        for grapheme in multi_char_graphemes(text) {
+            *self.counts.entry(grapheme.to_string()).or_default() += 1;
+        }
+    }
+
+    pub fn combine_with(mut self, mut rhs: Self) -> Self {
+        if self.counts.len() < rhs.counts.len() {
+            std::mem::swap(&mut self, &mut rhs);
+        }
+        for (grapheme, count) in rhs.counts {
+            *self.counts.entry(grapheme).or_default() += count;
+        }
+        self.total += rhs.total;
+        self
+    }
+
+    /// The `amount` most common graphemes with their frequency, most common first.
+    pub fn most_common(&self, amount: usize) -> Vec<(&str, f64)> {
+        let mut res = self
+            .counts
+            .iter()
+            .map(|(grapheme, &count)| (grapheme.as_str(), count as f64 / self.total as f64))
+            .collect::<Vec<_>>();
+        res.sort_by(|(g1, f1), (g2, f2)| TotalScore(*f2).cmp(&TotalScore(*f1)).then(g1.cmp(g2)));
+        res.truncate(amount);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_to_composed_characters() {
+        assert!(matches!(nfc("café"), Cow::Borrowed("café")));
+        assert_eq!(nfc("cafe\u{301}"), "café");
+        assert_eq!(nfc("A\u{30a}ngstro\u{308}m"), "Ångström");
+        // there is no x with a tilde, so it stays 2 characters
+        assert_eq!(nfc("x\u{303}").chars().count(), 2);
+    }
+
+    #[test]
+    fn finds_graphemes_of_several_characters() {
+        let text = nfc("e\u{301}x\u{303}a q\u{323}\u{307} \u{1f469}\u{200d}\u{1f4bb}!");
+        let graphemes = multi_char_graphemes(&text).collect::<Vec<_>>();
+        assert_eq!(
+            graphemes,
+            vec!["x\u{303}", "q\u{323}\u{307}", "\u{1f469}\u{200d}\u{1f4bb}"]
+        );
+        assert_eq!(multi_char_graphemes("qwerty").count(), 0);
+    }
+
+    #[test]
+    fn layout_keys() {
+        assert!(check_single_chars("qwertyuiopasdfghjkl;zxcvbnm,./").is_ok());
+        assert!(check_single_chars(&nfc("qwe\u{301}rtyuiop")).is_ok());
+
+        let error = check_single_chars("qwx\u{303}rtyuiop")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("(U+0078 U+0303)"), "{error}");
+    }
+
+    #[test]
+    fn counts() {
+        let mut first = GraphemeCounts::default();
+        first.add("x\u{303}yx\u{303}");
+        let mut second = GraphemeCounts::default();
+        second.add("g\u{303}aaaa");
+        second.add("");
+
+        let counts = first.combine_with(second);
+        assert_eq!(counts.total, 11);
+        assert_eq!(
+            counts.most_common(5),
+            vec![("x\u{303}", 2.0 / 11.0), ("g\u{303}", 1.0 / 11.0)]
+        );
+        assert_eq!(counts.most_common(1).len(), 1);
+    }
+}
@@ -0,0 +1,249 @@
+use std::fmt::Write;
+
+/// Names kmonad and kanata both use for the 30 keys, which are the keys of qwerty.
+const KEY_NAMES: [&str; 30] = [
+    "q", "w", "e", "r", "t", "y", "u", "i", "o", "p", "a", "s", "d", "f", "g", "h", "j", "k", "l",
+    ";", "z", "x", "c", "v", "b", "n", "m", ",", ".", "/",
+];
+
+/// Keys outside of the 30 that type the same character on every base layout, with their name.
+const OTHER_KEYS: [(char, &str); 15] = [
+    ('\'', "'"),
+    ('-', "-"),
+    ('=', "="),
+    ('[', "["),
+    (']', "]"),
+    ('1', "1"),
+    ('2', "2"),
+    ('3', "3"),
+    ('4', "4"),
+    ('5', "5"),
+    ('6', "6"),
+    ('7', "7"),
+    ('8', "8"),
+    ('9', "9"),
+    ('0', "0"),
+];
+
+/// Software remapper a keymap is written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemapTool {
+    Kmonad,
+    Kanata,
+}
+
+impl TryFrom<&str> for RemapTool {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, &'static str> {
+        match value.to_lowercase().as_str() {
+            "kmonad" => Ok(Self::Kmonad),
+            "kanata" => Ok(Self::Kanata),
+            _ => Err("keymaps can be written for kmonad and kanata"),
+        }
+    }
+}
+
+/// Layout the operating system is set to, which the remapped keys are typed through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemapBase {
+    Qwerty,
+    Colemak,
+}
+
+impl TryFrom<&str> for RemapBase {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, &'static str> {
+        match value.to_lowercase().as_str() {
+            "qwerty" => Ok(Self::Qwerty),
+            "colemak" => Ok(Self::Colemak),
+            _ => Err("the base layout has to be qwerty or colemak"),
+        }
+    }
+}
+
+impl RemapBase {
+    /// The 30 characters the base layout types on the keys of `KEY_NAMES`.
+    fn chars(&self) -> &'static str {
+        match self {
+            Self::Qwerty => "qwertyuiopasdfghjkl;zxcvbnm,./",
+            Self::Colemak => "qwfpgjluy;arstdhneiozxcvbkm,./",
+        }
+    }
+}
+
+/// A kmonad or kanata config that remaps qwerty keys to a layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    pub config: String,
+    /// Characters of the layout the remapper can't type, whose keys are left as they are.
+    pub unmapped: Vec<char>,
+}
+
+impl Keymap {
+    /// Writes the `defsrc` and `deflayer` blocks that put `keys`, row by row, on the 30 keys of a
+    /// keyboard. The keys are sent as the key that types them on `base`, so it works when the
+    /// operating system is set to `base`. kmonad also needs a `defcfg` for the keyboard it reads
+    /// from, which is left to the user.
+    pub fn new(name: &str, keys: &[char; 30], tool: RemapTool, base: RemapBase) -> Self {
+        let mut unmapped = Vec::new();
+        let layer = keys.map(|c| match key_name(c, base) {
+            Some(name) => name.to_string(),
+            None if tool == RemapTool::Kanata && !c.is_whitespace() => format!("(unicode {c})"),
+            None => {
+                if !c.is_whitespace() {
+                    unmapped.push(c);
+                }
+                "_".to_string()
+            }
+        });
+        let width = layer
+            .iter()
+            .map(|key| key.chars().count())
+            .max()
+            .unwrap_or(1)
+            + 1;
+
+        let mut config = String::new();
+        let tool_name = match tool {
+            RemapTool::Kmonad => "kmonad",
+            RemapTool::Kanata => "kanata",
+        };
+        let base_name = match base {
+            RemapBase::Qwerty => "qwerty",
+            RemapBase::Colemak => "colemak",
+        };
+        writeln!(
+            config,
+            ";; {name} for {tool_name}, with the system layout set to {base_name}"
+        )
+        .unwrap();
+        if !unmapped.is_empty() {
+            let missing = unmapped.iter().collect::<String>();
+            writeln!(
+                config,
+                ";; {missing} can't be typed, so those keys are left as they are"
+            )
+            .unwrap();
+        }
+        if tool == RemapTool::Kmonad {
+            writeln!(
+                config,
+                ";; add a defcfg with the input and output of your keyboard"
+            )
+            .unwrap();
+        }
+
+        config.push_str("\n(defsrc\n");
+        write_rows(&mut config, &KEY_NAMES.map(str::to_string), width);
+        writeln!(config, ")\n\n(deflayer {}", layer_name(name)).unwrap();
+        write_rows(&mut config, &layer, width);
+        config.push_str(")\n");
+
+        Self { config, unmapped }
+    }
+}
+
+/// Name of the key that types `c` when the operating system is set to `base`.
+fn key_name(c: char, base: RemapBase) -> Option<&'static str> {
+    let c = c.to_ascii_lowercase();
+    match base.chars().chars().position(|k| k == c) {
+        Some(pos) => Some(KEY_NAMES[pos]),
+        None => OTHER_KEYS
+            .iter()
+            .find(|&&(k, _)| k == c)
+            .map(|&(_, name)| name),
+    }
+}
+
+/// `name` with everything but letters, digits, `-` and `_` replaced, to use it as a layer name.
+fn layer_name(name: &str) -> String {
+    let res = name
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    match res.is_empty() {
+        true => "layout".to_string(),
+        false => res,
+    }
+}
+
+fn write_rows(config: &mut String, keys: &[String; 30], width: usize) {
+    for row in keys.chunks(10) {
+        let line = row
+            .iter()
+            .map(|key| format!("{key:width$}"))
+            .collect::<String>();
+        writeln!(config, "  {}", line.trim_end()).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(s: &str) -> [char; 30] {
+        s.chars().collect::<Vec<_>>().try_into().unwrap()
+    }
+
+    #[test]
+    fn qwerty_stays_the_same() {
+        let qwerty = keys("qwertyuiopasdfghjkl;zxcvbnm,./");
+        for tool in [RemapTool::Kmonad, RemapTool::Kanata] {
+            let keymap = Keymap::new("qwerty", &qwerty, tool, RemapBase::Qwerty);
+            let rows = |block: &str| {
+                let (_, rest) = keymap.config.split_once(block).unwrap();
+                rest.lines().skip(1).take(3).collect::<Vec<_>>()
+            };
+            assert_eq!(rows("(defsrc"), rows("(deflayer qwerty"));
+            assert_eq!(rows("(defsrc")[0], "  q w e r t y u i o p");
+            assert!(keymap.unmapped.is_empty());
+        }
+    }
+
+    #[test]
+    fn layers() {
+        let colemak = keys("qwfpgjluy;arstdhneiozxcvbkm,./");
+        let keymap = Keymap::new("colemak", &colemak, RemapTool::Kanata, RemapBase::Qwerty);
+        assert!(keymap
+            .config
+            .contains("(deflayer colemak\n  q w f p g j l u y ;\n"));
+        assert!(keymap.config.contains("\n  a r s t d h n e i o\n"));
+
+        // typed through colemak, colemak is sent as qwerty keys
+        let keymap = Keymap::new("colemak", &colemak, RemapTool::Kanata, RemapBase::Colemak);
+        assert!(keymap
+            .config
+            .contains("(deflayer colemak\n  q w e r t y u i o p\n"));
+
+        let dvorak = keys("',.pyfgcrlaoeuidhtns;qjkxbmwvz");
+        let keymap = Keymap::new(
+            "dvorak (ansi)",
+            &dvorak,
+            RemapTool::Kmonad,
+            RemapBase::Colemak,
+        );
+        assert!(keymap
+            .config
+            .contains("(deflayer dvorak__ansi_\n  ' , . r o e t c s u\n"));
+
+        let swedish = keys("qwertyuiopasdfghjklözxcvbnm,.å");
+        let keymap = Keymap::new("swedish", &swedish, RemapTool::Kanata, RemapBase::Qwerty);
+        assert!(keymap.config.contains("(unicode ö)"));
+        assert!(keymap.unmapped.is_empty());
+        let keymap = Keymap::new("swedish", &swedish, RemapTool::Kmonad, RemapBase::Qwerty);
+        assert_eq!(keymap.unmapped, vec!['ö', 'å']);
+        assert!(keymap.config.contains("\n;; öå can't be typed"));
+    }
+
+    #[test]
+    fn parse_options() {
+        assert_eq!(RemapTool::try_from("Kanata"), Ok(RemapTool::Kanata));
+        assert_eq!(RemapBase::try_from("colemak"), Ok(RemapBase::Colemak));
+        assert!(RemapBase::try_from("dvorak").is_err());
+    }
+}
@@ -3,19 +3,27 @@ use arrayvec::ArrayVec;
 use fxhash::FxHashMap;
 use indexmap::IndexMap;
 use itertools::Itertools;
+use nanorand::{tls_rng, Rng};
+use serde::de::IgnoredAny;
 use serde::Deserialize;
 use serde_json;
 
-use std::fs::File;
-use std::io::prelude::*;
+use std::hash::Hash;
 use std::path::Path;
 
-use crate::utility::ConvertU8;
+use crate::data_source::{DataSource, FileSource};
+use crate::trigram_cache;
+use crate::utility::{ConvertU8, MAX_CHARS};
+use crate::weights::Weights;
 
-pub type CharacterData = ArrayVec<f64, 60>;
+pub type CharacterData = ArrayVec<f64, MAX_CHARS>;
 pub type SlowBigramData = FxHashMap<[u8; 2], f64>;
 pub type BigramData = Vec<f64>;
 pub type TrigramData = Vec<([u8; 3], f64)>;
+pub type QuadgramData = Vec<([u8; 4], f64)>;
+
+/// Stands for space in `LanguageData::space_trigrams`, as space isn't one of the characters.
+pub const SPACE: u8 = u8::MAX;
 
 trait BigramLookup {
     fn lookup(&self, c1: usize, c2: usize, char_count: usize) -> f64;
@@ -27,15 +35,172 @@ impl BigramLookup for BigramData {
     }
 }
 
-#[derive(Deserialize)]
-struct LanguageDataInter {
+/// Language data as it is stored in json. Trigrams are read as `IgnoredAny` when they come from a
+/// trigram cache instead, which skips over them without allocating.
+#[derive(Deserialize, Default)]
+struct LanguageDataInter<T = IndexMap<String, f64>> {
     pub language: String,
     pub characters: FxHashMap<char, f64>,
     pub bigrams: FxHashMap<String, f64>,
     pub skipgrams: FxHashMap<String, f64>,
     pub skipgrams2: FxHashMap<String, f64>,
     pub skipgrams3: FxHashMap<String, f64>,
-    pub trigrams: IndexMap<String, f64>,
+    pub trigrams: T,
+    #[serde(default)]
+    pub space_trigrams: IndexMap<String, f64>,
+    #[serde(default)]
+    pub quadgrams: IndexMap<String, f64>,
+    #[serde(default)]
+    pub samples: Vec<String>,
+}
+
+impl LanguageDataInter<IgnoredAny> {
+    fn without_trigrams(self) -> LanguageDataInter {
+        LanguageDataInter {
+            language: self.language,
+            characters: self.characters,
+            bigrams: self.bigrams,
+            skipgrams: self.skipgrams,
+            skipgrams2: self.skipgrams2,
+            skipgrams3: self.skipgrams3,
+            trigrams: IndexMap::new(),
+            space_trigrams: self.space_trigrams,
+            quadgrams: self.quadgrams,
+            samples: self.samples,
+        }
+    }
+}
+
+/// Characters that are always part of the language data, even when they never occur.
+const ALWAYS_INCLUDED: [char; 6] = ['\'', ',', '.', ';', '/', '~'];
+
+impl LanguageDataInter {
+    fn from_source(source: &dyn DataSource, language: &str) -> Result<Self> {
+        Ok(serde_json::from_str(&source.language_data(language)?)?)
+    }
+
+    /// The data of several languages, where every language makes up its share of the total. See
+    /// `LanguageData::combined`.
+    fn combined(source: &dyn DataSource, languages: &[(&str, f64)]) -> Result<Self> {
+        let total = languages.iter().map(|(_, share)| share).sum::<f64>();
+        if languages.is_empty() || total <= 0.0 {
+            anyhow::bail!("at least one language with a share above 0 is needed to combine");
+        }
+
+        let mut combined: LanguageDataInter = Default::default();
+        for &(language, share) in languages {
+            let data = LanguageDataInter::from_source(source, language)?;
+            combined.add_weighted(data, share / total);
+        }
+
+        combined.language = languages.iter().map(|(l, _)| *l).join("+");
+        for ngrams in [&mut combined.trigrams, &mut combined.quadgrams] {
+            ngrams.sort_by(|_, f1, _, f2| f2.partial_cmp(f1).unwrap());
+        }
+        Ok(combined)
+    }
+
+    /// Adds the frequencies of `other` multiplied by `weight` to the ones in `self`.
+    fn add_weighted(&mut self, other: Self, weight: f64) {
+        for (c, freq) in other.characters {
+            *self.characters.entry(c).or_insert(0.0) += freq * weight;
+        }
+
+        for (into, from) in [
+            (&mut self.bigrams, other.bigrams),
+            (&mut self.skipgrams, other.skipgrams),
+            (&mut self.skipgrams2, other.skipgrams2),
+            (&mut self.skipgrams3, other.skipgrams3),
+        ] {
+            for (bigram, freq) in from {
+                *into.entry(bigram).or_insert(0.0) += freq * weight;
+            }
+        }
+
+        for (into, from) in [
+            (&mut self.trigrams, other.trigrams),
+            (&mut self.space_trigrams, other.space_trigrams),
+            (&mut self.quadgrams, other.quadgrams),
+        ] {
+            for (ngram, freq) in from {
+                *into.entry(ngram).or_insert(0.0) += freq * weight;
+            }
+        }
+
+        self.samples.extend(other.samples);
+    }
+
+    /// Removes the least frequent characters until at most `max` are left, not counting the ones
+    /// that are always included. Returns the removed characters, most frequent first.
+    fn keep_most_frequent(&mut self, max: usize) -> Vec<(char, f64)> {
+        let mut chars = self
+            .characters
+            .iter()
+            .filter(|(c, _)| !ALWAYS_INCLUDED.contains(*c))
+            .map(|(&c, &f)| (c, f))
+            .collect::<Vec<_>>();
+
+        if chars.len() <= max {
+            return Vec::new();
+        }
+        chars.sort_by(|(_, f1), (_, f2)| f2.partial_cmp(f1).unwrap());
+        let removed = chars.split_off(max);
+        for (c, _) in removed.iter() {
+            self.characters.remove(c);
+        }
+        removed
+    }
+
+    /// The data as it is typed when `retype` changes characters of n-grams, which keeps the first
+    /// character of every n-gram as it is. Characters are counted again from the bigrams that
+    /// changed, and skipgrams from the trigrams that did, so skipgrams with 2 or 3 characters in
+    /// between stay the same.
+    fn retyped(mut self, retype: &dyn Fn(&str) -> String) -> Self {
+        let ends = |trigram: &str| trigram.chars().step_by(2).collect::<String>();
+        for (trigram, &freq) in self.trigrams.iter() {
+            let (old, new) = (ends(trigram), ends(&retype(trigram)));
+            if old != new {
+                move_freq(&mut self.skipgrams, old, new, freq);
+            }
+        }
+
+        for (bigram, freq) in std::mem::take(&mut self.bigrams) {
+            let retyped = retype(&bigram);
+            if let (Some(old), Some(new)) = (bigram.chars().nth(1), retyped.chars().nth(1)) {
+                if old != new {
+                    move_freq(&mut self.characters, old, new, freq);
+                }
+            }
+            *self.bigrams.entry(retyped).or_insert(0.0) += freq;
+        }
+
+        for ngrams in [
+            &mut self.trigrams,
+            &mut self.space_trigrams,
+            &mut self.quadgrams,
+        ] {
+            let mut res = IndexMap::new();
+            for (ngram, freq) in std::mem::take(ngrams) {
+                *res.entry(retype(&ngram)).or_insert(0.0) += freq;
+            }
+            res.sort_by(|_, f1, _, f2| f2.partial_cmp(f1).unwrap());
+            *ngrams = res;
+        }
+        self
+    }
+}
+
+/// Moves `freq` from `from` to `to` in `map`, or as much of it as `from` has.
+fn move_freq<K: Eq + Hash>(map: &mut FxHashMap<K, f64>, from: K, to: K, freq: f64) {
+    let moved = match map.get_mut(&from) {
+        Some(f) => {
+            let moved = f.min(freq);
+            *f -= moved;
+            moved
+        }
+        None => return,
+    };
+    *map.entry(to).or_insert(0.0) += moved;
 }
 
 fn get_char_data(data: FxHashMap<char, f64>, con: &mut ConvertU8) -> CharacterData {
@@ -59,8 +224,15 @@ fn get_bigram_data(data: FxHashMap<String, f64>, con: &mut ConvertU8) -> BigramD
 fn get_trigram_data(data: IndexMap<String, f64>, con: &mut ConvertU8) -> TrigramData {
     let mut res = TrigramData::new();
     for (trigram, freq) in data {
-        let tv = trigram.chars().collect::<Vec<char>>();
-        let tv_u8 = con.to(tv);
+        // a trigram with a character there is no room for can't be typed on any layout anyway
+        let tv_u8 = match trigram
+            .chars()
+            .map(|c| con.try_to_single(c))
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(tv_u8) => tv_u8,
+            None => continue,
+        };
 
         if tv_u8[0] != tv_u8[1] && tv_u8[1] != tv_u8[2] {
             let new_trigram = [tv_u8[0], tv_u8[1], tv_u8[2]];
@@ -69,23 +241,80 @@ fn get_trigram_data(data: IndexMap<String, f64>, con: &mut ConvertU8) -> Trigram
     }
     res
 }
+
+/// Like `get_trigram_data`, but trigrams with characters that aren't in `con` yet are left out
+/// instead of being added.
+fn get_space_trigram_data(data: IndexMap<String, f64>, con: &ConvertU8) -> TrigramData {
+    let mut res = TrigramData::new();
+    for (trigram, freq) in data {
+        let tv_u8 = trigram
+            .chars()
+            .map(|c| match c {
+                ' ' => SPACE,
+                c => con.to_single_lossy(c),
+            })
+            .collect::<Vec<_>>();
+
+        if let [u1, u2, u3] = tv_u8[..] {
+            let known = [u1, u2, u3].iter().all(|&u| u == SPACE || u < con.len());
+            if known && u1 != u2 && u2 != u3 {
+                res.push(([u1, u2, u3], freq));
+            }
+        }
+    }
+    res
+}
+
+/// Like `get_space_trigram_data`, leaving out quadgrams with characters that aren't in `con`, or
+/// with the same character twice in a row.
+fn get_quadgram_data(data: IndexMap<String, f64>, con: &ConvertU8) -> QuadgramData {
+    let mut res = QuadgramData::new();
+    for (quadgram, freq) in data {
+        let qv_u8 = quadgram
+            .chars()
+            .map(|c| con.to_single_lossy(c))
+            .collect::<Vec<_>>();
+
+        if let [u1, u2, u3, u4] = qv_u8[..] {
+            let known = [u1, u2, u3, u4].iter().all(|&u| u < con.len());
+            if known && u1 != u2 && u2 != u3 && u3 != u4 {
+                res.push(([u1, u2, u3, u4], freq));
+            }
+        }
+    }
+    res
+}
+
+#[derive(Clone)]
 pub struct LanguageData {
     pub characters: CharacterData,
     pub bigrams: BigramData,
     pub skipgrams: BigramData,
     pub skipgrams2: BigramData,
     pub skipgrams3: BigramData,
+    /// What every pair of characters adds to finger speed when it is on one finger, which is empty
+    /// until `weigh_bigrams` fills it in.
     pub weighted_bigrams: BigramData,
     pub trigrams: TrigramData,
+    /// Trigrams with a space in them, which is [`SPACE`] here. Only language data made since these
+    /// were added has them.
+    pub space_trigrams: TrigramData,
+    /// Sequences of 4 characters, most common first. Like `space_trigrams`, only language data
+    /// made since these were added has them.
+    pub quadgrams: QuadgramData,
     pub language: String,
     pub convert_u8: ConvertU8,
+    pub samples: Vec<String>,
+    /// Characters that were left out for not fitting in `MAX_CHARS`, most frequent first.
+    pub dropped_chars: Vec<(char, f64)>,
 }
 
 impl From<LanguageDataInter> for LanguageData {
     fn from(mut inter: LanguageDataInter) -> Self {
         let mut convert_u8 = ConvertU8::new();
+        let dropped_chars = inter.keep_most_frequent(MAX_CHARS - ALWAYS_INCLUDED.len());
 
-        for c in ['\'', ',', '.', ';', '/', '~'] {
+        for c in ALWAYS_INCLUDED {
             if !inter.characters.contains_key(&c) {
                 inter.characters.insert(c, 0.0);
             }
@@ -101,6 +330,8 @@ impl From<LanguageDataInter> for LanguageData {
         let weighted_bigrams = BigramData::new();
 
         let trigrams = get_trigram_data(inter.trigrams, &mut convert_u8);
+        let space_trigrams = get_space_trigram_data(inter.space_trigrams, &convert_u8);
+        let quadgrams = get_quadgram_data(inter.quadgrams, &convert_u8);
 
         Self {
             characters,
@@ -109,9 +340,13 @@ impl From<LanguageDataInter> for LanguageData {
             skipgrams2,
             skipgrams3,
             trigrams,
+            space_trigrams,
+            quadgrams,
             weighted_bigrams,
             language: inter.language,
             convert_u8,
+            samples: inter.samples,
+            dropped_chars,
         }
     }
 }
@@ -122,19 +357,219 @@ impl LanguageData {
         Ok(LanguageData::from(data))
     }
 
+    /// Fills in `weighted_bigrams`: the bigrams and skipgrams of every pair of characters in both
+    /// orders, with skipgrams weighted by the dsfb ratios, times the fspeed weight. Both orders
+    /// are added up, so finger speed takes one lookup for a pair of keys.
+    pub fn weigh_bigrams(&mut self, weights: &Weights) {
+        let len = self.characters.len();
+        let weigh = |c1: usize, c2: usize| {
+            self.bigrams.lookup(c1, c2, len)
+                + self.skipgrams.lookup(c1, c2, len) * weights.dsfb_ratio
+                + self.skipgrams2.lookup(c1, c2, len) * weights.dsfb_ratio2
+                + self.skipgrams3.lookup(c1, c2, len) * weights.dsfb_ratio3
+        };
+
+        let weighted_bigrams = (0..len)
+            .cartesian_product(0..len)
+            .map(|(c1, c2)| (weigh(c1, c2) + weigh(c2, c1)) * weights.fspeed)
+            .collect();
+        self.weighted_bigrams = weighted_bigrams;
+    }
+
+    pub fn random_samples(&self, amount: usize) -> Vec<&str> {
+        let mut samples = self.samples.iter().map(String::as_str).collect::<Vec<_>>();
+        tls_rng().shuffle(&mut samples);
+        samples.truncate(amount);
+        samples
+    }
+
     pub fn from_file<P>(base_path: P, language: &str) -> Result<LanguageData>
     where
         P: AsRef<Path>,
     {
-        let file_path = base_path.as_ref().join(language.to_lowercase() + ".json");
-        let mut file = File::open(file_path)?;
+        Self::from_source(&Self::file_source(base_path), language)
+    }
+
+    /// Reads the language data of `language` from `source`. Trigrams are the slowest part to
+    /// decode, so they are read from the trigram cache of `source` when it was made from the same
+    /// json. Loading never writes the cache, that is up to `cache_trigrams`.
+    pub fn from_source(source: &dyn DataSource, language: &str) -> Result<LanguageData> {
+        let json = source.language_data(language)?;
+        let key = trigram_cache::key(&json);
+
+        if let Some(bytes) = source.read_trigram_cache(language) {
+            let inter: LanguageDataInter<IgnoredAny> = serde_json::from_str(&json)?;
+            let mut data = LanguageData::from(inter.without_trigrams());
+            if data.set_cached_trigrams(&bytes, key) {
+                return Ok(data);
+            }
+        }
 
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+        let data = LanguageData::from(serde_json::from_str::<LanguageDataInter>(&json)?);
+        Ok(data)
+    }
+
+    /// Writes the trigram cache of `language` to `source`, unless the one it has was already made
+    /// from the current json.
+    pub fn cache_trigrams(source: &dyn DataSource, language: &str) -> Result<()> {
+        let json = source.language_data(language)?;
+        let key = trigram_cache::key(&json);
+
+        let cached = source.read_trigram_cache(language);
+        if cached.is_some_and(|bytes| trigram_cache::decode(&bytes, key).is_some()) {
+            return Ok(());
+        }
+
+        let data = LanguageData::from(serde_json::from_str::<LanguageDataInter>(&json)?);
+        let cache = trigram_cache::encode(key, &data.convert_u8, &data.trigrams);
+        source.write_trigram_cache(language, &cache)?;
+        Ok(())
+    }
+
+    /// Replaces the trigrams with the ones in a trigram cache, if it was made with `key` and
+    /// converts the characters of `self` the same way. The cache is read into memory as a whole
+    /// and its records are copied into `trigrams`.
+    fn set_cached_trigrams(&mut self, bytes: &[u8], key: u64) -> bool {
+        let (chars, records) = match trigram_cache::decode(bytes, key) {
+            Some(cached) => cached,
+            None => return false,
+        };
+        let known = self.convert_u8.chars();
+        if !chars.starts_with(known) {
+            return false;
+        }
+
+        let new_chars = chars[known.len()..].to_vec();
+        self.convert_u8.insert(new_chars);
+        self.trigrams = records.collect();
+        true
+    }
+
+    /// Source that reads the language data in `base_path` itself, like `static/language_data`.
+    fn file_source<P: AsRef<Path>>(base_path: P) -> FileSource {
+        FileSource {
+            language_data_dir: base_path.as_ref().to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    /// Combines the data of several languages, where every language makes up its share of the
+    /// total, like `[("english", 0.6), ("german", 0.4)]`. Shares don't have to add up to 1.
+    pub fn combined<P>(base_path: P, languages: &[(&str, f64)]) -> Result<LanguageData>
+    where
+        P: AsRef<Path>,
+    {
+        Self::combined_from_source(&Self::file_source(base_path), languages)
+    }
+
+    pub fn combined_from_source(
+        source: &dyn DataSource,
+        languages: &[(&str, f64)],
+    ) -> Result<LanguageData> {
+        let combined = LanguageDataInter::combined(source, languages)?;
+        Ok(LanguageData::from(combined))
+    }
+
+    /// Like `combined_from_source`, as it is typed when `retype` changes the characters of every
+    /// n-gram after the first one, like a repeat key typing the second `l` of `ll`.
+    pub fn retyped_from_source(
+        source: &dyn DataSource,
+        languages: &[(&str, f64)],
+        retype: &dyn Fn(&str) -> String,
+    ) -> Result<LanguageData> {
+        let inter = LanguageDataInter::combined(source, languages)?;
+        Ok(LanguageData::from(inter.retyped(retype)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn more_chars_than_fit() {
+        let chars = (0..140)
+            .map(|i| char::from_u32(0x410 + i).unwrap())
+            .collect::<Vec<_>>();
+        let characters = chars
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("\"{c}\": {}", 1.0 / (i + 1) as f64))
+            .join(", ");
+        let (first, last) = (chars[0], chars[139]);
+        let json = format!(
+            r#"{{"language": "test", "characters": {{{characters}}}, "bigrams": {{}},
+            "skipgrams": {{}}, "skipgrams2": {{}}, "skipgrams3": {{}},
+            "trigrams": {{"{first}{last}{first}": 0.5, "{first}.,": 0.25}}}}"#
+        );
+        let data = LanguageData::new(&json).unwrap();
+
+        assert_eq!(data.characters.len(), MAX_CHARS);
+        assert_eq!(
+            data.dropped_chars.len(),
+            chars.len() + ALWAYS_INCLUDED.len() - MAX_CHARS
+        );
+        assert_eq!(
+            data.dropped_chars[0].0,
+            chars[MAX_CHARS - ALWAYS_INCLUDED.len()]
+        );
+        assert_eq!(data.dropped_chars.last().unwrap().0, last);
+        assert_eq!(data.trigrams.len(), 2);
+        assert!(data.convert_u8.len() as usize > MAX_CHARS);
+    }
+
+    #[test]
+    fn weighted_bigrams() {
+        let json = r#"{"language": "test", "characters": {"a": 0.5, "b": 0.5},
+            "bigrams": {"ab": 0.25, "ba": 0.125}, "skipgrams": {"ab": 0.5},
+            "skipgrams2": {}, "skipgrams3": {"bb": 0.25}, "trigrams": {}}"#;
+        let mut data = LanguageData::new(json).unwrap();
+        assert!(data.weighted_bigrams.is_empty());
+
+        let mut weights = crate::weights::Config::default().weights;
+        weights.fspeed = 2.0;
+        weights.dsfb_ratio = 0.5;
+        weights.dsfb_ratio3 = 0.25;
+        data.weigh_bigrams(&weights);
+
+        let len = data.characters.len();
+        assert_eq!(data.weighted_bigrams.len(), len * len);
+        let a = data.convert_u8.to_single('a') as usize;
+        let b = data.convert_u8.to_single('b') as usize;
+        let expected = (0.25 + 0.125 + 0.5 * 0.5) * 2.0;
+        assert_eq!(data.weighted_bigrams.lookup(a, b, len), expected);
+        assert_eq!(data.weighted_bigrams.lookup(b, a, len), expected);
+        assert_eq!(
+            data.weighted_bigrams.lookup(b, b, len),
+            0.25 * 0.25 * 2.0 * 2.0
+        );
+        assert_eq!(data.weighted_bigrams.lookup(a, a, len), 0.0);
+    }
 
-        let data: LanguageDataInter = serde_json::from_str(contents.as_str())?;
-        let res = LanguageData::from(data);
+    #[test]
+    fn retyped() {
+        let json = r#"{"language": "test", "characters": {"a": 0.5, "l": 0.5},
+            "bigrams": {"ll": 0.25, "al": 0.5}, "skipgrams": {"al": 0.25},
+            "skipgrams2": {}, "skipgrams3": {}, "trigrams": {"lal": 0.125, "all": 0.25}}"#;
+        let inter = serde_json::from_str::<LanguageDataInter>(json).unwrap();
+        let repeat = |ngram: &str| -> String {
+            let mut prev = None;
+            ngram
+                .chars()
+                .map(|c| match prev.replace(c) {
+                    Some(p) if p == c => '@',
+                    _ => c,
+                })
+                .collect()
+        };
+        let data = inter.retyped(&repeat);
 
-        Ok(res)
+        assert_eq!(data.characters[&'l'], 0.25);
+        assert_eq!(data.characters[&'@'], 0.25);
+        assert_eq!(data.bigrams["l@"], 0.25);
+        assert!(!data.bigrams.contains_key("ll"));
+        assert_eq!(data.skipgrams["al"], 0.0);
+        assert_eq!(data.skipgrams["a@"], 0.25);
+        assert_eq!(data.trigrams.keys().collect::<Vec<_>>(), ["al@", "lal"]);
     }
 }
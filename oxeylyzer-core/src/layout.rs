@@ -1,9 +1,23 @@
-use crate::trigram_patterns::{TrigramPattern, TRIGRAM_COMBINATIONS};
+use fxhash::FxHashSet;
+
+use crate::error::RusylyzerError;
+use crate::trigram_patterns::{trigram_index, TrigramPattern, TrigramTable, TRIGRAM_COMBINATIONS};
 use crate::utility::*;
 
-pub type CharToFinger = [usize; 60];
+pub type CharToFinger = [usize; MAX_CHARS];
 pub type Matrix<T> = [T; 30];
 
+/// Value of an empty thumb key in `FastLayout::thumbs`.
+pub const NO_THUMB: u8 = u8::MAX;
+
+/// Removes every layout that has the same columns as one before it, which are identical,
+/// mirrored or column permuted versions of it. When `layouts` are sorted by score, the best of
+/// every group is kept.
+pub fn dedup_columns(layouts: &mut Vec<FastLayout>) {
+    let mut seen = FxHashSet::default();
+    layouts.retain(|layout| seen.insert(layout.column_key()));
+}
+
 pub trait Layout<T: Copy + Default> {
     fn new() -> Self;
 
@@ -38,8 +52,15 @@ pub trait Layout<T: Copy + Default> {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct FastLayout {
+    /// The 30 keys row by row, as the bytes `ConvertU8` turns characters into, so n-gram data can
+    /// be indexed with them directly. `ConvertU8::as_str` turns them back into characters.
     pub matrix: Matrix<u8>,
     pub char_to_finger: CharToFinger,
+    /// Finger that presses every position. Has to be the same as the one of the `LayoutGeneration`
+    /// the layout is scored with, which `with_fingers` and `set_fingers` take care of.
+    pub fingers: FingerAssignment,
+    /// Keys on the left and right thumb. These aren't part of `matrix`, so they are never swapped.
+    pub thumbs: [u8; 2],
     pub score: f64,
 }
 
@@ -49,37 +70,195 @@ impl Default for FastLayout {
     }
 }
 
-impl From<[u8; 30]> for FastLayout {
-    fn from(layout: [u8; 30]) -> Self {
-        let mut new_layout = FastLayout::new();
-
-        for (i, byte) in layout.into_iter().enumerate() {
-            new_layout.matrix[i] = byte;
-            new_layout.char_to_finger[byte as usize] = I_TO_COL[i];
-        }
-        new_layout
-    }
-}
-
 impl TryFrom<&[u8]> for FastLayout {
-    type Error = anyhow::Error;
+    type Error = RusylyzerError;
 
     fn try_from(layout_bytes: &[u8]) -> Result<Self, Self::Error> {
         if layout_bytes.len() >= 30 {
             let mut new_layout = FastLayout::new();
 
-            for (i, &byte) in layout_bytes.into_iter().enumerate() {
+            for (i, &byte) in layout_bytes.iter().take(30).enumerate() {
                 new_layout.matrix[i] = byte;
-                new_layout.char_to_finger[byte as usize] = I_TO_COL[i];
+                match new_layout.char_to_finger.get_mut(byte as usize) {
+                    Some(finger) => *finger = new_layout.fingers[i],
+                    None => {
+                        return Err(RusylyzerError::LayoutParse(format!(
+                            "key {i} is not one of the {} most common characters of the language",
+                            new_layout.char_to_finger.len()
+                        )))
+                    }
+                }
             }
             Ok(new_layout)
         } else {
-            anyhow::bail!("you should provide at least 30 bytes to create a layout from.")
+            Err(RusylyzerError::LayoutParse(format!(
+                "a layout needs 30 keys, but only {} were provided",
+                layout_bytes.len()
+            )))
         }
     }
 }
 
 impl FastLayout {
+    /// Layout with `chars` on it row by row, pressed by `fingers`. Scoring it needs the fingers of
+    /// the `LayoutGeneration` it is scored with, like `gen.fingers`.
+    pub fn with_fingers(chars: [u8; 30], fingers: &FingerAssignment) -> Self {
+        let mut res = FastLayout::new();
+        res.fingers = *fingers;
+        res.set_matrix(chars);
+        res
+    }
+
+    /// Shuffles `chars` into this layout in place, leaving the positions in `pins` where they are.
+    pub fn randomize(&mut self, mut chars: [u8; 30], pins: &[usize]) {
+        shuffle_pins::<30, u8>(&mut chars, pins);
+        self.set_matrix(chars);
+    }
+
+    /// Puts `chars` on this layout in the order they are in, keeping the thumb keys.
+    pub fn set_matrix(&mut self, chars: [u8; 30]) {
+        self.char_to_finger = [usize::MAX; MAX_CHARS];
+        for (i, byte) in chars.into_iter().enumerate() {
+            self.matrix[i] = byte;
+            self.char_to_finger[byte as usize] = self.fingers[i];
+        }
+        self.apply_thumbs();
+        self.score = 0.0;
+    }
+
+    /// This layout with its left and right half swapped. Thumb keys switch thumbs as well, but the
+    /// finger assignment stays the same, so a custom one isn't mirrored along with the keys.
+    pub fn mirrored(&self) -> Self {
+        let mut res = self.clone();
+        res.thumbs = [self.thumbs[1], self.thumbs[0]];
+        res.set_matrix(std::array::from_fn(|i| {
+            self.matrix[i / 10 * 10 + 9 - i % 10]
+        }));
+        res
+    }
+
+    /// Lets `fingers` press the keys of this layout instead of its current finger assignment.
+    pub fn set_fingers(&mut self, fingers: &FingerAssignment) {
+        self.fingers = *fingers;
+        for (&c, &finger) in self.matrix.iter().zip(fingers) {
+            if let Some(f) = self.char_to_finger.get_mut(c as usize) {
+                *f = finger;
+            }
+        }
+    }
+
+    /// Puts `c` on the left (0) or right (1) thumb, replacing whatever was there before. If `c` was
+    /// on the other thumb, it is moved.
+    pub fn set_thumb(&mut self, thumb: usize, c: u8) -> Result<(), RusylyzerError> {
+        if thumb >= THUMB_FINGERS.len() {
+            return Err(RusylyzerError::LayoutParse(format!(
+                "there are only 2 thumbs, so thumb {thumb} doesn't exist"
+            )));
+        }
+        if c as usize >= self.char_to_finger.len() {
+            return Err(RusylyzerError::LayoutParse(format!(
+                "thumb key is not one of the {} most common characters of the language",
+                self.char_to_finger.len()
+            )));
+        }
+        if self.matrix.contains(&c) {
+            return Err(RusylyzerError::LayoutParse(
+                "a thumb key can't be on the rest of the layout as well".to_string(),
+            ));
+        }
+
+        if let Some(old) = self.char_to_finger.get_mut(self.thumbs[thumb] as usize) {
+            *old = usize::MAX;
+        }
+        if self.thumbs[1 - thumb] == c {
+            self.thumbs[1 - thumb] = NO_THUMB;
+        }
+        self.thumbs[thumb] = c;
+        self.apply_thumbs();
+        Ok(())
+    }
+
+    fn apply_thumbs(&mut self) {
+        for (&c, finger) in self.thumbs.iter().zip(THUMB_FINGERS) {
+            if c != NO_THUMB {
+                self.char_to_finger[c as usize] = finger;
+            }
+        }
+    }
+
+    /// Same as `get_trigram_pattern`, but classifies with `table` instead of the default rules.
+    pub fn get_trigram_pattern_in(
+        &self,
+        table: &TrigramTable,
+        trigram: &[u8; 3],
+    ) -> TrigramPattern {
+        let a = self
+            .char_to_finger
+            .get(trigram[0] as usize)
+            .copied()
+            .unwrap_or(usize::MAX);
+        let b = self
+            .char_to_finger
+            .get(trigram[1] as usize)
+            .copied()
+            .unwrap_or(usize::MAX);
+        let c = self
+            .char_to_finger
+            .get(trigram[2] as usize)
+            .copied()
+            .unwrap_or(usize::MAX);
+        if (a | b | c) == usize::MAX {
+            return TrigramPattern::Invalid;
+        }
+        // a, b and c are numbers between 0 and 9, thumbs included. They fit in 4 bits each
+        table[trigram_index(a, b, c)]
+    }
+
+    /// Positions that hold a different key in `other` than in this layout.
+    pub fn moved_keys(&self, other: &FastLayout) -> [bool; 30] {
+        let mut res = [false; 30];
+        for (moved, (c1, c2)) in res.iter_mut().zip(self.matrix.iter().zip(other.matrix)) {
+            *moved = *c1 != c2;
+        }
+        res
+    }
+
+    /// Number of columns of this layout whose keys also make up a single column in `other`. The
+    /// order within the column and where the column is don't matter, so mirrored columns count.
+    pub fn shared_columns(&self, other: &FastLayout) -> usize {
+        fn columns(layout: &FastLayout) -> [[u8; 3]; 10] {
+            let mut res = [[0; 3]; 10];
+            for (x, column) in res.iter_mut().enumerate() {
+                *column = [layout.c(x), layout.c(x + 10), layout.c(x + 20)];
+                column.sort_unstable();
+            }
+            res
+        }
+        let other_columns = columns(other);
+
+        columns(self)
+            .iter()
+            .filter(|column| other_columns.contains(column))
+            .count()
+    }
+
+    /// The columns of this layout from top to bottom in sorted order, with the thumb keys. Layouts
+    /// with the same columns in other places, like a mirrored layout, have the same key.
+    fn column_key(&self) -> ([[u8; 3]; 10], [u8; 2]) {
+        let mut columns: [[u8; 3]; 10] =
+            std::array::from_fn(|x| [self.c(x), self.c(x + 10), self.c(x + 20)]);
+        columns.sort_unstable();
+        let mut thumbs = self.thumbs;
+        thumbs.sort_unstable();
+        (columns, thumbs)
+    }
+
+    /// Whether `other` has the same columns as this layout, with the same keys from top to bottom,
+    /// but maybe in other places. Such layouts only differ in which fingers press the columns.
+    pub fn same_columns(&self, other: &FastLayout) -> bool {
+        self.column_key() == other.column_key()
+    }
+
     pub fn layout_str(&self, con: &ConvertU8) -> String {
         con.as_str(&self.matrix)
     }
@@ -99,6 +278,21 @@ impl FastLayout {
             res.push(' ');
         }
 
+        if self.thumbs != [NO_THUMB; 2] {
+            let thumb = |u: u8| {
+                if u == NO_THUMB {
+                    '_'
+                } else {
+                    con.from_single(u)
+                }
+            };
+            res.push_str(&format!(
+                "\n        {}  {} ",
+                thumb(self.thumbs[0]),
+                thumb(self.thumbs[1])
+            ));
+        }
+
         res
     }
 }
@@ -107,19 +301,21 @@ impl Layout<u8> for FastLayout {
     fn new() -> FastLayout {
         FastLayout {
             matrix: [u8::MAX; 30],
-            char_to_finger: [usize::MAX; 60],
+            char_to_finger: [usize::MAX; MAX_CHARS],
+            fingers: I_TO_COL,
+            thumbs: [NO_THUMB; 2],
             score: 0.0,
         }
     }
 
     fn random(mut with_chars: [u8; 30]) -> FastLayout {
         shuffle_pins::<30, u8>(&mut with_chars, &[]);
-        FastLayout::from(with_chars)
+        FastLayout::with_fingers(with_chars, &I_TO_COL)
     }
 
     fn random_pins(mut layout_chars: [u8; 30], pins: &[usize]) -> FastLayout {
         shuffle_pins::<30, u8>(&mut layout_chars, pins);
-        FastLayout::from(layout_chars)
+        FastLayout::with_fingers(layout_chars, &I_TO_COL)
     }
 
     #[inline(always)]
@@ -145,8 +341,8 @@ impl Layout<u8> for FastLayout {
 
             self.matrix[i1] = char2;
             self.matrix[i2] = char1;
-            self.char_to_finger[char1 as usize] = I_TO_COL[i2];
-            self.char_to_finger[char2 as usize] = I_TO_COL[i1];
+            self.char_to_finger[char1 as usize] = self.fingers[i2];
+            self.char_to_finger[char2 as usize] = self.fingers[i1];
 
             return Some(());
         } else {
@@ -163,8 +359,8 @@ impl Layout<u8> for FastLayout {
         *self.matrix.get_unchecked_mut(i1) = char2;
         *self.matrix.get_unchecked_mut(i2) = char1;
 
-        *self.char_to_finger.get_unchecked_mut(char1 as usize) = *I_TO_COL.get_unchecked(i2);
-        *self.char_to_finger.get_unchecked_mut(char2 as usize) = *I_TO_COL.get_unchecked(i1);
+        *self.char_to_finger.get_unchecked_mut(char1 as usize) = *self.fingers.get_unchecked(i2);
+        *self.char_to_finger.get_unchecked_mut(char2 as usize) = *self.fingers.get_unchecked(i1);
     }
 
     #[inline(always)]
@@ -202,24 +398,7 @@ impl Layout<u8> for FastLayout {
     }
 
     fn get_trigram_pattern(&self, trigram: &[u8; 3]) -> TrigramPattern {
-        let a = *self
-            .char_to_finger
-            .get(trigram[0] as usize)
-            .unwrap_or_else(|| &usize::MAX);
-        let b = *self
-            .char_to_finger
-            .get(trigram[1] as usize)
-            .unwrap_or_else(|| &usize::MAX);
-        let c = *self
-            .char_to_finger
-            .get(trigram[2] as usize)
-            .unwrap_or_else(|| &usize::MAX);
-        if (a | b | c) == usize::MAX {
-            return TrigramPattern::Invalid;
-        }
-        // a, b and c are numbers between 0 and 7. This means they fit in exactly 3 bits (7 == 0b111)
-        let combination = (a << 6) | (b << 3) | c;
-        TRIGRAM_COMBINATIONS[combination]
+        self.get_trigram_pattern_in(&TRIGRAM_COMBINATIONS, trigram)
     }
 
     unsafe fn get_trigram_pattern_unchecked(&self, trigram: &[u8; 3]) -> TrigramPattern {
@@ -235,9 +414,8 @@ impl Layout<u8> for FastLayout {
             .char_to_finger
             .get(trigram[2] as usize)
             .unwrap_unchecked();
-        // a, b and c are numbers between 0 and 7. This means they fit in exactly 3 bits (7 == 0b111)
-        let combination = (a << 6) | (b << 3) | c;
-        TRIGRAM_COMBINATIONS[combination]
+        // a, b and c are numbers between 0 and 9, thumbs included. They fit in 4 bits each
+        TRIGRAM_COMBINATIONS[trigram_index(a, b, c)]
     }
 }
 
@@ -248,6 +426,20 @@ mod tests {
     static CON: Lazy<ConvertU8> =
         Lazy::new(|| ConvertU8::with_chars("abcdefghijklmnopqrstuvwxyz'.,;/"));
 
+    #[test]
+    fn layout_parse_errors() {
+        let short = CON.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,.".chars());
+        let err = FastLayout::try_from(short.as_slice()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "layout could not be parsed: a layout needs 30 keys, but only 29 were provided"
+        );
+
+        let mut uncommon = CON.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        uncommon[3] = 200;
+        assert!(FastLayout::try_from(uncommon.as_slice()).is_err());
+    }
+
     #[test]
     fn layout_str() {
         let qwerty_bytes = CON.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
@@ -405,6 +597,146 @@ mod tests {
         assert_eq!(qwerty.c(22), CON.to_single_lossy('c'));
     }
 
+    #[test]
+    fn randomize_keeps_pins() {
+        let qwerty_bytes = CON.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).expect("couldn't create qwerty");
+        let pins = [0, 10, 13, 29];
+
+        let mut layout = FastLayout::new();
+        for _ in 0..10 {
+            layout.randomize(qwerty.matrix, &pins);
+
+            for &p in pins.iter() {
+                assert_eq!(layout.c(p), qwerty.c(p));
+            }
+            for (i, &c) in layout.matrix.iter().enumerate() {
+                assert_eq!(layout.char_to_finger[c as usize], I_TO_COL[i]);
+            }
+
+            let mut sorted = layout.matrix;
+            let mut expected = qwerty.matrix;
+            sorted.sort();
+            expected.sort();
+            assert_eq!(sorted, expected);
+        }
+    }
+
+    #[test]
+    fn diff_layouts() {
+        let qwerty_bytes = CON.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let mut other = qwerty.clone();
+
+        assert_eq!(qwerty.moved_keys(&other), [false; 30]);
+        assert_eq!(qwerty.shared_columns(&other), 10);
+
+        unsafe { other.swap_cols_no_bounds(0, 9) };
+        other.swap(1, 11);
+        other.swap(3, 4);
+
+        let moved = qwerty.moved_keys(&other);
+        assert_eq!(moved.iter().filter(|&&m| m).count(), 10);
+        assert!(moved[0] && moved[29] && moved[11] && moved[4]);
+        assert!(!moved[12]);
+        assert_eq!(qwerty.shared_columns(&other), 8);
+    }
+
+    #[test]
+    fn thumbs() {
+        let qwerty_bytes = CON.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let mut layout = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let quote = CON.to_single_lossy('\'');
+
+        assert!(layout.set_thumb(0, CON.to_single_lossy('a')).is_err());
+        assert!(layout.set_thumb(2, quote).is_err());
+
+        layout.set_thumb(1, quote).unwrap();
+        assert_eq!(layout.char_to_finger[quote as usize], 9);
+        assert!(layout.formatted_string(&CON).ends_with("\n        _  ' "));
+        assert_eq!(
+            layout.get_trigram_pattern(&CON.to_trigram_lossy(['a', '\'', 'q'])),
+            TrigramPattern::AlternateSfs
+        );
+
+        layout.randomize(layout.matrix, &[]);
+        assert_eq!(layout.char_to_finger[quote as usize], 9);
+
+        layout.set_thumb(0, quote).unwrap();
+        assert_eq!(layout.thumbs, [quote, NO_THUMB]);
+        assert_eq!(layout.char_to_finger[quote as usize], 8);
+    }
+
+    #[test]
+    fn set_fingers() {
+        let qwerty_bytes = CON.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let mut layout = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let (c, v) = (CON.to_single_lossy('c'), CON.to_single_lossy('v'));
+
+        let mut fingers = I_TO_COL;
+        fingers[23] = 2;
+        layout.set_fingers(&fingers);
+        assert_eq!(FastLayout::with_fingers(layout.matrix, &fingers), layout);
+        assert_eq!(layout.char_to_finger[v as usize], 2);
+        assert_eq!(
+            layout.get_trigram_pattern(&CON.to_trigram_lossy(['d', 'v', 's'])),
+            TrigramPattern::BadSfb
+        );
+
+        layout.swap(22, 23).unwrap();
+        assert_eq!(layout.char_to_finger[c as usize], 2);
+        assert_eq!(layout.char_to_finger[v as usize], 2);
+
+        layout.randomize(layout.matrix, &[]);
+        assert_eq!(layout.fingers, fingers);
+    }
+
+    #[test]
+    fn mirrored() {
+        let qwerty_bytes = CON.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let mut layout = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let quote = CON.to_single_lossy('\'');
+        layout.set_thumb(1, quote).unwrap();
+
+        let mirrored = layout.mirrored();
+        assert_eq!(mirrored.layout_str(&CON), "poiuytrewq;lkjhgfdsa/.,mnbvcxz");
+        assert_eq!(mirrored.thumbs, [quote, NO_THUMB]);
+        assert_eq!(mirrored.char_to_finger[quote as usize], 8);
+        assert_eq!(
+            mirrored.char_to_finger[CON.to_single_lossy('q') as usize],
+            7
+        );
+        assert_eq!(mirrored.mirrored(), layout);
+    }
+
+    #[test]
+    fn dedup_columns() {
+        let qwerty_bytes = CON.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let qwerty = FastLayout::try_from(qwerty_bytes.as_slice()).unwrap();
+        let mut swapped = qwerty.clone();
+        unsafe { swapped.swap_cols_no_bounds(1, 9) };
+        let mut other = qwerty.clone();
+        other.swap(10, 11);
+        let mut flipped = qwerty.clone();
+        flipped.swap(0, 10);
+
+        assert!(qwerty.same_columns(&qwerty.mirrored()));
+        assert!(qwerty.same_columns(&swapped));
+        assert!(!qwerty.same_columns(&other));
+        assert!(!qwerty.same_columns(&flipped));
+
+        let mut layouts = vec![
+            qwerty.clone(),
+            qwerty.mirrored(),
+            other.clone(),
+            swapped,
+            qwerty.clone(),
+            other.mirrored(),
+        ];
+        super::dedup_columns(&mut layouts);
+        assert_eq!(layouts, vec![qwerty, other]);
+    }
+
     // #[test]
     // fn random_layouts() {
     // 	use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
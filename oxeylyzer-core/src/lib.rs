@@ -1,13 +1,37 @@
+pub mod baseline;
+pub mod bigram_penalties;
+pub mod corpus_stats;
+pub mod csv;
+pub mod data_source;
+pub mod error;
+pub mod external_metric;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fit_weights;
 pub mod generate;
 // pub mod generate_annealing;
+pub mod graphemes;
+pub mod keymap;
 pub mod language_data;
 pub mod languages_cfg;
 pub mod layout;
 pub mod load_text;
+pub mod metadata;
+pub mod one_handed;
+pub mod pin_templates;
+pub mod replay;
+pub mod schema;
+pub mod sensitivity;
+pub mod score_cache;
+pub mod snapshot;
+pub mod svg;
+pub mod text_reader;
 pub mod translation;
+pub mod trigram_cache;
 pub mod trigram_patterns;
 pub mod utility;
 pub mod weights;
 
 pub use rayon;
+pub use regex;
 pub use serde;
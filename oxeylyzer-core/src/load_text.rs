@@ -1,28 +1,32 @@
+use crate::graphemes::{code_points, GraphemeCounts};
+use crate::text_reader::TextChunks;
 use crate::translation::Translator;
 
-use std::fs::{read_dir, File};
+use std::fs::read_dir;
 use std::iter::FromIterator;
 use std::path::PathBuf;
 use std::time::Instant;
 
 use anyhow::Result;
-use file_chunker::FileChunker;
 use fxhash::FxHashMap as HashMap;
 use indexmap::IndexMap;
-use rayon::iter::{IntoParallelRefIterator, ParallelBridge, ParallelIterator};
+use nanorand::{tls_rng, Rng};
+use rayon::iter::{ParallelBridge, ParallelIterator};
 use serde::{Deserialize, Serialize};
-use smartstring::{LazyCompact, SmartString, SmartStringMode};
+use smartstring::{LazyCompact, SmartString};
 
-const FOUR_MB: u64 = 1024 * 1024 * 4;
+const SAMPLE_SENTENCES: usize = 100;
+/// Graphemes of several characters that are shown after loading a corpus that has them.
+const REPORTED_GRAPHEMES: usize = 10;
 
-pub fn load_raw(language: &str) {
-    load_data(language, Translator::raw(true)).unwrap();
+pub fn load_raw(language: &str, max_bytes: Option<u64>) {
+    load_data(language, Translator::raw(true), max_bytes).unwrap();
 }
 
 #[allow(dead_code)]
 pub(crate) fn load_default(language: &str) {
     let translator = Translator::language_or_raw(language);
-    if let Err(error) = load_data(language, translator) {
+    if let Err(error) = load_data(language, translator, None) {
         println!("{language} failed to update: '{error}'");
     }
 }
@@ -45,66 +49,56 @@ pub(crate) fn load_all_default() -> Result<()> {
     Ok(())
 }
 
-pub fn load_data(language: &str, translator: Translator) -> Result<()> {
+/// Counts the ngrams in every file in `static/text/<language>` and saves them as language data.
+/// Files are read in chunks, so the corpus never has to fit in memory. With `max_bytes`, only
+/// that many bytes of it are read, which is much quicker for trying out corpus rules.
+pub fn load_data(language: &str, translator: Translator, max_bytes: Option<u64>) -> Result<()> {
     let start_total = Instant::now();
     let is_raw = translator.is_raw;
 
-    let chunkers = read_dir(format!("static/text/{language}"))?
-        .par_bridge()
+    let mut paths = read_dir(format!("static/text/{language}"))?
         .filter_map(Result::ok)
-        .flat_map(|dir_entry| File::open(dir_entry.path()))
-        .map(|f| {
-            let len = f.metadata().unwrap().len() + 1;
-            let count = (len / FOUR_MB).max(1);
-            (FileChunker::new(&f).unwrap(), count as usize)
-        })
-        .collect::<Vec<_>>();
-
-    let chunkers_time = Instant::now();
-    println!(
-        "Prepared text files in {}ms",
-        (chunkers_time - start_total).as_millis()
-    );
-
-    let strings = chunkers
-        .par_iter()
-        .flat_map(|(chunker, count)| chunker.chunks(*count, Some(' ')).unwrap())
-        .map(|chunk| {
-            std::str::from_utf8(chunk).expect(
-                "one of the files provided is not encoded as utf-8.\
-                Make sure all files in the directory are valid utf-8.",
-            )
-        })
-        .map(|s| {
-            let mut last_chars = SmartString::<LazyCompact>::new();
-            let mut inter = [' '; 5];
-            s.chars()
-                .rev()
-                .take(5)
-                .enumerate()
-                .for_each(|(i, c)| unsafe { *inter.get_unchecked_mut(4 - i) = c });
-
-            inter.into_iter().for_each(|c| last_chars.push(c));
-            last_chars.push_str("     ");
-
-            (s, last_chars)
+        .map(|dir_entry| dir_entry.path())
+        .filter(|path| path.is_file())
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+
+    // every chunk starts with the last 4 characters of the one before, so no quingram is lost
+    let chunks = TextChunks::new(paths, 4, max_bytes).with_progress();
+    let mut sampler = SentenceSampler::new(SAMPLE_SENTENCES);
+
+    let (quingrams, graphemes) = chunks
+        .inspect(|chunk| {
+            if let Ok(chunk) = chunk {
+                sampler.add(chunk.body());
+            }
         })
-        .collect::<Vec<_>>();
+        .par_bridge()
+        .try_fold(
+            || (TextNgrams::default(), GraphemeCounts::default()),
+            |(mut ngrams, mut graphemes), chunk| {
+                let chunk = chunk?;
+                ngrams.add_str(&chunk.text);
+                graphemes.add(chunk.body());
+                Ok::<_, std::io::Error>((ngrams, graphemes))
+            },
+        )
+        .try_reduce(
+            || (TextNgrams::default(), GraphemeCounts::default()),
+            |(n1, g1), (n2, g2)| Ok((n1.combine_with(n2), g1.combine_with(g2))),
+        )?;
 
     println!(
-        "Converted to utf8 in {}ms",
-        (Instant::now() - chunkers_time).as_millis()
+        "Counted {} unique ngrams in {}ms",
+        quingrams.ngrams.len(),
+        (Instant::now() - start_total).as_millis()
     );
 
-    let quingrams = strings
-        .par_iter()
-        .map(|(s, last)| TextNgrams::from_str_last(s, &last))
-        .reduce(
-            || TextNgrams::default(),
-            |accum, new| accum.combine_with(new),
-        );
+    print_graphemes(&graphemes);
 
-    TextData::from((quingrams, language, translator)).save(is_raw)?;
+    let mut data = TextData::from((quingrams, language, translator));
+    data.samples = sampler.into_samples();
+    data.save(is_raw)?;
     println!(
         "loading {} took {}ms",
         language,
@@ -114,37 +108,95 @@ pub fn load_data(language: &str, translator: Translator) -> Result<()> {
     Ok(())
 }
 
-#[derive(Default, Debug)]
-pub struct TextNgrams<'a, const N: usize> {
-    pub ngrams: HashMap<&'a str, usize>,
+/// Tells which graphemes of several characters are in the corpus, because they are split into
+/// their characters instead of being counted as one key.
+fn print_graphemes(graphemes: &GraphemeCounts) {
+    if graphemes.counts.is_empty() {
+        return;
+    }
+    println!(
+        "{} graphemes are several characters, which can't be a single key. The most common are:",
+        graphemes.counts.len()
+    );
+    for (grapheme, freq) in graphemes.most_common(REPORTED_GRAPHEMES) {
+        println!("  {grapheme} ({}): {:.4}%", code_points(grapheme), freq * 100.0);
+    }
 }
 
-impl<'a, const N: usize> TextNgrams<'a, N> {
-    fn from_str_last<M: SmartStringMode>(s: &'a str, last: &'a SmartString<M>) -> Self {
-        let mut ngrams = HashMap::default();
-        let it1 = s.char_indices().map(|(i, _)| i);
-        let it2 = s.char_indices().map(|(i, _)| i).skip(N);
-        it1.zip(it2).map(|(i1, i2)| &s[i1..i2]).for_each(|ngram| {
-            ngrams.entry(ngram).and_modify(|f| *f += 1).or_insert(1);
-        });
+/// Keeps `amount` random sentences of a readable length out of all the text it is given, using
+/// reservoir sampling so the corpus never has to be collected into sentences all at once.
+pub(crate) struct SentenceSampler {
+    amount: usize,
+    seen: usize,
+    samples: Vec<String>,
+}
 
-        let it1 = last.char_indices().map(|(i, _)| i);
-        let it2 = last.char_indices().map(|(i, _)| i).skip(N);
-        it1.zip(it2)
-            .map(|(i1, i2)| &last[i1..i2])
-            .for_each(|ngram| {
-                ngrams.entry(ngram).and_modify(|f| *f += 1).or_insert(1);
+impl SentenceSampler {
+    pub(crate) fn new(amount: usize) -> Self {
+        Self {
+            amount,
+            seen: 0,
+            samples: Vec::with_capacity(amount),
+        }
+    }
+
+    pub(crate) fn add(&mut self, text: &str) {
+        let mut rng = tls_rng();
+
+        text.split_inclusive(['.', '!', '?', '\n'])
+            .map(str::trim)
+            .filter(|s| (20..=200).contains(&s.chars().count()))
+            .for_each(|sentence| {
+                if self.seen < self.amount {
+                    self.samples.push(sentence.to_string());
+                } else {
+                    let j = rng.generate_range(0..=self.seen);
+                    if j < self.amount {
+                        self.samples[j] = sentence.to_string();
+                    }
+                }
+                self.seen += 1;
             });
+    }
 
-        Self { ngrams }
+    pub(crate) fn into_samples(self) -> Vec<String> {
+        self.samples
     }
 }
 
-impl<'a, const N: usize> TextNgrams<'a, N> {
-    fn combine_with(mut self, rhs: Self) -> Self {
-        for (trigram, freq) in rhs.ngrams.into_iter() {
+/// Counts of every window of `N` characters in a text.
+#[derive(Default, Debug)]
+pub struct TextNgrams<const N: usize> {
+    pub ngrams: HashMap<SmartString<LazyCompact>, usize>,
+}
+
+impl<const N: usize> TextNgrams<N> {
+    /// Counts every window of `N` characters that fits in `s`.
+    fn add_str(&mut self, s: &str) {
+        let starts = s.char_indices().map(|(i, _)| i);
+        let ends = s
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(s.len()))
+            .skip(N);
+
+        for ngram in starts.zip(ends).map(|(i1, i2)| &s[i1..i2]) {
+            match self.ngrams.get_mut(ngram) {
+                Some(freq) => *freq += 1,
+                None => {
+                    self.ngrams.insert(ngram.into(), 1);
+                }
+            }
+        }
+    }
+
+    fn combine_with(mut self, mut rhs: Self) -> Self {
+        if self.ngrams.len() < rhs.ngrams.len() {
+            std::mem::swap(&mut self, &mut rhs);
+        }
+        for (ngram, freq) in rhs.ngrams.into_iter() {
             self.ngrams
-                .entry(trigram)
+                .entry(ngram)
                 .and_modify(|f| *f += freq)
                 .or_insert(freq);
         }
@@ -162,6 +214,17 @@ pub struct TextData {
     skipgrams2: IndexMap<SmartString<LazyCompact>, f64>,
     skipgrams3: IndexMap<SmartString<LazyCompact>, f64>,
     trigrams: IndexMap<SmartString<LazyCompact>, f64>,
+    /// Trigrams with a single space in them, which `trigrams` leaves out. They are only used to
+    /// show how typing across word boundaries goes with space on a thumb.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    space_trigrams: IndexMap<SmartString<LazyCompact>, f64>,
+    /// Sequences of 4 characters without spaces, to tell apart what trigrams can't, like three
+    /// keys rolled on one hand that are followed by the other hand or turn around.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    quadgrams: IndexMap<SmartString<LazyCompact>, f64>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    samples: Vec<String>,
 
     #[serde(skip)]
     char_sum: f64,
@@ -175,6 +238,10 @@ pub struct TextData {
     skipgram3_sum: f64,
     #[serde(skip)]
     trigram_sum: f64,
+    #[serde(skip)]
+    space_trigram_sum: f64,
+    #[serde(skip)]
+    quadgram_sum: f64,
 }
 
 impl std::fmt::Display for TextData {
@@ -209,7 +276,7 @@ impl TextData {
     }
 }
 
-impl<'a> From<(TextNgrams<'a, 5>, &str, Translator)> for TextData {
+impl From<(TextNgrams<5>, &str, Translator)> for TextData {
     fn from((ngrams, language, translator): (TextNgrams<5>, &str, Translator)) -> Self {
         let mut res = TextData::new(language);
 
@@ -218,7 +285,7 @@ impl<'a> From<(TextNgrams<'a, 5>, &str, Translator)> for TextData {
             if first != ' ' {
                 if let Some(first_t) = translator.table.get(&first) {
                     if first_t != " " {
-                        let mut trans = translator.translate(ngram);
+                        let mut trans = translator.translate(&ngram);
                         match trans.chars().count() {
                             5.. => {
                                 trans.push(' ');
@@ -243,6 +310,9 @@ impl<'a> From<(TextNgrams<'a, 5>, &str, Translator)> for TextData {
                         }
                     }
                 }
+            } else {
+                // the rest of an ngram starting with a space is counted by the ngrams after it
+                res.from_n_subsequent::<3>(&translator.translate(&ngram), freq as f64);
             }
         }
 
@@ -264,6 +334,12 @@ impl<'a> From<(TextNgrams<'a, 5>, &str, Translator)> for TextData {
         res.trigrams
             .iter_mut()
             .for_each(|(_, f)| *f /= res.trigram_sum);
+        res.space_trigrams
+            .iter_mut()
+            .for_each(|(_, f)| *f /= res.space_trigram_sum);
+        res.quadgrams
+            .iter_mut()
+            .for_each(|(_, f)| *f /= res.quadgram_sum);
 
         res.characters
             .sort_by(|_, f1, _, f2| f2.partial_cmp(f1).unwrap());
@@ -277,6 +353,10 @@ impl<'a> From<(TextNgrams<'a, 5>, &str, Translator)> for TextData {
             .sort_by(|_, f1, _, f2| f2.partial_cmp(f1).unwrap());
         res.trigrams
             .sort_by(|_, f1, _, f2| f2.partial_cmp(f1).unwrap());
+        res.space_trigrams
+            .sort_by(|_, f1, _, f2| f2.partial_cmp(f1).unwrap());
+        res.quadgrams
+            .sort_by(|_, f1, _, f2| f2.partial_cmp(f1).unwrap());
 
         res
     }
@@ -300,17 +380,26 @@ impl TextData {
 
                 // c1 and c3 for skipgrams
                 match chars.next() {
+                    Some(' ') if N > 2 && c2 != ' ' => {
+                        self.add_space_trigram([c1, c2, ' '], freq);
+                    },
                     Some(c3) if N > 2 && c3 != ' ' => {
                         self.add_skipgram([c1, c3], freq);
 
                         if c2 != ' ' {
                             self.add_trigram([c1, c2, c3], freq);
+                        } else {
+                            self.add_space_trigram([c1, ' ', c3], freq);
                         }
 
                         match chars.next() {
                             Some(c4) if N > 3 && c4 != ' ' => {
                                 self.add_skipgram2([c1, c4], freq);
 
+                                if c2 != ' ' {
+                                    self.add_quadgram([c1, c2, c3, c4], freq);
+                                }
+
                                 match chars.next() {
                                     Some(c5) if N > 4 && c5 != ' ' => {
                                         self.add_skipgram3([c1, c5], freq);
@@ -324,6 +413,14 @@ impl TextData {
                     _ => {}
                 }
             },
+            // a word starting after a space, or after shift
+            Some(' ') if N > 2 => {
+                if let (Some(c2), Some(c3)) = (chars.next(), chars.next()) {
+                    if c2 != ' ' && c3 != ' ' {
+                        self.add_space_trigram([' ', c2, c3], freq);
+                    }
+                }
+            },
             _ => {},
         }
     }
@@ -376,6 +473,22 @@ impl TextData {
         self.trigram_sum += freq;
     }
 
+    pub(crate) fn add_space_trigram(&mut self, trigram: [char; 3], freq: f64) {
+        self.space_trigrams
+            .entry(SmartString::from_iter(trigram))
+            .and_modify(|e| *e += freq)
+            .or_insert(freq);
+        self.space_trigram_sum += freq;
+    }
+
+    pub(crate) fn add_quadgram(&mut self, quadgram: [char; 4], freq: f64) {
+        self.quadgrams
+            .entry(SmartString::from_iter(quadgram))
+            .and_modify(|e| *e += freq)
+            .or_insert(freq);
+        self.quadgram_sum += freq;
+    }
+
     fn save(&self, pass: bool) -> Result<()> {
         use std::fs::OpenOptions;
         use std::io::Write;
@@ -411,12 +524,12 @@ mod tests {
     #[test]
     fn from_textngrams() {
         let mut ngrams = TextNgrams::<5>::default();
-        ngrams.ngrams.insert("Amogu", 1);
-        ngrams.ngrams.insert("mogus", 1);
-        ngrams.ngrams.insert("ogus ", 1);
-        ngrams.ngrams.insert("gus  ", 1);
-        ngrams.ngrams.insert("us   ", 1);
-        ngrams.ngrams.insert("s    ", 1);
+        ngrams.ngrams.insert("Amogu".into(), 1);
+        ngrams.ngrams.insert("mogus".into(), 1);
+        ngrams.ngrams.insert("ogus ".into(), 1);
+        ngrams.ngrams.insert("gus  ".into(), 1);
+        ngrams.ngrams.insert("us   ".into(), 1);
+        ngrams.ngrams.insert("s    ".into(), 1);
         let translator = Translator::new().letters_to_lowercase("amogus").build();
         let data = TextData::from((ngrams, "among", translator));
 
@@ -426,6 +539,14 @@ mod tests {
         assert_eq!(data.skipgram2_sum, 3.0,);
         assert_eq!(data.skipgram3_sum, 2.0,);
         assert_eq!(data.trigram_sum, data.skipgram_sum);
+        assert_eq!(data.space_trigram_sum, 2.0);
+        let mut space_trigrams = data.space_trigrams.keys().map(|t| t.as_str()).collect::<Vec<_>>();
+        space_trigrams.sort();
+        assert_eq!(space_trigrams, vec![" am", "us "]);
+        assert_eq!(data.quadgram_sum, data.skipgram2_sum);
+        let mut quadgrams = data.quadgrams.keys().map(|q| q.as_str()).collect::<Vec<_>>();
+        quadgrams.sort();
+        assert_eq!(quadgrams, vec!["amog", "mogu", "ogus"]);
 
         for (_, f) in data.characters {
             assert!(f.approx_eq_dbg(1.0 / 6.0, 15));
@@ -447,6 +568,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn ngrams_across_chunks() {
+        let text = "the quick brown föx jumps";
+        let mut whole = TextNgrams::<5>::default();
+        whole.add_str(&format!("{text}    "));
+        assert_eq!(whole.ngrams.values().sum::<usize>(), text.chars().count());
+
+        let path = std::env::temp_dir().join("rusylyzer_ngrams_across_chunks.txt");
+        std::fs::write(&path, text).unwrap();
+        let mut chunked = TextNgrams::<5>::default();
+        for chunk in TextChunks::new(vec![path.clone()], 4, None).with_chunk_size(3) {
+            chunked.add_str(&chunk.unwrap().text);
+        }
+        assert_eq!(chunked.ngrams, whole.ngrams);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn sample_sentences_length() {
+        let text = "Short. This sentence is long enough to be sampled! So is this one, surely? \
+            Nope.\nThe last line of this text is a sentence too";
+
+        let sample = |texts: &[&str], amount: usize| {
+            let mut sampler = SentenceSampler::new(amount);
+            for text in texts {
+                sampler.add(text);
+            }
+            sampler.into_samples()
+        };
+
+        let samples = sample(&[text], 10);
+        assert_eq!(samples.len(), 3);
+        assert!(samples.contains(&"This sentence is long enough to be sampled!".to_string()));
+        assert!(samples.contains(&"The last line of this text is a sentence too".to_string()));
+
+        let samples = sample(&[text, text, text], 2);
+        assert_eq!(samples.len(), 2);
+    }
+
     #[test]
     fn load_language_data() {
         use language_data::*;
@@ -0,0 +1,465 @@
+use serde::Serialize;
+
+use crate::generate::serialize_named;
+use crate::layout::FastLayout;
+use crate::utility::ConvertU8;
+use crate::weights::{Weights, DEFAULT_PROFILE};
+
+/// Optional information about a layout, read from `# key: value` comments in its `.kb` file.
+/// Comments that don't start with a known key are ignored, and `notes` can be used more than once.
+/// Serializes to the `layout_metadata` schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LayoutMetadata {
+    pub author: Option<String>,
+    pub link: Option<String>,
+    /// Keyboard the layout was made for, like `ortho` or `ansi angle`.
+    pub geometry: Option<String>,
+    /// Layout this one was improved from, which `save` writes for generated layouts.
+    pub parent: Option<String>,
+    /// Characters that stay where they are when the layout is improved.
+    pub pins: Vec<char>,
+    /// Labels to group layouts by, like `ergo` or `rolls`, which `tag` adds.
+    pub tags: Vec<String>,
+    /// Other names the layout can be found by, like `cdh` for `colemak_dh`.
+    pub aliases: Vec<String>,
+    /// Layout this one is a variant of, like `colemak` for `colemak_dh`, so variants can be ranked
+    /// and summarized together.
+    pub family: Option<String>,
+    pub notes: Vec<String>,
+    /// Weights and language the layout was saved with.
+    pub config: ConfigSnapshot,
+}
+
+/// The part of the config that decides how a layout scores, which `save` writes as
+/// `# language:`, `# trigram_precision:`, `# profile:` and `# weights:` comments so a score can be
+/// reproduced after the weights change.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConfigSnapshot {
+    pub language: Option<String>,
+    pub trigram_precision: Option<usize>,
+    /// Weight profile, or `None` for the default one.
+    pub profile: Option<String>,
+    /// Every weight by the name `set` uses, see `Weights::values`.
+    #[serde(serialize_with = "serialize_named")]
+    pub weights: Vec<(String, f64)>,
+}
+
+impl ConfigSnapshot {
+    pub fn new(
+        language: &str,
+        trigram_precision: usize,
+        profile: Option<&str>,
+        weights: &Weights,
+    ) -> Self {
+        Self {
+            language: Some(language.to_string()),
+            trigram_precision: Some(trigram_precision),
+            profile: profile.map(str::to_string),
+            weights: weights.values(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The snapshot as comments for the top of a `.kb` file, one per line.
+    pub fn comments(&self) -> String {
+        let mut res = String::new();
+        if let Some(language) = &self.language {
+            res.push_str(&format!("# language: {language}\n"));
+        }
+        if let Some(precision) = self.trigram_precision {
+            res.push_str(&format!("# trigram_precision: {precision}\n"));
+        }
+        if let Some(profile) = &self.profile {
+            res.push_str(&format!("# profile: {profile}\n"));
+        }
+        if !self.weights.is_empty() {
+            let weights = self
+                .weights
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect::<Vec<_>>();
+            res.push_str(&format!("# weights: {}\n", weights.join(" ")));
+        }
+        res
+    }
+
+    /// Weights that are different in `other`, with their value in `self` and in `other`. Weights
+    /// only one of them has are left out.
+    pub fn weight_changes(&self, other: &Self) -> Vec<(String, f64, f64)> {
+        self.weights
+            .iter()
+            .filter_map(|(name, value)| {
+                let (_, other_value) = other.weights.iter().find(|(n, _)| n == name)?;
+                (value != other_value).then(|| (name.clone(), *value, *other_value))
+            })
+            .collect()
+    }
+
+    fn parse_weights(value: &str) -> Vec<(String, f64)> {
+        value
+            .split_whitespace()
+            .filter_map(|weight| {
+                let (name, value) = weight.split_once('=')?;
+                Some((name.to_string(), value.parse().ok()?))
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for ConfigSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(language) = &self.language {
+            writeln!(f, "Language: {language}")?;
+        }
+        if let Some(precision) = self.trigram_precision {
+            writeln!(f, "Trigram precision: {precision}")?;
+        }
+        writeln!(
+            f,
+            "Profile: {}",
+            self.profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+        )?;
+        for (name, value) in self.weights.iter() {
+            writeln!(f, "  {name:<24}{value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Lowercase key and trimmed value of a `# key: value` comment.
+fn key_value(line: &str) -> Option<(String, &str)> {
+    let comment = line.trim_start().strip_prefix('#')?;
+    let (key, value) = comment.split_once(':')?;
+    Some((key.trim().to_lowercase(), value.trim()))
+}
+
+fn is_tags_key(key: &str) -> bool {
+    key == "tags" || key == "tag"
+}
+
+/// `name` in lowercase with `-` and spaces as `_`, so `Colemak-DH` and `colemak dh` are both
+/// `colemak_dh`.
+fn normalize_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| match c {
+            '-' | ' ' => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+/// Whether `name1` and `name2` are the same layout name apart from case, `-`, `_` and spaces.
+pub fn same_name(name1: &str, name2: &str) -> bool {
+    normalize_name(name1) == normalize_name(name2)
+}
+
+/// Amount of characters that have to be added, removed or replaced to turn `s1` into `s2`.
+fn edit_distance(s1: &str, s2: &str) -> usize {
+    let s2 = s2.chars().collect::<Vec<_>>();
+    let mut row = (0..=s2.len()).collect::<Vec<_>>();
+
+    for (i, c1) in s1.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &c2) in s2.iter().enumerate() {
+            let replaced = diagonal + usize::from(c1 != c2);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[s2.len()]
+}
+
+/// Names of `names` that look like a misspelling of `name`, closest first, for a did-you-mean
+/// when there is no layout called `name`. A third of the characters may be different.
+pub fn similar_names<'a, I>(name: &str, names: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let name = normalize_name(name);
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    let mut similar = names
+        .into_iter()
+        .map(|n| (edit_distance(&name, &normalize_name(n)), n))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .collect::<Vec<_>>();
+    similar.sort_by_key(|&(distance, _)| distance);
+    similar.into_iter().map(|(_, n)| n).collect()
+}
+
+impl LayoutMetadata {
+    pub fn parse(layout_str: &str) -> Self {
+        let mut res = Self::default();
+
+        for line in layout_str.lines() {
+            let (key, value) = match key_value(line) {
+                Some((key, value)) if !value.is_empty() => (key, value.to_string()),
+                _ => continue,
+            };
+
+            match key.as_str() {
+                "author" => res.author = Some(value),
+                "link" | "source" => res.link = Some(value),
+                "geometry" | "keyboard" => res.geometry = Some(value),
+                "parent" => res.parent = Some(value),
+                "family" => res.family = Some(value),
+                "pins" | "pinned" => {
+                    res.pins = value.chars().filter(|c| !c.is_whitespace()).collect()
+                }
+                "notes" | "note" => res.notes.push(value),
+                "aliases" | "alias" => res.aliases.extend(
+                    value
+                        .split(|c: char| c.is_whitespace() || c == ',')
+                        .filter(|alias| !alias.is_empty())
+                        .map(str::to_string),
+                ),
+                "language" => res.config.language = Some(value),
+                "trigram_precision" => res.config.trigram_precision = value.parse().ok(),
+                "profile" => res.config.profile = Some(value),
+                "weights" => res.config.weights = ConfigSnapshot::parse_weights(&value),
+                key if is_tags_key(key) => {
+                    for tag in value.split(|c: char| c.is_whitespace() || c == ',') {
+                        res.add_tag(tag);
+                    }
+                }
+                _ => {}
+            }
+        }
+        res
+    }
+
+    /// Name of the family of the layout called `name`, which is its own name when it has none.
+    pub fn family_of<'a>(metadata: Option<&'a Self>, name: &'a str) -> &'a str {
+        metadata
+            .and_then(|metadata| metadata.family.as_deref())
+            .unwrap_or(name)
+    }
+
+    /// Whether `name` is one of the aliases of the layout, see [`same_name`].
+    pub fn has_alias(&self, name: &str) -> bool {
+        self.aliases.iter().any(|alias| same_name(alias, name))
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// Adds `tag` unless it is empty or the layout already has it. Returns whether it was added.
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        let added = !tag.is_empty() && !self.has_tag(tag);
+        if added {
+            self.tags.push(tag.to_string());
+        }
+        added
+    }
+
+    /// Removes `tag`, returning whether the layout had it.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let len = self.tags.len();
+        self.tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+        self.tags.len() != len
+    }
+
+    /// `layout_str` with its `# tags:` comment replaced by the tags of `self`, or removed when there
+    /// are none. A layout that had no tags yet gets the comment at the top.
+    pub fn write_tags(&self, layout_str: &str) -> String {
+        let tags_line = (!self.tags.is_empty()).then(|| format!("# tags: {}", self.tags.join(" ")));
+        let mut lines = Vec::new();
+        let mut replaced = false;
+
+        for line in layout_str.lines() {
+            match key_value(line) {
+                Some((key, _)) if is_tags_key(&key) => {
+                    if !replaced {
+                        lines.extend(tags_line.clone());
+                        replaced = true;
+                    }
+                }
+                _ => lines.push(line.to_string()),
+            }
+        }
+        if !replaced {
+            if let Some(tags_line) = tags_line {
+                lines.insert(0, tags_line);
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Positions of the pinned characters on `layout`. Pins that aren't on it are left out.
+    pub fn pin_positions(&self, layout: &FastLayout, con: &ConvertU8) -> Vec<usize> {
+        self.pins
+            .iter()
+            .filter_map(|&c| {
+                let u = con.to_single_lossy(c);
+                layout.matrix.iter().position(|&k| k == u)
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for LayoutMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = [
+            ("Author", &self.author),
+            ("Link", &self.link),
+            ("Geometry", &self.geometry),
+            ("Parent", &self.parent),
+            ("Family", &self.family),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                writeln!(f, "{name}: {value}")?;
+            }
+        }
+        if !self.pins.is_empty() {
+            writeln!(f, "Pins: {}", self.pins.iter().collect::<String>())?;
+        }
+        if !self.tags.is_empty() {
+            writeln!(f, "Tags: {}", self.tags.join(" "))?;
+        }
+        if !self.aliases.is_empty() {
+            writeln!(f, "Aliases: {}", self.aliases.join(" "))?;
+        }
+        for note in self.notes.iter() {
+            writeln!(f, "Notes: {note}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_metadata() {
+        let layout_str = concat!(
+            "# author: someone\n",
+            "# Source: https://example.com/layout\n",
+            "# geometry: ortho\n",
+            "# family: sturdy\n",
+            "# pins: e a o\n",
+            "# notes: made for english\n",
+            "# notes: and a bit of code\n",
+            "# Sfb -0.310%, Dsfb +0.120%\n",
+            "# unknown: ignored\n",
+            "v m l c p  q z u o ,\n",
+            "s t r d y  f n e a i\n",
+            "x k j g w  b h ; ' .\n"
+        );
+        let metadata = LayoutMetadata::parse(layout_str);
+
+        assert_eq!(metadata.author.as_deref(), Some("someone"));
+        assert_eq!(metadata.link.as_deref(), Some("https://example.com/layout"));
+        assert_eq!(metadata.geometry.as_deref(), Some("ortho"));
+        assert_eq!(metadata.parent, None);
+        assert_eq!(metadata.family.as_deref(), Some("sturdy"));
+        assert_eq!(LayoutMetadata::family_of(Some(&metadata), "semi"), "sturdy");
+        assert_eq!(LayoutMetadata::family_of(None, "semi"), "semi");
+        assert_eq!(metadata.pins, vec!['e', 'a', 'o']);
+        assert_eq!(
+            metadata.notes,
+            vec!["made for english", "and a bit of code"]
+        );
+        assert!(metadata
+            .to_string()
+            .contains("Link: https://example.com/layout\n"));
+        assert!(metadata.to_string().contains("Family: sturdy\n"));
+
+        assert!(LayoutMetadata::parse("a b c\n# parent:\n").is_empty());
+    }
+
+    #[test]
+    fn tags() {
+        let layout_str = "# author: someone\n# tags: ergo, Rolls\n# tag: ergo\na b c\n";
+        let mut metadata = LayoutMetadata::parse(layout_str);
+
+        assert_eq!(metadata.tags, vec!["ergo", "Rolls"]);
+        assert!(metadata.has_tag("rolls"));
+        assert!(!metadata.add_tag("ERGO"));
+        assert!(metadata.add_tag("mine"));
+        assert!(metadata.remove_tag("rolls"));
+        assert!(!metadata.remove_tag("rolls"));
+
+        let tagged = metadata.write_tags(layout_str);
+        assert_eq!(tagged, "# author: someone\n# tags: ergo mine\na b c\n");
+        assert_eq!(LayoutMetadata::parse(&tagged), metadata);
+
+        let untagged = LayoutMetadata::default().write_tags(&tagged);
+        assert_eq!(untagged, "# author: someone\na b c\n");
+        assert_eq!(
+            metadata.write_tags(&untagged),
+            "# tags: ergo mine\n# author: someone\na b c\n"
+        );
+    }
+
+    #[test]
+    fn names() {
+        let metadata = LayoutMetadata::parse("# aliases: cdh, colemak-mod-dh\n# alias: dh\n");
+        assert_eq!(metadata.aliases, vec!["cdh", "colemak-mod-dh", "dh"]);
+        assert!(metadata.has_alias("Colemak Mod DH"));
+        assert!(metadata.has_alias("dh"));
+        assert!(!metadata.has_alias("colemak_dh"));
+        assert!(metadata
+            .to_string()
+            .contains("Aliases: cdh colemak-mod-dh dh\n"));
+
+        assert!(same_name("Colemak-DH", "colemak_dh"));
+        assert!(!same_name("colemakdh", "colemak_dh"));
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        let names = ["colemak", "colemak_dh", "colemak_dhv", "qwerty"];
+        assert_eq!(
+            similar_names("colmak-dh", names),
+            vec!["colemak_dh", "colemak_dhv"]
+        );
+        assert_eq!(similar_names("qwrety", names), vec!["qwerty"]);
+        assert!(similar_names("dvorak", names).is_empty());
+    }
+
+    #[test]
+    fn config_snapshot() {
+        let weights = crate::weights::Config::default().weights;
+        let snapshot = ConfigSnapshot::new("english", 1000, Some("prose"), &weights);
+        assert!(snapshot
+            .weights
+            .iter()
+            .any(|(name, value)| name == "fspeed" && *value == weights.fspeed));
+
+        let layout_str = format!("# parent: qwerty\n{}a b c\n", snapshot.comments());
+        let metadata = LayoutMetadata::parse(&layout_str);
+        assert_eq!(metadata.parent.as_deref(), Some("qwerty"));
+        assert_eq!(metadata.config, snapshot);
+        assert!(snapshot.weight_changes(&metadata.config).is_empty());
+
+        let mut changed = weights.clone();
+        changed.fspeed += 1.0;
+        let current = ConfigSnapshot::new("english", 1000, None, &changed);
+        assert_eq!(
+            snapshot.weight_changes(&current),
+            vec![("fspeed".to_string(), weights.fspeed, weights.fspeed + 1.0)]
+        );
+        assert!(LayoutMetadata::parse("a b c\n").config.is_empty());
+    }
+
+    #[test]
+    fn pin_positions() {
+        let con = ConvertU8::with_chars("abcdefghijklmnopqrstuvwxyz',.;");
+        let bytes = con.to_lossy("qwertyuiopasdfghjkl;zxcvbnm,.'".chars());
+        let layout = FastLayout::try_from(bytes.as_slice()).unwrap();
+        let metadata = LayoutMetadata::parse("# pins: ea/\n");
+
+        assert_eq!(metadata.pin_positions(&layout, &con), vec![2, 10]);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::generate::LayoutGeneration;
+use crate::layout::FastLayout;
+use crate::trigram_patterns::{TrigramPattern, ONE_HAND_COMBINATIONS};
+
+use anyhow::Result;
+
+/// Finger for every position on the layout when it is typed with one hand, going from pinky (0)
+/// to thumb (4).
+pub type FingerMap = [usize; 30];
+
+pub const DEFAULT_FINGER_MAP: FingerMap = [
+    0, 1, 2, 3, 3, 3, 3, 2, 1, 0, 0, 1, 2, 3, 3, 3, 3, 2, 1, 0, 0, 1, 2, 3, 3, 3, 3, 2, 1, 0,
+];
+
+pub fn parse_finger_map(map: &str) -> Result<FingerMap> {
+    let fingers = map
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c.to_digit(10) {
+            Some(f) if f <= 4 => Ok(f as usize),
+            _ => Err(anyhow::format_err!(
+                "'{c}' is not a valid finger, use 0 (pinky) up to 4 (thumb)"
+            )),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let len = fingers.len();
+    fingers.try_into().map_err(|_| {
+        anyhow::format_err!("a finger map needs exactly 30 fingers, but {len} were provided")
+    })
+}
+
+#[derive(Clone, Default)]
+pub struct OneHandedStats {
+    pub sfb: f64,
+    pub dsfb: f64,
+    pub adjacent: f64,
+    pub inrolls: f64,
+    pub outrolls: f64,
+    pub redirects: f64,
+    pub redirects_sfs: f64,
+    pub bad_redirects: f64,
+    pub bad_redirects_sfs: f64,
+    pub bad_sfbs: f64,
+    pub sfts: f64,
+    pub finger_usage: [f64; 5],
+}
+
+impl std::fmt::Display for OneHandedStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let usage = self
+            .finger_usage
+            .iter()
+            .map(|u| format!("{:.2}%", u * 100.0))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        write!(
+            f,
+            "Sfb:  {:.3}%\n\
+			Dsfb: {:.3}%\n\
+			Adjacent finger bigrams: {:.3}%\n\
+			Finger usage: [{}]\n\n\
+			Inrolls: {:.3}%\n\
+			Outrolls: {:.3}%\n\
+			Total Rolls: {:.3}%\n\n\
+			Redirects: {:.3}%\n\
+			Redirects Sfs: {:.3}%\n\
+			Bad Redirects: {:.3}%\n\
+			Bad Redirects Sfs: {:.3}%\n\
+			Total Redirects: {:.3}%\n\n\
+			Bad Sfbs: {:.3}%\n\
+			Sft: {:.3}%\n",
+            self.sfb * 100.0,
+            self.dsfb * 100.0,
+            self.adjacent * 100.0,
+            usage,
+            self.inrolls * 100.0,
+            self.outrolls * 100.0,
+            (self.inrolls + self.outrolls) * 100.0,
+            self.redirects * 100.0,
+            self.redirects_sfs * 100.0,
+            self.bad_redirects * 100.0,
+            self.bad_redirects_sfs * 100.0,
+            (self.redirects + self.redirects_sfs + self.bad_redirects + self.bad_redirects_sfs)
+                * 100.0,
+            self.bad_sfbs * 100.0,
+            self.sfts * 100.0
+        )
+    }
+}
+
+impl LayoutGeneration {
+    /// Analyzes a layout as if every key is typed by the same hand, using `fingers` to decide which
+    /// finger presses which position. There is no alternation, so every trigram is either a roll,
+    /// a redirect or contains a same finger bigram.
+    pub fn one_handed_stats(&self, layout: &FastLayout, fingers: &FingerMap) -> OneHandedStats {
+        use TrigramPattern::*;
+
+        let len = self.data.characters.len();
+        let mut char_fingers = [usize::MAX; u8::MAX as usize + 1];
+        let mut res = OneHandedStats::default();
+
+        for (i, &c) in layout.matrix.iter().enumerate() {
+            char_fingers[c as usize] = fingers[i];
+            res.finger_usage[fingers[i]] += *self.data.characters.get(c as usize).unwrap_or(&0.0);
+        }
+
+        let total_usage = res.finger_usage.iter().sum::<f64>();
+        if total_usage > 0.0 {
+            res.finger_usage.iter_mut().for_each(|u| *u /= total_usage);
+        }
+
+        for (i1, &c1) in layout.matrix.iter().enumerate() {
+            for (i2, &c2) in layout.matrix.iter().enumerate() {
+                if i1 == i2 {
+                    continue;
+                }
+                let bigram = c1 as usize * len + c2 as usize;
+                let (f1, f2) = (fingers[i1], fingers[i2]);
+
+                if f1 == f2 {
+                    res.sfb += self.data.bigrams.get(bigram).unwrap_or(&0.0);
+                    res.dsfb += self.data.skipgrams.get(bigram).unwrap_or(&0.0);
+                } else if f1.abs_diff(f2) == 1 {
+                    res.adjacent += self.data.bigrams.get(bigram).unwrap_or(&0.0);
+                }
+            }
+        }
+
+        for &([c1, c2, c3], freq) in self.data.trigrams.iter() {
+            let f1 = char_fingers[c1 as usize];
+            let f2 = char_fingers[c2 as usize];
+            let f3 = char_fingers[c3 as usize];
+
+            if f1 == usize::MAX || f2 == usize::MAX || f3 == usize::MAX {
+                continue;
+            }
+
+            match ONE_HAND_COMBINATIONS[f1 * 25 + f2 * 5 + f3] {
+                Inroll => res.inrolls += freq,
+                Outroll => res.outrolls += freq,
+                Redirect => res.redirects += freq,
+                RedirectSfs => res.redirects_sfs += freq,
+                BadRedirect => res.bad_redirects += freq,
+                BadRedirectSfs => res.bad_redirects_sfs += freq,
+                BadSfb => res.bad_sfbs += freq,
+                Sft => res.sfts += freq,
+                _ => {}
+            }
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::ApproxEq;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn finger_map() {
+        let map = parse_finger_map("01233 33210\n01233 33210\n01233 33210").unwrap();
+        assert_eq!(map, DEFAULT_FINGER_MAP);
+
+        assert!(parse_finger_map("01233 33210").is_err());
+        assert!(parse_finger_map("01233 33215\n01233 33210\n01233 33210").is_err());
+    }
+
+    #[test]
+    fn one_handed_stats() {
+        let layout = FastLayout::with_fingers(GEN.chars_for_generation, &GEN.fingers);
+        let stats = GEN.one_handed_stats(&layout, &DEFAULT_FINGER_MAP);
+
+        assert!(stats.finger_usage.iter().sum::<f64>().approx_eq_dbg(1.0, 7));
+        assert_eq!(stats.finger_usage[4], 0.0);
+
+        let trigram_stats = GEN.trigram_stats(&layout, usize::MAX);
+        let classified = stats.inrolls
+            + stats.outrolls
+            + stats.redirects
+            + stats.redirects_sfs
+            + stats.bad_redirects
+            + stats.bad_redirects_sfs
+            + stats.bad_sfbs
+            + stats.sfts;
+        let total = GEN.data.trigrams.iter().map(|(_, f)| f).sum::<f64>();
+
+        assert!(classified.approx_eq_dbg(total - trigram_stats.invalid, 7));
+    }
+}
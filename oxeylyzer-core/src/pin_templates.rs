@@ -0,0 +1,98 @@
+use crate::layout::*;
+use crate::utility::ConvertU8;
+
+/// Positions of qwerty, which is where the keys of every template are kept.
+const QWERTY: &str = "qwertyuiopasdfghjkl;zxcvbnm,./";
+
+/// A named set of keys that stay where they are on qwerty, for people who want to keep their
+/// shortcuts or punctuation without knowing the positions to pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinTemplate {
+    pub name: &'static str,
+    pub about: &'static str,
+    keys: &'static str,
+}
+
+pub const PIN_TEMPLATES: [PinTemplate; 3] = [
+    PinTemplate {
+        name: "zxcv",
+        about: "undo, cut, copy and paste",
+        keys: "zxcv",
+    },
+    PinTemplate {
+        name: "shortcuts",
+        about: "the common shortcuts on the left hand: quit, close, select all, save, zxcv",
+        keys: "qwaszxcv",
+    },
+    PinTemplate {
+        name: "punctuation",
+        about: "the punctuation on the bottom row",
+        keys: ",./",
+    },
+];
+
+impl TryFrom<&str> for PinTemplate {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, String> {
+        PIN_TEMPLATES
+            .into_iter()
+            .find(|template| template.name.eq_ignore_ascii_case(value))
+            .ok_or_else(|| {
+                let names = PIN_TEMPLATES.map(|template| template.name).join(", ");
+                format!("'{value}' is not a pin template. Use one of {names}")
+            })
+    }
+}
+
+impl PinTemplate {
+    /// Every key of the template with its position on qwerty.
+    fn positions(&self) -> impl Iterator<Item = (char, usize)> + '_ {
+        self.keys
+            .chars()
+            .filter_map(|c| Some((c, QWERTY.chars().position(|k| k == c)?)))
+    }
+
+    /// Moves the keys of the template on `layout` to their place, swapping them with whatever is
+    /// there. Keys that aren't on the layout are left out.
+    pub fn apply(&self, layout: &mut FastLayout, con: &ConvertU8) {
+        for (c, pos) in self.positions() {
+            let u = con.to_single_lossy(c);
+            if let Some(i) = layout.matrix.iter().position(|&k| k == u) {
+                layout.swap(i, pos);
+            }
+        }
+    }
+
+    /// Positions of the keys of the template that are in their place on `layout`, to pin them.
+    pub fn pins(&self, layout: &FastLayout, con: &ConvertU8) -> Vec<usize> {
+        self.positions()
+            .filter(|&(c, pos)| layout.c(pos) == con.to_single_lossy(c))
+            .map(|(_, pos)| pos)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates() {
+        let con = ConvertU8::with_chars("abcdefghijklmnopqrstuvwxyz',.;/");
+        let bytes = con.to_lossy("flhvzqwuoysrntkcdeaix'bmjpg,./".chars());
+        let mut layout = FastLayout::try_from(bytes.as_slice()).unwrap();
+
+        let zxcv = PinTemplate::try_from("ZXCV").unwrap();
+        assert!(PinTemplate::try_from("sfb").is_err());
+        assert_eq!(zxcv.pins(&layout, &con), Vec::<usize>::new());
+
+        zxcv.apply(&mut layout, &con);
+        assert_eq!(zxcv.pins(&layout, &con), vec![20, 21, 22, 23]);
+        assert_eq!(con.as_str(&layout.matrix[20..24]), "zxcv");
+        assert_eq!(layout.char_to_finger[con.to_single_lossy('z') as usize], 0);
+
+        let punctuation = PinTemplate::try_from("punctuation").unwrap();
+        assert_eq!(punctuation.pins(&layout, &con), vec![27, 28, 29]);
+    }
+}
@@ -0,0 +1,262 @@
+use std::path::Path;
+
+use crate::error::RusylyzerError;
+use crate::generate::LayoutGeneration;
+use crate::layout::FastLayout;
+use crate::text_reader::TextChunks;
+use crate::utility::{get_scissor_indices, is_left_hand, FingerAssignment};
+
+/// Counts gathered by typing out a text on a layout one key at a time. Spaces are pressed with a
+/// thumb, so they end same hand runs but still sit between the keys around them for dsfbs.
+#[derive(Clone, Default)]
+pub struct ReplayStats {
+    pub keystrokes: usize,
+    pub skipped: usize,
+    pub sfbs: usize,
+    pub dsfbs: usize,
+    pub scissors: usize,
+    pub hand_switches: usize,
+    pub thumb_presses: usize,
+    pub same_hand_runs: usize,
+    pub longest_run: usize,
+}
+
+impl ReplayStats {
+    pub fn per_thousand(&self, count: usize) -> f64 {
+        if self.keystrokes == 0 {
+            0.0
+        } else {
+            count as f64 * 1000.0 / self.keystrokes as f64
+        }
+    }
+}
+
+impl std::fmt::Display for ReplayStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Keystrokes: {} ({} characters not on the layout were skipped)\n\
+			Per 1000 keystrokes:\n\
+			Sfbs: {:.2}\n\
+			Dsfbs: {:.2}\n\
+			Scissors: {:.2}\n\
+			Hand switches: {:.2}\n\
+			Thumb presses: {:.2}\n\
+			Same hand runs (3+ keys): {:.2}\n\
+			Longest same hand run: {}",
+            self.keystrokes,
+            self.skipped,
+            self.per_thousand(self.sfbs),
+            self.per_thousand(self.dsfbs),
+            self.per_thousand(self.scissors),
+            self.per_thousand(self.hand_switches),
+            self.per_thousand(self.thumb_presses),
+            self.per_thousand(self.same_hand_runs),
+            self.longest_run
+        )
+    }
+}
+
+fn end_run(run: &mut usize, res: &mut ReplayStats) {
+    if *run >= 3 {
+        res.same_hand_runs += 1;
+    }
+    res.longest_run = res.longest_run.max(*run);
+    *run = 0;
+}
+
+#[derive(Clone, Copy)]
+enum Keystroke {
+    Key(usize),
+    Thumb,
+}
+
+/// Everything `replay` keeps track of while typing, so a text can be typed out a chunk at a time.
+struct Replayer<'a> {
+    gen: &'a LayoutGeneration,
+    fingers: &'a FingerAssignment,
+    char_to_pos: [usize; u8::MAX as usize + 1],
+    is_scissor: [[bool; 30]; 30],
+    res: ReplayStats,
+    prev: [Option<Keystroke>; 2],
+    run: usize,
+}
+
+impl<'a> Replayer<'a> {
+    fn new(gen: &'a LayoutGeneration, layout: &'a FastLayout) -> Self {
+        let mut char_to_pos = [usize::MAX; u8::MAX as usize + 1];
+        for (i, &c) in layout.matrix.iter().enumerate() {
+            char_to_pos[c as usize] = i;
+        }
+
+        let mut is_scissor = [[false; 30]; 30];
+        for pair in get_scissor_indices() {
+            is_scissor[pair.0][pair.1] = true;
+            is_scissor[pair.1][pair.0] = true;
+        }
+
+        Self {
+            gen,
+            fingers: &layout.fingers,
+            char_to_pos,
+            is_scissor,
+            res: ReplayStats::default(),
+            prev: [None, None],
+            run: 0,
+        }
+    }
+
+    fn type_text(&mut self, text: &str) {
+        let fingers = self.fingers;
+        let res = &mut self.res;
+        let run = &mut self.run;
+
+        for c in text.chars() {
+            let stroke = if c.is_whitespace() {
+                Keystroke::Thumb
+            } else {
+                let lower = c.to_lowercase().next().unwrap_or(c);
+                let u = self.gen.data.convert_u8.to_single_lossy(lower);
+
+                match self.char_to_pos[u as usize] {
+                    usize::MAX => {
+                        res.skipped += 1;
+                        end_run(run, res);
+                        self.prev = [None, None];
+                        continue;
+                    }
+                    pos => Keystroke::Key(pos),
+                }
+            };
+
+            res.keystrokes += 1;
+
+            match (self.prev[1], stroke) {
+                (Some(Keystroke::Key(p1)), Keystroke::Key(p2)) => {
+                    if fingers[p1] == fingers[p2] && p1 != p2 {
+                        res.sfbs += 1;
+                    }
+                    if self.is_scissor[p1][p2] {
+                        res.scissors += 1;
+                    }
+                    if is_left_hand(fingers[p1]) == is_left_hand(fingers[p2]) {
+                        *run += 1;
+                    } else {
+                        res.hand_switches += 1;
+                        end_run(run, res);
+                        *run = 1;
+                    }
+                }
+                (_, Keystroke::Key(_)) => {
+                    end_run(run, res);
+                    *run = 1;
+                }
+                (_, Keystroke::Thumb) => {
+                    res.thumb_presses += 1;
+                    end_run(run, res);
+                }
+            }
+
+            if let (Some(Keystroke::Key(p1)), Keystroke::Key(p3)) = (self.prev[0], stroke) {
+                if fingers[p1] == fingers[p3] && p1 != p3 {
+                    res.dsfbs += 1;
+                }
+            }
+
+            self.prev = [self.prev[1], Some(stroke)];
+        }
+    }
+
+    fn finish(mut self) -> ReplayStats {
+        end_run(&mut self.run, &mut self.res);
+        self.res
+    }
+}
+
+impl LayoutGeneration {
+    /// Types out `text` on `layout` keystroke by keystroke. Characters that aren't on the layout
+    /// and aren't whitespace are skipped and reset the context, so nothing is counted across them.
+    pub fn replay(&self, layout: &FastLayout, text: &str) -> ReplayStats {
+        let mut replayer = Replayer::new(self, layout);
+        replayer.type_text(text);
+        replayer.finish()
+    }
+
+    /// Types out the file at `path` like `replay`, reading it a chunk at a time so files of any
+    /// size can be replayed. With `max_bytes`, only that many bytes of it are typed.
+    pub fn replay_file<P: AsRef<Path>>(
+        &self,
+        layout: &FastLayout,
+        path: P,
+        max_bytes: Option<u64>,
+    ) -> Result<ReplayStats, RusylyzerError> {
+        let path = path.as_ref();
+        let mut replayer = Replayer::new(self, layout);
+
+        for chunk in TextChunks::new(vec![path.to_path_buf()], 0, max_bytes) {
+            let chunk = chunk.map_err(|e| RusylyzerError::io(path, e))?;
+            replayer.type_text(&chunk.text);
+        }
+        Ok(replayer.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::ApproxEq;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    fn qwerty() -> FastLayout {
+        let bytes = GEN
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        FastLayout::try_from(bytes.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn replay_counts() {
+        let stats = GEN.replay(&qwerty(), "Deed ex");
+
+        assert_eq!(stats.keystrokes, 7);
+        assert_eq!(stats.sfbs, 2);
+        assert_eq!(stats.dsfbs, 3);
+        assert_eq!(stats.scissors, 1);
+        assert_eq!(stats.thumb_presses, 1);
+        assert_eq!(stats.hand_switches, 0);
+        assert_eq!(stats.same_hand_runs, 1);
+        assert_eq!(stats.longest_run, 4);
+    }
+
+    #[test]
+    fn replay_runs_and_skips() {
+        let stats = GEN.replay(&qwerty(), "asdf jkl\u{1F600}sd");
+
+        assert_eq!(stats.keystrokes, 10);
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.sfbs, 0);
+        assert_eq!(stats.same_hand_runs, 2);
+        assert_eq!(stats.longest_run, 4);
+        assert!(stats
+            .per_thousand(stats.thumb_presses)
+            .approx_eq_dbg(100.0, 7));
+    }
+
+    #[test]
+    fn replay_file() {
+        let path = std::env::temp_dir().join("rusylyzer_replay_test.txt");
+        std::fs::write(&path, "Deed ex").unwrap();
+
+        let stats = GEN.replay_file(&qwerty(), &path, None).unwrap();
+        assert_eq!(stats.keystrokes, 7);
+        assert_eq!(stats.dsfbs, 3);
+        let sample = GEN.replay_file(&qwerty(), &path, Some(4)).unwrap();
+        assert_eq!(sample.keystrokes, 4);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(GEN.replay_file(&qwerty(), &path, None).is_err());
+    }
+}
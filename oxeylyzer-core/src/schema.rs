@@ -0,0 +1,470 @@
+use serde_json::{json, Value};
+
+/// Names of every artifact there is a schema for, in the order the `schema` command lists them.
+pub const SCHEMAS: [&str; 5] = [
+    "generate",
+    "language_data",
+    "layout_metadata",
+    "score",
+    "stats",
+];
+
+/// Version of the schemas and the csv columns made from them. Fields are only ever added within a
+/// version, so this goes up when one is renamed, removed or changes meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// JSON Schema for an exported artifact, or `None` if there is no artifact called `name`.
+pub fn schema(name: &str) -> Option<Value> {
+    match name {
+        "generate" => Some(generate()),
+        "language_data" => Some(language_data()),
+        "layout_metadata" => Some(layout_metadata()),
+        "score" => Some(score()),
+        "stats" => Some(stats()),
+        _ => None,
+    }
+}
+
+fn frequencies(description: &str) -> Value {
+    json!({
+        "type": "object",
+        "description": description,
+        "additionalProperties": { "type": "number", "minimum": 0.0 }
+    })
+}
+
+fn percentages(description: &str) -> Value {
+    json!({
+        "type": "number",
+        "description": format!("{description}, as a fraction between 0 and 1."),
+        "minimum": 0.0
+    })
+}
+
+fn per_finger(description: &str) -> Value {
+    json!({
+        "type": "array",
+        "description": format!("{description}, from left pinky to right pinky."),
+        "items": { "type": "number" },
+        "minItems": 8,
+        "maxItems": 8
+    })
+}
+
+fn optional_string(description: &str) -> Value {
+    json!({
+        "type": ["string", "null"],
+        "description": description
+    })
+}
+
+fn strings(description: &str) -> Value {
+    json!({
+        "type": "array",
+        "description": description,
+        "items": { "type": "string" }
+    })
+}
+
+/// The summary of a run `generate --json` prints, one entry per generated layout.
+fn generate() -> Value {
+    json!({
+        "$schema": DRAFT,
+        "title": "generate",
+        "version": SCHEMA_VERSION,
+        "description": "Layouts a single run of `generate` found, from the best score to the worst.",
+        "type": "array",
+        "items": {
+            "type": "object",
+            "required": ["layout", "score"],
+            "properties": {
+                "layout": {
+                    "type": "string",
+                    "description": "The 30 keys from the top left to the bottom right, row by row.",
+                    "minLength": 30
+                },
+                "score": { "type": "number" },
+                "languages": {
+                    "type": "array",
+                    "description": "How the layout does on every language of the mix, only for the 10 best layouts of a run for several languages.",
+                    "items": {
+                        "type": "object",
+                        "required": ["language", "share", "score", "rank", "lopsided", "stats"],
+                        "properties": {
+                            "language": { "type": "string" },
+                            "share": percentages("Share of the language in the mix"),
+                            "score": { "type": "number" },
+                            "rank": {
+                                "type": "integer",
+                                "description": "Rank of the score among the 10 best layouts, where 1 is the best.",
+                                "minimum": 1
+                            },
+                            "lopsided": {
+                                "type": "boolean",
+                                "description": "Whether the layout is in the best third for the mix, but in the worst third for this language."
+                            },
+                            "stats": {
+                                "description": "Stats with this language on its own.",
+                                "$ref": "stats.schema.json"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// What `analyze --json` shows as `metadata`, with the same field names as `LayoutMetadata`.
+fn layout_metadata() -> Value {
+    json!({
+        "$schema": DRAFT,
+        "title": "layout_metadata",
+        "version": SCHEMA_VERSION,
+        "description": "Information about a layout from the `# key: value` comments of its file.",
+        "type": "object",
+        "required": [
+            "author", "link", "geometry", "parent", "pins", "tags", "aliases", "family", "notes",
+            "config"
+        ],
+        "properties": {
+            "author": optional_string("Who made the layout."),
+            "link": optional_string("Where the layout comes from."),
+            "geometry": optional_string("Keyboard the layout was made for, like `ortho`."),
+            "parent": optional_string("Layout this one was improved from."),
+            "pins": {
+                "type": "array",
+                "description": "Characters that stay where they are when the layout is improved.",
+                "items": { "type": "string", "minLength": 1, "maxLength": 1 }
+            },
+            "tags": strings("Labels the layout is grouped by."),
+            "aliases": strings("Other names the layout can be found by."),
+            "family": optional_string("Layout this one is a variant of."),
+            "notes": strings("Every `notes` comment, in order."),
+            "config": {
+                "type": "object",
+                "description": "Weights and language the layout was saved with.",
+                "required": ["language", "trigram_precision", "profile", "weights"],
+                "properties": {
+                    "language": optional_string("Language the layout was saved with."),
+                    "trigram_precision": { "type": ["integer", "null"], "minimum": 0 },
+                    "profile": optional_string("Weight profile, or null for the default one."),
+                    "weights": {
+                        "type": "object",
+                        "description": "Every weight by the name `set` uses. Empty when the file doesn't have them.",
+                        "additionalProperties": { "type": "number" }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// The corpus files `load` writes to `static/language_data`.
+fn language_data() -> Value {
+    json!({
+        "$schema": DRAFT,
+        "title": "language_data",
+        "version": SCHEMA_VERSION,
+        "description": "Character, bigram, skipgram and trigram frequencies of a corpus, sorted from most to least frequent.",
+        "type": "object",
+        "required": [
+            "language", "characters", "bigrams", "skipgrams", "skipgrams2", "skipgrams3", "trigrams"
+        ],
+        "properties": {
+            "language": { "type": "string" },
+            "characters": frequencies("Frequency of every character."),
+            "bigrams": frequencies("Frequency of every pair of consecutive characters."),
+            "skipgrams": frequencies("Frequency of pairs with 1 character in between."),
+            "skipgrams2": frequencies("Frequency of pairs with 2 characters in between."),
+            "skipgrams3": frequencies("Frequency of pairs with 3 characters in between."),
+            "trigrams": frequencies("Frequency of every 3 consecutive characters."),
+            "samples": {
+                "type": "array",
+                "description": "Random sentences from the corpus, used by `sample` and `replay`.",
+                "items": { "type": "string" }
+            }
+        }
+    })
+}
+
+/// What the score of a layout is made of, with the same field names as `ScoreComponents`.
+fn score() -> Value {
+    let penalty = |description: &str| {
+        json!({
+            "type": "number",
+            "description": format!("{description}, subtracted from the score."),
+            "minimum": 0.0
+        })
+    };
+
+    json!({
+        "$schema": DRAFT,
+        "title": "score",
+        "version": SCHEMA_VERSION,
+        "description": "Weighted parts of the score of a single layout on a corpus.",
+        "type": "object",
+        "required": [
+            "trigrams", "onehand_rolls", "quadgrams", "effort", "fspeed_usage", "scissors", "lsbs",
+            "row_skips", "bigram_penalties", "hand_alternation", "row_usage", "shortcuts",
+            "external_metric", "total"
+        ],
+        "properties": {
+            "trigrams": {
+                "type": "number",
+                "description": "Weighted trigram patterns, added to the score."
+            },
+            "onehand_rolls": {
+                "type": "number",
+                "description": "Weighted onehand rolls, added to the score."
+            },
+            "quadgrams": {
+                "type": "number",
+                "description": "Weighted quadgram patterns, added to the score. 0 without quadgram data."
+            },
+            "effort": penalty("Heatmap effort, including travel"),
+            "fspeed_usage": penalty("Finger speed and finger use over `max_finger_use`"),
+            "scissors": penalty("Weighted scissors"),
+            "lsbs": penalty("Weighted lateral stretch bigrams"),
+            "row_skips": penalty("Weighted row skips"),
+            "bigram_penalties": penalty("Every penalty in `[bigram_penalties]` together"),
+            "hand_alternation": penalty("Distance from the hand alternation target"),
+            "row_usage": penalty("Distance of the rows from the `row_usage` limits"),
+            "shortcuts": penalty("Share of the shortcut keys out of reach of the left hand"),
+            "external_metric": {
+                "type": "number",
+                "description": "Weighted result of the program in `[external_metric]`, added to the score."
+            },
+            "total": {
+                "type": "number",
+                "description": "The score, which is higher for better layouts."
+            }
+        }
+    })
+}
+
+/// The stats shown by `analyze`, with the same field names as `LayoutStats`.
+fn stats() -> Value {
+    let trigram_fields = [
+        "alternates",
+        "alternates_sfs",
+        "inrolls",
+        "outrolls",
+        "onehands",
+        "redirects",
+        "redirects_sfs",
+        "bad_redirects",
+        "bad_redirects_sfs",
+        "sfbs",
+        "bad_sfbs",
+        "sfts",
+        "other",
+        "invalid",
+        "adjacent_rolls",
+    ];
+    let mut trigram_properties = trigram_fields
+        .iter()
+        .map(|&f| {
+            (
+                f.to_string(),
+                percentages(&format!("Trigrams that are {f}")),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>();
+    for f in ["inrolls_by_row", "outrolls_by_row"] {
+        let by_row = json!({
+            "type": "array",
+            "description": "Split by the row of the rolling keys: top, home, bottom and across rows.",
+            "items": { "type": "number", "minimum": 0.0 },
+            "minItems": 4,
+            "maxItems": 4
+        });
+        trigram_properties.insert(f.to_string(), by_row);
+    }
+    for f in ["inrolls_by_hand", "outrolls_by_hand"] {
+        let by_hand = json!({
+            "type": "array",
+            "description": "Split by the hand of the rolling keys: left and right.",
+            "items": { "type": "number", "minimum": 0.0 },
+            "minItems": 2,
+            "maxItems": 2
+        });
+        trigram_properties.insert(f.to_string(), by_hand);
+    }
+
+    json!({
+        "$schema": DRAFT,
+        "title": "stats",
+        "version": SCHEMA_VERSION,
+        "description": "Stats of a single layout on a corpus.",
+        "type": "object",
+        "required": [
+            "sfb", "sfb_per_finger", "sfb_distance", "sfb_distance_per_finger", "dsfb", "dsfb2",
+            "dsfb3", "scissors", "lsbs", "row_skips", "trigram_stats", "fspeed", "finger_speed",
+            "hand_usage", "row_usage", "hand_alternation", "shortcut_reach", "onehand_rolls",
+            "travel"
+        ],
+        "properties": {
+            "sfb": percentages("Same finger bigrams"),
+            "sfb_per_finger": per_finger("Same finger bigrams on every finger"),
+            "sfb_distance": {
+                "type": "number",
+                "description": "Same finger bigrams multiplied by the distance between their keys.",
+                "minimum": 0.0
+            },
+            "sfb_distance_per_finger": per_finger("Sfb distance on every finger"),
+            "dsfb": percentages("Same finger skipgrams with 1 key in between"),
+            "dsfb2": percentages("Same finger skipgrams with 2 keys in between"),
+            "dsfb3": percentages("Same finger skipgrams with 3 keys in between"),
+            "scissors": percentages("Scissors"),
+            "lsbs": percentages("Lateral stretch bigrams"),
+            "row_skips": percentages(
+                "Bigrams between the top and bottom row on the same or adjacent fingers"
+            ),
+            "trigram_stats": {
+                "type": "object",
+                "required": trigram_properties.keys().collect::<Vec<_>>(),
+                "properties": trigram_properties
+            },
+            "fspeed": {
+                "type": "number",
+                "description": "Total finger speed, a tenth of what `analyze` shows."
+            },
+            "finger_speed": per_finger("Finger speed"),
+            "hand_usage": {
+                "type": "array",
+                "description": "Share of keypresses on the left and right hand.",
+                "items": { "type": "number", "minimum": 0.0, "maximum": 1.0 },
+                "minItems": 2,
+                "maxItems": 2
+            },
+            "row_usage": {
+                "type": "array",
+                "description": "Share of keypresses on the top, home and bottom row.",
+                "items": { "type": "number", "minimum": 0.0, "maximum": 1.0 },
+                "minItems": 3,
+                "maxItems": 3
+            },
+            "hand_alternation": percentages("Bigrams typed with one hand after the other"),
+            "shortcut_reach": percentages(
+                "Share of the shortcut keys on the home and bottom row of the left hand"
+            ),
+            "onehand_rolls": percentages("Estimated onehand rolls into the other hand"),
+            "travel": {
+                "type": "number",
+                "description": "Average distance fingers travel per keystroke, in key widths.",
+                "minimum": 0.0
+            },
+            "bigram_penalties": {
+                "type": "object",
+                "description": "Bigrams of every penalty in `[bigram_penalties]`, by name.",
+                "additionalProperties": { "type": "number", "minimum": 0.0, "maximum": 1.0 }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_schemas_exist() {
+        for name in SCHEMAS {
+            let schema = schema(name).unwrap();
+            assert_eq!(schema["title"], name);
+
+            // a run summary is a list of layouts, which are objects like the others
+            let object = match schema["type"].as_str() {
+                Some("array") => &schema["items"],
+                _ => &schema,
+            };
+            assert_eq!(object["type"], "object");
+            let required = object["required"].as_array().unwrap();
+            for field in required {
+                assert!(object["properties"].get(field.as_str().unwrap()).is_some());
+            }
+        }
+        assert!(schema("layout_stats").is_none());
+    }
+
+    #[test]
+    fn stats_match_schema() {
+        let stats = serde_json::to_value(crate::generate::LayoutStats::default()).unwrap();
+        let schema = schema("stats").unwrap();
+
+        let fields = stats.as_object().unwrap();
+        for field in schema["required"].as_array().unwrap() {
+            assert!(fields.contains_key(field.as_str().unwrap()), "{field}");
+        }
+        for field in fields.keys() {
+            assert!(schema["properties"].get(field).is_some(), "{field}");
+        }
+        let trigram_stats = &schema["properties"]["trigram_stats"];
+        for field in stats["trigram_stats"].as_object().unwrap().keys() {
+            assert!(trigram_stats["properties"].get(field).is_some(), "{field}");
+        }
+        assert!(stats["bigram_penalties"].is_object());
+    }
+
+    #[test]
+    fn score_matches_schema() {
+        let score = serde_json::to_value(crate::generate::ScoreComponents::default()).unwrap();
+        let schema = schema("score").unwrap();
+
+        let fields = score.as_object().unwrap();
+        let required = schema["required"].as_array().unwrap();
+        assert_eq!(fields.len(), required.len());
+        for field in required {
+            assert!(fields.contains_key(field.as_str().unwrap()), "{field}");
+        }
+    }
+
+    #[test]
+    fn metadata_matches_schema() {
+        let metadata = crate::metadata::LayoutMetadata {
+            pins: vec!['e'],
+            ..Default::default()
+        };
+        let metadata = serde_json::to_value(metadata).unwrap();
+        let schema = schema("layout_metadata").unwrap();
+
+        let fields = metadata.as_object().unwrap();
+        let required = schema["required"].as_array().unwrap();
+        assert_eq!(fields.len(), required.len());
+        for field in required {
+            assert!(fields.contains_key(field.as_str().unwrap()), "{field}");
+        }
+        assert_eq!(metadata["pins"], json!(["e"]));
+        assert!(metadata["config"]["weights"].is_object());
+    }
+
+    /// Renaming or removing any of these needs a new `SCHEMA_VERSION`, and this test updated.
+    #[test]
+    fn stable_field_names() {
+        for name in SCHEMAS {
+            assert_eq!(schema(name).unwrap()["version"], SCHEMA_VERSION);
+        }
+
+        let stats = serde_json::to_value(crate::generate::LayoutStats::default()).unwrap();
+        let stats_fields = concat!(
+            "sfb sfb_per_finger sfb_distance sfb_distance_per_finger dsfb dsfb2 dsfb3 scissors ",
+            "lsbs row_skips trigram_stats fspeed finger_speed hand_usage row_usage ",
+            "hand_alternation shortcut_reach onehand_rolls travel bigram_penalties"
+        );
+        for field in stats_fields.split_whitespace() {
+            assert!(stats.get(field).is_some(), "{field}");
+        }
+
+        let trigram_fields = concat!(
+            "alternates alternates_sfs inrolls outrolls onehands redirects redirects_sfs ",
+            "bad_redirects bad_redirects_sfs sfbs bad_sfbs sfts other invalid inrolls_by_row ",
+            "outrolls_by_row inrolls_by_hand outrolls_by_hand adjacent_rolls"
+        );
+        for field in trigram_fields.split_whitespace() {
+            assert!(stats["trigram_stats"].get(field).is_some(), "{field}");
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::RusylyzerError;
+
+/// Name of the file in every layout directory that holds the scores of its layouts.
+pub const SCORE_CACHE_FILE: &str = ".scores.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct CachedScore {
+    layout: String,
+    score: f64,
+}
+
+/// Scores of the layouts in a directory, so they don't have to be scored again every time they are
+/// loaded. A score is only used while both the layout and the `key` it was scored with are the same.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ScoreCache {
+    /// Hash of everything that goes into a score, like the weights and the language data.
+    key: u64,
+    scores: BTreeMap<String, CachedScore>,
+}
+
+impl ScoreCache {
+    pub fn new(key: u64) -> Self {
+        Self {
+            key,
+            scores: BTreeMap::new(),
+        }
+    }
+
+    /// Reads the cache at `path`. If it doesn't exist, can't be read or was made with a different
+    /// `key`, the cache is empty.
+    pub fn read<P: AsRef<Path>>(path: P, key: u64) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Self>(&s).ok())
+            .filter(|cache| cache.key == key)
+            .unwrap_or_else(|| Self::new(key))
+    }
+
+    /// Score of the layout called `name`, if it was cached for the same `layout` string.
+    pub fn get(&self, name: &str, layout: &str) -> Option<f64> {
+        self.scores
+            .get(name)
+            .filter(|cached| cached.layout == layout)
+            .map(|cached| cached.score)
+    }
+
+    pub fn insert(&mut self, name: String, layout: String, score: f64) {
+        self.scores.insert(name, CachedScore { layout, score });
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), RusylyzerError> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| RusylyzerError::io(path, std::io::Error::from(e)))?;
+        std::fs::write(path, json).map_err(|e| RusylyzerError::io(path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_scores() {
+        let path = std::env::temp_dir().join("rusylyzer_score_cache_test.json");
+        let mut cache = ScoreCache::new(42);
+        cache.insert("qwerty".to_string(), "qwertyuiop".to_string(), -12.5);
+
+        assert_eq!(cache.get("qwerty", "qwertyuiop"), Some(-12.5));
+        assert_eq!(cache.get("qwerty", "qwertyuiopx"), None);
+        assert_eq!(cache.get("dvorak", "qwertyuiop"), None);
+
+        cache.write(&path).unwrap();
+        assert_eq!(ScoreCache::read(&path, 42), cache);
+        assert_eq!(ScoreCache::read(&path, 43), ScoreCache::new(43));
+        assert_eq!(
+            ScoreCache::read(path.with_extension("missing"), 1),
+            ScoreCache::new(1)
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
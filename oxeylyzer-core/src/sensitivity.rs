@@ -0,0 +1,118 @@
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::generate::LayoutGeneration;
+use crate::language_data::LanguageData;
+use crate::layout::FastLayout;
+use crate::weights::{Config, Weights};
+
+/// How the score and rank of a layout change when a single weight is lowered and raised.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightSensitivity {
+    pub weight: &'static str,
+    /// Change in score with the weight lowered and raised respectively.
+    pub score_changes: [f64; 2],
+    /// Rank among all layouts with the weight lowered and raised, where 1 is the best.
+    pub ranks: [usize; 2],
+}
+
+impl WeightSensitivity {
+    /// The largest change in score, either way.
+    pub fn max_change(&self) -> f64 {
+        self.score_changes[0].abs().max(self.score_changes[1].abs())
+    }
+}
+
+/// Rank of `score` among `others`, where 1 means none of them score higher.
+pub fn rank<I: IntoIterator<Item = f64>>(score: f64, others: I) -> usize {
+    1 + others.into_iter().filter(|&other| other > score).count()
+}
+
+/// Lowers and raises every weight of `config` that isn't 0 by `change`, like 0.1 for 10%, and
+/// scores `layout` and `others` with it. Sorted by the largest change in score, most first.
+pub fn sensitivity(
+    chars_language: &str,
+    data: &LanguageData,
+    config: &Config,
+    layout: &FastLayout,
+    others: &[&FastLayout],
+    change: f64,
+) -> Vec<WeightSensitivity> {
+    let score_with = |config: Config| {
+        let gen = LayoutGeneration::from_data(chars_language, data.clone(), config);
+        let others = others
+            .iter()
+            .map(|other| gen.score(other))
+            .collect::<Vec<_>>();
+        (gen.score(layout), others)
+    };
+    // every changed config gets its dsfb ratios set again, so the base has to as well
+    let mut base = config.clone();
+    base.weights.set_dsfb_ratios();
+    let (base_score, _) = score_with(base);
+
+    let mut res = Weights::NAMES
+        .par_iter()
+        .filter(|&&name| {
+            let mut weights = config.weights.clone();
+            matches!(weights.weight_mut(name), Some(weight) if *weight != 0.0)
+        })
+        .map(|&weight| {
+            let mut score_changes = [0.0; 2];
+            let mut ranks = [0; 2];
+
+            for (i, factor) in [1.0 - change, 1.0 + change].into_iter().enumerate() {
+                let mut config = config.clone();
+                if let Some(value) = config.weights.weight_mut(weight) {
+                    *value *= factor;
+                }
+                config.weights.set_dsfb_ratios();
+
+                let (score, others) = score_with(config);
+                score_changes[i] = score - base_score;
+                ranks[i] = rank(score, others);
+            }
+
+            WeightSensitivity {
+                weight,
+                score_changes,
+                ranks,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    res.sort_by(|a, b| b.max_change().partial_cmp(&a.max_change()).unwrap());
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Layout;
+
+    #[test]
+    fn weight_sensitivity() {
+        let data = LanguageData::from_file("static/language_data", "english").unwrap();
+        let config = Config::default();
+        let bytes = data
+            .convert_u8
+            .to_lossy("qwertyuiopasdfghjkl;zxcvbnm,./".chars());
+        let layout = FastLayout::try_from(bytes.as_slice()).unwrap();
+        let other = FastLayout::random(layout.matrix);
+
+        let res = sensitivity("english", &data, &config, &layout, &[&other], 0.1);
+
+        assert!(res.iter().all(|s| s.weight != "row_skips"));
+        assert!(res
+            .windows(2)
+            .all(|w| w[0].max_change() >= w[1].max_change()));
+        assert!(res
+            .iter()
+            .all(|s| s.ranks.iter().all(|&r| r == 1 || r == 2)));
+
+        let fspeed = res.iter().find(|s| s.weight == "fspeed").unwrap();
+        assert!(fspeed.score_changes[0] > 0.0);
+        assert!(fspeed.score_changes[1] < 0.0);
+
+        assert_eq!(rank(1.0, [2.0, 0.5, 1.0]), 2);
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::csv::stats_columns;
+use crate::error::RusylyzerError;
+use crate::generate::LayoutGeneration;
+use crate::layout::FastLayout;
+
+/// Name of the file in every layout directory that holds the expected stats of its language.
+pub const SNAPSHOT_FILE: &str = ".snapshot.json";
+
+/// Well known layouts `verify` checks, when they are in the layout directory of a language.
+pub const SNAPSHOT_LAYOUTS: [&str; 6] = [
+    "qwerty",
+    "dvorak",
+    "colemak",
+    "colemak_dh",
+    "workman",
+    "semimak",
+];
+
+/// Score and stats of a set of layouts, to find out whether a change to the weights, the language
+/// data or the scoring itself changed them. Stats are named like the columns of
+/// [`stats_columns`], with the score as the `score` column.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Snapshot {
+    layouts: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+/// A value in a [`Snapshot`] that is different now. It is `None` when the layout or the stat
+/// isn't there anymore.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotDifference {
+    pub layout: String,
+    pub column: String,
+    pub expected: f64,
+    pub actual: Option<f64>,
+}
+
+impl Snapshot {
+    /// Score and stats of every layout in `layouts`, scored by `gen`.
+    pub fn new<'a, I>(gen: &LayoutGeneration, layouts: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a FastLayout)>,
+    {
+        let layouts = layouts
+            .into_iter()
+            .map(|(name, layout)| {
+                let columns = std::iter::once(("score".to_string(), gen.score(layout)))
+                    .chain(stats_columns(&gen.get_layout_stats(layout)))
+                    .collect();
+                (name.to_string(), columns)
+            })
+            .collect();
+
+        Self { layouts }
+    }
+
+    /// Reads the snapshot at `path`, or `None` if there is none yet.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Option<Self>, RusylyzerError> {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(s) => serde_json::from_str(&s)
+                .map(Some)
+                .map_err(|e| RusylyzerError::io(path, std::io::Error::from(e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(RusylyzerError::io(path, e)),
+        }
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), RusylyzerError> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| RusylyzerError::io(path, std::io::Error::from(e)))?;
+        std::fs::write(path, json).map_err(|e| RusylyzerError::io(path, e))
+    }
+
+    /// Names of the layouts in the snapshot.
+    pub fn layouts(&self) -> impl Iterator<Item = &str> {
+        self.layouts.keys().map(String::as_str)
+    }
+
+    /// Every value of this snapshot that is different in `actual` by more than `tolerance`, which
+    /// is relative for values above 1. Stats that only `actual` has, like ones that were added
+    /// since, aren't differences.
+    pub fn differences(&self, actual: &Snapshot, tolerance: f64) -> Vec<SnapshotDifference> {
+        let mut res = Vec::new();
+
+        for (layout, columns) in self.layouts.iter() {
+            let actual_columns = actual.layouts.get(layout);
+            for (column, &expected) in columns.iter() {
+                let value = actual_columns
+                    .and_then(|columns| columns.get(column))
+                    .copied();
+                let same = value.is_some_and(|value| {
+                    (value - expected).abs() <= tolerance * expected.abs().max(1.0)
+                });
+                if !same {
+                    res.push(SnapshotDifference {
+                        layout: layout.clone(),
+                        column: column.clone(),
+                        expected,
+                        actual: value,
+                    });
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weights::Config;
+    use once_cell::sync::Lazy;
+
+    static GEN: Lazy<LayoutGeneration> =
+        Lazy::new(|| LayoutGeneration::new("english", "static", None).unwrap());
+
+    #[test]
+    fn snapshot_differences() {
+        let layout = |keys: &str| {
+            let bytes = GEN.convert_u8.to_lossy(keys.chars());
+            FastLayout::try_from(bytes.as_slice()).unwrap()
+        };
+        let qwerty = layout("qwertyuiopasdfghjkl;zxcvbnm,./");
+        let dvorak = layout("',.pyfgcrlaoeuidhtns;qjkxbmwvz");
+
+        let snapshot = Snapshot::new(&GEN, [("qwerty", &qwerty), ("dvorak", &dvorak)]);
+        assert_eq!(
+            snapshot.layouts().collect::<Vec<_>>(),
+            vec!["dvorak", "qwerty"]
+        );
+        assert!(snapshot.differences(&snapshot, 0.0).is_empty());
+
+        let again = Snapshot::new(&GEN, [("qwerty", &qwerty)]);
+        let missing = snapshot.differences(&again, 1e-9);
+        assert!(!missing.is_empty());
+        assert!(missing
+            .iter()
+            .all(|d| d.layout == "dvorak" && d.actual.is_none()));
+
+        let mut config = Config::default();
+        config.weights.fspeed *= 2.0;
+        let changed = LayoutGeneration::new("english", "static", Some(config)).unwrap();
+        let rescored = Snapshot::new(&changed, [("qwerty", &qwerty), ("dvorak", &dvorak)]);
+        let differences = snapshot.differences(&rescored, 1e-9);
+        assert!(differences.iter().any(|d| d.column == "score"));
+        assert!(differences.iter().all(|d| d.column != "sfb"));
+
+        let path = std::env::temp_dir().join("rusylyzer_snapshot_test.json");
+        snapshot.write(&path).unwrap();
+        let read = Snapshot::read(&path).unwrap().unwrap();
+        // floats don't always survive json exactly, so they only have to be close
+        assert!(read.differences(&snapshot, 1e-12).is_empty());
+        assert!(snapshot.differences(&read, 1e-12).is_empty());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Snapshot::read(&path).unwrap(), None);
+    }
+}
@@ -0,0 +1,251 @@
+use std::fmt::Write;
+
+use crate::utility::{key_position, FingerAssignment, KeyboardType};
+
+/// Colors used for every layout in a chart, in order. Wraps around when comparing more layouts.
+pub const PALETTE: [&str; 6] = [
+    "#4e79a7", "#f28e2b", "#59a14f", "#e15759", "#b07aa1", "#76b7b2",
+];
+
+/// Outline colors for every finger, from left pinky to right pinky.
+pub const FINGER_COLORS: [&str; 8] = [
+    "#e15759", "#f28e2b", "#59a14f", "#4e79a7", "#4e79a7", "#59a14f", "#f28e2b", "#e15759",
+];
+
+/// A tiny svg writer that supports just enough shapes for the charts the analyzer exports.
+pub struct Svg {
+    width: f64,
+    height: f64,
+    body: String,
+}
+
+impl Svg {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self {
+            width,
+            height,
+            body: String::new(),
+        }
+    }
+
+    pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, fill: &str) -> &mut Self {
+        writeln!(
+            self.body,
+            r#"  <rect x="{x:.1}" y="{y:.1}" width="{width:.1}" height="{height:.1}" fill="{fill}"/>"#
+        )
+        .unwrap();
+        self
+    }
+
+    pub fn outlined_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        fill: &str,
+        stroke: &str,
+    ) -> &mut Self {
+        writeln!(
+            self.body,
+            concat!(
+                r#"  <rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="4" "#,
+                r#"fill="{}" stroke="{}" stroke-width="2"/>"#
+            ),
+            x, y, width, height, fill, stroke
+        )
+        .unwrap();
+        self
+    }
+
+    pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, stroke: &str) -> &mut Self {
+        writeln!(
+            self.body,
+            r#"  <line x1="{x1:.1}" y1="{y1:.1}" x2="{x2:.1}" y2="{y2:.1}" stroke="{stroke}"/>"#
+        )
+        .unwrap();
+        self
+    }
+
+    /// Adds text, where `anchor` is one of `start`, `middle` or `end`.
+    pub fn text(&mut self, x: f64, y: f64, size: f64, anchor: &str, content: &str) -> &mut Self {
+        writeln!(
+            self.body,
+            r#"  <text x="{x:.1}" y="{y:.1}" font-size="{size:.1}" text-anchor="{anchor}">{}</text>"#,
+            escape(content)
+        )
+        .unwrap();
+        self
+    }
+
+    pub fn finish(&self) -> String {
+        format!(
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0:.0}" height="{1:.0}" "#,
+                r#"viewBox="0 0 {0:.0} {1:.0}" font-family="sans-serif">"#,
+                "\n",
+                r#"  <rect width="100%" height="100%" fill="white"/>"#,
+                "\n{2}</svg>\n"
+            ),
+            self.width, self.height, self.body
+        )
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Draws a grouped bar chart with a group for every metric and a bar for every layout in it.
+/// Every metric is scaled to its own highest value, because they don't share a unit.
+pub fn comparison_chart(layout_names: &[&str], metrics: &[(&str, Vec<f64>)]) -> String {
+    const BAR_WIDTH: f64 = 28.0;
+    const GROUP_GAP: f64 = 36.0;
+    const CHART_HEIGHT: f64 = 240.0;
+    const TOP: f64 = 30.0;
+    const LEFT: f64 = 20.0;
+
+    let group_width = BAR_WIDTH * layout_names.len() as f64;
+    let width = LEFT * 2.0 + (group_width + GROUP_GAP) * metrics.len() as f64;
+    let legend_top = TOP + CHART_HEIGHT + 50.0;
+    let height = legend_top + 20.0 * layout_names.len() as f64 + 10.0;
+
+    let mut svg = Svg::new(width.max(200.0), height);
+    let baseline = TOP + CHART_HEIGHT;
+
+    for (m, (metric, values)) in metrics.iter().enumerate() {
+        let group_x = LEFT + GROUP_GAP / 2.0 + (group_width + GROUP_GAP) * m as f64;
+        let max = values.iter().fold(0.0f64, |max, v| max.max(v.abs()));
+
+        for (l, value) in values.iter().enumerate() {
+            let bar_height = if max > 0.0 {
+                value.abs() / max * CHART_HEIGHT
+            } else {
+                0.0
+            };
+            let x = group_x + BAR_WIDTH * l as f64;
+
+            svg.rect(
+                x + 2.0,
+                baseline - bar_height,
+                BAR_WIDTH - 4.0,
+                bar_height,
+                PALETTE[l % PALETTE.len()],
+            )
+            .text(
+                x + BAR_WIDTH / 2.0,
+                baseline - bar_height - 4.0,
+                9.0,
+                "middle",
+                &format!("{value:.2}"),
+            );
+        }
+
+        svg.text(
+            group_x + group_width / 2.0,
+            baseline + 18.0,
+            12.0,
+            "middle",
+            metric,
+        );
+    }
+
+    svg.line(LEFT, baseline, width - LEFT, baseline, "black");
+
+    for (l, name) in layout_names.iter().enumerate() {
+        let y = legend_top + 20.0 * l as f64;
+        svg.rect(LEFT, y - 10.0, 12.0, 12.0, PALETTE[l % PALETTE.len()])
+            .text(LEFT + 18.0, y, 12.0, "start", name);
+    }
+
+    svg.finish()
+}
+
+/// Same coloring as the heatmap shown in the terminal, from white for a heat of 0 to red for 1.
+fn heat_color(heat: f64) -> String {
+    let complement = (215.0 - heat * 215.0).clamp(0.0, 215.0) as u8;
+    format!("#d7{complement:02x}{complement:02x}")
+}
+
+/// Draws the 30 keys of a layout as they are placed on a keyboard of `keyboard_type`, colored by
+/// their heat from 0 to 1, like `LayoutGeneration::key_heat` returns. With `finger_zones`, every
+/// key is outlined in the color of the finger that presses it.
+pub fn heatmap(
+    keys: &[(char, f64); 30],
+    keyboard_type: &KeyboardType,
+    finger_zones: Option<&FingerAssignment>,
+) -> String {
+    const KEY: f64 = 50.0;
+    const MARGIN: f64 = 10.0;
+
+    let positions = (0..30)
+        .map(|i| key_position(i, keyboard_type))
+        .collect::<Vec<_>>();
+    let right = positions.iter().fold(0.0f64, |max, &(x, _)| max.max(x)) + 1.0;
+    let bottom = positions.iter().fold(0.0f64, |max, &(_, y)| max.max(y)) + 1.0;
+
+    let mut svg = Svg::new(right * KEY + MARGIN * 2.0, bottom * KEY + MARGIN * 2.0);
+
+    for (i, ((c, heat), (x, y))) in keys.iter().zip(positions).enumerate() {
+        let (x, y) = (MARGIN + x * KEY, MARGIN + y * KEY);
+        let stroke = match finger_zones {
+            Some(fingers) => FINGER_COLORS[fingers[i]],
+            None => "#505050",
+        };
+
+        svg.outlined_rect(
+            x + 2.0,
+            y + 2.0,
+            KEY - 4.0,
+            KEY - 4.0,
+            &heat_color(*heat),
+            stroke,
+        )
+        .text(x + KEY / 2.0, y + KEY * 0.6, 20.0, "middle", &c.to_string());
+    }
+
+    svg.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utility::I_TO_COL;
+
+    #[test]
+    fn escapes_text() {
+        let mut svg = Svg::new(10.0, 10.0);
+        svg.text(0.0, 0.0, 10.0, "start", "<a & b>");
+
+        assert!(svg.finish().contains("&lt;a &amp; b&gt;"));
+    }
+
+    #[test]
+    fn heatmap_keys() {
+        let mut keys = [('a', 0.0); 30];
+        keys[0] = ('<', 1.0);
+
+        let svg = heatmap(&keys, &KeyboardType::Ortho, Some(&I_TO_COL));
+
+        assert_eq!(svg.matches("<rect").count(), 31);
+        assert!(svg.contains(r##"fill="#d70000" stroke="#e15759""##));
+        assert!(svg.contains(">&lt;</text>"));
+        assert!(svg.contains(r#"width="545" height="170""#));
+    }
+
+    #[test]
+    fn chart_bars() {
+        let chart = comparison_chart(
+            &["semimak", "qwerty"],
+            &[("Sfb", vec![1.0, 6.5]), ("Dsfb", vec![7.0, 0.0])],
+        );
+
+        assert!(chart.starts_with("<svg"));
+        assert_eq!(chart.matches("<rect").count(), 1 + 4 + 2);
+        assert!(chart.contains(r##"height="240.0" fill="#f28e2b""##));
+        assert!(chart.contains(">semimak</text>"));
+    }
+}
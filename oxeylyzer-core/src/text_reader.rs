@@ -0,0 +1,290 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::graphemes::nfc;
+
+/// Amount of bytes read from a file at a time.
+pub const CHUNK_SIZE: u64 = 1024 * 1024 * 4;
+
+/// A piece of a text file read by [`TextChunks`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextChunk {
+    /// The text, starting with the end of the chunk before it.
+    pub text: String,
+    /// Byte index in `text` where the part that wasn't in the chunk before it starts.
+    start: usize,
+}
+
+impl TextChunk {
+    /// The text without the characters repeated from the chunk before it.
+    pub fn body(&self) -> &str {
+        &self.text[self.start..]
+    }
+}
+
+/// Reads text files one chunk at a time, so a corpus of any size can be processed without ever
+/// having all of it in memory. Every chunk starts with the last `overlap` characters of the chunk
+/// before it in the same file, and every file ends with a chunk of its last characters followed
+/// by `overlap` spaces. That way every character starts exactly one window of `overlap + 1`
+/// characters that fits in a single chunk.
+pub struct TextChunks {
+    paths: std::vec::IntoIter<PathBuf>,
+    file: Option<(PathBuf, File)>,
+    overlap: usize,
+    chunk_size: u64,
+    /// Bytes that can still be read, when the amount is limited.
+    remaining: Option<u64>,
+    /// Bytes of a character that was cut off at the end of the last read.
+    pending: Vec<u8>,
+    /// The last `overlap` characters of the current file that were read.
+    carry: String,
+    progress: Option<ProgressBar>,
+}
+
+fn invalid_utf8(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} is not encoded as utf-8", path.display()),
+    )
+}
+
+/// The last `n` characters of `s`, or all of it when it is shorter.
+fn last_chars(s: &str, n: usize) -> &str {
+    match n {
+        0 => "",
+        n => s
+            .char_indices()
+            .rev()
+            .nth(n - 1)
+            .map_or(s, |(i, _)| &s[i..]),
+    }
+}
+
+impl TextChunks {
+    /// Chunks of the files at `paths` in order. With `max_bytes`, reading stops after that many
+    /// bytes, which gives a quick sample of a large corpus.
+    pub fn new(paths: Vec<PathBuf>, overlap: usize, max_bytes: Option<u64>) -> Self {
+        Self {
+            paths: paths.into_iter(),
+            file: None,
+            overlap,
+            chunk_size: CHUNK_SIZE,
+            remaining: max_bytes,
+            pending: Vec::new(),
+            carry: String::new(),
+            progress: None,
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Shows a progress bar of the bytes that have been read.
+    pub fn with_progress(mut self) -> Self {
+        let total = self
+            .paths
+            .as_slice()
+            .iter()
+            .filter_map(|path| path.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum::<u64>();
+        let total = self.remaining.map_or(total, |max| total.min(max));
+
+        let pb = ProgressBar::new(total);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "[{elapsed_precise}] [{wide_bar:.white/white}] [eta: {eta:>3}] - \
+                    {binary_bytes_per_sec:>11} {bytes:>10}/{total_bytes}",
+                )
+                .expect("couldn't initialize the progress bar template")
+                .progress_chars("=>-"),
+        );
+        self.progress = Some(pb);
+        self
+    }
+
+    /// Closes the current file, and returns its last chunk if it needs one.
+    fn end_file(&mut self) -> Option<io::Result<TextChunk>> {
+        let (path, _) = self.file.take()?;
+        let pending = std::mem::take(&mut self.pending);
+        let carry = std::mem::take(&mut self.carry);
+
+        // a character that was cut off by `max_bytes` is fine, one at the end of a file isn't
+        if !pending.is_empty() && self.remaining != Some(0) {
+            return Some(Err(invalid_utf8(&path)));
+        }
+        if self.overlap == 0 || carry.is_empty() {
+            return None;
+        }
+        let start = carry.len();
+        let mut text = carry;
+        text.push_str(&" ".repeat(self.overlap));
+        Some(Ok(TextChunk { text, start }))
+    }
+}
+
+impl Iterator for TextChunks {
+    type Item = io::Result<TextChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.remaining == Some(0) {
+                return self.end_file();
+            }
+            if self.file.is_none() {
+                let path = self.paths.next()?;
+                match File::open(&path) {
+                    Ok(file) => self.file = Some((path, file)),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            let (path, file) = self.file.as_mut().unwrap();
+
+            let size = self
+                .remaining
+                .map_or(self.chunk_size, |r| r.min(self.chunk_size));
+            let mut bytes = std::mem::take(&mut self.pending);
+            let read = match file.take(size).read_to_end(&mut bytes) {
+                Ok(read) => read as u64,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Some(remaining) = self.remaining.as_mut() {
+                *remaining -= read;
+            }
+            if let Some(pb) = &self.progress {
+                pb.inc(read);
+            }
+
+            if read == 0 {
+                self.pending = bytes;
+                match self.end_file() {
+                    Some(chunk) => return Some(chunk),
+                    None => continue,
+                }
+            }
+
+            let valid = match std::str::from_utf8(&bytes) {
+                Ok(s) => s.len(),
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(_) => return Some(Err(invalid_utf8(path))),
+            };
+            self.pending = bytes.split_off(valid);
+            let mut body = String::from_utf8(bytes).expect("only valid utf-8 is left");
+            // an accent at the very start of a read isn't combined with the letter before it
+            if let Cow::Owned(normalized) = nfc(&body) {
+                body = normalized;
+            }
+
+            let mut text = std::mem::take(&mut self.carry);
+            let start = text.len();
+            text.push_str(&body);
+            self.carry = last_chars(&text, self.overlap).to_string();
+
+            return Some(Ok(TextChunk { text, start }));
+        }
+    }
+}
+
+impl Drop for TextChunks {
+    fn drop(&mut self) {
+        if let Some(pb) = &self.progress {
+            pb.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_files(name: &str, texts: &[&[u8]]) -> (PathBuf, Vec<PathBuf>) {
+        let dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let path = dir.join(format!("{i}.txt"));
+                std::fs::write(&path, text).unwrap();
+                path
+            })
+            .collect();
+        (dir, paths)
+    }
+
+    #[test]
+    fn chunks_overlap() {
+        let (dir, paths) = write_files(
+            "rusylyzer_text_chunks_test",
+            &["héllo wörld".as_bytes(), b"", b"ab"],
+        );
+
+        let chunks = TextChunks::new(paths.clone(), 2, None)
+            .with_chunk_size(4)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let bodies = chunks.iter().map(TextChunk::body).collect::<String>();
+        assert_eq!(bodies, "héllo wörld  ab  ");
+        assert!(chunks.iter().all(|c| c.text.len() <= 2 * 2 + 4));
+        assert_eq!(chunks[0].text, "hél");
+        assert_eq!(chunks[1].text, "éllo w");
+        assert_eq!(chunks.last().unwrap().text, "ab  ");
+
+        // every character starts one window of 3 that fits in a chunk
+        let windows = chunks
+            .iter()
+            .map(|c| c.text.chars().count().saturating_sub(2))
+            .sum::<usize>();
+        assert_eq!(windows, "héllo wörld".chars().count() + 2);
+
+        let text = TextChunks::new(paths.clone(), 0, Some(5))
+            .with_chunk_size(2)
+            .map(|c| c.unwrap().text)
+            .collect::<String>();
+        assert_eq!(text, "héll");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn normalized_text() {
+        let (dir, paths) = write_files(
+            "rusylyzer_text_chunks_nfc",
+            &["cafe\u{301} x\u{303}".as_bytes()],
+        );
+
+        let text = TextChunks::new(paths, 0, None)
+            .map(|c| c.unwrap().text)
+            .collect::<String>();
+        assert_eq!(text, "café x\u{303}");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_text() {
+        let (dir, paths) = write_files(
+            "rusylyzer_text_chunks_invalid",
+            &[&b"ab\xffcd"[..], &b"ab\xc3"[..]],
+        );
+
+        let err = TextChunks::new(vec![paths[0].clone()], 0, None)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(TextChunks::new(vec![paths[1].clone()], 0, None)
+            .collect::<io::Result<Vec<_>>>()
+            .is_err());
+        assert_eq!(last_chars("héllo", 3), "llo");
+        assert_eq!(last_chars("hé", 3), "hé");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,6 +1,23 @@
 use anyhow::Result;
 use fxhash::FxHashMap;
+use regex::Regex;
+use serde::Deserialize;
 use smartstring::{Compact, LazyCompact, SmartString};
+use unicode_normalization::char::{decompose_canonical, is_combining_mark};
+
+/// What a translator does with uppercase letters it would otherwise turn into a shift press and
+/// the lowercase letter, like `A` into ` a`.
+#[derive(Deserialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseFolding {
+    /// Uppercase letters become a space, which stands for shift, followed by the lowercase letter.
+    #[default]
+    Shift,
+    /// Uppercase letters become the lowercase letter, as if shift was never pressed.
+    Lowercase,
+    /// Uppercase letters are kept as characters of their own.
+    Keep,
+}
 
 #[derive(Clone)]
 pub struct Translator {
@@ -75,6 +92,55 @@ impl Translator {
         Translator::new().raw(unshift_chars).build()
     }
 
+    /// Applies `case` to every uppercase letter that is translated into a shift press and its
+    /// lowercase version. Other characters are left alone.
+    pub fn fold_case(mut self, case: CaseFolding) -> Self {
+        if case == CaseFolding::Shift {
+            return self;
+        }
+        for (&from, to) in self.table.iter_mut() {
+            if !from.is_uppercase() {
+                continue;
+            }
+            let mut chars = to.chars();
+            if let (Some(' '), Some(lower), None) = (chars.next(), chars.next(), chars.next()) {
+                if from.to_lowercase().eq(std::iter::once(lower)) {
+                    *to = match case {
+                        CaseFolding::Lowercase => SmartString::<Compact>::from(lower),
+                        _ => SmartString::<Compact>::from(from),
+                    };
+                }
+            }
+        }
+        self
+    }
+
+    /// Translates letters with accents that have no translation of their own the same way as the
+    /// letter without them, so `é` becomes whatever `e` becomes and `É` whatever `E` does.
+    pub fn strip_accents(mut self) -> Self {
+        for i in 128u32..75_000 {
+            let c = match char::from_u32(i) {
+                Some(c) if c.is_alphabetic() && !self.table.contains_key(&c) => c,
+                _ => continue,
+            };
+
+            let (mut base, mut only_marks) = (None, true);
+            decompose_canonical(c, |d| match base {
+                None => base = Some(d),
+                Some(_) => only_marks &= is_combining_mark(d),
+            });
+            match base {
+                Some(base) if base != c && only_marks => {
+                    if let Some(to) = self.table.get(&base).cloned() {
+                        self.table.insert(c, to);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self
+    }
+
     pub fn translate(&self, s: &str) -> SmartString<LazyCompact> {
         let mut res = SmartString::<LazyCompact>::new();
 
@@ -141,6 +207,21 @@ impl TranslatorBuilder {
         self
     }
 
+    /// Keeps every character that matches `pattern` as is, like `\p{L}` for every letter. The
+    /// pattern is matched against every character on its own.
+    pub fn keep_matching(&mut self, pattern: &Regex) -> &mut Self {
+        let mut buf = [0; 4];
+        for i in 0u32..75_000 {
+            match char::from_u32(i) {
+                Some(c) if !c.is_control() && pattern.is_match(c.encode_utf8(&mut buf)) => {
+                    self.keep_one(c);
+                }
+                _ => {}
+            }
+        }
+        self
+    }
+
     pub fn one_to_one(&mut self, from: &str, to: &str) -> &mut Self {
         assert_eq!(from.chars().count(), to.chars().count());
 
@@ -568,6 +649,28 @@ mod tests {
         assert_eq!(translator.translate("ŽAaØ ď"), "* z aa  ď");
     }
 
+    #[test]
+    fn normalization_rules() {
+        let translator = Translator::new()
+            .letters_to_lowercase("ae")
+            .custom_unshift("?", "/")
+            .build();
+        assert_eq!(translator.translate("Ae?"), " ae /");
+
+        let lowercase = translator.clone().fold_case(CaseFolding::Lowercase);
+        assert_eq!(lowercase.translate("Ae?"), "ae /");
+        let keep = translator.clone().fold_case(CaseFolding::Keep);
+        assert_eq!(keep.translate("Ae?"), "Ae /");
+
+        assert_eq!(translator.translate("éÉä"), "   ");
+        let stripped = translator.fold_case(CaseFolding::Lowercase).strip_accents();
+        assert_eq!(stripped.translate("éÉäø"), "eea ");
+
+        let greek = Regex::new(r"\p{Greek}").unwrap();
+        let translator = Translator::new().keep_matching(&greek).keep("a").build();
+        assert_eq!(translator.translate("αΩab"), "αΩa ");
+    }
+
     #[test]
     fn add_translators_together() {
         let t1 = Translator::new()
@@ -0,0 +1,156 @@
+use std::hash::{Hash, Hasher};
+
+use crate::language_data::TrigramData;
+use crate::utility::ConvertU8;
+
+/// Extension of the trigram cache stored next to the json of a language, like `english.trigrams`.
+pub const TRIGRAM_CACHE_EXTENSION: &str = "trigrams";
+
+const MAGIC: &[u8; 4] = b"RTRI";
+const RECORD_LEN: usize = 11;
+
+/// Key of a language data json, which changes whenever the json or the way its trigrams are
+/// converted does.
+pub fn key(json: &str) -> u64 {
+    let mut hasher = fxhash::FxHasher::default();
+
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    json.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Trigrams converted to bytes by `con`, in a compact little endian format: `RTRI`, the `key` of
+/// the json they came from, the amount of characters as a `u32` followed by every character, the
+/// amount of trigrams as a `u64` and then a record of 3 character bytes and an `f64` frequency for
+/// every trigram. Records all have the same size, so reading them back only takes a length
+/// check instead of parsing.
+pub fn encode(key: u64, con: &ConvertU8, trigrams: &TrigramData) -> Vec<u8> {
+    let chars = con.chars();
+    let mut res = Vec::with_capacity(24 + chars.len() * 4 + trigrams.len() * RECORD_LEN);
+
+    res.extend_from_slice(MAGIC);
+    res.extend_from_slice(&key.to_le_bytes());
+    res.extend_from_slice(&(chars.len() as u32).to_le_bytes());
+    for &c in chars {
+        res.extend_from_slice(&(c as u32).to_le_bytes());
+    }
+    res.extend_from_slice(&(trigrams.len() as u64).to_le_bytes());
+    for (trigram, freq) in trigrams {
+        res.extend_from_slice(trigram);
+        res.extend_from_slice(&freq.to_le_bytes());
+    }
+    res
+}
+
+fn take<const N: usize>(bytes: &[u8]) -> Option<([u8; N], &[u8])> {
+    if bytes.len() < N {
+        return None;
+    }
+    let (head, rest) = bytes.split_at(N);
+    Some((head.try_into().ok()?, rest))
+}
+
+/// Characters and trigrams in `bytes`, or `None` if it isn't a complete trigram cache made with
+/// `key`.
+pub fn decode(bytes: &[u8], key: u64) -> Option<(Vec<char>, TrigramRecords<'_>)> {
+    let rest = bytes.strip_prefix(MAGIC)?;
+    let (cache_key, rest) = take::<8>(rest)?;
+    if u64::from_le_bytes(cache_key) != key {
+        return None;
+    }
+
+    let (len, mut rest) = take::<4>(rest)?;
+    let mut chars = Vec::new();
+    for _ in 0..u32::from_le_bytes(len) {
+        let (c, next) = take::<4>(rest)?;
+        chars.push(char::from_u32(u32::from_le_bytes(c))?);
+        rest = next;
+    }
+
+    let (count, rest) = take::<8>(rest)?;
+    if rest.len() as u64 != u64::from_le_bytes(count) * RECORD_LEN as u64 {
+        return None;
+    }
+    let records = TrigramRecords {
+        records: rest.chunks_exact(RECORD_LEN),
+    };
+    Some((chars, records))
+}
+
+/// Trigrams in the bytes of a cache, decoded one record at a time.
+#[derive(Clone, Debug)]
+pub struct TrigramRecords<'a> {
+    records: std::slice::ChunksExact<'a, u8>,
+}
+
+impl Iterator for TrigramRecords<'_> {
+    type Item = ([u8; 3], f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.records.next()?;
+        let freq = f64::from_le_bytes(record[3..].try_into().unwrap());
+        Some(([record[0], record[1], record[2]], freq))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.records.size_hint()
+    }
+}
+
+impl ExactSizeIterator for TrigramRecords<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::FileSource;
+    use crate::language_data::LanguageData;
+
+    #[test]
+    fn trigram_records() {
+        let con = ConvertU8::with_chars("abcé");
+        let trigrams = vec![([0, 1, 2], 0.5), ([3, 2, 0], 0.25)];
+        let bytes = encode(7, &con, &trigrams);
+
+        let (chars, records) = decode(&bytes, 7).unwrap();
+        assert_eq!(chars, con.chars());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records.collect::<TrigramData>(), trigrams);
+
+        assert!(decode(&bytes, 8).is_none());
+        assert!(decode(&bytes[..bytes.len() - 1], 7).is_none());
+        assert!(decode(&bytes[1..], 7).is_none());
+    }
+
+    #[test]
+    fn cached_language_data() {
+        // a directory of its own, so other runs of this test don't remove the cache under it
+        let dir = std::env::temp_dir().join(format!(
+            "rusylyzer_trigram_cache_test_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(
+            "static/language_data/english.json",
+            dir.join("english.json"),
+        )
+        .unwrap();
+        let source = FileSource {
+            language_data_dir: dir.clone(),
+            ..Default::default()
+        };
+        let cache_path = dir.join(format!("english.{TRIGRAM_CACHE_EXTENSION}"));
+        let _ = std::fs::remove_file(&cache_path);
+
+        let from_json = LanguageData::from_source(&source, "english").unwrap();
+        assert!(!cache_path.exists());
+        LanguageData::cache_trigrams(&source, "english").unwrap();
+        assert!(cache_path.exists());
+        let from_cache = LanguageData::from_source(&source, "english").unwrap();
+
+        assert_eq!(from_cache.trigrams, from_json.trigrams);
+        assert_eq!(from_cache.convert_u8.chars(), from_json.convert_u8.chars());
+        assert_eq!(from_cache.bigrams, from_json.bigrams);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+use serde::Deserialize;
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum TrigramPattern {
     Alternate,
@@ -82,12 +84,29 @@ impl Finger {
         self as u8 == other as u8
     }
 
+    /// Where the finger sits on the keyboard from left to right, which puts the thumbs between
+    /// the index fingers. Comparing positions is what makes a roll go inwards or outwards.
+    const fn position(self) -> u8 {
+        match self {
+            LP => 0,
+            LR => 1,
+            LM => 2,
+            LI => 3,
+            LT => 4,
+            RT => 5,
+            RI => 6,
+            RM => 7,
+            RR => 8,
+            RP => 9,
+        }
+    }
+
     pub const fn gt(self, other: Self) -> bool {
-        self as u8 > other as u8
+        self.position() > other.position()
     }
 
     pub const fn lt(self, other: Self) -> bool {
-        (self as u8) < (other as u8)
+        self.position() < other.position()
     }
 
     const fn hand(&self) -> Hand {
@@ -257,16 +276,25 @@ impl Trigram {
     }
 }
 
-const fn get_trigram_combinations() -> [TrigramPattern; 512] {
-    let mut combinations: [TrigramPattern; 512] = [TrigramPattern::Other; 512];
+/// Number of fingers a key can be assigned to, being the 8 regular fingers and both thumbs.
+pub const FINGER_COUNT: usize = 10;
+
+/// Index into a `TrigramTable` for three fingers, as numbered by `Finger::from_usize`. Every finger
+/// gets 4 bits, which leaves room for the thumbs (8 and 9).
+pub const fn trigram_index(f1: usize, f2: usize, f3: usize) -> usize {
+    (f1 << 8) | (f2 << 4) | f3
+}
+
+const fn get_trigram_combinations() -> TrigramTable {
+    let mut combinations: TrigramTable = [TrigramPattern::Invalid; 4096];
 
     let mut c3 = 0;
-    while c3 < 8 {
+    while c3 < FINGER_COUNT {
         let mut c2 = 0;
-        while c2 < 8 {
+        while c2 < FINGER_COUNT {
             let mut c1 = 0;
-            while c1 < 8 {
-                let index = c3 * 64 + c2 * 8 + c1;
+            while c1 < FINGER_COUNT {
+                let index = trigram_index(c3, c2, c1);
                 let trigram = Trigram::new(
                     Finger::from_usize(c3),
                     Finger::from_usize(c2),
@@ -282,7 +310,130 @@ const fn get_trigram_combinations() -> [TrigramPattern; 512] {
     combinations
 }
 
-pub static TRIGRAM_COMBINATIONS: [TrigramPattern; 512] = get_trigram_combinations();
+pub static TRIGRAM_COMBINATIONS: TrigramTable = get_trigram_combinations();
+
+/// Trigram patterns indexed by `trigram_index`. Indices that don't belong to a finger are `Invalid`.
+pub type TrigramTable = [TrigramPattern; 4096];
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FingerKind {
+    Pinky,
+    Ring,
+    Middle,
+    Index,
+    Thumb,
+}
+
+impl From<Finger> for FingerKind {
+    fn from(value: Finger) -> Self {
+        match value {
+            LP | RP => Self::Pinky,
+            LR | RR => Self::Ring,
+            LM | RM => Self::Middle,
+            LI | RI => Self::Index,
+            LT | RT => Self::Thumb,
+        }
+    }
+}
+
+/// Rules deciding how finger combinations are classified, which can be changed from the
+/// `[trigram_rules]` section of the config. The defaults match `TRIGRAM_COMBINATIONS`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TrigramRules {
+    /// A redirect is bad when all three of its keys are pressed by one of these fingers.
+    pub bad_redirect_fingers: Vec<FingerKind>,
+    /// Whether redirects where the first and last key use the same finger get their own pattern.
+    pub redirect_sfs: bool,
+    /// Whether alternates where the first and last key use the same finger get their own pattern.
+    pub alternate_sfs: bool,
+}
+
+impl Default for TrigramRules {
+    fn default() -> Self {
+        Self {
+            bad_redirect_fingers: vec![FingerKind::Pinky, FingerKind::Ring, FingerKind::Middle],
+            redirect_sfs: true,
+            alternate_sfs: true,
+        }
+    }
+}
+
+impl TrigramRules {
+    fn is_bad(&self, finger: Finger) -> bool {
+        self.bad_redirect_fingers
+            .contains(&FingerKind::from(finger))
+    }
+
+    /// Builds the lookup table used for scoring, indexed the same way as `TRIGRAM_COMBINATIONS`.
+    pub fn build_table(&self) -> TrigramTable {
+        use TrigramPattern::*;
+
+        let mut table = TRIGRAM_COMBINATIONS;
+
+        for (index, pattern) in table.iter_mut().enumerate() {
+            if *pattern == Invalid {
+                continue;
+            }
+            let f1 = Finger::from_usize(index >> 8);
+            let f2 = Finger::from_usize((index >> 4) & 0b1111);
+            let f3 = Finger::from_usize(index & 0b1111);
+            let bad = self.is_bad(f1) && self.is_bad(f2) && self.is_bad(f3);
+
+            *pattern = match *pattern {
+                Redirect | BadRedirect if bad => BadRedirect,
+                Redirect | BadRedirect => Redirect,
+                RedirectSfs | BadRedirectSfs => match (self.redirect_sfs, bad) {
+                    (true, true) => BadRedirectSfs,
+                    (true, false) => RedirectSfs,
+                    (false, true) => BadRedirect,
+                    (false, false) => Redirect,
+                },
+                AlternateSfs if !self.alternate_sfs => Alternate,
+                pattern => pattern,
+            };
+        }
+        table
+    }
+}
+
+/// Fingers of a single hand, ordered from the outside in. Used when all keys are typed by one hand.
+const ONE_HAND_FINGERS: [Finger; 5] = [LP, LR, LM, LI, LT];
+
+const fn get_one_hand_combinations() -> [TrigramPattern; 125] {
+    let mut combinations: [TrigramPattern; 125] = [TrigramPattern::Other; 125];
+
+    let mut c1 = 0;
+    while c1 < 5 {
+        let mut c2 = 0;
+        while c2 < 5 {
+            let mut c3 = 0;
+            while c3 < 5 {
+                let index = c1 * 25 + c2 * 5 + c3;
+                let trigram = Trigram::new(
+                    ONE_HAND_FINGERS[c1],
+                    ONE_HAND_FINGERS[c2],
+                    ONE_HAND_FINGERS[c3],
+                );
+                // every trigram is on one hand, so there is no alternation and every onehand is a roll
+                combinations[index] = match trigram.get_one_hand() {
+                    TrigramPattern::Onehand if c1 < c2 => TrigramPattern::Inroll,
+                    TrigramPattern::Onehand => TrigramPattern::Outroll,
+                    pattern => pattern,
+                };
+                c3 += 1;
+            }
+            c2 += 1;
+        }
+        c1 += 1;
+    }
+    combinations
+}
+
+/// Trigram patterns for one handed layouts, indexed by `f1 * 25 + f2 * 5 + f3` where fingers go from
+/// pinky (0) to thumb (4).
+pub static ONE_HAND_COMBINATIONS: [TrigramPattern; 125] = get_one_hand_combinations();
 
 #[cfg(test)]
 mod tests {
@@ -414,6 +565,79 @@ mod tests {
         assert!(t4.is_bad_redir());
     }
 
+    #[test]
+    fn thumbs() {
+        let pattern = |f1: Finger, f2: Finger, f3: Finger| {
+            TRIGRAM_COMBINATIONS[trigram_index(f1 as usize, f2 as usize, f3 as usize)]
+        };
+
+        assert_eq!(pattern(LT, RI, LM), Alternate);
+        assert_eq!(pattern(RM, LT, RM), AlternateSfs);
+        assert_eq!(pattern(LI, LT, RM), Inroll);
+        assert_eq!(pattern(LT, LI, RM), Outroll);
+        assert_eq!(pattern(LM, RI, RT), Inroll);
+        assert_eq!(pattern(RT, RI, LM), Outroll);
+        assert_eq!(pattern(LM, LI, LT), Onehand);
+        assert_eq!(pattern(LI, LT, LM), Redirect);
+        assert_eq!(pattern(LT, LT, RI), Sfb);
+        assert_eq!(pattern(LT, RT, LT), AlternateSfs);
+
+        assert_eq!(FingerKind::from(RT), FingerKind::Thumb);
+        assert_eq!(TRIGRAM_COMBINATIONS[trigram_index(10, 0, 0)], Invalid);
+    }
+
+    #[test]
+    fn one_hand_combinations() {
+        let pattern =
+            |f1: usize, f2: usize, f3: usize| ONE_HAND_COMBINATIONS[f1 * 25 + f2 * 5 + f3];
+
+        assert_eq!(pattern(0, 1, 2), Inroll);
+        assert_eq!(pattern(1, 3, 4), Inroll);
+        assert_eq!(pattern(3, 2, 0), Outroll);
+        assert_eq!(pattern(1, 3, 2), Redirect);
+        assert_eq!(pattern(3, 1, 3), RedirectSfs);
+        assert_eq!(pattern(0, 2, 1), BadRedirect);
+        assert_eq!(pattern(2, 0, 2), BadRedirectSfs);
+        assert_eq!(pattern(1, 1, 2), BadSfb);
+        assert_eq!(pattern(4, 3, 3), BadSfb);
+        assert_eq!(pattern(2, 2, 2), Sft);
+    }
+
+    #[test]
+    fn default_rules_table() {
+        assert_eq!(TrigramRules::default().build_table(), TRIGRAM_COMBINATIONS);
+    }
+
+    #[test]
+    fn custom_rules_table() {
+        let index = |f1: Finger, f2: Finger, f3: Finger| {
+            trigram_index(f1 as usize, f2 as usize, f3 as usize)
+        };
+
+        let rules = TrigramRules {
+            bad_redirect_fingers: vec![FingerKind::Pinky, FingerKind::Ring],
+            redirect_sfs: false,
+            alternate_sfs: false,
+        };
+        let table = rules.build_table();
+
+        assert_eq!(table[index(LR, LM, LP)], Redirect);
+        assert_eq!(table[index(LP, LR, LP)], BadRedirect);
+        assert_eq!(table[index(LI, LR, LI)], Redirect);
+        assert_eq!(table[index(LR, RI, LR)], Alternate);
+        assert_eq!(table[index(LR, LM, LI)], Onehand);
+
+        let rules = TrigramRules {
+            bad_redirect_fingers: vec![FingerKind::Index, FingerKind::Middle, FingerKind::Ring],
+            ..Default::default()
+        };
+        let table = rules.build_table();
+
+        assert_eq!(table[index(LM, LI, LR)], BadRedirect);
+        assert_eq!(table[index(LI, LM, LI)], BadRedirectSfs);
+        assert_eq!(table[index(LR, LM, LP)], Redirect);
+    }
+
     #[test]
     fn trigram_combinations() {
         let dvorak_bytes = CON.to_lossy("',.pyfgcrlaoeuidhtns;qjkxbmwvz".chars());
@@ -1,3 +1,5 @@
+use crate::error::RusylyzerError;
+use crate::graphemes::check_single_chars;
 use crate::languages_cfg::read_cfg;
 
 use arrayvec::ArrayVec;
@@ -16,11 +18,41 @@ pub fn shuffle_pins<const N: usize, T>(slice: &mut [T], pins: &[usize]) {
     }
 }
 
-pub static I_TO_COL: [usize; 30] = [
+/// Finger that presses every position on the layout, from left pinky (0) to right pinky (7).
+pub type FingerAssignment = [usize; 30];
+
+pub static I_TO_COL: FingerAssignment = [
     0, 1, 2, 3, 3, 4, 4, 5, 6, 7, 0, 1, 2, 3, 3, 4, 4, 5, 6, 7, 0, 1, 2, 3, 3, 4, 4, 5, 6, 7,
 ];
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+/// Reads a finger for every position from `assignment`, ignoring whitespace.
+pub fn parse_finger_assignment(assignment: &str) -> anyhow::Result<FingerAssignment> {
+    let fingers = assignment
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c.to_digit(10) {
+            Some(f) if f <= 7 => Ok(f as usize),
+            _ => Err(anyhow::format_err!(
+                "'{c}' is not a valid finger, use 0 (left pinky) up to 7 (right pinky)"
+            )),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let len = fingers.len();
+    fingers.try_into().map_err(|_| {
+        anyhow::format_err!("a finger assignment needs exactly 30 fingers, but {len} were provided")
+    })
+}
+
+/// Fingers of the left and right thumb, numbered after the 8 fingers in `I_TO_COL`.
+pub const THUMB_FINGERS: [usize; 2] = [8, 9];
+
+/// Whether `finger`, numbered the same way as `FastLayout::char_to_finger`, is on the left hand.
+pub const fn is_left_hand(finger: usize) -> bool {
+    finger < 4 || finger == THUMB_FINGERS[0]
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct PosPair(pub usize, pub usize);
 
 const AFFECTS_SCISSOR: [bool; 30] = [
@@ -61,6 +93,74 @@ impl std::fmt::Display for PosPair {
     }
 }
 
+/// A change generation can make to a layout. Moves are ordered by their positions, swaps first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Move {
+    /// Swaps the keys on two positions.
+    Swap(PosPair),
+    /// Moves the key on the first position to the second, the one on the second to the third and
+    /// the one on the third to the first, which is the same as swapping the first position with the
+    /// second and then with the third.
+    Rotate([usize; 3]),
+}
+
+impl Move {
+    /// The move that puts every key back where it was.
+    pub fn inverse(&self) -> Self {
+        match *self {
+            Self::Swap(swap) => Self::Swap(swap),
+            Self::Rotate([i1, i2, i3]) => Self::Rotate([i1, i3, i2]),
+        }
+    }
+}
+
+impl From<PosPair> for Move {
+    fn from(swap: PosPair) -> Self {
+        Self::Swap(swap)
+    }
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Swap(swap) => write!(f, "{swap}"),
+            Self::Rotate([i1, i2, i3]) => write!(f, "({i1} -> {i2} -> {i3})"),
+        }
+    }
+}
+
+/// A score that can be sorted by, even when it is NaN. NaN is lower than every other score, so a
+/// layout that scores NaN ends up as the worst one instead of panicking the sort.
+#[derive(Copy, Clone, Debug)]
+pub struct TotalScore(pub f64);
+
+impl PartialEq for TotalScore {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for TotalScore {}
+
+impl PartialOrd for TotalScore {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TotalScore {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        match (self.0.is_nan(), other.0.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => self.0.partial_cmp(&other.0).unwrap(),
+        }
+    }
+}
+
 pub const POSSIBLE_SWAPS: [PosPair; 435] = get_possible_swaps();
 
 const fn get_possible_swaps() -> [PosPair; 435] {
@@ -80,6 +180,9 @@ const fn get_possible_swaps() -> [PosPair; 435] {
     res
 }
 
+/// Most characters the language data of a language can have. Less common ones are left out.
+pub const MAX_CHARS: usize = 128;
+
 #[derive(Clone, Default)]
 pub struct ConvertU8 {
     from: Vec<char>,
@@ -113,6 +216,16 @@ impl ConvertU8 {
         }
     }
 
+    /// Same as `to_single`, but `None` when `c` is new and there is no room for more characters.
+    /// `u8::MAX` is never used, because it stands for an empty thumb key.
+    pub fn try_to_single(&mut self, c: char) -> Option<u8> {
+        if self.to.contains_key(&c) || self.from.len() < u8::MAX as usize {
+            Some(self.to_single(c))
+        } else {
+            None
+        }
+    }
+
     pub fn to_bigram(&mut self, from: [char; 2]) -> [u8; 2] {
         [self.to_single(from[0]), self.to_single(from[1])]
     }
@@ -193,6 +306,11 @@ impl ConvertU8 {
             .collect()
     }
 
+    /// Every character, at the index it is converted to.
+    pub fn chars(&self) -> &[char] {
+        &self.from
+    }
+
     pub fn len(&self) -> u8 {
         debug_assert_eq!(self.to.len(), self.from.len());
 
@@ -200,7 +318,7 @@ impl ConvertU8 {
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Clone, Debug)]
 pub enum KeyboardType {
     AnsiAngle,
     IsoAngle,
@@ -235,6 +353,57 @@ impl TryFrom<String> for KeyboardType {
     }
 }
 
+/// Vertical offset of every column on a column staggered keyboard, in key widths.
+const COLSTAG_OFFSETS: [f64; 10] = [0.35, 0.15, 0.0, 0.1, 0.2, 0.2, 0.1, 0.0, 0.15, 0.35];
+
+/// Horizontal offset of every row on a row staggered keyboard, in key widths.
+const ROWSTAG_OFFSETS: [f64; 3] = [0.0, 0.25, 0.75];
+
+/// Where a key goes on a keyboard of `keyboard_type`, in key widths from the top left.
+pub fn key_position(i: usize, keyboard_type: &KeyboardType) -> (f64, f64) {
+    use KeyboardType::*;
+
+    let (col, row) = (i % 10, i / 10);
+    match keyboard_type {
+        Ortho => (col as f64 + f64::from(u8::from(col >= 5)) * 0.5, row as f64),
+        Colstag => (
+            col as f64 + f64::from(u8::from(col >= 5)) * 0.5,
+            row as f64 + COLSTAG_OFFSETS[col],
+        ),
+        _ => (col as f64 + ROWSTAG_OFFSETS[row], row as f64),
+    }
+}
+
+/// The key every finger rests on, from left pinky to right pinky.
+pub const HOME_KEYS: [usize; 8] = [10, 11, 12, 13, 16, 17, 18, 19];
+
+/// Straight line distance between 2 keys on a keyboard of `keyboard_type`, in key widths.
+pub fn key_distance(i1: usize, i2: usize, keyboard_type: &KeyboardType) -> f64 {
+    let (x1, y1) = key_position(i1, keyboard_type);
+    let (x2, y2) = key_position(i2, keyboard_type);
+    ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
+}
+
+/// How far every key is from the home key of the finger that presses it, in key widths.
+pub fn get_home_distances(keyboard_type: &KeyboardType, fingers: &FingerAssignment) -> [f64; 30] {
+    let mut res = [0.0; 30];
+    for (i, dist) in res.iter_mut().enumerate() {
+        *dist = key_distance(i, HOME_KEYS[fingers[i]], keyboard_type);
+    }
+    res
+}
+
+/// How much shorter the way between the keys of every same finger pair is when going directly,
+/// instead of returning to the home key in between. Lines up with [`get_finger_pairs`].
+pub fn get_sfb_travel(keyboard_type: &KeyboardType, fingers: &FingerAssignment) -> Vec<f64> {
+    let home = get_home_distances(keyboard_type, fingers);
+    get_finger_pairs(fingers)
+        .0
+        .into_iter()
+        .map(|PosPair(i1, i2)| key_distance(i1, i2, keyboard_type) - home[i1] - home[i2])
+        .collect()
+}
+
 pub fn get_effort_map(heatmap_weight: f64, ktype: KeyboardType) -> [f64; 30] {
     use KeyboardType::*;
 
@@ -270,6 +439,52 @@ pub fn get_effort_map(heatmap_weight: f64, ktype: KeyboardType) -> [f64; 30] {
     res
 }
 
+/// Every pair of positions pressed by the same finger, grouped by finger from left pinky to right
+/// pinky, together with the start and length of the pairs of every finger. Positions are paired
+/// column by column, so the default assignment gives the same pairs as [`get_sfb_indices`].
+pub fn get_finger_pairs(fingers: &FingerAssignment) -> (Vec<PosPair>, [(usize, usize); 8]) {
+    let mut pairs = Vec::new();
+    let mut ranges = [(0, 0); 8];
+
+    for (finger, range) in ranges.iter_mut().enumerate() {
+        let mut keys = (0..30)
+            .filter(|&i| fingers[i] == finger)
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|&i| (i % 10, i / 10));
+
+        let start = pairs.len();
+        for (k, &i1) in keys.iter().enumerate() {
+            pairs.extend(keys[(k + 1)..].iter().map(|&i2| PosPair(i1, i2)));
+        }
+        *range = (start, pairs.len() - start);
+    }
+    (pairs, ranges)
+}
+
+/// Distance between the keys of a pair pressed by `finger`, as used for finger speed. Weaker
+/// fingers count the same distance as longer, except for the index fingers.
+pub fn pair_distance(pair: PosPair, finger: usize, lat_multiplier: f64) -> f64 {
+    let strength = match finger {
+        0 | 7 => 1.4,
+        1 | 6 => 3.6,
+        2 | 5 => 4.8,
+        _ => 5.5,
+    };
+    let x_dist = (pair.0 % 10).abs_diff(pair.1 % 10) as f64;
+    let y_dist = (pair.0 / 10).abs_diff(pair.1 / 10) as f64;
+
+    (x_dist.powi(2) * lat_multiplier + y_dist.powi(2)).powf(0.65) * (5.5 / strength)
+}
+
+/// Like [`get_fspeed`], but for any finger assignment. Lines up with [`get_finger_pairs`].
+pub fn get_finger_fspeed(fingers: &FingerAssignment, lat_multiplier: f64) -> Vec<(PosPair, f64)> {
+    get_finger_pairs(fingers)
+        .0
+        .into_iter()
+        .map(|pair| (pair, pair_distance(pair, fingers[pair.0], lat_multiplier)))
+        .collect()
+}
+
 pub fn get_fspeed(lat_multiplier: f64) -> [(PosPair, f64); 48] {
     let mut res = Vec::new();
     for (b, dist) in get_sfb_indices().iter().zip(get_distances(lat_multiplier)) {
@@ -412,6 +627,21 @@ pub const fn get_lsb_indices() -> [PosPair; 16] {
     res
 }
 
+/// Pairs of a top and a bottom row key on the same hand that are pressed by the same finger or
+/// fingers next to each other, which means skipping over the home row.
+pub fn get_row_skip_indices(fingers: &FingerAssignment) -> Vec<PosPair> {
+    let mut res = Vec::new();
+    for top in 0..10 {
+        for bottom in 20..30 {
+            let (f1, f2) = (fingers[top], fingers[bottom]);
+            if is_left_hand(f1) == is_left_hand(f2) && f1.abs_diff(f2) <= 1 {
+                res.push(PosPair(top, bottom));
+            }
+        }
+    }
+    res
+}
+
 pub const fn get_scissor_indices() -> [PosPair; 28] {
     let mut res = [PosPair::default(); 28];
 
@@ -504,12 +734,137 @@ pub(crate) fn layout_name(entry: &std::fs::DirEntry) -> Option<String> {
     None
 }
 
-pub(crate) fn format_layout_str(layout_str: &str) -> String {
+/// Lines of a layout file with keys on them, so without comments and blank lines. The 30 keys
+/// come first, followed by an optional line with the 2 thumb keys and an optional second layer.
+fn key_lines(layout_str: &str) -> Vec<&str> {
     layout_str
-        .split("\n")
+        .lines()
+        .filter(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .collect()
+}
+
+/// Lines the 30 keys of `key_lines` are on: 3 rows of 10, or all 30 on a single line without
+/// spaces.
+fn main_rows(lines: &[&str]) -> usize {
+    match lines.first().map(|line| line.trim()) {
+        Some(line) if !line.contains(char::is_whitespace) && line.chars().count() == 30 => 1,
+        _ => 3,
+    }
+}
+
+/// The 30 keys of a layout file, row by row. Anything that could make the keys end up somewhere
+/// else than intended is an error, like a row that doesn't have 10 keys or a line after the
+/// layout that isn't thumb keys, a layer or a magic key.
+pub(crate) fn format_layout_str(layout_str: &str) -> Result<String, RusylyzerError> {
+    let error = |message: String| Err(RusylyzerError::LayoutParse(message));
+    let lines = key_lines(layout_str);
+    let rows = main_rows(&lines);
+    if lines.len() < rows {
+        return error(format!(
+            "a layout needs 3 rows of 10 keys, but it has {}",
+            lines.len()
+        ));
+    }
+
+    let mut res = String::new();
+    for (row, line) in lines[..rows].iter().enumerate() {
+        let keys = line.split_whitespace().collect::<String>();
+        check_single_chars(&keys)?;
+        match keys.chars().count() {
+            count if count * rows == 30 => res.push_str(&keys),
+            count => {
+                return error(format!(
+                    "row {} has {count} keys instead of 10: '{}'",
+                    row + 1,
+                    line.trim()
+                ))
+            }
+        }
+    }
+
+    let mut rest = lines[rows..].iter().copied().peekable();
+    if let Some(line) = rest.next_if(|line| layer_hold(line).is_none() && magic(line).is_none()) {
+        let thumbs = line.split_whitespace().count();
+        if thumbs > 2 {
+            return error(format!(
+                "the line after the 30 keys can only have the 2 thumb keys, but it has {thumbs}: \
+                '{}'",
+                line.trim()
+            ));
+        }
+    }
+    // the rows of a layer are checked by `Layer::parse`
+    if rest.next_if(|line| layer_hold(line).is_some()).is_some() {
+        rest.nth(2);
+    }
+    // the magic key is checked by `MagicKey::parse`
+    rest.next_if(|line| magic(line).is_some());
+    match rest.next() {
+        Some(line) => error(format!(
+            "'{}' comes after the layout, but it isn't thumb keys, a layer or a magic key",
+            line.trim()
+        )),
+        None => Ok(res),
+    }
+}
+
+/// Thumb keys from the optional line after the 30 keys of a layout file, which holds the left and
+/// right thumb key. A `_` leaves that thumb empty.
+pub(crate) fn format_thumbs_str(layout_str: &str) -> [Option<char>; 2] {
+    let mut res = [None; 2];
+    let lines = key_lines(layout_str);
+
+    if let Some(line) = lines
+        .get(main_rows(&lines))
+        .copied()
+        .filter(|line| layer_hold(line).is_none() && magic(line).is_none())
+    {
+        for (thumb, key) in res.iter_mut().zip(line.split_whitespace()) {
+            *thumb = key.chars().next().filter(|&c| c != '_');
+        }
+    }
+    res
+}
+
+/// What comes after `layer` on the line that starts a second layer, like `layer right`.
+fn layer_hold(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix("layer")?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(rest.trim())
+}
+
+/// What comes after `magic` on the line that describes a magic key, like `magic @ ou`.
+fn magic(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix("magic")?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(rest.trim())
+}
+
+/// The magic key of a layout file and its rules, from the `magic` line that comes last. See
+/// `MagicKey::parse` for the format.
+pub(crate) fn format_magic_str(layout_str: &str) -> Option<&str> {
+    let lines = key_lines(layout_str);
+    lines
+        .iter()
+        .copied()
+        .skip(main_rows(&lines))
+        .find_map(magic)
+}
+
+/// The hold key and the 30 keys of the second layer of a layout file, which are the 3 rows after
+/// a `layer <hold>` line below the main layer and its thumb keys. A `_` leaves that key empty.
+pub(crate) fn format_layer_str(layout_str: &str) -> Option<(&str, Vec<char>)> {
+    let lines = key_lines(layout_str);
+    let mut lines = lines.iter().copied().skip(main_rows(&lines));
+    let hold = lines.by_ref().take(2).find_map(layer_hold)?;
+
+    let keys = lines
         .take(3)
-        .map(|line| line.split_whitespace().take(10).collect::<String>())
-        .collect::<String>()
+        .flat_map(|line| line.split_whitespace().take(10))
+        .filter_map(|key| key.chars().next())
+        .collect();
+    Some((hold, keys))
 }
 
 #[cfg(test)]
@@ -534,6 +889,68 @@ mod tests {
         }
     }
 
+    #[test]
+    fn row_skip_indices() {
+        let indices = get_row_skip_indices(&I_TO_COL);
+
+        assert_eq!(indices.len(), 30);
+        assert!(indices.contains(&PosPair(0, 20)));
+        assert!(indices.contains(&PosPair(0, 21)));
+        assert!(indices.contains(&PosPair(4, 22)));
+        assert!(!indices.contains(&PosPair(0, 22)));
+        assert!(!indices.contains(&PosPair(4, 25)));
+        assert!(indices.iter().all(|pair| pair.affects_scissor()));
+    }
+
+    #[test]
+    fn travel_distances() {
+        let home = get_home_distances(&KeyboardType::Ortho, &I_TO_COL);
+        for i in HOME_KEYS {
+            assert_eq!(home[i], 0.0);
+        }
+        assert!(home[2].approx_eq_dbg(1.0, 7));
+        assert!(home[24].approx_eq_dbg(2.0f64.sqrt(), 7));
+
+        // going directly is never longer than going through the home key
+        let sfb_travel = get_sfb_travel(&KeyboardType::Ortho, &I_TO_COL);
+        assert!(sfb_travel.iter().all(|&travel| travel <= 1e-9));
+        assert!(sfb_travel.iter().any(|&travel| travel < 0.0));
+    }
+
+    #[test]
+    fn finger_pairs() {
+        let (pairs, ranges) = get_finger_pairs(&I_TO_COL);
+        let fspeed = get_finger_fspeed(&I_TO_COL, 1.3);
+        let default = get_fspeed(1.3);
+
+        assert_eq!(pairs.len(), 48);
+        assert_eq!(ranges[0], (0, 3));
+        assert_eq!(ranges[3], (9, 15));
+        assert_eq!(ranges[7], (45, 3));
+        for (pair, dist) in default {
+            assert!(fspeed.contains(&(pair, dist)), "failed on {pair:?}");
+        }
+
+        // the left middle finger takes the bottom key of the left index column
+        let mut fingers = I_TO_COL;
+        fingers[23] = 2;
+        let (pairs, ranges) = get_finger_pairs(&fingers);
+
+        assert_eq!(pairs.len(), 46);
+        assert_eq!(ranges[2], (6, 6));
+        assert_eq!(ranges[3], (12, 10));
+        assert!(pairs.contains(&PosPair(2, 23)));
+        assert!(!pairs.contains(&PosPair(3, 23)));
+        assert!(pair_distance(PosPair(22, 23), 2, 1.3) > pair_distance(PosPair(3, 4), 3, 1.3));
+
+        assert_eq!(
+            parse_finger_assignment(&"0123344567".repeat(3)).unwrap(),
+            I_TO_COL
+        );
+        assert!(parse_finger_assignment("01233 44567").is_err());
+        assert!(parse_finger_assignment(&"0123344568".repeat(3)).is_err());
+    }
+
     #[test]
     fn approx_eq() {
         assert!((0.123456789).approx_eq(0.0, 0));
@@ -568,9 +985,90 @@ mod tests {
     #[test]
     fn format_layout_string() {
         let str1 = "v m l c p  q z u o , \ns t r d y  f n e a i \nx k j g w  b h ; ' .";
-        let str2 = "a b    c d e f g h i \n j k l \n m n o p q \n r s t u v w x y z";
+        let keys = "vmlcpqzuo,strdyfneaixkjgwbh;'.";
+
+        assert_eq!(format_layout_str(str1).unwrap(), keys);
+        let str3 = "# parent: sturdy\n# Sfb -0.310%\nv m l c p  q z u o , \ns t r d y  f n e a i \nx k j g w  b h ; ' .";
+        assert_eq!(format_layout_str(str3).unwrap(), keys);
+        let blank_lines = "\nv m l c p  q z u o ,\r\n\n   \ns t r d y  f n e a i\n\nx k j g w  b h ; ' .\n\n";
+        assert_eq!(format_layout_str(blank_lines).unwrap(), keys);
+        assert_eq!(format_layout_str(keys).unwrap(), keys);
+        assert_eq!(format_layout_str("vmlcp qzuo,\nstrdy fneai\nxkjgw bh;'.").unwrap(), keys);
+
+        let error = |layout_str: &str| format_layout_str(layout_str).unwrap_err().to_string();
+        let ragged = "a b    c d e f g h i \n j k l \n m n o p q \n r s t u v w x y z";
+        assert!(error(ragged).contains("row 1 has 9 keys instead of 10"));
+        let long_row = "v m l c p  q z u o ,\ns t r d y  f n e a i '\nx k j g w  b h ; ' .";
+        assert!(error(long_row).contains("row 2 has 11 keys"));
+        assert!(error("v m l c p  q z u o ,\n# a b\n").contains("but it has 1"));
+        assert!(error(&format!("{str1}\na b c")).contains("only have the 2 thumb keys"));
+        assert!(error(&format!("{str1}\n_ e\nstats")).contains("'stats' comes after"));
+        assert!(error(&format!("{str1}\nlayer\na\nb\nc\nd")).contains("'d' comes after"));
+    }
+
+    #[test]
+    fn format_thumbs_string() {
+        let str1 = "v m l c p  q z u o , \ns t r d y  f n e a i \nx k j g w  b h ; ' .";
+        let str2 = "# thumbs\nv m l c p  q z u o , \ns t r d y  f n e a i \nx k j g w  b h ; ' .\n        _  e ";
+
+        assert_eq!(format_thumbs_str(str1), [None, None]);
+        assert_eq!(format_thumbs_str(str2), [None, Some('e')]);
+        assert_eq!(format_thumbs_str("a\nb\nc\nr t"), [Some('r'), Some('t')]);
+        assert_eq!(format_thumbs_str("a\n\nb\nc\n\nr t"), [Some('r'), Some('t')]);
+        assert_eq!(format_layout_str(str2).unwrap(), "vmlcpqzuo,strdyfneaixkjgwbh;'.");
+    }
+
+    #[test]
+    fn format_layer_string() {
+        let layer = "layer right\n! @ # $ %  ^ & * ( )\n_ _ _ _ _  _ - = [ ]\n_ _ _ _ _  _ _ _ _ _";
+        let str1 = format!("a\nb\nc\n{layer}");
+        let str2 = format!("a\nb\nc\n_ e\n# symbols\n{layer}");
+
+        assert_eq!(format_thumbs_str(&str1), [None, None]);
+        assert_eq!(format_thumbs_str(&str2), [None, Some('e')]);
+
+        let (hold, keys) = format_layer_str(&str2).unwrap();
+        assert_eq!(hold, "right");
+        assert_eq!(keys.len(), 30);
+        assert_eq!(keys[..3], ['!', '@', '#']);
+        assert_eq!(keys[16], '-');
+        assert_eq!(format_layer_str(&str1).map(|(hold, _)| hold), Some("right"));
+        assert_eq!(format_layer_str("a\nb\nc\nd e\nf"), None);
+        assert_eq!(format_layer_str("a\nb\nc\nlayers"), None);
+
+        let layout_str = format!("v m l c p  q z u o ,\ns t r d y  f n e a i\nx k j g w  b h ; ' .\n_ e\n\n{layer}");
+        assert!(format_layout_str(&layout_str).is_ok());
+        let one_line = format!("vmlcpqzuo,strdyfneaixkjgwbh;'.\n{layer}");
+        assert_eq!(format_layer_str(&one_line).map(|(hold, _)| hold), Some("right"));
+    }
 
-        assert_eq!(format_layout_str(str1), "vmlcpqzuo,strdyfneaixkjgwbh;'.");
-        assert_eq!(format_layout_str(str2), "abcdefghijklmnopq");
+    #[test]
+    fn format_magic_string() {
+        let keys = "v m l c p  q z u o ,\ns t r d y  f n e a i\nx k j g w  b h ; ' .";
+        let with_thumbs = format!("{keys}\n_ @\nmagic @ ou");
+        assert_eq!(format_magic_str(&with_thumbs), Some("@ ou"));
+        assert_eq!(format_thumbs_str(&with_thumbs), [None, Some('@')]);
+        assert!(format_layout_str(&with_thumbs).is_ok());
+
+        let without_thumbs = format!("{keys}\nmagic");
+        assert_eq!(format_magic_str(&without_thumbs), Some(""));
+        assert_eq!(format_thumbs_str(&without_thumbs), [None, None]);
+        assert!(format_layout_str(&without_thumbs).is_ok());
+        assert_eq!(format_magic_str(keys), None);
+        assert_eq!(format_magic_str("a\nb\nc\nmagical"), None);
+
+        let after = format_layout_str(&format!("{keys}\nmagic @\n_ e")).unwrap_err();
+        assert!(after.to_string().contains("'_ e' comes after"));
+    }
+
+    #[test]
+    fn total_score_order() {
+        let mut scores = [1.0, f64::NAN, -2.0, f64::INFINITY, 0.5].map(TotalScore);
+        scores.sort();
+        assert!(scores[0].0.is_nan());
+        let sorted = scores[1..].iter().map(|s| s.0).collect::<Vec<_>>();
+        assert_eq!(sorted, [-2.0, 0.5, 1.0, f64::INFINITY]);
+        assert_eq!(TotalScore(f64::NAN), TotalScore(f64::NAN));
+        assert!(TotalScore(f64::NAN) < TotalScore(f64::NEG_INFINITY));
     }
 }
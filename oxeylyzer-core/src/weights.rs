@@ -1,20 +1,56 @@
-use crate::utility::KeyboardType;
+use crate::bigram_penalties::{BigramPenalty, BigramPenaltyRule};
+use crate::data_source::{DataSource, FileSource};
+use crate::error::RusylyzerError;
+use crate::external_metric::ExternalMetricConfig;
+use crate::generate::LayoutStats;
+use crate::graphemes::nfc;
+use crate::one_handed::{parse_finger_map, FingerMap, DEFAULT_FINGER_MAP};
+use crate::pin_templates::PinTemplate;
+use crate::trigram_patterns::TrigramRules;
+use crate::utility::{
+    is_left_hand, parse_finger_assignment, FingerAssignment, KeyboardType, I_TO_COL,
+};
 use serde::Deserialize;
-use std::fs::File;
-use std::io::Read;
+use std::collections::BTreeMap;
 
 #[derive(Deserialize, Debug)]
 pub struct WeightDefaultsLoad {
     pub language: String,
     pub keyboard_type: String,
     trigram_precision: usize,
+    space_thumb: SpaceThumb,
+    references: Vec<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Clone, Debug)]
 pub struct WeightDefaults {
     pub language: String,
     pub keyboard_type: KeyboardType,
     pub trigram_precision: usize,
+    pub space_thumb: SpaceThumb,
+    /// Layouts `analyze` compares every layout with, by name.
+    pub references: Vec<String>,
+}
+
+/// Thumb that presses space when showing the trigrams that cross a word boundary, if any.
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SpaceThumb {
+    #[default]
+    None,
+    Left,
+    Right,
+}
+
+impl SpaceThumb {
+    /// The thumb as it is used by `FastLayout::set_thumb`, 0 for left and 1 for right.
+    pub fn thumb(self) -> Option<usize> {
+        match self {
+            Self::None => None,
+            Self::Left => Some(0),
+            Self::Right => Some(1),
+        }
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -26,6 +62,288 @@ pub struct MaxFingerUse {
     pub index: f64,
 }
 
+/// Share of bigrams typed with alternating hands that layouts are pushed towards, for styles that
+/// like a lot of alternation. Layouts are penalized by how far they are from `target` either way,
+/// instead of being rewarded for alternating more.
+#[derive(Deserialize, Clone, Debug)]
+pub struct HandAlternation {
+    pub penalty: f64,
+    pub target: f64,
+}
+
+impl Default for HandAlternation {
+    fn default() -> Self {
+        Self {
+            penalty: 0.0,
+            target: 0.6,
+        }
+    }
+}
+
+impl HandAlternation {
+    /// How far `alternation` is from the target, in the same unit.
+    pub fn distance(&self, alternation: f64) -> f64 {
+        (alternation - self.target).abs()
+    }
+}
+
+/// Limits on how much of the typing every row gets, for layouts designed around the home row.
+/// Layouts are penalized by how far the home row is below `home` and the top and bottom row are
+/// above `top` and `bottom`, and not at all within the limits.
+#[derive(Deserialize, Clone, Debug)]
+pub struct RowUsage {
+    pub penalty: f64,
+    pub top: f64,
+    pub home: f64,
+    pub bottom: f64,
+}
+
+impl Default for RowUsage {
+    fn default() -> Self {
+        Self {
+            penalty: 0.0,
+            top: 0.3,
+            home: 0.7,
+            bottom: 0.12,
+        }
+    }
+}
+
+impl RowUsage {
+    /// How far the share of the top, home and bottom row in `usage` is outside of the limits,
+    /// added up.
+    pub fn distance(&self, usage: &[f64; 3]) -> f64 {
+        (usage[0] - self.top).max(0.0)
+            + (self.home - usage[1]).max(0.0)
+            + (usage[2] - self.bottom).max(0.0)
+    }
+}
+
+/// Keys of shortcuts like ctrl+c, which are easiest to reach with the left hand on the home and
+/// bottom row while the left pinky holds ctrl. Layouts are penalized by the share of them that
+/// end up anywhere else.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Shortcuts {
+    pub penalty: f64,
+    pub keys: String,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Self {
+            penalty: 0.0,
+            keys: "zxcvafw".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct HandDominance {
+    pub left: f64,
+    pub right: f64,
+}
+
+impl Default for HandDominance {
+    fn default() -> Self {
+        Self {
+            left: 1.0,
+            right: 1.0,
+        }
+    }
+}
+
+impl HandDominance {
+    pub fn is_symmetric(&self) -> bool {
+        self.left == self.right
+    }
+
+    /// Multiplier for a key pressed by `finger`, by the hand it is on.
+    pub fn for_finger(&self, finger: usize) -> f64 {
+        if is_left_hand(finger) {
+            self.left
+        } else {
+            self.right
+        }
+    }
+}
+
+/// Multipliers for the `inrolls` and `outrolls` weights of rolls on the left and right hand, to
+/// favor rolls on one hand over the other.
+#[derive(Deserialize, Clone, Debug)]
+pub struct RollHands {
+    pub left: f64,
+    pub right: f64,
+}
+
+impl Default for RollHands {
+    fn default() -> Self {
+        Self {
+            left: 1.0,
+            right: 1.0,
+        }
+    }
+}
+
+impl RollHands {
+    pub fn is_neutral(&self) -> bool {
+        self.left == 1.0 && self.right == 1.0
+    }
+
+    /// Rolls on the left and right hand with the multipliers applied.
+    pub fn apply(&self, [left, right]: [f64; 2]) -> f64 {
+        self.left * left + self.right * right
+    }
+}
+
+/// Multipliers for the finger speed of every finger, on top of the strength every finger has by
+/// default. Above 1 a finger counts as slower, like for a weak pinky, and below 1 as faster.
+#[derive(Deserialize, Clone, Debug)]
+pub struct FingerSpeeds {
+    pub pinky: f64,
+    pub ring: f64,
+    pub middle: f64,
+    pub index: f64,
+}
+
+impl Default for FingerSpeeds {
+    fn default() -> Self {
+        Self {
+            pinky: 1.0,
+            ring: 1.0,
+            middle: 1.0,
+            index: 1.0,
+        }
+    }
+}
+
+impl FingerSpeeds {
+    pub fn is_neutral(&self) -> bool {
+        [self.pinky, self.ring, self.middle, self.index] == [1.0; 4]
+    }
+
+    /// Multiplier for `finger`, counting from the left pinky at 0 to the right pinky at 7.
+    pub fn for_finger(&self, finger: usize) -> f64 {
+        match finger {
+            0 | 7 => self.pinky,
+            1 | 6 => self.ring,
+            2 | 5 => self.middle,
+            _ => self.index,
+        }
+    }
+}
+
+/// Upper limit for a stat, in the unit `analyze` shows it in, so `sfb = 1.0` allows at most 1%
+/// sfbs. Once a layout is under it, generation won't make swaps that take it over again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constraint {
+    pub metric: String,
+    pub max: f64,
+}
+
+impl Constraint {
+    /// Value of the constrained stat in `stats`, as compared to `max`.
+    pub fn value(&self, stats: &LayoutStats) -> f64 {
+        stats.metric(&self.metric).map_or(0.0, |(_, value)| value)
+    }
+}
+
+/// Where generation puts the characters before it starts swapping them.
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedStrategy {
+    /// Every character is put in a random spot.
+    Random,
+    /// Vowels go on one hand and the most common consonants on the other, which starts out with a
+    /// lot of alternation. Which hand gets the vowels is picked at random.
+    VowelSplit,
+}
+
+/// How generation picks the next swap when several improve the layout about as much.
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapPolicy {
+    /// Swaps are tried in order, and of swaps that score the same within `SWAP_EPSILON`, the one
+    /// with the lowest positions wins. The same starting layout always ends up the same.
+    Deterministic,
+    /// Swaps are tried in a random order every time, and the first of several that score the same
+    /// wins. This explores more different layouts from the same start.
+    Shuffled,
+}
+
+/// A hand of `locked_hands` in `[generation]`.
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GenerationOptions {
+    pub seed_strategy: SeedStrategy,
+    pub swap_policy: SwapPolicy,
+    /// Whether a layout no swap improves anymore is also tried with every 3 keys moved around in a
+    /// cycle. Without it, only the 3 keys of a column are, as there are a lot more cycles than
+    /// swaps.
+    pub rotations: bool,
+    /// Characters `vowel_split` puts on the same hand.
+    pub vowels: String,
+    /// Characters `vowel_split` puts on the other hand, in order of importance. Without these, the
+    /// most common letters that aren't vowels are used.
+    pub consonants: Option<String>,
+    /// Whether to also score the mirrored version of every generated layout, and keep it if it
+    /// scores better.
+    pub try_mirrored: bool,
+    /// Whether optimizing starts with a sixteenth of `trigram_precision` and goes up to all of it
+    /// as the layout gets better, which is quicker than using every trigram from the start.
+    pub progressive_precision: bool,
+    /// Amount of populations generation is split over, which exchange their best layouts every
+    /// `migration_interval` layouts. With fewer than 2, every layout is generated on its own.
+    pub islands: usize,
+    pub migration_interval: usize,
+    /// Characters the `punctuation` command reports on.
+    pub punctuation: String,
+    /// Whether `improve` can move the punctuation of a layout. Without it, punctuation stays where
+    /// it is and only the other keys are optimized.
+    pub move_punctuation: bool,
+    /// Characters that make up more than this percentage of the corpus are never put on a key in
+    /// `[avoid_keys]`. At 0, any character can go there.
+    pub avoided_key_ban: f64,
+    /// Hands whose keys are never moved by generation, like to only improve the left hand.
+    pub locked_hands: Vec<Hand>,
+    /// Columns from 0 to 9 whose keys stay together in the same order. They are never swapped
+    /// with other keys, but a column can move as a whole when columns are permuted.
+    pub column_units: Vec<usize>,
+    /// How willing optimizing is to make a layout worse once no swap improves it anymore, to get
+    /// off a plateau. A random swap that lowers the score by `d` is made with a chance of
+    /// `e^(-d / temperature)`, and optimizing goes on from there. At 0, it only makes swaps that
+    /// improve the layout.
+    pub temperature: f64,
+}
+
+impl Default for GenerationOptions {
+    fn default() -> Self {
+        Self {
+            seed_strategy: SeedStrategy::Random,
+            swap_policy: SwapPolicy::Deterministic,
+            rotations: false,
+            vowels: "aeiou".to_string(),
+            consonants: None,
+            try_mirrored: false,
+            progressive_precision: false,
+            islands: 0,
+            migration_interval: 10,
+            punctuation: ",.';-".to_string(),
+            move_punctuation: true,
+            avoided_key_ban: 0.0,
+            locked_hands: Vec::new(),
+            column_units: Vec::new(),
+            temperature: 0.0,
+        }
+    }
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct Weights {
     pub heatmap: f64,
@@ -38,8 +356,14 @@ pub struct Weights {
     pub dsfb_ratio3: f64,
     pub scissors: f64,
     pub lsbs: f64,
+    #[serde(default)]
+    pub row_skips: f64,
     pub inrolls: f64,
     pub outrolls: f64,
+    #[serde(default)]
+    pub home_row_rolls: f64,
+    #[serde(default)]
+    pub adjacent_rolls: f64,
     pub onehands: f64,
     pub alternates: f64,
     pub alternates_sfs: f64,
@@ -47,40 +371,678 @@ pub struct Weights {
     pub redirects_sfs: f64,
     pub bad_redirects: f64,
     pub bad_redirects_sfs: f64,
+    #[serde(default)]
+    pub onehand_rolls: f64,
+    #[serde(default)]
+    pub quad_rolls: f64,
+    #[serde(default)]
+    pub long_onehands: f64,
+    #[serde(default)]
+    pub onehand_redirects: f64,
+    #[serde(default)]
+    pub double_redirects: f64,
+    #[serde(default)]
+    pub travel: f64,
     pub max_finger_use: MaxFingerUse,
+    #[serde(default)]
+    pub hand_dominance: HandDominance,
+    #[serde(default)]
+    pub hand_alternation: HandAlternation,
+    #[serde(default)]
+    pub row_usage: RowUsage,
+    #[serde(default)]
+    pub shortcuts: Shortcuts,
+    #[serde(default)]
+    pub fingers: FingerSpeeds,
+    #[serde(default)]
+    pub roll_hands: RollHands,
+}
+
+/// Tables in `[weights]` that belong to the weights themselves. Every other table is a profile.
+const WEIGHT_TABLES: [&str; 7] = [
+    "max_finger_use",
+    "hand_dominance",
+    "hand_alternation",
+    "row_usage",
+    "shortcuts",
+    "fingers",
+    "roll_hands",
+];
+
+/// Profile that uses the weights in `[weights]` as they are.
+pub const DEFAULT_PROFILE: &str = "default";
+
+impl Weights {
+    /// Names of every weight `weight_mut` can change.
+    pub const NAMES: [&'static str; 28] = [
+        "heatmap",
+        "lateral_penalty",
+        "fspeed",
+        "dsfb_ratio",
+        "scissors",
+        "lsbs",
+        "row_skips",
+        "inrolls",
+        "outrolls",
+        "home_row_rolls",
+        "adjacent_rolls",
+        "onehands",
+        "alternates",
+        "alternates_sfs",
+        "redirects",
+        "redirects_sfs",
+        "bad_redirects",
+        "bad_redirects_sfs",
+        "onehand_rolls",
+        "quad_rolls",
+        "long_onehands",
+        "onehand_redirects",
+        "double_redirects",
+        "travel",
+        "max_finger_use",
+        "hand_alternation",
+        "row_usage",
+        "shortcuts",
+    ];
+
+    /// The weight called `name`, with the name it has in `config.toml`. `max_finger_use`,
+    /// `hand_alternation`, `row_usage` and `shortcuts` are their penalty. Changing `dsfb_ratio`
+    /// needs `set_dsfb_ratios` afterwards.
+    pub fn weight_mut(&mut self, name: &str) -> Option<&mut f64> {
+        let weight = match name {
+            "heatmap" => &mut self.heatmap,
+            "lateral_penalty" => &mut self.lateral_penalty,
+            "fspeed" => &mut self.fspeed,
+            "dsfb_ratio" => &mut self.dsfb_ratio,
+            "scissors" => &mut self.scissors,
+            "lsbs" => &mut self.lsbs,
+            "row_skips" => &mut self.row_skips,
+            "inrolls" => &mut self.inrolls,
+            "outrolls" => &mut self.outrolls,
+            "home_row_rolls" => &mut self.home_row_rolls,
+            "adjacent_rolls" => &mut self.adjacent_rolls,
+            "onehands" => &mut self.onehands,
+            "alternates" => &mut self.alternates,
+            "alternates_sfs" => &mut self.alternates_sfs,
+            "redirects" => &mut self.redirects,
+            "redirects_sfs" => &mut self.redirects_sfs,
+            "bad_redirects" => &mut self.bad_redirects,
+            "bad_redirects_sfs" => &mut self.bad_redirects_sfs,
+            "onehand_rolls" => &mut self.onehand_rolls,
+            "quad_rolls" => &mut self.quad_rolls,
+            "long_onehands" => &mut self.long_onehands,
+            "onehand_redirects" => &mut self.onehand_redirects,
+            "double_redirects" => &mut self.double_redirects,
+            "travel" => &mut self.travel,
+            "max_finger_use" => &mut self.max_finger_use.penalty,
+            "hand_alternation" => &mut self.hand_alternation.penalty,
+            "row_usage" => &mut self.row_usage.penalty,
+            "shortcuts" => &mut self.shortcuts.penalty,
+            _ => return None,
+        };
+        Some(weight)
+    }
+
+    /// Every weight with the name it has in `config.toml`, followed by the values in tables as
+    /// dotted names like `max_finger_use.pinky`. Finger use limits, the alternation target and row
+    /// usage limits are percentages, like in the config.
+    pub fn values(&self) -> Vec<(String, f64)> {
+        let mut weights = self.clone();
+        let mut res = Self::NAMES
+            .iter()
+            .filter_map(|&name| Some((name.to_string(), *weights.weight_mut(name)?)))
+            .collect::<Vec<_>>();
+
+        let mfu = &self.max_finger_use;
+        // rounded so 9% doesn't come out as 9.000000000000002
+        let percent = |fraction: f64| (fraction * 100.0 * 1e9).round() / 1e9;
+        res.extend(
+            [
+                ("max_finger_use.pinky", percent(mfu.pinky)),
+                ("max_finger_use.ring", percent(mfu.ring)),
+                ("max_finger_use.middle", percent(mfu.middle)),
+                ("max_finger_use.index", percent(mfu.index)),
+                ("hand_dominance.left", self.hand_dominance.left),
+                ("hand_dominance.right", self.hand_dominance.right),
+                (
+                    "hand_alternation.target",
+                    percent(self.hand_alternation.target),
+                ),
+                ("row_usage.top", percent(self.row_usage.top)),
+                ("row_usage.home", percent(self.row_usage.home)),
+                ("row_usage.bottom", percent(self.row_usage.bottom)),
+                ("fingers.pinky", self.fingers.pinky),
+                ("fingers.ring", self.fingers.ring),
+                ("fingers.middle", self.fingers.middle),
+                ("fingers.index", self.fingers.index),
+                ("roll_hands.left", self.roll_hands.left),
+                ("roll_hands.right", self.roll_hands.right),
+            ]
+            .map(|(name, value)| (name.to_string(), value)),
+        );
+        res
+    }
+
+    /// The weights as a `[weights.<name>]` profile for `config.toml`, with only the weights that are
+    /// different from `base`. Tables like `max_finger_use` get their penalty as an inline table, so
+    /// the rest of the table stays as it is in `[weights]`.
+    pub fn profile_toml(&self, name: &str, base: &Weights) -> Result<String, String> {
+        let valid = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if name.is_empty() || !valid {
+            return Err(format!(
+                "'{name}' can only have letters, numbers, _ and - to be a profile"
+            ));
+        }
+        if name == DEFAULT_PROFILE || WEIGHT_TABLES.contains(&name) {
+            return Err(format!("'{name}' can't be used as the name of a profile"));
+        }
+
+        let (mut weights, mut base) = (self.clone(), base.clone());
+        let mut res = format!("[weights.{name}]\n");
+        for weight in Self::NAMES {
+            let value = weights.weight_mut(weight).copied();
+            if value == base.weight_mut(weight).copied() {
+                continue;
+            }
+            if let Some(value) = value {
+                if WEIGHT_TABLES.contains(&weight) {
+                    res.push_str(&format!("{weight} = {{ penalty = {value:?} }}\n"));
+                } else {
+                    res.push_str(&format!("{weight} = {value:?}\n"));
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Sets the ratios for skipgrams with 2 and 3 keys in between, which follow from `dsfb_ratio`.
+    pub fn set_dsfb_ratios(&mut self) {
+        self.dsfb_ratio2 = (self.dsfb_ratio * 6.0).powi(3) / 6.5;
+        self.dsfb_ratio3 = (self.dsfb_ratio * 6.0).powi(5) / 7.0;
+    }
+}
+
+/// Values used for everything `config.toml` leaves out. `one_handed_fingers`, `finger_assignment`,
+/// `defaults.profile` and `generation.consonants` have no default and are only used when set.
+pub const DEFAULT_CONFIG: &str = r#"
+pins = """
+..... .....
+..... .....
+..... .....
+"""
+pin_templates = []
+
+[defaults]
+language = "english"
+trigram_precision = 1000
+keyboard_type = "ansi angle"
+space_thumb = "none"
+references = ["qwerty", "dvorak", "colemak", "workman"]
+
+[weights]
+heatmap = 1.65
+fspeed = 18.0
+lateral_penalty = 1.0
+dsfb_ratio = 0.11
+scissors = 4.5
+lsbs = 1.5
+row_skips = 0.0
+inrolls = 1.5
+outrolls = 1.35
+home_row_rolls = 0.0
+adjacent_rolls = 0.0
+onehands = 0.9
+alternates = 0.9
+alternates_sfs = 0.6
+redirects = 1.4
+redirects_sfs = 2.2
+bad_redirects = 4.5
+bad_redirects_sfs = 6.0
+onehand_rolls = 0.0
+quad_rolls = 0.0
+long_onehands = 0.0
+onehand_redirects = 0.0
+double_redirects = 0.0
+travel = 0.0
+
+[weights.max_finger_use]
+penalty = 2.5
+pinky = 9.0
+ring = 14.0
+middle = 20.0
+index = 20.0
+
+[weights.hand_dominance]
+left = 1.0
+right = 1.0
+
+[weights.hand_alternation]
+penalty = 0.0
+target = 60.0
+
+[weights.row_usage]
+penalty = 0.0
+top = 30.0
+home = 70.0
+bottom = 12.0
+
+[weights.shortcuts]
+penalty = 0.0
+keys = "zxcvafw"
+
+[weights.fingers]
+pinky = 1.0
+ring = 1.0
+middle = 1.0
+index = 1.0
+
+[weights.roll_hands]
+left = 1.0
+right = 1.0
+
+[trigram_rules]
+bad_redirect_fingers = ["pinky", "ring", "middle"]
+redirect_sfs = true
+alternate_sfs = true
+
+[generation]
+seed_strategy = "random"
+swap_policy = "deterministic"
+rotations = false
+vowels = "aeiou"
+try_mirrored = false
+progressive_precision = false
+islands = 0
+migration_interval = 10
+punctuation = ",.';-"
+move_punctuation = true
+avoided_key_ban = 0.0
+locked_hands = []
+column_units = []
+temperature = 0.0
+
+[constraints]
+
+[bigram_penalties]
+
+[avoid_keys]
+
+[external_metric]
+command = []
+weight = 0.0
+"#;
+
+/// Keys without a default in `DEFAULT_CONFIG` that aren't unknown when they are set.
+const OPTIONAL_KEYS: [&str; 4] = [
+    "one_handed_fingers",
+    "finger_assignment",
+    "defaults.profile",
+    "generation.consonants",
+];
+
+/// Values that can't be negative, as the table they are in and their key.
+const NON_NEGATIVE: [(&str, &str); 15] = [
+    ("weights", "heatmap"),
+    ("weights", "fspeed"),
+    ("weights", "lateral_penalty"),
+    ("weights", "dsfb_ratio"),
+    ("weights", "scissors"),
+    ("weights", "lsbs"),
+    ("weights", "row_skips"),
+    ("weights", "travel"),
+    ("weights.max_finger_use", "penalty"),
+    ("weights.hand_alternation", "penalty"),
+    ("weights.row_usage", "penalty"),
+    ("weights.shortcuts", "penalty"),
+    ("weights.roll_hands", "left"),
+    ("weights.roll_hands", "right"),
+    ("generation", "temperature"),
+];
+
+/// Values that have to be above 0.
+const POSITIVE: [(&str, &str); 7] = [
+    ("defaults", "trigram_precision"),
+    ("weights.hand_dominance", "left"),
+    ("weights.hand_dominance", "right"),
+    ("weights.fingers", "pinky"),
+    ("weights.fingers", "ring"),
+    ("weights.fingers", "middle"),
+    ("weights.fingers", "index"),
+];
+
+/// Values that are a percentage, from 0 to 100.
+const PERCENTAGES: [(&str, &str); 9] = [
+    ("weights.max_finger_use", "pinky"),
+    ("weights.max_finger_use", "ring"),
+    ("weights.max_finger_use", "middle"),
+    ("weights.max_finger_use", "index"),
+    ("weights.hand_alternation", "target"),
+    ("weights.row_usage", "top"),
+    ("weights.row_usage", "home"),
+    ("weights.row_usage", "bottom"),
+    ("generation", "avoided_key_ban"),
+];
+
+/// Line `key` is set on in `[table]` of `config`, counting from 1. Top level keys have an empty
+/// `table`.
+fn line_of(config: &str, table: &str, key: &str) -> Option<usize> {
+    let mut current = "";
+
+    for (i, line) in config.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            current = header.split(']').next().unwrap_or_default().trim();
+        } else if current == table {
+            if let Some((k, _)) = line.split_once('=') {
+                if k.trim().trim_matches('"') == key {
+                    return Some(i + 1);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// `line_of` as the start of a message, or nothing when the key can't be found. Weights set by
+/// `profile` are looked for in the profile first.
+fn location(config: &str, table: &str, key: &str, profile: Option<&str>) -> String {
+    let in_profile = profile
+        .zip(table.strip_prefix("weights"))
+        .and_then(|(profile, rest)| {
+            let profile_table = format!("weights.{profile}{rest}");
+            line_of(config, &profile_table, key).or_else(|| {
+                // inline tables like `max_finger_use = { pinky = 12.0 }`
+                let (parent, inline) = profile_table.rsplit_once('.')?;
+                line_of(config, parent, inline).filter(|_| !rest.is_empty())
+            })
+        });
+    match in_profile.or_else(|| line_of(config, table, key)) {
+        Some(line) => format!("line {line}: "),
+        None => String::new(),
+    }
+}
+
+/// Every key of `config` that isn't in `defaults` or `OPTIONAL_KEYS`, with where it is. Profiles in
+/// `[weights]` are checked against the weights themselves, and `[constraints]`,
+/// `[bigram_penalties]` and `[avoid_keys]` are checked when they are parsed.
+fn unknown_keys(
+    source: &str,
+    config: &toml::value::Table,
+    defaults: &toml::value::Table,
+    path: &str,
+    res: &mut Vec<String>,
+) {
+    for (key, value) in config {
+        let full = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+        if ["constraints", "bigram_penalties", "avoid_keys"].contains(&full.as_str())
+            || OPTIONAL_KEYS.contains(&full.as_str())
+        {
+            continue;
+        }
+        match (defaults.get(key), value) {
+            (Some(toml::Value::Table(defaults)), toml::Value::Table(table)) => {
+                unknown_keys(source, table, defaults, &full, res)
+            }
+            (Some(_), _) => {}
+            (None, toml::Value::Table(table)) if path == "weights" => {
+                unknown_keys(source, table, defaults, &full, res)
+            }
+            (None, _) => res.push(format!(
+                "{}'{full}' is not a known setting and is ignored",
+                location(source, path, key, None)
+            )),
+        }
+    }
+}
+
+/// The first value in `config` that is `nan` or `inf`, as the table it is in, its key and the
+/// value. Scores that use them are NaN, which can't be ranked.
+fn non_finite(config: &toml::Value, table: &str) -> Option<(String, String, f64)> {
+    for (key, value) in config.as_table()? {
+        match value {
+            toml::Value::Float(f) if !f.is_finite() => {
+                return Some((table.to_string(), key.clone(), *f))
+            }
+            toml::Value::Table(_) => {
+                let inner = if table.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{table}.{key}")
+                };
+                if let Some(res) = non_finite(value, &inner) {
+                    return Some(res);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Checks that every value is a number, and the range of every value in `NON_NEGATIVE`,
+/// `POSITIVE` and `PERCENTAGES`, after the profile was applied.
+fn check_ranges(
+    source: &str,
+    config: &toml::Value,
+    profile: Option<&str>,
+) -> Result<(), RusylyzerError> {
+    if let Some((table, key, number)) = non_finite(config, "") {
+        return Err(RusylyzerError::Config(format!(
+            "{}{key} is {number}, but it has to be a number",
+            location(source, &table, &key, profile)
+        )));
+    }
+
+    let ranges = [
+        (&NON_NEGATIVE[..], 0.0, f64::MAX, "can't be negative"),
+        (
+            &POSITIVE[..],
+            f64::MIN_POSITIVE,
+            f64::MAX,
+            "has to be above 0",
+        ),
+        (
+            &PERCENTAGES[..],
+            0.0,
+            100.0,
+            "is a percentage between 0 and 100",
+        ),
+    ];
+
+    for (values, min, max, reason) in ranges {
+        for &(table, key) in values {
+            let value = table
+                .split('.')
+                .try_fold(config, |value, name| value.get(name))
+                .and_then(|table| table.get(key));
+            let number = match value {
+                Some(toml::Value::Float(f)) => *f,
+                Some(toml::Value::Integer(i)) => *i as f64,
+                _ => continue,
+            };
+            if !(min..=max).contains(&number) {
+                return Err(RusylyzerError::Config(format!(
+                    "{}{key} is {number}, but it {reason}",
+                    location(source, table, key, profile)
+                )));
+            }
+        }
+    }
+    Ok(())
 }
 
 #[derive(Deserialize)]
 struct ConfigLoad {
     pub pins: String,
+    pub pin_templates: Vec<String>,
+    #[serde(default)]
+    pub one_handed_fingers: Option<String>,
+    #[serde(default)]
+    pub finger_assignment: Option<String>,
     pub defaults: WeightDefaultsLoad,
     pub weights: Weights,
+    #[serde(default)]
+    pub trigram_rules: TrigramRules,
+    #[serde(default)]
+    pub constraints: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub bigram_penalties: BTreeMap<String, BigramPenaltyRule>,
+    #[serde(default)]
+    pub generation: GenerationOptions,
+    #[serde(default)]
+    pub avoid_keys: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub external_metric: ExternalMetricConfig,
+    #[serde(skip)]
+    pub profiles: Vec<String>,
+    #[serde(skip)]
+    pub profile: Option<String>,
+    #[serde(skip)]
+    pub warnings: Vec<String>,
 }
 
 impl ConfigLoad {
-    pub fn new() -> Self {
-        let mut f = File::open("config.toml").expect("The config.toml is missing! Help!");
+    /// Parses `config` on top of `DEFAULT_CONFIG`, so anything that is left out gets its default.
+    /// Values that are out of range are an error, and keys that aren't known are a warning.
+    pub fn parse(config: &str, profile: Option<&str>) -> Result<Self, RusylyzerError> {
+        let config = &nfc(config);
+        let mut value: toml::Value =
+            toml::from_str(config).map_err(|e| RusylyzerError::Config(e.to_string()))?;
+        let mut defaults: toml::Value = toml::from_str(DEFAULT_CONFIG).unwrap();
 
-        let mut buf = Vec::new();
-        f.read_to_end(&mut buf)
-            .expect("Failed to read config.toml for some reason");
+        let mut warnings = Vec::new();
+        if let (Some(table), Some(defaults)) = (value.as_table(), defaults.as_table()) {
+            unknown_keys(config, table, defaults, "", &mut warnings);
+        }
+
+        let (profiles, profile) = apply_profile(&mut value, profile)?;
+        if let (Some(defaults), Some(table)) = (defaults.as_table_mut(), value.as_table()) {
+            merge_tables(defaults, table);
+        }
+        check_ranges(config, &defaults, profile.as_deref())?;
 
-        let mut res: Self =
-            toml::from_slice(&buf).expect("Failed to parse config.toml. Values might be missing.");
+        let mut res: Self = defaults
+            .try_into()
+            .map_err(|e| RusylyzerError::Config(e.to_string()))?;
         res.pins = res.pins.trim().replace(' ', "").replace('\n', "");
-        res
+        res.profiles = profiles;
+        res.profile = profile;
+        res.warnings = warnings;
+        Ok(res)
+    }
+}
+
+/// Takes the profiles out of `[weights]` and puts the values of the chosen one over the weights.
+/// Without a `profile`, the one in `[defaults]` is used if there is one. Returns the names of all
+/// profiles and the one that was used, which is `None` for the default profile.
+fn apply_profile(
+    config: &mut toml::Value,
+    profile: Option<&str>,
+) -> Result<(Vec<String>, Option<String>), RusylyzerError> {
+    let profile = profile
+        .or_else(|| config.get("defaults")?.get("profile")?.as_str())
+        .filter(|&p| p != DEFAULT_PROFILE)
+        .map(str::to_string);
+
+    let weights = match config
+        .get_mut("weights")
+        .and_then(toml::Value::as_table_mut)
+    {
+        Some(weights) => weights,
+        None => return Ok((Vec::new(), None)),
+    };
+    let names = weights
+        .iter()
+        .filter(|(key, value)| value.is_table() && !WEIGHT_TABLES.contains(&key.as_str()))
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    let mut profiles = BTreeMap::new();
+    for name in names.iter() {
+        if let Some(toml::Value::Table(table)) = weights.remove(name) {
+            profiles.insert(name.clone(), table);
+        }
+    }
+
+    if let Some(name) = &profile {
+        let overrides = profiles.get(name).ok_or_else(|| {
+            RusylyzerError::Config(format!(
+                "there is no [weights.{name}] profile. Use one of: {}",
+                std::iter::once(DEFAULT_PROFILE)
+                    .chain(names.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })?;
+        merge_tables(weights, overrides);
+    }
+    Ok((names, profile))
+}
+
+/// Overwrites the values in `base` with the ones in `overrides`, going into tables that are in both.
+fn merge_tables(base: &mut toml::value::Table, overrides: &toml::value::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(value)) => {
+                merge_tables(base, value)
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
     }
 }
 
+#[derive(Clone)]
 pub struct Config {
     pub pins: Vec<usize>,
+    /// Templates of keys that stay where they are on qwerty when improving a layout.
+    pub pin_templates: Vec<PinTemplate>,
+    pub one_handed_fingers: FingerMap,
+    pub fingers: FingerAssignment,
     pub defaults: WeightDefaults,
     pub weights: Weights,
+    pub trigram_rules: TrigramRules,
+    pub constraints: Vec<Constraint>,
+    /// Extra bigrams that are penalized on top of scissors, from `[bigram_penalties]`.
+    pub bigram_penalties: Vec<BigramPenalty>,
+    pub generation: GenerationOptions,
+    /// Positions that are harder to press than usual, like a key that sticks, with what their
+    /// effort is multiplied by. From `[avoid_keys]`.
+    pub avoid_keys: Vec<(usize, f64)>,
+    /// Program that scores layouts on top of the built in metrics, from `[external_metric]`.
+    pub external_metric: ExternalMetricConfig,
+    /// Names of the weight profiles in `config.toml`, not including the default one.
+    pub profiles: Vec<String>,
+    /// Profile the weights are from, or `None` for the default profile.
+    pub profile: Option<String>,
+    /// Things in `config.toml` that were ignored, like keys that aren't known.
+    pub warnings: Vec<String>,
 }
 
 impl Config {
-    pub fn new() -> Self {
-        let mut load = ConfigLoad::new();
+    /// Reads `config.toml` from the current directory.
+    pub fn new() -> Result<Self, RusylyzerError> {
+        Self::with_profile(None)
+    }
+
+    /// Reads `config.toml` from the current directory, using the weights of `profile`. Without it,
+    /// the profile set in `[defaults]` is used.
+    pub fn with_profile(profile: Option<&str>) -> Result<Self, RusylyzerError> {
+        Self::from_source(&FileSource::default(), profile)
+    }
+
+    /// Reads the config of `source`, using the weights of `profile` like `with_profile` does.
+    pub fn from_source(
+        source: &dyn DataSource,
+        profile: Option<&str>,
+    ) -> Result<Self, RusylyzerError> {
+        let mut load = ConfigLoad::parse(&source.config()?, profile)?;
 
         load.weights.max_finger_use = MaxFingerUse {
             penalty: load.weights.max_finger_use.penalty,
@@ -89,24 +1051,109 @@ impl Config {
             middle: load.weights.max_finger_use.middle / 100.0,
             index: load.weights.max_finger_use.index / 100.0,
         };
+        load.weights.hand_alternation.target /= 100.0;
+        load.weights.row_usage.top /= 100.0;
+        load.weights.row_usage.home /= 100.0;
+        load.weights.row_usage.bottom /= 100.0;
         let mut pins = Vec::new();
         for (i, c) in load.pins.chars().enumerate() {
             if c == 'x' {
                 pins.push(i);
             }
         }
-        load.weights.dsfb_ratio2 = (load.weights.dsfb_ratio * 6.0).powi(3) / 6.5;
-        load.weights.dsfb_ratio3 = (load.weights.dsfb_ratio * 6.0).powi(5) / 7.0;
-        Self {
+        let pin_templates = load
+            .pin_templates
+            .iter()
+            .map(|name| PinTemplate::try_from(name.as_str()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| RusylyzerError::Config(format!("pin_templates: {e}")))?;
+        let one_handed_fingers = match load.one_handed_fingers {
+            Some(map) => parse_finger_map(&map)
+                .map_err(|e| RusylyzerError::Config(format!("one_handed_fingers: {e}")))?,
+            None => DEFAULT_FINGER_MAP,
+        };
+        let fingers = match load.finger_assignment {
+            Some(assignment) => parse_finger_assignment(&assignment)
+                .map_err(|e| RusylyzerError::Config(format!("finger_assignment: {e}")))?,
+            None => I_TO_COL,
+        };
+        let mut constraints = Vec::new();
+        for (metric, max) in load.constraints {
+            if LayoutStats::default().metric(&metric).is_none() {
+                return Err(RusylyzerError::Config(format!(
+                    "constraints: '{metric}' is not a stat that can be constrained"
+                )));
+            }
+            constraints.push(Constraint { metric, max });
+        }
+        let bigram_penalties = load
+            .bigram_penalties
+            .iter()
+            .map(|(name, rule)| {
+                rule.resolve(name, &fingers)
+                    .map_err(|e| RusylyzerError::Config(format!("bigram_penalties.{name}: {e}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut avoid_keys = Vec::new();
+        for (key, multiplier) in load.avoid_keys {
+            let position = key
+                .parse::<usize>()
+                .ok()
+                .filter(|&i| i < 30)
+                .ok_or_else(|| {
+                    RusylyzerError::Config(format!(
+                        "avoid_keys: '{key}' is not a position from 0 to 29"
+                    ))
+                })?;
+            if multiplier <= 0.0 {
+                return Err(RusylyzerError::Config(format!(
+                    "avoid_keys: {key} is {multiplier}, but it has to be above 0"
+                )));
+            }
+            avoid_keys.push((position, multiplier));
+        }
+        let keyboard_type = KeyboardType::try_from(load.defaults.keyboard_type.clone())
+            .unwrap_or_else(|_| {
+                load.warnings.push(format!(
+                    "keyboard_type '{}' is not known, so 'ansi angle' is used",
+                    load.defaults.keyboard_type
+                ));
+                KeyboardType::AnsiAngle
+            });
+        if load.generation.islands > 1 && load.generation.migration_interval == 0 {
+            return Err(RusylyzerError::Config(
+                "migration_interval has to be at least 1 when using islands".to_string(),
+            ));
+        }
+        if let Some(col) = load.generation.column_units.iter().find(|&&col| col >= 10) {
+            return Err(RusylyzerError::Config(format!(
+                "column_units: {col} is not a column from 0 to 9"
+            )));
+        }
+        load.weights.set_dsfb_ratios();
+        Ok(Self {
             pins,
+            pin_templates,
+            one_handed_fingers,
+            fingers,
             defaults: WeightDefaults {
                 language: load.defaults.language,
-                keyboard_type: KeyboardType::try_from(load.defaults.keyboard_type)
-                    .unwrap_or(KeyboardType::AnsiAngle),
+                keyboard_type,
                 trigram_precision: load.defaults.trigram_precision,
+                space_thumb: load.defaults.space_thumb,
+                references: load.defaults.references,
             },
             weights: load.weights,
-        }
+            trigram_rules: load.trigram_rules,
+            constraints,
+            bigram_penalties,
+            generation: load.generation,
+            avoid_keys,
+            external_metric: load.external_metric,
+            profiles: load.profiles,
+            profile: load.profile,
+            warnings: load.warnings,
+        })
     }
 
     pub fn default() -> Self {
@@ -115,6 +1162,10 @@ impl Config {
                 language: "english".to_string(),
                 keyboard_type: KeyboardType::AnsiAngle,
                 trigram_precision: 1000,
+                space_thumb: SpaceThumb::None,
+                references: ["qwerty", "dvorak", "colemak", "workman"]
+                    .map(String::from)
+                    .to_vec(),
             },
             weights: Weights {
                 heatmap: 0.85,
@@ -125,8 +1176,11 @@ impl Config {
                 dsfb_ratio3: (0.08 * 6.0f64).powi(3),
                 scissors: 5.0,
                 lsbs: 2.0,
+                row_skips: 0.0,
                 inrolls: 1.6,
                 outrolls: 1.3,
+                home_row_rolls: 0.0,
+                adjacent_rolls: 0.0,
                 onehands: 0.8,
                 alternates: 0.7,
                 alternates_sfs: 0.35,
@@ -134,6 +1188,12 @@ impl Config {
                 redirects_sfs: 2.75,
                 bad_redirects: 4.0,
                 bad_redirects_sfs: 6.0,
+                onehand_rolls: 0.0,
+                quad_rolls: 0.0,
+                long_onehands: 0.0,
+                onehand_redirects: 0.0,
+                double_redirects: 0.0,
+                travel: 0.0,
                 max_finger_use: MaxFingerUse {
                     penalty: 2.5,
                     pinky: 9.0,
@@ -141,8 +1201,26 @@ impl Config {
                     middle: 19.5,
                     index: 18.0,
                 },
+                hand_dominance: HandDominance::default(),
+                hand_alternation: HandAlternation::default(),
+                row_usage: RowUsage::default(),
+                shortcuts: Shortcuts::default(),
+                fingers: FingerSpeeds::default(),
+                roll_hands: RollHands::default(),
             },
+            trigram_rules: TrigramRules::default(),
+            constraints: Vec::new(),
+            bigram_penalties: Vec::new(),
+            generation: GenerationOptions::default(),
+            avoid_keys: Vec::new(),
+            external_metric: ExternalMetricConfig::default(),
+            profiles: Vec::new(),
+            profile: None,
+            warnings: Vec::new(),
             pins: Vec::new(),
+            pin_templates: Vec::new(),
+            one_handed_fingers: DEFAULT_FINGER_MAP,
+            fingers: I_TO_COL,
         }
     }
 
@@ -150,3 +1228,193 @@ impl Config {
         self.defaults.trigram_precision
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::MemorySource;
+
+    const CONFIG: &str = r#"
+        [defaults]
+        profile = "prose"
+
+        [weights]
+        fspeed = 8.0
+        scissors = 5.0
+
+        [weights.max_finger_use]
+        penalty = 2.5
+        pinky = 9.0
+
+        [weights.prose]
+        fspeed = 10.0
+
+        [weights.code]
+        scissors = 2.0
+        max_finger_use = { pinky = 12.0 }
+    "#;
+
+    #[test]
+    fn weight_profiles() {
+        let weight = |config: &toml::Value, path: &[&str]| {
+            path.iter()
+                .fold(&config["weights"], |value, key| &value[key])
+                .as_float()
+                .unwrap()
+        };
+
+        let mut config = toml::from_str::<toml::Value>(CONFIG).unwrap();
+        let (profiles, profile) = apply_profile(&mut config, None).unwrap();
+        assert_eq!(profiles, vec!["code", "prose"]);
+        assert_eq!(profile.as_deref(), Some("prose"));
+        assert_eq!(weight(&config, &["fspeed"]), 10.0);
+        assert!(config["weights"].get("prose").is_none());
+
+        let mut config = toml::from_str::<toml::Value>(CONFIG).unwrap();
+        let (_, profile) = apply_profile(&mut config, Some("code")).unwrap();
+        assert_eq!(profile.as_deref(), Some("code"));
+        assert_eq!(weight(&config, &["fspeed"]), 8.0);
+        assert_eq!(weight(&config, &["scissors"]), 2.0);
+        assert_eq!(weight(&config, &["max_finger_use", "penalty"]), 2.5);
+        assert_eq!(weight(&config, &["max_finger_use", "pinky"]), 12.0);
+
+        let mut config = toml::from_str::<toml::Value>(CONFIG).unwrap();
+        let (_, profile) = apply_profile(&mut config, Some(DEFAULT_PROFILE)).unwrap();
+        assert_eq!(profile, None);
+        assert_eq!(weight(&config, &["fspeed"]), 8.0);
+
+        let mut config = toml::from_str::<toml::Value>(CONFIG).unwrap();
+        assert!(apply_profile(&mut config, Some("gaming")).is_err());
+
+        let base = Config::default().weights;
+        let mut fitted = base.clone();
+        fitted.fspeed = 9.5;
+        fitted.max_finger_use.penalty = 3.0;
+        let profile = fitted.profile_toml("fitted", &base).unwrap();
+        assert_eq!(
+            profile,
+            "[weights.fitted]\nfspeed = 9.5\nmax_finger_use = { penalty = 3.0 }\n"
+        );
+        let mut config = toml::from_str::<toml::Value>(&format!("[weights]\n{profile}")).unwrap();
+        apply_profile(&mut config, Some("fitted")).unwrap();
+        assert_eq!(weight(&config, &["max_finger_use", "penalty"]), 3.0);
+        assert!(fitted.profile_toml("max_finger_use", &base).is_err());
+        assert!(fitted.profile_toml("my profile", &base).is_err());
+    }
+
+    #[test]
+    fn defaults_and_diagnostics() {
+        let config = |toml: &str| Config::from_source(&MemorySource::new(toml.to_string()), None);
+        let error = |toml: &str| config(toml).err().unwrap().to_string();
+
+        let partial = config("[weights]\nfspeed = 12.0\n").unwrap();
+        assert_eq!(partial.weights.fspeed, 12.0);
+        assert_eq!(partial.weights.heatmap, 1.65);
+        assert_eq!(partial.defaults.trigram_precision, 1000);
+        assert_eq!(
+            partial.defaults.references,
+            ["qwerty", "dvorak", "colemak", "workman"]
+        );
+        let no_references = config("[defaults]\nreferences = []\n").unwrap();
+        assert!(no_references.defaults.references.is_empty());
+        assert_eq!(partial.weights.hand_alternation.target, 0.6);
+        assert_eq!(partial.weights.row_usage.home, 0.7);
+        assert!(error("[weights.row_usage]\nhome = 120").contains("line 2: home"));
+        let row_usage = &partial.weights.row_usage;
+        assert_eq!(row_usage.distance(&[0.2, 0.7, 0.1]), 0.0);
+        assert!((row_usage.distance(&[0.35, 0.5, 0.15]) - 0.28).abs() < 1e-9);
+        assert_eq!(partial.weights.shortcuts.keys, "zxcvafw");
+        let shortcuts = config("[weights.shortcuts]\npenalty = 2.0\nkeys = \"zxcv\"").unwrap();
+        assert_eq!(shortcuts.weights.shortcuts.keys, "zxcv");
+        assert!(error("[weights.shortcuts]\npenalty = -1").contains("line 2: penalty"));
+        assert!(partial.warnings.is_empty());
+        assert!(config("").is_ok());
+
+        let negative = error("[weights]\nfspeed = 12.0\ndsfb_ratio = -0.1\n");
+        assert!(
+            negative.contains("line 3: dsfb_ratio is -0.1"),
+            "{negative}"
+        );
+        let percentage = error("[weights.max_finger_use]\npinky = 120\n");
+        assert!(percentage.contains("line 2: pinky is 120"), "{percentage}");
+        let in_profile = error("[defaults]\nprofile = \"prose\"\n\n[weights.prose]\nlsbs = -1\n");
+        assert!(in_profile.contains("line 5: lsbs"), "{in_profile}");
+        assert!(error("[defaults]\ntrigram_precision = 0").contains("line 2"));
+        assert!(error("[weights.hand_alternation]\ntarget = 150").contains("line 2: target"));
+        assert!(error("[weights.fingers]\npinky = 0.0").contains("line 2: pinky"));
+        let fingers = config("[weights.fingers]\npinky = 1.3\n").unwrap();
+        assert_eq!(fingers.weights.fingers.for_finger(7), 1.3);
+        assert_eq!(fingers.weights.fingers.for_finger(3), 1.0);
+        assert!(fingers.warnings.is_empty());
+        assert!(error("[weights.roll_hands]\nright = -0.5").contains("line 2: right"));
+        let nan = error("[weights]\nfspeed = 12.0\nlsbs = nan\n");
+        assert!(nan.contains("line 3: lsbs is NaN"), "{nan}");
+        let inf = error("[weights.hand_dominance]\nleft = inf\n");
+        assert!(inf.contains("line 2: left is inf"), "{inf}");
+
+        let penalties = config(concat!(
+            "[bigram_penalties.ring_pinky]\n",
+            "weight = 2.0\n",
+            "fingers = [\"ring\", \"pinky\"]\n",
+            "rows = \"same\"\n",
+        ))
+        .unwrap();
+        assert_eq!(penalties.bigram_penalties[0].name, "ring_pinky");
+        assert_eq!(penalties.bigram_penalties[0].pairs.len(), 6);
+        assert!(penalties.warnings.is_empty());
+        let no_pairs = error("[bigram_penalties.empty]\nweight = 1.0\n");
+        assert!(no_pairs.contains("bigram_penalties.empty"), "{no_pairs}");
+
+        let avoided = config("[avoid_keys]\n20 = 3.0\n3 = 1.5\n").unwrap();
+        assert_eq!(avoided.avoid_keys, vec![(20, 3.0), (3, 1.5)]);
+        assert!(avoided.warnings.is_empty());
+        assert!(error("[avoid_keys]\n30 = 2.0\n").contains("'30' is not a position"));
+        assert!(error("[avoid_keys]\nq = 2.0\n").contains("'q' is not a position"));
+        assert!(error("[avoid_keys]\n20 = 0.0\n").contains("has to be above 0"));
+        assert!(error("[generation]\navoided_key_ban = 120").contains("line 2: avoided_key_ban"));
+
+        let external =
+            config("[external_metric]\ncommand = [\"python3\", \"scorer.py\"]\n").unwrap();
+        assert_eq!(
+            external.external_metric.command,
+            vec!["python3", "scorer.py"]
+        );
+        assert_eq!(external.external_metric.weight, 0.0);
+        assert!(external.warnings.is_empty());
+        assert!(config("").unwrap().external_metric.command.is_empty());
+
+        let locks = config("[generation]\nlocked_hands = [\"right\"]\ncolumn_units = [0, 9]\n");
+        let locks = locks.unwrap().generation;
+        assert_eq!(locks.locked_hands, vec![Hand::Right]);
+        assert_eq!(locks.column_units, vec![0, 9]);
+        assert!(error("[generation]\ncolumn_units = [10]").contains("10 is not a column"));
+        assert!(config("[generation]\nlocked_hands = [\"up\"]").is_err());
+        assert!(error("[generation]\ntemperature = -0.1").contains("line 2: temperature"));
+
+        let templates = config("pin_templates = [\"zxcv\"]\n").unwrap();
+        assert_eq!(templates.pin_templates[0].name, "zxcv");
+        assert!(error("pin_templates = [\"qwerty\"]\n").contains("pin_templates: 'qwerty'"));
+
+        let unknown = config(concat!(
+            "fspeed = 1.0\n",
+            "[weights]\n",
+            "fspeeed = 1.0\n",
+            "[weights.prose]\n",
+            "scisors = 1.0\n",
+            "[generation]\n",
+            "consonants = \"tn\"\n",
+            "[defaults]\n",
+            "keyboard_type = \"dvorak\"\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            unknown.warnings,
+            vec![
+                "line 1: 'fspeed' is not a known setting and is ignored",
+                "line 3: 'weights.fspeeed' is not a known setting and is ignored",
+                "line 5: 'weights.prose.scisors' is not a known setting and is ignored",
+                "keyboard_type 'dvorak' is not known, so 'ansi angle' is used",
+            ]
+        );
+    }
+}
@@ -1,5 +1,5 @@
-use oxeylyzer_repl::repl;
+use oxeylyzer_repl::cli;
 
 fn main() -> Result<(), String> {
-    repl::Repl::run()
+    cli::run()
 }
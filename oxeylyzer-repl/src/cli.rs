@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use clap::{ArgAction, Parser, Subcommand};
+use oxeylyzer_core::generate::StopCriteria;
+use serde_json::Value;
+
+use crate::logger;
+use crate::repl::{parse_seconds, Repl};
+
+/// Analyzes and generates keyboard layouts. Without a command, it starts the interactive repl.
+#[derive(Parser, Debug)]
+#[command(name = "rusylyzer", version)]
+pub struct Cli {
+    /// Language to use instead of the default language in `config.toml`.
+    #[arg(short, long, global = true)]
+    pub language: Option<String>,
+    /// Weight profile of `config.toml` to use.
+    #[arg(short, long, global = true)]
+    pub profile: Option<String>,
+    /// Logs what generating and analyzing do to stderr, like how long things take. `-vv` also
+    /// logs every swap generating makes.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Shows the stats of a layout.
+    Analyze {
+        name: String,
+        /// Prints the layout, its score and its stats as json.
+        #[arg(long)]
+        json: bool,
+        /// Shows every stat, like the bigram stats of every finger.
+        #[arg(short, long)]
+        full: bool,
+        /// Leaves out trigrams with characters the layout doesn't have.
+        #[arg(short, long)]
+        renormalize: bool,
+    },
+    /// Generates layouts and shows the best ones.
+    Generate {
+        /// How many layouts to generate, 10 by default. With a time limit, target score or
+        /// patience, it is the most that are generated, without a limit by default.
+        #[arg(short = 'n', long)]
+        amount: Option<usize>,
+        /// Stops starting new layouts after this many seconds.
+        #[arg(long, value_parser = parse_seconds)]
+        max_seconds: Option<Duration>,
+        /// Stops once a layout has at least this score.
+        #[arg(long, allow_negative_numbers = true)]
+        target_score: Option<f64>,
+        /// Stops once this many layouts in a row didn't beat the best score.
+        #[arg(long)]
+        patience: Option<usize>,
+        /// A character for each of the 30 keys, which stays where it is. Free keys are a `.`.
+        #[arg(long)]
+        pins: Option<String>,
+        /// Prints the generated layouts and their scores as json, from best to worst.
+        #[arg(long)]
+        json: bool,
+        /// Generates for all of these languages at once, like `english,swedish`, and shows how
+        /// the best layouts do on each of them.
+        #[arg(long, value_delimiter = ',')]
+        languages: Vec<String>,
+    },
+    /// Ranks every layout of the language by score, or by a stat like sfb.
+    Rank {
+        /// Stat to rank by from low to high, with the same names as the `metric` constraints.
+        #[arg(short, long)]
+        by: Option<String>,
+        /// Only ranks the layouts with this tag.
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Only shows the best layout of every family.
+        #[arg(short, long)]
+        group: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Starts the interactive repl.
+    Repl,
+    /// Checks that well known layouts still have the score and stats that are stored for them.
+    Verify {
+        /// Stores the current score and stats instead.
+        #[arg(short, long)]
+        save: bool,
+        /// Fraction values may differ by.
+        #[arg(short, long, default_value_t = 1e-6)]
+        tolerance: f64,
+    },
+}
+
+fn print_json(value: &Value) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Runs the command given on the command line, or the interactive repl when there is none.
+pub fn run() -> Result<(), String> {
+    let cli = Cli::parse();
+    logger::init(cli.verbose);
+    let mut repl = Repl::with_options("static", cli.language.as_deref(), cli.profile.as_deref())?;
+
+    match cli.command {
+        None | Some(Command::Repl) => repl.interact(),
+        Some(Command::Analyze {
+            name, json: true, ..
+        }) => print_json(&repl.analyze_json(&name)?),
+        Some(Command::Analyze {
+            name,
+            full,
+            renormalize,
+            ..
+        }) => match repl.layout_by_name(&name) {
+            Some(_) => {
+                repl.analyze_name(&name, full, renormalize);
+                Ok(())
+            }
+            None => Err(repl.no_layout(&name)),
+        },
+        Some(Command::Generate {
+            amount,
+            max_seconds,
+            target_score,
+            patience,
+            pins,
+            json,
+            languages,
+        }) => {
+            if !languages.is_empty() {
+                let shares = languages
+                    .iter()
+                    .map(|language| (language.as_str(), 1.0))
+                    .collect::<Vec<_>>();
+                repl.use_languages(&shares)?;
+            }
+            let criteria = StopCriteria {
+                max_time: max_seconds,
+                target_score,
+                patience,
+            };
+            let amount = match (amount, criteria.is_empty()) {
+                (Some(amount), _) => amount,
+                (None, true) => 10,
+                (None, false) => usize::MAX,
+            };
+            match repl.generate(amount, pins.as_deref(), json, &criteria)? {
+                Some(value) => print_json(&value),
+                None => Ok(()),
+            }
+        }
+        Some(Command::Verify { save, tolerance }) => match repl.verify(save, tolerance)? {
+            0 => Ok(()),
+            differences => Err(format!("{differences} stats are different")),
+        },
+        Some(Command::Rank {
+            by,
+            tag,
+            group,
+            json: true,
+        }) => print_json(&repl.rank_json(by.as_deref(), tag.as_deref(), group)?),
+        Some(Command::Rank { by, tag, group, .. }) => match by {
+            Some(metric) => repl.print_rank_by(&metric, tag.as_deref(), group),
+            None => {
+                repl.rank(false, tag.as_deref(), group);
+                Ok(())
+            }
+        },
+    }
+}
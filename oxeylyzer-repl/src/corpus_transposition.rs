@@ -1,4 +1,5 @@
 use glob::glob;
+use oxeylyzer_core::regex::Regex;
 use oxeylyzer_core::translation::*;
 use serde::Deserialize;
 
@@ -40,12 +41,19 @@ struct CorpusConfigLoad {
     letters_to_lowercase: String,
     #[serde(default)]
     keep: String,
-    #[serde(default)]
+    #[serde(default, alias = "to_multiple")]
     multiple: Multiple,
     #[serde(default)]
     one_to_one: OneToOne,
     #[serde(default)]
     punct_unshifted: OneToOne,
+    /// Every character matching this regex is kept as is.
+    #[serde(default)]
+    keep_matching: Option<String>,
+    #[serde(default)]
+    uppercase: CaseFolding,
+    #[serde(default)]
+    strip_accents: bool,
 }
 
 impl CorpusConfigLoad {
@@ -105,8 +113,11 @@ pub struct CorpusConfig {
     letters_to_lowercase: String,
     punct_unshifted: OneToOne,
     keep: String,
+    keep_matching: Option<Regex>,
     to_multiple: Vec<(char, String)>,
     one_to_one: OneToOne,
+    uppercase: CaseFolding,
+    strip_accents: bool,
 }
 
 impl CorpusConfig {
@@ -117,30 +128,38 @@ impl CorpusConfig {
         //     Some(Multiple(l)) => l,
         //     None => Vec::new()
         // };
+        let keep_matching = loaded
+            .keep_matching
+            .map(|pattern| {
+                Regex::new(&pattern).map_err(|e| format!("keep_matching is not a valid regex: {e}"))
+            })
+            .transpose()?;
+
         Ok(Self {
             // source_language: loaded.source.unwrap_or_else(|| language.to_string()),
             inherits: loaded.inherits,
             letters_to_lowercase: loaded.letters_to_lowercase,
             punct_unshifted: loaded.punct_unshifted,
             keep: loaded.keep,
+            keep_matching,
             to_multiple: Self::get_to_multiple(loaded.multiple),
             one_to_one: loaded.one_to_one,
+            uppercase: loaded.uppercase,
+            strip_accents: loaded.strip_accents,
         })
     }
 
     fn get_to_multiple(multiple: Multiple) -> Vec<(char, String)> {
         let mut res = Vec::new();
-        if multiple.uppercase_versions {
-            for [from, to] in multiple.list {
-                if from.chars().count() == 1 {
-                    let c = from.chars().next().unwrap();
-                    res.push((c, to.clone()));
-
-                    let mut upper = c.to_uppercase();
-                    if upper.clone().count() == 1 {
-                        let upper_c = upper.next().unwrap();
-                        res.push((upper_c, to))
-                    }
+        for [from, to] in multiple.list {
+            if from.chars().count() == 1 {
+                let c = from.chars().next().unwrap();
+                res.push((c, to.clone()));
+
+                let mut upper = c.to_uppercase();
+                if multiple.uppercase_versions && upper.clone().count() == 1 {
+                    let upper_c = upper.next().unwrap();
+                    res.push((upper_c, to))
                 }
             }
         }
@@ -176,7 +195,12 @@ impl CorpusConfig {
     }
 
     pub fn translator(self) -> Translator {
-        let mut res = Translator::new()
+        let mut builder = Translator::new();
+        // first, so the other rules can still change what happens to these characters
+        if let Some(pattern) = &self.keep_matching {
+            builder.keep_matching(pattern);
+        }
+        let mut res = builder
             .letters_to_lowercase(&self.letters_to_lowercase)
             .keep(&self.keep)
             .one_to_one(&self.one_to_one.from, &self.one_to_one.to)
@@ -189,6 +213,12 @@ impl CorpusConfig {
                 res = res + new.translator();
             }
         }
+
+        // after the inherited rules, so they apply to their letters as well
+        res = res.fold_case(self.uppercase);
+        if self.strip_accents {
+            res = res.strip_accents();
+        }
         res
     }
 
@@ -0,0 +1,210 @@
+use std::io::{stdout, Write};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{read, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+use oxeylyzer_core::generate::{LayoutCache, LayoutGeneration};
+use oxeylyzer_core::layout::*;
+use oxeylyzer_core::utility::PosPair;
+
+/// The stats the editor shows below the layout.
+#[derive(Clone, Copy, Default)]
+struct LiveStats {
+    score: f64,
+    sfb: f64,
+    dsfb: f64,
+    rolls: f64,
+    alternates: f64,
+}
+
+/// Puts the terminal in raw mode on an alternate screen, and restores it when dropped so an error
+/// while editing doesn't leave the terminal unusable.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> Result<Self, String> {
+        enable_raw_mode().map_err(|e| e.to_string())?;
+        execute!(stdout(), EnterAlternateScreen, Hide).map_err(|e| e.to_string())?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+/// Edits a layout one swap at a time. Every swap is scored with a `LayoutCache` the same way
+/// generation does, so the score updates right away instead of scoring the whole layout again.
+pub struct Editor<'a> {
+    gen: &'a LayoutGeneration,
+    name: String,
+    layout: FastLayout,
+    cache: LayoutCache,
+    start: LiveStats,
+    cursor: usize,
+    selected: Option<usize>,
+    history: Vec<PosPair>,
+}
+
+impl<'a> Editor<'a> {
+    pub fn new(gen: &'a LayoutGeneration, name: &str, layout: FastLayout) -> Self {
+        let cache = gen.initialize_cache(&layout);
+        let mut res = Self {
+            gen,
+            name: name.to_string(),
+            layout,
+            cache,
+            start: LiveStats::default(),
+            cursor: 0,
+            selected: None,
+            history: Vec::new(),
+        };
+        res.start = res.stats();
+        res
+    }
+
+    fn stats(&self) -> LiveStats {
+        let ts = self
+            .gen
+            .trigram_stats(&self.layout, self.gen.trigram_precision);
+
+        LiveStats {
+            score: self.cache.total_score(),
+            sfb: self
+                .gen
+                .bigram_percent(&self.layout, "sfb")
+                .unwrap_or_default(),
+            dsfb: self
+                .gen
+                .bigram_percent(&self.layout, "dsfb")
+                .unwrap_or_default(),
+            rolls: ts.inrolls + ts.outrolls,
+            alternates: ts.alternates + ts.alternates_sfs,
+        }
+    }
+
+    fn swap(&mut self, swap: PosPair) {
+        self.gen
+            .accept_swap(&mut self.layout, &swap.into(), &mut self.cache);
+    }
+
+    /// Swaps the selected key with the one under the cursor, or selects the key under the cursor
+    /// when nothing is selected yet.
+    fn select(&mut self) {
+        match self.selected.take() {
+            Some(selected) if selected != self.cursor => {
+                let swap = PosPair(selected.min(self.cursor), selected.max(self.cursor));
+                self.swap(swap);
+                self.history.push(swap);
+            }
+            Some(_) => {}
+            None => self.selected = Some(self.cursor),
+        }
+    }
+
+    fn undo(&mut self) {
+        self.selected = None;
+        if let Some(swap) = self.history.pop() {
+            self.swap(swap);
+        }
+    }
+
+    fn move_cursor(&mut self, dx: isize, dy: isize) {
+        let x = (self.cursor % 10) as isize + dx;
+        let y = (self.cursor / 10) as isize + dy;
+        self.cursor = (y.rem_euclid(3) * 10 + x.rem_euclid(10)) as usize;
+    }
+
+    fn draw(&self, out: &mut impl Write) -> std::io::Result<()> {
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0), Print(&self.name))?;
+
+        let con = &self.gen.data.convert_u8;
+        for row in 0..3 {
+            queue!(out, MoveTo(2, row as u16 + 2))?;
+            for col in 0..10 {
+                let i = row * 10 + col;
+                if col == 5 {
+                    queue!(out, Print(" "))?;
+                }
+                if i == self.cursor {
+                    queue!(out, SetAttribute(Attribute::Reverse))?;
+                }
+                if Some(i) == self.selected {
+                    queue!(out, SetAttribute(Attribute::Underlined))?;
+                }
+                let c = con.from_single(self.layout.matrix[i]);
+                queue!(out, Print(c), SetAttribute(Attribute::Reset), Print(" "))?;
+            }
+        }
+
+        let (stats, start) = (self.stats(), self.start);
+        let lines = [
+            format!(
+                "Score:      {:.3} ({:+.3})",
+                stats.score,
+                stats.score - start.score
+            ),
+            format!(
+                "Sfb:        {:.3}% ({:+.3}%)",
+                stats.sfb * 100.0,
+                (stats.sfb - start.sfb) * 100.0
+            ),
+            format!(
+                "Dsfb:       {:.3}% ({:+.3}%)",
+                stats.dsfb * 100.0,
+                (stats.dsfb - start.dsfb) * 100.0
+            ),
+            format!(
+                "Rolls:      {:.2}% ({:+.2}%)",
+                stats.rolls * 100.0,
+                (stats.rolls - start.rolls) * 100.0
+            ),
+            format!(
+                "Alternates: {:.2}% ({:+.2}%)",
+                stats.alternates * 100.0,
+                (stats.alternates - start.alternates) * 100.0
+            ),
+            format!("Swaps:      {}", self.history.len()),
+            String::new(),
+            "arrows/hjkl: move, space/enter: pick a key and swap it, u: undo, q: done".to_string(),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            queue!(out, MoveTo(0, i as u16 + 6), Print(line))?;
+        }
+        out.flush()
+    }
+
+    /// Lets the user swap keys until they quit, and returns the edited layout.
+    pub fn run(mut self) -> Result<FastLayout, String> {
+        let _terminal = RawTerminal::enter()?;
+        let mut out = stdout();
+
+        loop {
+            self.draw(&mut out).map_err(|e| e.to_string())?;
+
+            let key = match read().map_err(|e| e.to_string())? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+            match key.code {
+                KeyCode::Left | KeyCode::Char('h') => self.move_cursor(-1, 0),
+                KeyCode::Right | KeyCode::Char('l') => self.move_cursor(1, 0),
+                KeyCode::Up | KeyCode::Char('k') => self.move_cursor(0, -1),
+                KeyCode::Down | KeyCode::Char('j') => self.move_cursor(0, 1),
+                KeyCode::Char(' ') | KeyCode::Enter => self.select(),
+                KeyCode::Char('u') | KeyCode::Backspace => self.undo(),
+                KeyCode::Esc if self.selected.is_some() => self.selected = None,
+                KeyCode::Esc | KeyCode::Char('q') => break,
+                _ => {}
+            }
+        }
+        Ok(self.layout)
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use crate::session::Session;
+
+/// A command that changed the session, with the session from before and after it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub command: String,
+    pub before: Session,
+    pub after: Session,
+}
+
+/// Every command that changed the generated layouts, the language or the weights, so `undo` can
+/// go back to before it and `redo` forward again.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    done: Vec<HistoryEntry>,
+    undone: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Remembers `command` when it changed the session. Anything that was undone can't be redone
+    /// after that.
+    pub fn record(&mut self, command: &str, before: Session, after: Session) {
+        if before == after {
+            return;
+        }
+        self.undone.clear();
+        self.done.push(HistoryEntry {
+            command: command.to_string(),
+            before,
+            after,
+        });
+    }
+
+    /// The last command that was done, which is undone from now on.
+    pub fn undo(&mut self) -> Option<&HistoryEntry> {
+        let entry = self.done.pop()?;
+        self.undone.push(entry);
+        self.undone.last()
+    }
+
+    /// The last command that was undone, which is done again from now on.
+    pub fn redo(&mut self) -> Option<&HistoryEntry> {
+        let entry = self.undone.pop()?;
+        self.done.push(entry);
+        self.done.last()
+    }
+
+    /// Commands that are done, from the first to the last.
+    pub fn commands(&self) -> impl Iterator<Item = &str> {
+        self.done.iter().map(|entry| entry.command.as_str())
+    }
+
+    /// Commands that can be redone, from the next one on.
+    pub fn undone_commands(&self) -> impl Iterator<Item = &str> {
+        self.undone.iter().rev().map(|entry| entry.command.as_str())
+    }
+
+    /// Writes the commands that are done to `path`, one per line, so `run` can do them again.
+    pub fn write_script<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let script = self.commands().fold(String::new(), |mut script, command| {
+            script.push_str(command);
+            script.push('\n');
+            script
+        });
+        std::fs::write(path, script).map_err(|e| format!("could not write {}: {e}", path.display()))
+    }
+}
+
+/// The commands of a script written by `history save`, or by hand. Empty lines and lines that
+/// start with `#` are left out.
+pub fn read_script<P: AsRef<Path>>(path: P) -> Result<Vec<String>, String> {
+    let path = path.as_ref();
+    let script = std::fs::read_to_string(path)
+        .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+    Ok(script
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
@@ -1,6 +1,11 @@
+pub mod cli;
 pub mod commands;
 pub mod corpus_transposition;
+pub mod editor;
+pub mod history;
+pub mod logger;
 pub mod repl;
+pub mod session;
 pub mod tui;
 
 // fn main() {
@@ -0,0 +1,36 @@
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Writes the log messages of the analyzer to stderr, where the progress bars are as well, so
+/// what is written to stdout, like json, stays the same.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level() && metadata.target().starts_with("oxeylyzer")
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let level = record.level().as_str().to_lowercase();
+            eprintln!("[{level}] {}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Shows warnings, like layouts that can't be loaded, and with a `verbosity` of 1, 2 or 3 also
+/// what takes how long, every swap generating makes, and everything else.
+pub fn init(verbosity: u8) {
+    let level = match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
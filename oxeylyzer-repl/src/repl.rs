@@ -1,14 +1,43 @@
 use std::io::Write;
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use getargs::Options;
 use indexmap::IndexMap;
+use indicatif::ProgressBar;
 use itertools::Itertools;
-use oxeylyzer_core::{generate::LayoutGeneration, layout::*, load_text, weights::Config};
+use oxeylyzer_core::{
+    baseline::{Baseline, BASELINE_FILE},
+    corpus_stats::CorpusStats,
+    data_source::FileSource,
+    fit_weights::fit_weights,
+    generate::{
+        wpm, Comparison, HeatmapMode, LayoutGeneration, LayoutStats, MagicKey, PunctuationCost,
+        StopCriteria, TypingTimeModel,
+    },
+    keymap::{Keymap, RemapBase, RemapTool},
+    language_data::LanguageData,
+    layout::*,
+    load_text,
+    metadata::{same_name, similar_names, ConfigSnapshot, LayoutMetadata},
+    one_handed::FingerMap,
+    pin_templates::PinTemplate,
+    rayon::iter::ParallelIterator,
+    schema,
+    sensitivity::{rank, sensitivity},
+    snapshot::{Snapshot, SNAPSHOT_FILE, SNAPSHOT_LAYOUTS},
+    utility::{TotalScore, MAX_CHARS},
+    weights::{Config, DEFAULT_PROFILE},
+};
 
 use crate::commands::*;
 use crate::corpus_transposition::CorpusConfig;
+use crate::editor::Editor;
+use crate::history::{read_script, History};
+use crate::session::{Session, SessionLayout};
 use crate::tui::*;
+use serde_json::{json, Value};
 use ArgumentType::*;
 
 pub struct Repl {
@@ -16,7 +45,15 @@ pub struct Repl {
     gen: LayoutGeneration,
     saved: IndexMap<String, FastLayout>,
     temp_generated: Vec<FastLayout>,
+    temp_parent: Option<String>,
     pins: Vec<usize>,
+    pin_templates: Vec<PinTemplate>,
+    one_handed_fingers: FingerMap,
+    /// Layouts `analyze` compares every layout with, from `references` in `config.toml`.
+    references: Vec<String>,
+    /// Weight profile picked with `profile`. Without one, the profile in `config.toml` is used.
+    profile: Option<String>,
+    history: History,
 }
 
 impl Repl {
@@ -24,16 +61,31 @@ impl Repl {
     where
         P: AsRef<Path>,
     {
-        let config = Config::new();
-        let language = config.defaults.language.clone();
+        Self::with_options(generator_base_path, None, None)
+    }
+
+    /// Like `new`, using `language` and the weights of `profile` instead of the ones set in
+    /// `config.toml`.
+    pub fn with_options<P>(
+        generator_base_path: P,
+        language: Option<&str>,
+        profile: Option<&str>,
+    ) -> Result<Self, String>
+    where
+        P: AsRef<Path>,
+    {
+        let config = Config::with_profile(profile).map_err(|e| e.to_string())?;
+        print_warnings(&config);
+        let language = language.map_or_else(|| config.defaults.language.clone(), str::to_string);
         let pins = config.pins.clone();
+        let pin_templates = config.pin_templates.clone();
+        let one_handed_fingers = config.one_handed_fingers;
+        let references = config.defaults.references.clone();
 
-        let mut gen = LayoutGeneration::new(
-            config.defaults.language.clone().as_str(),
-            generator_base_path.as_ref(),
-            Some(config),
-        )
-        .expect(format!("Could not read language data for {}", language).as_str());
+        let mut gen =
+            LayoutGeneration::new(&language, generator_base_path.as_ref(), Some(config))
+                .map_err(|e| e.to_string())?;
+        cache_trigrams(generator_base_path.as_ref(), &language);
 
         Ok(Self {
             saved: gen
@@ -45,13 +97,23 @@ impl Repl {
             language,
             gen,
             temp_generated: Vec::new(),
+            temp_parent: None,
             pins,
+            pin_templates,
+            one_handed_fingers,
+            references,
+            profile: profile.map(str::to_string),
+            history: History::default(),
         })
     }
 
     pub fn run() -> Result<(), String> {
-        let mut env = Self::new("static")?;
+        crate::logger::init(0);
+        Self::new("static")?.interact()
+    }
 
+    /// Reads commands until `quit`.
+    pub fn interact(&mut self) -> Result<(), String> {
         loop {
             let line = readline()?;
             let line = line.trim();
@@ -59,7 +121,7 @@ impl Repl {
                 continue;
             }
 
-            match env.respond(line) {
+            match self.respond_recorded(line) {
                 Ok(true) => break,
                 Ok(false) => continue,
                 Err(err) => {
@@ -71,26 +133,529 @@ impl Repl {
         Ok(())
     }
 
-    pub fn rank(&self) {
+    /// Ranks every layout, or only the ones tagged `tag`, by score, next to the typing speed
+    /// `TypingTimeModel` estimates for it. With `group`, only the best layout of every family is
+    /// shown.
+    pub fn rank(&self, verbose: bool, tag: Option<&str>, group: bool) {
+        let model = TypingTimeModel::default();
+
+        for (name, members) in self.ranked(tag, group) {
+            let layout = &self.saved[name];
+            let speed = wpm(self.gen.typing_time(layout, &model));
+            println!(
+                "{:10}{:<11}{}{}",
+                format!("{:.3}:", layout.score),
+                format!("{speed:.1} wpm"),
+                name,
+                family_note(self.family(name), members)
+            );
+
+            if let Some(metadata) = self.gen.layout_metadata.get(name).filter(|_| verbose) {
+                for line in metadata.to_string().lines() {
+                    println!("{:21}{}", "", line);
+                }
+            }
+        }
+    }
+
+    /// Every layout, or only the ones tagged `tag`, by the value of `metric` from low to high.
+    /// Values are in the unit `analyze` shows them in.
+    pub fn rank_by(&self, metric: &str, tag: Option<&str>) -> Result<Vec<(&str, f64)>, String> {
+        let mut res = Vec::new();
         for (name, layout) in self.saved.iter() {
-            println!("{:10}{}", format!("{:.3}:", layout.score), name);
+            if tag.map_or(false, |tag| !self.has_tag(name, tag)) {
+                continue;
+            }
+            let (_, value) = self
+                .gen
+                .get_layout_stats(layout)
+                .metric(metric)
+                .ok_or_else(|| format!("'{metric}' is not a stat layouts can be ranked by"))?;
+            res.push((name.as_str(), value));
+        }
+        res.sort_by(|(_, v1), (_, v2)| TotalScore(*v1).cmp(&TotalScore(*v2)));
+        Ok(res)
+    }
+
+    /// Layouts in the order `rank` shows them, with the amount of layouts in their family that
+    /// are ranked. With `group`, only the best layout of every family is left.
+    fn ranked(&self, tag: Option<&str>, group: bool) -> Vec<(&str, usize)> {
+        let names = self
+            .saved
+            .keys()
+            .map(String::as_str)
+            .filter(|name| tag.map_or(true, |tag| self.has_tag(name, tag)));
+        if group {
+            // the best layouts are shown last
+            let mut res = self.best_of_families(names.rev());
+            res.reverse();
+            res
+        } else {
+            names.map(|name| (name, 1)).collect()
+        }
+    }
+
+    /// Like `ranked`, for `rank_by`.
+    fn ranked_by(
+        &self,
+        metric: &str,
+        tag: Option<&str>,
+        group: bool,
+    ) -> Result<Vec<(&str, f64, usize)>, String> {
+        let ranked = self.rank_by(metric, tag)?;
+        let counts = if group {
+            self.best_of_families(ranked.iter().map(|&(name, _)| name))
+        } else {
+            ranked.iter().map(|&(name, _)| (name, 1)).collect()
+        };
+        Ok(counts
+            .into_iter()
+            .filter_map(|(name, members)| {
+                let (_, value) = ranked.iter().find(|(n, _)| *n == name)?;
+                Some((name, *value, members))
+            })
+            .collect())
+    }
+
+    /// Shows the layouts `rank_by` ranks, with their values. With `group`, only the best layout
+    /// of every family is shown.
+    pub fn print_rank_by(
+        &self,
+        metric: &str,
+        tag: Option<&str>,
+        group: bool,
+    ) -> Result<(), String> {
+        for (name, value, members) in self.ranked_by(metric, tag, group)? {
+            let note = family_note(self.family(name), members);
+            println!("{:10}{}{}", format!("{value:.3}:"), name, note);
+        }
+        Ok(())
+    }
+
+    /// Ranked layouts as json, with the value of `by` if it is given. See `rank` and `rank_by`.
+    /// With `group`, only the best layout of every family is included, along with its family and
+    /// the amount of layouts in it.
+    pub fn rank_json(
+        &self,
+        by: Option<&str>,
+        tag: Option<&str>,
+        group: bool,
+    ) -> Result<Value, String> {
+        let ranked = match by {
+            Some(metric) => self
+                .ranked_by(metric, tag, group)?
+                .into_iter()
+                .map(|(name, value, members)| (name, Some(value), members))
+                .collect::<Vec<_>>(),
+            None => self
+                .ranked(tag, group)
+                .into_iter()
+                .map(|(name, members)| (name, None, members))
+                .collect(),
+        };
+
+        let layouts = ranked
+            .into_iter()
+            .map(|(name, value, members)| {
+                let mut res = json!({ "name": name, "score": self.saved[name].score });
+                if let Some(value) = value {
+                    res["value"] = json!(value);
+                }
+                if group {
+                    res["family"] = json!(self.family(name));
+                    res["variants"] = json!(members);
+                }
+                res
+            })
+            .collect::<Vec<_>>();
+        Ok(Value::Array(layouts))
+    }
+
+    /// Name, keys, score and stats of the layout called `name` as json. The stats follow the
+    /// `stats` schema, the parts of the score the `score` schema and the metadata, which is null
+    /// for a layout without any, the `layout_metadata` schema.
+    pub fn analyze_json(&self, name: &str) -> Result<Value, String> {
+        let name = self.layout_name(name).ok_or_else(|| self.no_layout(name))?;
+        let layout = &self.saved[name];
+        let stats = serde_json::to_value(self.gen.get_layout_stats(layout))
+            .map_err(|e| e.to_string())?;
+        let components = self.gen.score_components(layout, self.gen.trigram_precision);
+
+        Ok(json!({
+            "schema_version": schema::SCHEMA_VERSION,
+            "name": name,
+            "layout": self.gen.convert_u8.as_str(&layout.matrix),
+            "score": components.total,
+            "score_components": components,
+            "stats": stats,
+            "metadata": self.gen.layout_metadata.get(name),
+        }))
+    }
+
+    /// Fails when layouts score NaN with the current language data and weights, as generating
+    /// would only find layouts that can't be ranked.
+    fn check_scores(&self) -> Result<(), String> {
+        let layout = FastLayout::with_fingers(self.gen.chars_for_generation, &self.gen.fingers);
+        self.gen
+            .checked_score("a layout of the characters to generate with", &layout)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Generates `amount` layouts like the `generate` command does, or fewer when `criteria` stop
+    /// it before that. With `pins`, every key that isn't a `.` is put there and stays there, like
+    /// `..... e....` for an `e` on the home row. With `json`, the layouts are returned as json
+    /// instead of shown. When several languages are combined, the 10 best also get their score
+    /// and stats for every language.
+    pub fn generate(
+        &mut self,
+        amount: usize,
+        pins: Option<&str>,
+        json: bool,
+        criteria: &StopCriteria,
+    ) -> Result<Option<Value>, String> {
+        self.check_scores()?;
+        let pinned = pins.map(|pins| self.pinned_layout(pins)).transpose()?;
+        if pinned.is_some() && !criteria.is_empty() {
+            return Err("a time limit, target score or patience can't be used with pins".into());
+        }
+
+        // progress bars are drawn on stderr, so the json is all there is on stdout
+        self.temp_generated = match (pinned, json) {
+            (Some((layout, pins)), false) => generate_n_with_pins(&self.gen, amount, layout, &pins),
+            (None, false) => generate_n(&self.gen, amount, criteria),
+            (Some((layout, pins)), true) => self
+                .gen
+                .generate_n_with_pins_iter(amount, layout, &pins)
+                .collect(),
+            (None, true) => generate_with_progress(&self.gen, amount, criteria),
+        };
+        if json {
+            self.temp_generated
+                .sort_by_key(|layout| std::cmp::Reverse(TotalScore(layout.score)));
+            dedup_columns(&mut self.temp_generated);
         }
+        self.temp_parent = None;
+
+        Ok(json.then(|| {
+            let top = &self.temp_generated[..self.temp_generated.len().min(10)];
+            let mut per_language = self.gen.language_results(top).into_iter();
+            let layouts = self
+                .temp_generated
+                .iter()
+                .map(|layout| {
+                    let mut value = json!({
+                        "layout": self.gen.convert_u8.as_str(&layout.matrix),
+                        "score": layout.score,
+                    });
+                    if let Some(results) = per_language.next().filter(|r| !r.is_empty()) {
+                        value["languages"] = results
+                            .into_iter()
+                            .map(|r| {
+                                json!({
+                                    "language": r.language,
+                                    "share": r.share,
+                                    "score": r.score,
+                                    "rank": r.rank,
+                                    "lopsided": r.lopsided,
+                                    "stats": r.stats,
+                                })
+                            })
+                            .collect();
+                    }
+                    value
+                })
+                .collect();
+            Value::Array(layouts)
+        }))
+    }
+
+    /// A layout of the characters used for generation with the keys of `pins` in place, along
+    /// with their positions. `pins` has a character for every key, where `.` is a free key.
+    fn pinned_layout(&self, pins: &str) -> Result<(FastLayout, Vec<usize>), String> {
+        let keys = pins.chars().filter(|c| !c.is_whitespace()).collect::<Vec<_>>();
+        if keys.len() != 30 {
+            return Err(format!(
+                "pins need a character or . for all 30 keys, but there are {}",
+                keys.len()
+            ));
+        }
+
+        let gen = &self.gen;
+        let mut layout = FastLayout::with_fingers(gen.chars_for_generation, &gen.fingers);
+        let mut pinned = Vec::new();
+        for (pos, c) in keys.into_iter().enumerate().filter(|&(_, c)| c != '.') {
+            let u = gen.convert_u8.to_single_lossy(c);
+            match layout.matrix.iter().position(|&k| k == u) {
+                Some(i) if pinned.contains(&i) => return Err(format!("'{c}' is pinned twice")),
+                Some(i) => {
+                    layout.swap(i, pos);
+                }
+                None => return Err(format!("'{c}' is not one of the keys that are generated")),
+            }
+            pinned.push(pos);
+        }
+        Ok((layout, pinned))
+    }
+
+    /// Name the layout called `name` is saved as. Besides by that name, a layout can be found by
+    /// the same name with another case or `-` or spaces instead of `_`, and by its aliases.
+    pub fn layout_name(&self, name: &str) -> Option<&str> {
+        let saved = match self.saved.get_key_value(name) {
+            Some((saved, _)) => Some(saved),
+            None => self
+                .saved
+                .keys()
+                .find(|saved| same_name(saved, name))
+                .or_else(|| {
+                    self.saved.keys().find(|saved| {
+                        self.gen
+                            .layout_metadata
+                            .get(*saved)
+                            .map_or(false, |metadata| metadata.has_alias(name))
+                    })
+                }),
+        };
+        saved.map(String::as_str)
     }
 
     pub fn layout_by_name(&self, name: &str) -> Option<&FastLayout> {
-        self.saved.get(name)
+        self.layout_name(name).and_then(|name| self.saved.get(name))
     }
 
-    pub fn analyze_name(&self, name: &str) {
-        let l = match self.layout_by_name(name) {
-            Some(layout) => layout,
+    /// Like [`Self::layout_name`], for when the name is needed after changing the repl.
+    fn saved_name(&self, name: &str) -> Result<String, String> {
+        self.layout_name(name)
+            .map(str::to_string)
+            .ok_or_else(|| self.no_layout(name))
+    }
+
+    /// Error for when there is no layout called `name`, with the names it looks like a typo of.
+    pub fn no_layout(&self, name: &str) -> String {
+        let similar = similar_names(name, self.saved.keys().map(String::as_str));
+        if similar.is_empty() {
+            format!("layout {name} does not exist!")
+        } else {
+            let similar = similar.into_iter().take(3).join(", ");
+            format!("layout {name} does not exist! did you mean {similar}?")
+        }
+    }
+
+    fn layout_path(&self, name: &str) -> PathBuf {
+        PathBuf::from(format!("static/layouts/{}/{}.kb", self.language, name))
+    }
+
+    fn has_tag(&self, name: &str, tag: &str) -> bool {
+        self.gen
+            .layout_metadata
+            .get(name)
+            .map_or(false, |metadata| metadata.has_tag(tag))
+    }
+
+    /// Names of the layouts tagged `tag`, in the order `rank` shows them.
+    fn tagged(&self, tag: &str) -> Vec<String> {
+        self.saved
+            .keys()
+            .filter(|name| self.has_tag(name, tag))
+            .cloned()
+            .collect()
+    }
+
+    /// Family of the layout called `name`, set with `# family:` in its file. A layout without one
+    /// is a family of its own.
+    fn family<'a>(&'a self, name: &'a str) -> &'a str {
+        LayoutMetadata::family_of(self.gen.layout_metadata.get(name), name)
+    }
+
+    /// The first layout of every family in `ranked` along with the amount of layouts of `ranked`
+    /// in its family, so with the best layouts first it keeps the best of every family.
+    fn best_of_families<'a, I>(&'a self, ranked: I) -> Vec<(&'a str, usize)>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut res: Vec<(&str, usize)> = Vec::new();
+        for name in ranked {
+            let family = self.family(name);
+            match res.iter_mut().find(|(best, _)| same_name(self.family(best), family)) {
+                Some((_, members)) => *members += 1,
+                None => res.push((name, 1)),
+            }
+        }
+        res
+    }
+
+    /// Names of the layouts in family `family` in the order `rank` shows them. When no layout has
+    /// a family called that, it is the family of the layout called `family`.
+    fn family_members(&self, family: &str) -> Vec<&str> {
+        let members = |family: &str| {
+            self.saved
+                .keys()
+                .map(String::as_str)
+                .filter(|name| same_name(self.family(name), family))
+                .collect::<Vec<_>>()
+        };
+        match members(family) {
+            found if !found.is_empty() => found,
+            _ => self
+                .layout_name(family)
+                .map_or_else(Vec::new, |name| members(self.family(name))),
+        }
+    }
+
+    /// Adds `tags` to the layout called `name`, or removes them with `remove`, and writes them to
+    /// its file.
+    fn tag(&mut self, name: &str, tags: &[&str], remove: bool) -> Result<(), String> {
+        let name = &self.saved_name(name)?;
+        let mut metadata = self.gen.layout_metadata.get(name).cloned().unwrap_or_default();
+
+        for &tag in tags {
+            if remove {
+                metadata.remove_tag(tag);
+            } else {
+                metadata.add_tag(tag);
+            }
+        }
+
+        if !tags.is_empty() {
+            let path = self.layout_path(name);
+            let layout_str = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            std::fs::write(&path, metadata.write_tags(&layout_str)).map_err(|e| e.to_string())?;
+        }
+
+        if metadata.tags.is_empty() {
+            println!("{name} has no tags");
+        } else {
+            println!("tags of {name}: {}", metadata.tags.join(" "));
+        }
+        self.gen.layout_metadata.insert(name.to_string(), metadata);
+        Ok(())
+    }
+
+    /// Deletes the files of the layouts in `names`, or moves them to an `archive` directory where
+    /// they aren't loaded anymore. Asks first when there is more than one.
+    fn remove_layouts(&mut self, names: &[String], archive: bool) -> Result<(), String> {
+        let verb = if archive { "archive" } else { "delete" };
+
+        let names = names
+            .iter()
+            .map(|name| self.saved_name(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        if names.len() > 1 {
+            println!("{verb} {} layouts: {}? (y/n)", names.len(), names.join(", "));
+            if readline()?.trim() != "y" {
+                println!("nothing was {verb}d");
+                return Ok(());
+            }
+        }
+
+        let archive_dir = Path::new("static/layouts").join(&self.language).join("archive");
+        if archive {
+            std::fs::create_dir_all(&archive_dir).map_err(|e| e.to_string())?;
+        }
+
+        for name in names.iter() {
+            let path = self.layout_path(name);
+            let res = if archive {
+                std::fs::rename(&path, archive_dir.join(format!("{name}.kb")))
+            } else {
+                std::fs::remove_file(&path)
+            };
+            res.map_err(|e| format!("could not {verb} {name}: {e}"))?;
+
+            self.saved.shift_remove(name);
+            self.gen.layout_metadata.remove(name);
+            self.gen.layout_layers.remove(name);
+            self.gen.layout_magic.remove(name);
+            println!("{verb}d {name}");
+        }
+        Ok(())
+    }
+
+    /// Renames the file of a layout, keeping its place in `rank` and everything loaded for it.
+    fn rename(&mut self, old: &str, new: &str) -> Result<(), String> {
+        let old = self.saved_name(old)?;
+        let new = new.replace(" ", "_");
+        if new.is_empty() || new.contains(['/', '\\']) {
+            return Err(format!("'{new}' can't be the name of a layout"));
+        }
+        if let Some(existing) = self.layout_name(&new).filter(|&existing| existing != old) {
+            return Err(format!("there already is a layout called {existing}"));
+        }
+
+        std::fs::rename(self.layout_path(&old), self.layout_path(&new))
+            .map_err(|e| format!("could not rename {old}: {e}"))?;
+
+        self.saved = std::mem::take(&mut self.saved)
+            .into_iter()
+            .map(|(name, l)| if name == old { (new.clone(), l) } else { (name, l) })
+            .collect();
+        if let Some(metadata) = self.gen.layout_metadata.remove(&old) {
+            self.gen.layout_metadata.insert(new.clone(), metadata);
+        }
+        if let Some(layer) = self.gen.layout_layers.remove(&old) {
+            self.gen.layout_layers.insert(new.clone(), layer);
+        }
+        if let Some(magic) = self.gen.layout_magic.remove(&old) {
+            self.gen.layout_magic.insert(new.clone(), magic);
+        }
+        if self.temp_parent.as_deref() == Some(old.as_str()) {
+            self.temp_parent = Some(new.clone());
+        }
+        println!("renamed {old} to {new}");
+        Ok(())
+    }
+
+    pub fn analyze_name(&self, name: &str, full: bool, renormalize: bool) {
+        let (name, l) = match self.layout_name(name) {
+            Some(name) => (name, &self.saved[name]),
             None => {
-                println!("layout {} does not exist!", name);
+                println!("{}", self.no_layout(name));
                 return;
             }
         };
         println!("{}", name);
-        self.analyze(&l);
+        if let Some(metadata) = self.gen.layout_metadata.get(name) {
+            print!("{metadata}");
+        }
+        match self.gen.layout_magic.get(name) {
+            Some(magic) => {
+                println!("{magic}");
+                match self.with_magic(magic, l) {
+                    Ok((gen, layout)) => self.analyze_with(&gen, &layout, full, renormalize),
+                    Err(e) => println!("{e}"),
+                }
+            }
+            None => self.analyze(&l, full, renormalize),
+        }
+
+        if let Some(layer) = self.gen.layout_layers.get(name) {
+            let stats = self.gen.layer_stats(l, layer);
+            println!("\n{}\n{stats}", layer.formatted_string(&self.gen.convert_u8));
+        }
+    }
+
+    /// A generator for the current languages as they are typed with `magic`, and `layout` with
+    /// its characters, so the layout can be analyzed with the magic key.
+    fn with_magic(
+        &self,
+        magic: &MagicKey,
+        layout: &FastLayout,
+    ) -> Result<(LayoutGeneration, FastLayout), String> {
+        let shares = match self.gen.languages.is_empty() {
+            true => vec![(self.language.as_str(), 1.0)],
+            false => self
+                .gen
+                .languages
+                .iter()
+                .map(|(language, share, _)| (language.as_str(), *share))
+                .collect(),
+        };
+        let gen = LayoutGeneration::with_magic(&shares, "static", magic, self.config()?)
+            .map_err(|e| e.to_string())?;
+        let layout = gen
+            .convert_with_thumbs(&self.gen, layout)
+            .map_err(|e| e.to_string())?;
+        Ok((gen, layout))
     }
 
     fn placeholder_name(&self, layout: &FastLayout) -> Result<String, String> {
@@ -114,248 +679,2253 @@ impl Repl {
         let new_name = if let Some(n) = name {
             n.replace(" ", "_")
         } else {
-            self.placeholder_name(&layout).unwrap()
+            self.placeholder_name(&layout)?
         };
 
         let mut f = std::fs::OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
-            .open(format!("static/layouts/{}/{}.kb", self.language, new_name))
+            .open(self.layout_path(&new_name))
             .map_err(|e| e.to_string())?;
 
         let layout_formatted = layout.formatted_string(&self.gen.data.convert_u8);
+        layout.score = self.gen.score(&layout);
+
+        let parent = self
+            .temp_parent
+            .as_ref()
+            .and_then(|name| self.layout_by_name(name).map(|l| (name, l)));
+
+        let mut header = if let Some((parent_name, parent)) = parent {
+            let mut deltas = self
+                .gen
+                .get_layout_stats(&layout)
+                .deltas(&self.gen.get_layout_stats(parent));
+            deltas.push(format!("Score {:+.3}", layout.score - parent.score));
+
+            println!("changes compared to {}:\n{}", parent_name, deltas.join("\n"));
+            format!("# parent: {}\n# {}\n", parent_name, deltas.join(", "))
+        } else {
+            String::new()
+        };
+
+        let config = self.config_snapshot();
+        header.push_str(&config.comments());
+        let metadata = LayoutMetadata {
+            parent: parent.map(|(parent_name, _)| parent_name.clone()),
+            config,
+            ..Default::default()
+        };
+        self.gen.layout_metadata.insert(new_name.clone(), metadata);
+
         println!("saved {}\n{}", new_name, layout_formatted);
-        f.write(layout_formatted.as_bytes()).unwrap();
+        f.write(header.as_bytes()).map_err(|e| e.to_string())?;
+        f.write(layout_formatted.as_bytes()).map_err(|e| e.to_string())?;
 
-        layout.score = self.gen.score(&layout);
         self.saved.insert(new_name, layout);
         self.saved
-            .sort_by(|_, a, _, b| a.score.partial_cmp(&b.score).unwrap());
+            .sort_by(|_, a, _, b| TotalScore(a.score).cmp(&TotalScore(b.score)));
 
         Ok(())
     }
 
-    pub fn analyze(&self, layout: &FastLayout) {
-        let stats = self.gen.get_layout_stats(layout);
+    /// Shows the stats and score of `layout`. With `full`, it is also scored on every trigram
+    /// instead of only the `trigram_precision` most common ones, along with the difference. With
+    /// `renormalize`, trigram stats leave out trigrams with characters that aren't on the layout.
+    pub fn analyze(&self, layout: &FastLayout, full: bool, renormalize: bool) {
+        self.analyze_with(&self.gen, layout, full, renormalize);
+    }
+
+    /// Like `analyze`, scoring `layout` with `gen` instead of the current generator.
+    fn analyze_with(
+        &self,
+        gen: &LayoutGeneration,
+        layout: &FastLayout,
+        full: bool,
+        renormalize: bool,
+    ) {
+        let undecided = gen.undecided_keys(layout).len();
+        let renormalize = renormalize || undecided > 0;
+        let mut stats = gen.get_layout_stats(layout);
+        let invalid = stats.trigram_stats.invalid_share();
+        if renormalize {
+            stats.trigram_stats = stats.trigram_stats.without_invalid();
+        }
         let score = if layout.score == 0.000 {
-            self.gen.score(layout)
+            gen.score(layout)
         } else {
             layout.score
         };
 
-        let layout_str = heatmap_string(&self.gen.data, layout);
+        let layout_str = heatmap_string(&gen.data, layout);
 
         println!("{}\n{}\nScore: {:.3}", layout_str, stats, score);
-    }
+        if undecided > 0 {
+            println!("{undecided} keys are undecided, so the stats only count the decided ones");
+        }
 
-    pub fn compare_name(&self, name1: &str, name2: &str) {
-        let l1 = match self.layout_by_name(name1) {
-            Some(layout) => layout,
-            None => {
-                println!("layout {} does not exist!", name1);
-                return;
+        if invalid >= INVALID_TRIGRAM_WARNING {
+            let chars = self
+                .gen
+                .invalid_trigram_chars(layout)
+                .into_iter()
+                .take(5)
+                .map(|(c, freq)| format!("'{c}' {:.2}%", freq * 100.0))
+                .collect::<Vec<_>>()
+                .join(", ");
+            println!(
+                "warning: {:.2}% of trigrams have characters that aren't on the layout: {chars}",
+                invalid * 100.0
+            );
+            if renormalize {
+                println!("trigram stats leave these out");
+            } else {
+                println!("use --renormalize to leave them out of the trigram stats");
             }
-        };
-        let l2 = match self.layout_by_name(name2) {
-            Some(layout) => layout,
-            None => {
-                println!("layout {} does not exist!", name2);
-                return;
+        }
+
+        if full {
+            let full_score = gen.score_full(layout);
+            println!(
+                "Full score: {:.3} ({:+.3} using all {} trigrams instead of {})",
+                full_score,
+                full_score - score,
+                gen.data.trigrams.len(),
+                gen.trigram_precision.min(gen.data.trigrams.len())
+            );
+        }
+
+        if let Some(mut ts) = gen.space_trigram_stats(layout) {
+            if renormalize {
+                ts = ts.without_invalid();
             }
-        };
-        println!("\n{:31}{}", name1, name2);
-        for y in 0..3 {
-            for (n, layout) in [l1, l2].into_iter().enumerate() {
-                for x in 0..10 {
-                    print!("{} ", heatmap_heat(&self.gen.data, layout.c(x + 10 * y)));
-                    if x == 4 {
-                        print!(" ");
-                    }
-                }
-                if n == 0 {
-                    print!("          ");
-                }
+            println!(
+                "Across spaces ({:?} thumb): Rolls {:.2}%, Alternates {:.2}%, Redirects {:.3}%, Onehands {:.3}%",
+                gen.space_thumb,
+                (ts.inrolls + ts.outrolls) * 100.0,
+                (ts.alternates + ts.alternates_sfs) * 100.0,
+                (ts.redirects + ts.redirects_sfs + ts.bad_redirects + ts.bad_redirects_sfs) * 100.0,
+                ts.onehands * 100.0
+            );
+        }
+
+        if let Some(qs) = gen.quadgram_stats(layout, usize::MAX) {
+            println!(
+                "Quadgrams: Quad Rolls {:.3}%, Long Onehands {:.3}%, Onehand Redirects {:.3}%, Double Redirects {:.3}%",
+                qs.quad_rolls * 100.0,
+                qs.long_onehands * 100.0,
+                qs.onehand_redirects * 100.0,
+                qs.double_redirects * 100.0
+            );
+        }
+
+        if !gen.languages.is_empty() {
+            println!("\nPer language:");
+            for (language, share, language_gen) in gen.languages.iter() {
+                let converted = gen.convert_layout(language_gen, layout);
+                let stats = language_gen.get_layout_stats(&converted);
+                let ts = &stats.trigram_stats;
+                println!(
+                    "{language} ({:.0}%): Sfb {:.3}%, Dsfb {:.3}%, Rolls {:.2}%, Alternates {:.2}%, Redirects {:.3}%",
+                    share * 100.0,
+                    stats.sfb * 100.0,
+                    stats.dsfb * 100.0,
+                    (ts.inrolls + ts.outrolls) * 100.0,
+                    (ts.alternates + ts.alternates_sfs) * 100.0,
+                    (ts.redirects + ts.redirects_sfs + ts.bad_redirects + ts.bad_redirects_sfs) * 100.0
+                );
             }
-            println!();
         }
-        let s1 = self.gen.get_layout_stats(l1);
-        let s2 = self.gen.get_layout_stats(l2);
-        let ts1 = s1.trigram_stats;
-        let ts2 = s2.trigram_stats;
+
+        let dominance = &gen.weights.hand_dominance;
+        if !dominance.is_symmetric() {
+            println!(
+                "Hand dominance: left x{:.2}, right x{:.2}",
+                dominance.left, dominance.right
+            );
+        }
+
+        let roll_hands = &gen.weights.roll_hands;
+        if !roll_hands.is_neutral() {
+            println!(
+                "Roll weights: left x{:.2}, right x{:.2}",
+                roll_hands.left, roll_hands.right
+            );
+        }
+
+        let speeds = &gen.weights.fingers;
+        if !speeds.is_neutral() {
+            println!(
+                "Finger speed: pinky x{:.2}, ring x{:.2}, middle x{:.2}, index x{:.2}",
+                speeds.pinky, speeds.ring, speeds.middle, speeds.index
+            );
+        }
+
+        let alternation = &gen.weights.hand_alternation;
+        if alternation.penalty != 0.0 {
+            println!(
+                "Hand alternation target: {:.2}%, {:.3}% away",
+                alternation.target * 100.0,
+                alternation.distance(stats.hand_alternation) * 100.0
+            );
+        }
+
+        let row_usage = &gen.weights.row_usage;
+        if row_usage.penalty != 0.0 {
+            println!(
+                "Row usage limits: top {:.2}%, home {:.2}%, bottom {:.2}%, {:.3}% outside of them",
+                row_usage.top * 100.0,
+                row_usage.home * 100.0,
+                row_usage.bottom * 100.0,
+                row_usage.distance(&stats.row_usage) * 100.0
+            );
+        }
+
+        let unreachable = gen.unreachable_shortcuts(layout);
+        if gen.weights.shortcuts.penalty != 0.0 && !unreachable.is_empty() {
+            println!(
+                "Shortcut keys out of reach: {}",
+                gen.convert_u8.as_str(&unreachable)
+            );
+        }
+
+        let external = &gen.external_metric;
+        if external.is_enabled() {
+            match external.value(&gen.convert_u8.as_str(&layout.matrix)) {
+                Ok(value) => println!(
+                    "External metric: {value:.3}, x{} in the score",
+                    external.config.weight
+                ),
+                Err(e) => println!("{e}"),
+            }
+        }
+
+        self.print_references(gen, layout, score);
+    }
+
+    /// Shows how much the score and the stats in `TABLE_COLUMNS` of `layout` differ from the ones
+    /// of every reference layout, leaving out the references that aren't in the current language.
+    /// `layout` is scored by `gen`, while the references are scored by the current generator.
+    fn print_references(&self, gen: &LayoutGeneration, layout: &FastLayout, score: f64) {
+        let references = self
+            .references
+            .iter()
+            .filter_map(|name| Some((name, self.layout_by_name(name)?)))
+            .filter(|(_, reference)| reference.matrix != layout.matrix)
+            .collect::<Vec<_>>();
+        if references.is_empty() {
+            return;
+        }
+
+        let mut values = table_values(gen, layout);
+        values[0] = score;
+        println!();
+        print_table_row("compared to", TABLE_COLUMNS.map(str::to_string));
+        for (name, reference) in references {
+            let reference = table_values(&self.gen, reference);
+            let deltas = std::array::from_fn(|i| format!("{:+.3}", values[i] - reference[i]));
+            print_table_row(name, deltas);
+        }
+    }
+
+    /// Reads `config.toml` again and rebuilds everything with it.
+    fn reload(&mut self) -> Result<(), String> {
+        let config = self.config()?;
+        print_warnings(&config);
+        self.pins = config.pins.clone();
+        self.pin_templates = config.pin_templates.clone();
+        self.one_handed_fingers = config.one_handed_fingers;
+        self.references = config.defaults.references.clone();
+
+        if !self.gen.languages.is_empty() {
+            let languages = std::mem::take(&mut self.gen.languages);
+            let shares = languages
+                .iter()
+                .map(|(language, share, _)| (language.as_str(), *share))
+                .collect::<Vec<_>>();
+            self.combine(&shares)?;
+        } else {
+            self.gen = LayoutGeneration::new(self.language.as_str(), "static", Some(config))
+                .map_err(|e| e.to_string())?;
+            self.saved = self
+                .gen
+                .load_layouts("static/layouts", self.language.as_str())
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Scores `amount` trigrams from now on, until the next `reload`, and scores every layout again
+    /// with them.
+    fn set_precision(&mut self, amount: usize) -> Result<(), String> {
+        if amount == 0 {
+            return Err("the trigram precision has to be at least 1".to_string());
+        }
+        self.gen.rebuild_trigrams(amount);
+
+        for layout in self.saved.values_mut().chain(self.temp_generated.iter_mut()) {
+            layout.score = self.gen.score(layout);
+        }
+        self.saved
+            .sort_by(|_, a, _, b| TotalScore(a.score).cmp(&TotalScore(b.score)));
+        self.temp_generated
+            .sort_by_key(|layout| std::cmp::Reverse(TotalScore(layout.score)));
+
         println!(
-            concat!(
-                "Sfb:               {: <11} Sfb:               {:.3}%\n",
-                "Dsfb:              {: <11} Dsfb:              {:.3}%\n",
-                "Finger Speed:      {: <11} Finger Speed:      {:.3}\n",
-                "Scissors           {: <11} Scissors:          {:.3}%\n",
-                "Lsbs               {: <11} Lsbs:              {:.3}%\n\n",
-                "Inrolls:           {: <11} Inrolls:           {:.2}%\n",
-                "Outrolls:          {: <11} Outrolls:          {:.2}%\n",
-                "Total Rolls:       {: <11} Total Rolls:       {:.2}%\n",
-                "Onehands:          {: <11} Onehands:          {:.3}%\n\n",
-                "Alternates:        {: <11} Alternates:        {:.2}%\n",
-                "Alternates Sfs:    {: <11} Alternates Sfs:    {:.2}%\n",
-                "Total Alternates:  {: <11} Total Alternates:  {:.2}%\n\n",
-                "Redirects:         {: <11} Redirects:         {:.3}%\n",
-                "Redirects Sfs:     {: <11} Redirects Sfs:     {:.3}%\n",
-                "Bad Redirects:     {: <11} Bad Redirects:     {:.3}%\n",
-                "Bad Redirects Sfs: {: <11} Bad Redirects Sfs: {:.3}%\n",
-                "Total Redirects:   {: <11} Total Redirects:   {:.3}%\n\n",
-                "Bad Sfbs:          {: <11} Bad Sfbs:          {:.3}%\n",
-                "Sft:               {: <11} Sft:               {:.3}%\n\n",
-                "Score:             {: <11} Score:             {:.3}\n"
-            ),
-            format!("{:.3}%", s1.sfb * 100.0),
-            s2.sfb * 100.0,
-            format!("{:.3}%", s1.dsfb * 100.0),
-            s2.dsfb * 100.0,
-            format!("{:.3}", s1.fspeed * 10.0),
-            s2.fspeed * 10.0,
-            format!("{:.3}%", s1.scissors * 100.0),
-            s2.scissors * 100.0,
-            format!("{:.3}%", s1.lsbs * 100.0),
-            s2.lsbs * 100.0,
-            format!("{:.2}%", ts1.inrolls * 100.0),
-            ts2.inrolls * 100.0,
-            format!("{:.2}%", ts1.outrolls * 100.0),
-            ts2.outrolls * 100.0,
-            format!("{:.2}%", (ts1.inrolls + ts1.outrolls) * 100.0),
-            (ts2.inrolls + ts2.outrolls) * 100.0,
-            format!("{:.3}%", ts1.onehands * 100.0),
-            ts2.onehands * 100.0,
-            format!("{:.2}%", ts1.alternates * 100.0),
-            ts2.alternates * 100.0,
-            format!("{:.2}%", ts1.alternates_sfs * 100.0),
-            ts2.alternates_sfs * 100.0,
-            format!("{:.2}%", (ts1.alternates + ts1.alternates_sfs) * 100.0),
-            (ts2.alternates + ts2.alternates_sfs) * 100.0,
-            format!("{:.3}%", ts1.redirects * 100.0),
-            ts2.redirects * 100.0,
-            format!("{:.3}%", ts1.redirects_sfs * 100.0),
-            ts2.redirects_sfs * 100.0,
-            format!("{:.3}%", ts1.bad_redirects * 100.0),
-            ts2.bad_redirects * 100.0,
-            format!("{:.3}%", ts1.bad_redirects_sfs * 100.0),
-            ts2.bad_redirects_sfs * 100.0,
-            format!(
-                "{:.3}%",
-                (ts1.redirects + ts1.redirects_sfs + ts1.bad_redirects + ts1.bad_redirects_sfs)
-                    * 100.0
-            ),
-            (ts2.redirects + ts2.redirects_sfs + ts2.bad_redirects + ts2.bad_redirects_sfs) * 100.0,
-            format!("{:.3}%", ts1.bad_sfbs * 100.0),
-            ts2.bad_sfbs * 100.0,
-            format!("{:.3}%", ts1.sfts * 100.0),
-            ts2.sfts * 100.0,
-            format!("{:.3}", l1.score),
-            l2.score
+            "Scoring the {} most common of {} trigrams",
+            amount.min(self.gen.data.trigrams.len()),
+            self.gen.data.trigrams.len()
         );
+        Ok(())
     }
 
-    fn get_nth(&self, nr: usize) -> Option<FastLayout> {
-        if nr < self.temp_generated.len() {
-            let l = self.temp_generated[nr].clone();
-            Some(l)
+    /// Scores the smallest amount of trigrams from now on that ranks the saved layouts the same as
+    /// every trigram does, except for `tolerance` percent of the pairs of layouts.
+    fn calibrate_precision(&mut self, tolerance: f64) -> Result<(), String> {
+        if !(0.0..=100.0).contains(&tolerance) {
+            return Err("the tolerance is a percentage between 0 and 100".to_string());
+        }
+        if self.saved.len() < 2 {
+            return Err("calibrating needs at least 2 layouts to rank".to_string());
+        }
+
+        let layouts = self.saved.values().cloned().collect::<Vec<_>>();
+        let calibration = self.gen.calibrate_precision(&layouts, tolerance / 100.0);
+        for (precision, agreement) in calibration.tried.iter() {
+            println!(
+                "{precision:>8} trigrams rank {:.2}% of pairs of layouts the same",
+                agreement * 100.0
+            );
+        }
+        self.set_precision(calibration.precision)
+    }
+
+    /// The weights and language layouts are scored with right now, which `save` stores with them.
+    fn config_snapshot(&self) -> ConfigSnapshot {
+        let profile = self.config().ok().and_then(|config| config.profile);
+
+        ConfigSnapshot::new(
+            &self.gen.language,
+            self.gen.trigram_precision,
+            profile.as_deref(),
+            &self.gen.weights,
+        )
+    }
+
+    /// Shows the effort of every key and the finger speed distance of same finger bigrams with it,
+    /// as the current weights and keyboard type make them.
+    fn show_effort(&self) {
+        println!("effort of every key:");
+        println!("{}\n", value_grid_string(&self.gen.effort_map()));
+        println!("average finger speed distance of the same finger bigrams with every key:");
+        println!("{}", value_grid_string(&self.gen.key_fspeed_distances()));
+    }
+
+    /// Shows the weights and language `name` was saved with, and which weights have changed since.
+    fn show_config(&self, name: &str) -> Result<(), String> {
+        let name = &self.saved_name(name)?;
+        let saved = self
+            .gen
+            .layout_metadata
+            .get(name)
+            .map(|metadata| &metadata.config)
+            .filter(|config| !config.is_empty());
+        let saved = match saved {
+            Some(saved) => saved,
+            None => {
+                println!("{name} was saved without its config");
+                return Ok(());
+            }
+        };
+
+        println!("{name} was saved with:\n{saved}");
+        let current = self.config_snapshot();
+        if saved.language.is_some() && saved.language != current.language {
+            println!("the language is now {}", current.language.as_deref().unwrap_or_default());
+        }
+        if saved.profile != current.profile {
+            let profile = current.profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+            println!("the profile is now {profile}");
+        }
+        let changes = saved.weight_changes(&current);
+        if changes.is_empty() {
+            println!("the weights haven't changed since");
         } else {
-            if self.temp_generated.len() == 0 {
-                println!("You haven't generated any layouts yet!");
-            } else {
-                println!("That's not a valid index!");
+            println!("weights that changed since:");
+            for (weight, old, new) in changes {
+                println!("  {weight:<24}{old} -> {new}");
             }
-            None
         }
+        Ok(())
     }
 
-    pub fn sfr_freq(&self) -> f64 {
-        let len = self.gen.data.characters.len();
-        let chars = 0..len;
-        chars
-            .clone()
-            .cartesian_product(chars)
-            .filter(|(i1, i2)| i1 == i2)
-            .map(|(c1, c2)| self.gen.data.bigrams.get(c1 * len + c2).unwrap_or(&0.0))
-            .sum()
+    /// The config with the weights of the current profile.
+    fn config(&self) -> Result<Config, String> {
+        Config::with_profile(self.profile.as_deref()).map_err(|e| e.to_string())
     }
 
-    fn sfbs(&self, name: &str, top_n: usize) {
-        if let Some(layout) = self.layout_by_name(name) {
-            println!("top {} sfbs for {name}:", top_n.min(48));
+    fn set_language(&mut self, language: &str) -> Result<(), String> {
+        let generator = LayoutGeneration::new(language, "static", Some(self.config()?))
+            .map_err(|e| e.to_string())?;
+        cache_trigrams("static", language);
+        self.switch_generator(generator, language)?;
 
-            for (bigram, freq) in self.gen.sfbs(layout, top_n) {
+        println!(
+            "Set language to {}. Sfr: {:.2}%, {} layouts",
+            language,
+            self.sfr_freq() * 100.0,
+            self.saved.len()
+        );
+        Ok(())
+    }
+
+    /// Uses `generator` from now on, with the layouts of `language` scored by it. Generated layouts
+    /// that weren't saved are dropped, as their keys only mean something with the characters of
+    /// the language they were made for. Nothing changes when the layouts can't be loaded.
+    fn switch_generator(
+        &mut self,
+        mut generator: LayoutGeneration,
+        language: &str,
+    ) -> Result<(), String> {
+        self.saved = generator
+            .load_layouts("static/layouts", language)
+            .map_err(|e| e.to_string())?;
+        self.language = language.to_string();
+        self.gen = generator;
+        self.temp_generated.clear();
+        self.temp_parent = None;
+        Ok(())
+    }
+
+    /// Uses a weighted combination of several languages for everything, like `english 60 german 40`.
+    /// Layouts are still loaded from and saved to the first language.
+    fn combine(&mut self, shares: &[(&str, f64)]) -> Result<(), String> {
+        self.use_languages(shares)?;
+
+        println!(
+            "Set language to {}. Sfr: {:.2}%",
+            self.gen.language,
+            self.sfr_freq() * 100.0
+        );
+        Ok(())
+    }
+
+    /// Like `combine`, without showing anything.
+    pub fn use_languages(&mut self, shares: &[(&str, f64)]) -> Result<(), String> {
+        let generator = LayoutGeneration::combined(shares, "static", Some(self.config()?))
+            .map_err(|e| e.to_string())?;
+        self.switch_generator(generator, shares[0].0)
+    }
+
+    /// Shows how `name` ranks among the saved layouts on every one of `languages` on its own, or on
+    /// every language of the mix without any, to see which languages the layout is good for.
+    fn by_language(&self, name: &str, languages: &[&str]) -> Result<(), String> {
+        let name = self.saved_name(name)?;
+        let combined;
+        let gen = if languages.is_empty() {
+            if self.gen.languages.is_empty() {
+                return Err("give the languages to compare, or 'combine' several first".into());
+            }
+            &self.gen
+        } else {
+            let shares = languages.iter().map(|&language| (language, 1.0)).collect::<Vec<_>>();
+            combined = LayoutGeneration::combined(&shares, "static", Some(self.config()?))
+                .map_err(|e| e.to_string())?;
+            &combined
+        };
+
+        let mut layouts = self
+            .saved
+            .iter()
+            .map(|(saved, l)| {
+                let mut converted = self.gen.convert_layout(gen, l);
+                converted.score = gen.score(&converted);
+                (saved, converted)
+            })
+            .collect::<Vec<_>>();
+        layouts.sort_by_key(|(_, l)| std::cmp::Reverse(TotalScore(l.score)));
+        let index = layouts.iter().position(|(saved, _)| **saved == name).unwrap();
+        let layouts = layouts.into_iter().map(|(_, l)| l).collect::<Vec<_>>();
+
+        let standing = gen.language_standing(&layouts, index);
+        println!("{name} among {} layouts, best language first:", layouts.len());
+        for r in standing.iter() {
+            let mark = if r.lopsided { " !" } else { "" };
+            println!(
+                "{:<12}{:>10.5}{:>6}  sfb {:.3}%, dsfb {:.3}%{mark}",
+                r.language,
+                r.score,
+                format!("#{}", r.rank),
+                r.stats.sfb * 100.0,
+                r.stats.dsfb * 100.0
+            );
+        }
+        if let (Some(best), Some(worst)) = (standing.first(), standing.last()) {
+            if best.rank < worst.rank {
+                println!(
+                    "best on {} (#{}), worst on {} (#{})",
+                    best.language, best.rank, worst.language, worst.rank
+                );
+            }
+        }
+        if standing.iter().any(|r| r.lopsided) {
+            println!("! good for the mix, but among the worst of these for that language");
+        }
+        Ok(())
+    }
+
+    /// The language, weight profile, pins and generated layouts as they are now.
+    fn session(&self) -> Session {
+        Session {
+            language: self.language.clone(),
+            combination: self
+                .gen
+                .languages
+                .iter()
+                .map(|(language, share, _)| (language.clone(), *share))
+                .collect(),
+            profile: self.profile.clone(),
+            pins: self.pins.clone(),
+            pin_templates: self
+                .pin_templates
+                .iter()
+                .map(|template| template.name.to_string())
+                .collect(),
+            parent: self.temp_parent.clone(),
+            generated: self
+                .temp_generated
+                .iter()
+                .map(|layout| SessionLayout {
+                    layout: self.gen.convert_u8.as_str(&layout.matrix),
+                    score: layout.score,
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes the language, weight profile, pins and generated layouts to `path`, so that
+    /// `session load` can pick up from here.
+    fn save_session(&self, path: &str) -> Result<(), String> {
+        let session = self.session();
+        session.write(path)?;
+
+        println!("saved the session with {} generated layouts to {path}", session.generated.len());
+        Ok(())
+    }
+
+    /// Picks up a session saved with `session save`. The generated layouts are scored again, and
+    /// when their scores changed, like after changing the weights or the language data, that is
+    /// pointed out.
+    fn load_session(&mut self, path: &str) -> Result<(), String> {
+        let changed = self.restore_session(Session::read(path)?, true)?;
+
+        if let Some(profile) = &self.profile {
+            println!("Using the weights of profile '{profile}'");
+        }
+        if changed > 0 {
+            println!(
+                "{changed} generated layouts score differently than when the session was saved, \
+                the weights or the language data have changed since"
+            );
+        }
+        self.list_generated(0..10);
+        Ok(())
+    }
+
+    /// Makes `session` the current one. The language data and weights are only loaded again with
+    /// `reload`, or when the session uses other ones. Returns how many generated layouts score
+    /// differently than when the session was made.
+    fn restore_session(&mut self, session: Session, reload: bool) -> Result<usize, String> {
+        let pin_templates = session
+            .pin_templates
+            .iter()
+            .map(|name| PinTemplate::try_from(name.as_str()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(pos) = session.pins.iter().find(|&&pos| pos >= 30) {
+            return Err(format!("pin {pos} is not one of the 30 keys"));
+        }
+        if let Some(saved) = session.generated.iter().find(|l| l.layout.chars().count() != 30) {
+            return Err(format!("'{}' does not have 30 keys", saved.layout));
+        }
+
+        let combination = self
+            .gen
+            .languages
+            .iter()
+            .map(|(language, share, _)| (language.clone(), *share))
+            .collect::<Vec<_>>();
+        let same_data = session.language == self.language
+            && session.combination == combination
+            && session.profile == self.profile;
+
+        let previous = std::mem::replace(&mut self.profile, session.profile.clone());
+        let loaded = if same_data && !reload {
+            Ok(())
+        } else if session.combination.is_empty() {
+            self.set_language(&session.language)
+        } else {
+            let shares = session
+                .combination
+                .iter()
+                .map(|(language, share)| (language.as_str(), *share))
+                .collect::<Vec<_>>();
+            self.combine(&shares)
+        };
+        if let Err(e) = loaded {
+            self.profile = previous;
+            return Err(e);
+        }
+
+        let mut generated = Vec::with_capacity(session.generated.len());
+        let mut changed = 0;
+        for saved in session.generated.iter() {
+            let bytes = self.gen.convert_u8.to_lossy(saved.layout.chars());
+            let mut layout = FastLayout::try_from(bytes.as_slice()).map_err(|e| e.to_string())?;
+            layout.set_fingers(&self.gen.fingers);
+            layout.score = self.gen.score(&layout);
+            if (layout.score - saved.score).abs() > 1e-6 * saved.score.abs().max(1.0) {
+                changed += 1;
+            }
+            generated.push(layout);
+        }
+
+        self.pins = session.pins;
+        self.pin_templates = pin_templates;
+        self.temp_parent = session.parent.filter(|parent| self.saved.contains_key(parent));
+        self.temp_generated = generated;
+        Ok(changed)
+    }
+
+    /// Like `respond`, remembering `line` in the history when it changes the session. Commands
+    /// that walk through the history aren't remembered themselves.
+    fn respond_recorded(&mut self, line: &str) -> Result<bool, String> {
+        let command = line.split_whitespace().next().unwrap_or_default();
+        if matches!(command, "undo" | "u" | "redo" | "history" | "run") {
+            return self.respond(line);
+        }
+
+        let before = self.session();
+        let res = self.respond(line);
+        self.history.record(line, before, self.session());
+        res
+    }
+
+    /// Goes back to before the last command that changed the generated layouts, the language or
+    /// the weights.
+    fn undo(&mut self) -> Result<(), String> {
+        let entry = match self.history.undo() {
+            Some(entry) => entry.clone(),
+            None => {
+                println!("there is nothing to undo");
+                return Ok(());
+            }
+        };
+        if let Err(e) = self.restore_session(entry.before, false) {
+            self.history.redo();
+            return Err(e);
+        }
+        println!("undid '{}'", entry.command);
+        Ok(())
+    }
+
+    /// Does the last command `undo` went back on again.
+    fn redo(&mut self) -> Result<(), String> {
+        let entry = match self.history.redo() {
+            Some(entry) => entry.clone(),
+            None => {
+                println!("there is nothing to redo");
+                return Ok(());
+            }
+        };
+        if let Err(e) = self.restore_session(entry.after, false) {
+            self.history.undo();
+            return Err(e);
+        }
+        println!("redid '{}'", entry.command);
+        Ok(())
+    }
+
+    /// Shows the commands that changed the session, and the ones that can be redone.
+    fn show_history(&self) {
+        let mut empty = true;
+        for (i, command) in self.history.commands().enumerate() {
+            println!("{:>4}  {command}", i + 1);
+            empty = false;
+        }
+        for command in self.history.undone_commands() {
+            println!("undone {command}");
+            empty = false;
+        }
+        if empty {
+            println!("no command changed the generated layouts, the language or the weights yet");
+        }
+    }
+
+    /// Runs every command in the script at `path` as if it was typed, until one fails. Returns
+    /// whether one of them quits.
+    fn run_script(&mut self, path: &str) -> Result<bool, String> {
+        for command in read_script(path)? {
+            println!("> {command}");
+            let quit = self
+                .respond_recorded(&command)
+                .map_err(|e| format!("'{command}' failed: {e}"))?;
+            if quit {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Compares 2 layouts, and with `retraining` also shows what switching from the first to the
+    /// second gains and how much has to be relearned for it.
+    pub fn compare_name(&self, name1: &str, name2: &str, retraining: bool) {
+        let name1 = match self.layout_name(name1) {
+            Some(name) => name,
+            None => {
+                println!("{}", self.no_layout(name1));
+                return;
+            }
+        };
+        let name2 = match self.layout_name(name2) {
+            Some(name) => name,
+            None => {
+                println!("{}", self.no_layout(name2));
+                return;
+            }
+        };
+        let comparison = self.compare(name1, &self.saved[name1], name2, &self.saved[name2]);
+        if retraining {
+            println!("\n{}", retraining_string(name1, name2, &comparison));
+        }
+    }
+
+    /// The layouts called `name1` and `name2` compared, without showing anything.
+    pub fn comparison(&self, name1: &str, name2: &str) -> Result<Comparison, String> {
+        let l1 = self.layout_by_name(name1).ok_or_else(|| self.no_layout(name1))?;
+        let l2 = self.layout_by_name(name2).ok_or_else(|| self.no_layout(name2))?;
+        Ok(self.gen.compare(l1, l2))
+    }
+
+    /// Names and scores of the layouts `rank` shows, from the worst to the best score.
+    pub fn rank_scores(&self, tag: Option<&str>, group: bool) -> Vec<(&str, f64)> {
+        self.ranked(tag, group)
+            .into_iter()
+            .map(|(name, _)| (name, self.saved[name].score))
+            .collect()
+    }
+
+    /// Stats of the layout called `name`, which `analyze` shows.
+    pub fn stats(&self, name: &str) -> Result<LayoutStats, String> {
+        self.layout_by_name(name)
+            .map(|layout| self.gen.get_layout_stats(layout))
+            .ok_or_else(|| self.no_layout(name))
+    }
+
+    /// Shows how the score and rank of `name` among the loaded layouts change when every weight is
+    /// changed by 10% either way.
+    fn sensitivity(&self, name: &str) -> Result<(), String> {
+        let layout = self
+            .layout_by_name(name)
+            .ok_or_else(|| self.no_layout(name))?;
+        let others = self
+            .saved
+            .iter()
+            .filter(|&(other, _)| other != name)
+            .map(|(_, layout)| layout)
+            .collect::<Vec<_>>();
+        let config = self.config()?;
+
+        println!("scoring {} layouts with every weight changed by 10%...", self.saved.len());
+        let res = sensitivity(&self.language, &self.gen.data, &config, layout, &others, 0.1);
+
+        let current = rank(layout.score, others.iter().map(|other| other.score));
+        println!(
+            "\n{name} has rank {current} of {} with a score of {:.3}\n",
+            self.saved.len(),
+            layout.score
+        );
+        println!("{:<18}{:<22}{}", "weight", "-10%", "+10%");
+        for s in res.iter() {
+            let change = |i: usize| format!("{:+.3} (rank {})", s.score_changes[i], s.ranks[i]);
+            println!("{:<18}{:<22}{}", s.weight, change(0), change(1));
+        }
+
+        if let Some(most) = res.first() {
+            println!(
+                "\nThe score is most sensitive to {}, which changes it by up to {:.3}.",
+                most.weight,
+                most.max_change()
+            );
+        }
+        Ok(())
+    }
+
+    /// Searches for weights that score the layouts of `names` in that order, best first, and adds
+    /// them to `config.toml` as `profile`. The search starts from the current weights.
+    fn fit_weights(&self, profile: &str, names: &[&str], candidates: usize) -> Result<(), String> {
+        if names.len() < 2 {
+            return Err("fit-weights needs at least 2 layouts to put in order".to_string());
+        }
+        let layouts = names
+            .iter()
+            .map(|&name| {
+                self.layout_by_name(name)
+                    .ok_or_else(|| self.no_layout(name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let config = self.config()?;
+        if config.profiles.iter().any(|p| p == profile) {
+            return Err(format!("there is a [weights.{profile}] profile already"));
+        }
+        // profiles are applied on top of [weights], so only differences with it are saved
+        let base = Config::with_profile(Some(DEFAULT_PROFILE))
+            .map_err(|e| e.to_string())?
+            .weights;
+        config.weights.profile_toml(profile, &base)?;
+
+        println!("searching for weights that rank {}...", names.join(" > "));
+        let pb = ProgressBar::new(candidates as u64);
+        let fit = fit_weights(
+            &self.language,
+            &self.gen.data,
+            &config,
+            &layouts,
+            candidates,
+            || pb.inc(1),
+        );
+        pb.finish_and_clear();
+
+        println!(
+            "{} of {} pairs are in the wrong order after trying {} weights, {} were before",
+            fit.wrong_pairs, fit.pairs, fit.candidates, fit.start_wrong_pairs
+        );
+        if fit.wrong_pairs >= fit.start_wrong_pairs {
+            println!("no weights were found that rank these layouts better than the current ones");
+            return Ok(());
+        }
+
+        let table = fit.weights.profile_toml(profile, &base)?;
+        let mut contents = std::fs::read_to_string("config.toml").map_err(|e| e.to_string())?;
+        let newline = if contents.contains("\r\n") { "\r\n" } else { "\n" };
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push_str(newline);
+        }
+        contents.push_str(newline);
+        contents.push_str(&table.replace('\n', newline));
+        std::fs::write("config.toml", contents).map_err(|e| e.to_string())?;
+
+        print!("\n{table}");
+        println!("\nsaved to config.toml, 'profile {profile}' ranks layouts with these weights");
+        Ok(())
+    }
+
+    /// Shows where the score and stats of `name` fall among `amount` layouts generated with the
+    /// current weights. The generated layouts are cached, so they are only generated again when
+    /// the weights, language or amount change.
+    fn percentile(&self, name: &str, amount: usize) -> Result<(), String> {
+        let layout = self
+            .layout_by_name(name)
+            .ok_or_else(|| self.no_layout(name))?;
+        if amount == 0 {
+            return Err("the baseline needs at least 1 layout".to_string());
+        }
+
+        let path = PathBuf::from("static/layouts")
+            .join(&self.language)
+            .join(BASELINE_FILE);
+        let key = self.gen.baseline_key();
+        let baseline = match Baseline::read(&path, key, amount) {
+            Some(baseline) => baseline,
+            None => {
+                self.check_scores()?;
+                println!("generating {amount} layouts to compare with...");
+                let layouts = generate_with_progress(&self.gen, amount, &StopCriteria::default());
+                let baseline = Baseline::new(&self.gen, &layouts);
+                if let Err(e) = baseline.write(&path) {
+                    println!("the baseline could not be cached: {e}");
+                }
+                baseline
+            }
+        };
+
+        // everything except these is a fraction, shown as a percentage
+        let format = |column: &str, value: f64| match column {
+            "score" | "sfb_distance" | "travel" => format!("{value:.3}"),
+            "fspeed" => format!("{:.3}", value * 10.0),
+            _ => format!("{:.3}%", value * 100.0),
+        };
+        let stats = self.gen.get_layout_stats(layout);
+
+        println!("{name} compared to {} generated layouts:", baseline.len());
+        println!("{:<20}{:>12}{:>12}{:>12}", "stat", "value", "median", "percentile");
+        let percentiles = baseline.percentiles(layout.score, &stats);
+        // per finger and per row stats would make the table too long to read
+        let shown = percentiles
+            .into_iter()
+            .filter(|(column, _, _)| PERCENTILE_STATS.contains(&column.as_str()));
+        for (column, value, percentile) in shown {
+            let median = baseline.median(&column).unwrap_or_default();
+            println!(
+                "{:<20}{:>12}{:>12}{:>11.1}%",
+                column,
+                format(&column, value),
+                format(&column, median),
+                percentile
+            );
+        }
+        println!("\na higher percentile means a higher value, which is better for the score");
+        Ok(())
+    }
+
+    /// Scores the well known layouts of `SNAPSHOT_LAYOUTS` again and compares their score and
+    /// stats with the ones stored for the language, to see whether changing the weights, the
+    /// language data or the scoring itself changed them. With `save`, the current ones are stored
+    /// instead. Returns the amount of differences.
+    pub fn verify(&self, save: bool, tolerance: f64) -> Result<usize, String> {
+        let path = PathBuf::from("static/layouts")
+            .join(&self.language)
+            .join(SNAPSHOT_FILE);
+        let layouts = SNAPSHOT_LAYOUTS
+            .into_iter()
+            .filter_map(|name| Some((name, self.layout_by_name(name)?)));
+        let current = Snapshot::new(&self.gen, layouts);
+
+        if save {
+            current.write(&path).map_err(|e| e.to_string())?;
+            println!("saved the stats of {}", current.layouts().join(", "));
+            return Ok(0);
+        }
+
+        let expected = Snapshot::read(&path)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| {
+                format!(
+                    "there are no stats stored for {} yet, use 'verify --save' to store them",
+                    self.language
+                )
+            })?;
+        let differences = expected.differences(&current, tolerance);
+
+        for d in differences.iter() {
+            match d.actual {
+                Some(actual) => println!(
+                    "{:<14}{:<24}expected {:.6}, got {:.6}",
+                    d.layout, d.column, d.expected, actual
+                ),
+                None => println!("{:<14}{:<24}is missing", d.layout, d.column),
+            }
+        }
+        if differences.is_empty() {
+            println!("the stats of {} are as expected", expected.layouts().join(", "));
+        } else {
+            println!(
+                "\n{} stats are different, use 'verify --save' if that is expected",
+                differences.len()
+            );
+        }
+        Ok(differences.len())
+    }
+
+    /// Compares `name` with its left and right half swapped. The mirrored layout replaces the
+    /// generated ones, so it can be saved with `save 0`.
+    fn mirror(&mut self, name: &str) -> Result<(), String> {
+        let name = &self.saved_name(name)?;
+        let layout = self.saved[name].clone();
+        let mut mirrored = layout.mirrored();
+        mirrored.score = self.gen.score(&mirrored);
+
+        self.compare(name, &layout, &format!("{name} (mirrored)"), &mirrored);
+        println!("use 'save 0 <name>' to save the mirrored layout");
+
+        self.temp_generated = vec![mirrored];
+        self.temp_parent = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Opens `name` in the editor, after which the edited layout can be saved like a generated one.
+    fn edit(&mut self, name: &str) -> Result<(), String> {
+        let name = &self.saved_name(name)?;
+        let layout = self.saved[name].clone();
+        let mut edited = Editor::new(&self.gen, name, layout.clone()).run()?;
+
+        if edited.matrix == layout.matrix {
+            println!("{name} wasn't changed");
+            return Ok(());
+        }
+        edited.score = self.gen.score(&edited);
+
+        self.compare(name, &layout, &format!("{name} (edited)"), &edited);
+        println!("use 'save 0 <name>' to save the edited layout");
+
+        self.temp_generated = vec![edited];
+        self.temp_parent = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Shows the most important stats of every layout tagged `tag` in a table, ordered like `rank`.
+    fn compare_tagged(&self, tag: &str) -> Result<(), String> {
+        let names = self.tagged(tag);
+        if names.is_empty() {
+            return Err(format!("no layouts are tagged '{tag}'"));
+        }
+
+        print_table_row("name", TABLE_COLUMNS.map(str::to_string));
+        for name in names.iter() {
+            let values = table_values(&self.gen, &self.saved[name]);
+            print_table_row(name, values.map(|v| format!("{v:.3}")));
+        }
+        Ok(())
+    }
+
+    /// Shows the stats of every layout in a family from best to worst, followed by how far apart
+    /// the best and worst value of every stat are. `family` can also be a layout of the family.
+    fn family_summary(&self, family: &str) -> Result<(), String> {
+        let mut members = self.family_members(family);
+        if members.is_empty() {
+            return Err(format!("no layouts are in the family '{family}'"));
+        }
+        members.reverse();
+        let family = self.family(members[0]);
+        println!("{family}: {} layouts, from best to worst", members.len());
+
+        print_table_row("name", TABLE_COLUMNS.map(str::to_string));
+        let mut lowest = [f64::MAX; 9];
+        let mut highest = [f64::MIN; 9];
+        for name in members.iter() {
+            let values = table_values(&self.gen, &self.saved[*name]);
+            for (i, value) in values.into_iter().enumerate() {
+                lowest[i] = lowest[i].min(value);
+                highest[i] = highest[i].max(value);
+            }
+            print_table_row(name, values.map(|v| format!("{v:.3}")));
+        }
+
+        println!();
+        print_table_row("lowest", lowest.map(|v| format!("{v:.3}")));
+        print_table_row("highest", highest.map(|v| format!("{v:.3}")));
+        let spread = std::array::from_fn::<_, 9, _>(|i| highest[i] - lowest[i]);
+        print_table_row("spread", spread.map(|v| format!("{v:.3}")));
+        Ok(())
+    }
+
+    /// Shows 2 layouts next to each other with their stats, and returns what is shown.
+    pub fn compare(
+        &self,
+        name1: &str,
+        l1: &FastLayout,
+        name2: &str,
+        l2: &FastLayout,
+    ) -> Comparison {
+        println!("\n{:31}{}", name1, name2);
+        for y in 0..3 {
+            for (n, layout) in [l1, l2].into_iter().enumerate() {
+                for x in 0..10 {
+                    print!("{} ", heatmap_heat(&self.gen.data, layout.c(x + 10 * y)));
+                    if x == 4 {
+                        print!(" ");
+                    }
+                }
+                if n == 0 {
+                    print!("          ");
+                }
+            }
+            println!();
+        }
+        let comparison = self.gen.compare(l1, l2);
+        println!("{}", comparison_string(&comparison));
+        comparison
+    }
+
+    fn get_nth(&self, nr: usize) -> Option<FastLayout> {
+        if nr < self.temp_generated.len() {
+            let l = self.temp_generated[nr].clone();
+            Some(l)
+        } else {
+            if self.temp_generated.len() == 0 {
+                println!("You haven't generated any layouts yet!");
+            } else {
+                println!("That's not a valid index!");
+            }
+            None
+        }
+    }
+
+    /// Shows the generated layouts in `range`, which is clamped to the amount that was generated.
+    fn list_generated(&self, range: Range<usize>) {
+        if self.temp_generated.is_empty() {
+            println!("You haven't generated any layouts yet!");
+            return;
+        }
+
+        let len = self.temp_generated.len();
+        let range = range.start.min(len)..range.end.min(len);
+
+        for (i, layout) in self.temp_generated[range.clone()].iter().enumerate() {
+            println!("{}", generated_string(&self.gen, range.start + i, layout));
+        }
+        println!("showing {}..{} of {} generated layouts", range.start, range.end, len);
+    }
+
+    fn diff_generated(&self, nr1: usize, nr2: usize) {
+        if let (Some(l1), Some(l2)) = (self.get_nth(nr1), self.get_nth(nr2)) {
+            self.compare(&format!("#{nr1}"), &l1, &format!("#{nr2}"), &l2);
+
+            self.print_moves(&l1, &l2);
+        }
+    }
+
+    fn diff_name(&self, name1: &str, name2: &str) {
+        match (self.layout_by_name(name1), self.layout_by_name(name2)) {
+            (Some(l1), Some(l2)) => {
+                println!("\n{:31}{}", name1, name2);
+                let grid1 = diff_string(&self.gen.data, l1, l2);
+                let grid2 = diff_string(&self.gen.data, l2, l1);
+                for (row1, row2) in grid1.lines().zip(grid2.lines()) {
+                    println!("{row1}          {row2}");
+                }
+                println!();
+                self.print_moves(l1, l2);
+            }
+            (None, _) => println!("{}", self.no_layout(name1)),
+            (_, None) => println!("{}", self.no_layout(name2)),
+        }
+    }
+
+    fn print_moves(&self, l1: &FastLayout, l2: &FastLayout) {
+        let moved = l1.moved_keys(l2).iter().filter(|&&m| m).count();
+        println!(
+            "{moved} keys are in a different position, {} of 10 columns are shared",
+            l1.shared_columns(l2)
+        );
+    }
+
+    pub fn sfr_freq(&self) -> f64 {
+        let len = self.gen.data.characters.len();
+        let chars = 0..len;
+        chars
+            .clone()
+            .cartesian_product(chars)
+            .filter(|(i1, i2)| i1 == i2)
+            .map(|(c1, c2)| self.gen.data.bigrams.get(c1 * len + c2).unwrap_or(&0.0))
+            .sum()
+    }
+
+    fn sfbs(&self, name: &str, top_n: usize) {
+        if let Some(layout) = self.layout_by_name(name) {
+            println!("top {} sfbs for {name}:", top_n.min(48));
+
+            for (bigram, freq) in self.gen.sfbs(layout, top_n) {
                 println!("{bigram}: {:.3}%", freq * 100.0)
             }
-        } else {
-            println!("layout {name} does not exist!")
-        }
-    }
-
-    fn respond(&mut self, line: &str) -> Result<bool, String> {
-        let args = shlex::split(line).ok_or("error: Invalid quoting")?;
-        let mut args = Options::new(args.iter().map(String::as_str));
+        } else {
+            println!("{}", self.no_layout(name))
+        }
+    }
+
+    /// Shows the most common same finger skipgrams of `name`, with an `_` for every key typed in
+    /// between.
+    fn sfs(&self, name: &str, top_n: usize) {
+        if let Some(layout) = self.layout_by_name(name) {
+            println!("top {top_n} same finger skipgrams for {name}:");
+
+            for sfs in self.gen.sfs(layout, top_n) {
+                let [c1, c2] = sfs.chars;
+                let skipgram = format!("{c1}{}{c2}", "_".repeat(sfs.gap));
+                println!("{skipgram: <6} {:.3}%", sfs.freq * 100.0)
+            }
+        } else {
+            println!("{}", self.no_layout(name))
+        }
+    }
+
+    fn coverage(&self, name: &str) {
+        if let Some(layout) = self.layout_by_name(name) {
+            println!("corpus coverage for {name}:\n{}", self.gen.coverage(layout));
+        } else {
+            println!("{}", self.no_layout(name))
+        }
+    }
+
+    /// Shows what every punctuation key of `name` costs in the bigrams it makes with other keys.
+    fn punctuation(&self, name: &str) -> Result<(), String> {
+        let layout = self
+            .layout_by_name(name)
+            .ok_or_else(|| self.no_layout(name))?;
+        let costs = self.gen.punctuation_costs(layout);
+
+        println!(
+            "{:<6}{:<6}{:>10}{:>10}{:>10}{:>10}{:>10}",
+            "char", "key", "freq", "sfb", "dsfb", "scissors", "lsbs"
+        );
+        for cost in costs.iter() {
+            let position = cost.position.map_or("-".to_string(), |pos| pos.to_string());
+            print!("{:<6}{:<6}{:>9.3}%", format!("{:?}", cost.c), position, cost.freq * 100.0);
+            for value in [cost.sfb, cost.dsfb, cost.scissors, cost.lsbs] {
+                print!("{:>9.3}%", value * 100.0);
+            }
+            println!();
+        }
+
+        let total =
+            |value: fn(&PunctuationCost) -> f64| costs.iter().map(value).sum::<f64>() * 100.0;
+        println!(
+            "{:<12}{:>9.3}%{:>9.3}%{:>9.3}%{:>9.3}%{:>9.3}%",
+            "total",
+            total(|cost| cost.freq),
+            total(|cost| cost.sfb),
+            total(|cost| cost.dsfb),
+            total(|cost| cost.scissors),
+            total(|cost| cost.lsbs)
+        );
+        if !self.gen.move_punctuation {
+            println!("\npunctuation stays where it is when using 'improve'");
+        }
+        Ok(())
+    }
+
+    /// Shows what lands on every key of `name`, with the keys that waste the most effort first.
+    fn keys(&self, name: &str) -> Result<(), String> {
+        let layout = self
+            .layout_by_name(name)
+            .ok_or_else(|| self.no_layout(name))?;
+
+        println!(
+            "{:<6}{:<6}{:>10}{:>10}{:>10}{:>10}{:>10}",
+            "key", "char", "freq", "effort", "wasted", "fspeed", "share"
+        );
+        for key in self.gen.key_costs(layout) {
+            println!(
+                "{:<6}{:<6}{:>9.3}%{:>10.3}{:>10.3}{:>10.3}{:>9.1}%",
+                key.position,
+                format!("{:?}", key.c),
+                key.freq * 100.0,
+                key.effort,
+                key.wasted_effort * 100.0,
+                key.fspeed * 10.0,
+                key.fspeed_share * 100.0
+            );
+        }
+        Ok(())
+    }
+
+    /// Keys of `layout` that stay in place when improving it: the pins set in the repl, keys of the
+    /// pin templates that are in their place, the ones in the metadata of `name` and punctuation
+    /// when it shouldn't be moved.
+    fn layout_pins(&self, name: &str, layout: &FastLayout) -> Vec<usize> {
+        let con = &self.gen.data.convert_u8;
+        let mut pins = self.pins.clone();
+        for template in self.pin_templates.iter() {
+            pins.extend(template.pins(layout, con));
+        }
+        let metadata = self
+            .layout_name(name)
+            .and_then(|name| self.gen.layout_metadata.get(name));
+        if let Some(metadata) = metadata {
+            pins.extend(metadata.pin_positions(layout, con));
+        }
+        pins.extend(self.gen.punctuation_pins(layout));
+        pins.sort_unstable();
+        pins.dedup();
+        pins
+    }
+
+    /// Generates `amount` improved versions of `name`. Keys of the pin templates in `config.toml`
+    /// and `keep` are moved to where they are on qwerty first, and stay there. `temperature`
+    /// replaces the one in `config.toml` for this time only.
+    fn improve(
+        &mut self,
+        name: &str,
+        amount: usize,
+        keep: &[PinTemplate],
+        temperature: Option<f64>,
+    ) -> Result<(), String> {
+        let name = &self.saved_name(name)?;
+        let mut layout = self.saved[name].clone();
+        let mut decided = Vec::new();
+        if !self.gen.undecided_keys(&layout).is_empty() {
+            (layout, decided) = self
+                .gen
+                .fill_placeholders(&layout)
+                .map_err(|e| e.to_string())?;
+        }
+        let con = &self.gen.data.convert_u8;
+
+        for template in self.pin_templates.iter().chain(keep) {
+            template.apply(&mut layout, con);
+        }
+        let mut pins = self.layout_pins(name, &layout);
+        pins.extend(decided);
+        for template in keep {
+            pins.extend(template.pins(&layout, con));
+        }
+        pins.sort_unstable();
+        pins.dedup();
+        self.check_scores()?;
+
+        let configured = self.gen.temperature;
+        self.gen.temperature = temperature.unwrap_or(configured);
+        self.temp_generated = generate_n_with_pins(&self.gen, amount, layout, &pins);
+        self.gen.temperature = configured;
+        self.temp_parent = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Shows the `count` single swaps that would improve `name` the most, without making them.
+    fn suggest(&self, name: &str, count: usize) -> Result<(), String> {
+        let layout = self
+            .layout_by_name(name)
+            .ok_or_else(|| self.no_layout(name))?;
+        let stats = self.gen.get_layout_stats(layout);
+        let pins = self.layout_pins(name, layout);
+        let suggestions = self.gen.suggest_swaps(layout, &pins, count);
+
+        if suggestions.is_empty() {
+            println!("every key of {name} is pinned, so there is nothing to swap");
+            return Ok(());
+        }
+        println!("best swaps for {name}:");
+        for (i, suggestion) in suggestions.iter().enumerate() {
+            let (c1, c2) = suggestion.chars;
+            let deltas = suggestion.stats.deltas(&stats);
+            println!(
+                "{:>3}. {c1} <-> {c2} {:<10} score {:+.3}",
+                i + 1,
+                suggestion.swap.to_string(),
+                suggestion.score_delta
+            );
+            if !deltas.is_empty() {
+                println!("     {}", deltas.join(", "));
+            }
+        }
+        if suggestions[0].score_delta <= 0.0 {
+            println!("no single swap improves {name}");
+        }
+        Ok(())
+    }
+
+    /// Shows the characters of the language that aren't on the 30 keys generation uses.
+    fn excluded(&self) {
+        let excluded = self.gen.excluded_chars();
+        let total = excluded.iter().map(|(_, freq)| freq).sum::<f64>();
+
+        println!(
+            "{} characters don't fit on the board, making up {:.3}% of the corpus:",
+            excluded.len(),
+            total * 100.0
+        );
+        for (c, freq) in excluded.iter() {
+            println!("{c:?} {:.3}%", freq * 100.0);
+        }
+
+        let dropped = self.gen.data.dropped_chars.len();
+        if dropped > 0 {
+            println!(
+                "{dropped} of them were left out of the language data, which keeps the {MAX_CHARS} \
+                most common characters"
+            );
+        }
+    }
+
+    /// Shows what the corpus of the current language is made of, with the `amount` most common
+    /// characters, bigrams and trigrams.
+    fn corpus_stats(&self, amount: usize) {
+        let stats = CorpusStats::new(&self.gen.data, amount);
+        let ngrams = |ngrams: &[(String, f64)]| {
+            ngrams
+                .iter()
+                .map(|(ngram, freq)| format!("{ngram:?} {:.3}%", freq * 100.0))
+                .collect::<Vec<_>>()
+                .chunks(5)
+                .map(|line| line.join("  "))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        println!("corpus of {}:\n\ncharacters (frequency, coverage):", self.language);
+        for (c, freq, coverage) in stats.chars.iter() {
+            println!("{c:?} {:>7.3}% {:>8.3}%", freq * 100.0, coverage * 100.0);
+        }
+        println!("\nbigrams:\n{}", ngrams(&stats.bigrams));
+        println!("\ntrigrams:\n{}", ngrams(&stats.trigrams));
+
+        let [chars, bigrams, trigrams] = stats.entropy;
+        println!(
+            "\nentropy: {chars:.3} bits per character, {bigrams:.3} per bigram, {trigrams:.3} per \
+            trigram"
+        );
+        let decay = stats
+            .skipgram_decay
+            .iter()
+            .enumerate()
+            .map(|(i, bits)| format!("{} apart {bits:.3}", i + 1))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("skipgram decay (bits shared by characters): {decay}");
+    }
+
+    fn one_handed(&self, name: &str) {
+        if let Some(layout) = self.layout_by_name(name) {
+            let stats = self.gen.one_handed_stats(layout, &self.one_handed_fingers);
+            println!(
+                "{name}, typed with one hand:\n{}\n{}",
+                heatmap_string(&self.gen.data, layout),
+                stats
+            );
+        } else {
+            println!("{}", self.no_layout(name))
+        }
+    }
+
+    fn replay(&self, name: &str, path: Option<&str>) {
+        let layout = match self.layout_by_name(name) {
+            Some(layout) => layout,
+            None => {
+                println!("{}", self.no_layout(name));
+                return;
+            }
+        };
+
+        let text = match path {
+            Some(path) => {
+                match self.gen.replay_file(layout, path, None) {
+                    Ok(stats) => println!("{name}:\n{stats}"),
+                    Err(e) => println!("could not read {path}: {e}"),
+                }
+                return;
+            }
+            None if self.gen.data.samples.is_empty() => {
+                println!(
+                    "There are no sentence samples for {0}. Run 'load {0}' or provide a file to replay.",
+                    self.language
+                );
+                return;
+            }
+            None => self.gen.data.samples.join(" "),
+        };
+
+        println!("{name}:\n{}", self.gen.replay(layout, &text));
+    }
+
+    fn chart(&self, metrics: &str, names: &[&str]) -> Result<(), String> {
+        let mut stats = Vec::with_capacity(names.len());
+        for &name in names {
+            match self.layout_by_name(name) {
+                Some(layout) => stats.push(self.gen.get_layout_stats(layout)),
+                None => return Err(self.no_layout(name)),
+            }
+        }
+
+        let mut values = Vec::new();
+        for metric in metrics.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+            let mut display_name = "";
+            let mut per_layout = Vec::with_capacity(stats.len());
+
+            for s in stats.iter() {
+                let (name, value) = s
+                    .metric(metric)
+                    .ok_or_else(|| format!("'{metric}' is not a metric that can be charted"))?;
+                display_name = name;
+                per_layout.push(value);
+            }
+            values.push((display_name, per_layout));
+        }
+
+        let chart = oxeylyzer_core::svg::comparison_chart(names, &values);
+
+        std::fs::create_dir_all("static/charts").map_err(|e| e.to_string())?;
+        let path = format!("static/charts/{}.svg", names.join("_vs_"));
+        std::fs::write(&path, chart).map_err(|e| e.to_string())?;
+
+        println!("saved chart to {path}");
+        Ok(())
+    }
+
+    fn save_schemas(&self) -> Result<(), String> {
+        std::fs::create_dir_all("static/schemas").map_err(|e| e.to_string())?;
+
+        for name in schema::SCHEMAS {
+            let path = format!("static/schemas/{name}.schema.json");
+            let schema = serde_json::to_string_pretty(&schema::schema(name).unwrap())
+                .map_err(|e| e.to_string())?;
+            std::fs::write(&path, schema).map_err(|e| e.to_string())?;
+            println!("saved {path}");
+        }
+        Ok(())
+    }
+
+    fn export_stats(&self, path: &str, with_generated: bool) -> Result<(), String> {
+        let mut layouts = self
+            .saved
+            .iter()
+            .map(|(name, layout)| (name.clone(), layout))
+            .collect::<Vec<_>>();
+        if with_generated {
+            layouts.extend(
+                self.temp_generated
+                    .iter()
+                    .enumerate()
+                    .map(|(i, layout)| (format!("#{i}"), layout)),
+            );
+        }
+
+        let stats = layouts
+            .iter()
+            .map(|(_, layout)| self.gen.get_layout_stats(layout))
+            .collect::<Vec<_>>();
+        let csv = oxeylyzer_core::csv::stats_csv(
+            layouts
+                .iter()
+                .zip(stats.iter())
+                .map(|((name, layout), stats)| (name.as_str(), layout.score, stats)),
+        );
+        std::fs::write(path, csv).map_err(|e| format!("could not write {path}: {e}"))?;
+
+        println!("saved stats of {} layouts to {path}", layouts.len());
+        Ok(())
+    }
+
+    fn heatmap_svg(
+        &self,
+        name: &str,
+        path: &str,
+        mode: HeatmapMode,
+        finger_zones: bool,
+    ) -> Result<(), String> {
+        let layout = self
+            .layout_by_name(name)
+            .ok_or_else(|| self.no_layout(name))?;
+
+        let heat = self.gen.key_heat(layout, mode);
+        let mut keys = [(' ', 0.0); 30];
+        for (i, key) in keys.iter_mut().enumerate() {
+            *key = (self.gen.data.convert_u8.from_single(layout.c(i)), heat[i]);
+        }
+        let keyboard_type = Config::new().map_err(|e| e.to_string())?.defaults.keyboard_type;
+
+        let fingers = finger_zones.then_some(&layout.fingers);
+        let svg = oxeylyzer_core::svg::heatmap(&keys, &keyboard_type, fingers);
+        std::fs::write(path, svg).map_err(|e| e.to_string())?;
+
+        println!("saved heatmap of {name} to {path}");
+        Ok(())
+    }
+
+    /// Shows a kmonad or kanata config that remaps a keyboard to the layout called `name`, or to
+    /// the generated layout with that number. With `path`, it is saved there instead.
+    fn keymap(
+        &self,
+        name_or_nr: &str,
+        tool: RemapTool,
+        base: RemapBase,
+        path: Option<&str>,
+    ) -> Result<(), String> {
+        let (name, layout) = match (name_or_nr.parse::<usize>(), self.layout_name(name_or_nr)) {
+            (_, Some(name)) => (name.to_string(), self.saved[name].clone()),
+            (Ok(nr), None) => match self.get_nth(nr) {
+                Some(layout) => (format!("generated-{nr}"), layout),
+                None => return Ok(()),
+            },
+            (Err(_), None) => return Err(self.no_layout(name_or_nr)),
+        };
+
+        let keys = layout.matrix.map(|c| self.gen.convert_u8.from_single(c));
+        let keymap = Keymap::new(&name, &keys, tool, base);
+        if !keymap.unmapped.is_empty() {
+            let unmapped = keymap.unmapped.iter().collect::<String>();
+            println!("warning: {unmapped} can't be typed with this keymap");
+        }
+
+        match path {
+            Some(path) => {
+                std::fs::write(path, &keymap.config)
+                    .map_err(|e| format!("could not write {path}: {e}"))?;
+                println!("saved the keymap of {name} to {path}");
+            }
+            None => print!("{}", keymap.config),
+        }
+        Ok(())
+    }
+
+    /// Shows the heatmaps of `name1` and `name2` next to each other, along with the keys whose
+    /// frequency changed by more than `threshold` percent.
+    fn heatmap_compare(&self, name1: &str, name2: &str, threshold: f64) -> Result<(), String> {
+        let name1 = self.layout_name(name1).ok_or_else(|| self.no_layout(name1))?;
+        let name2 = self.layout_name(name2).ok_or_else(|| self.no_layout(name2))?;
+        let (l1, l2) = (&self.saved[name1], &self.saved[name2]);
+        let data = &self.gen.data;
+
+        println!("{name1:<24}{name2:<24}changed by more than {threshold}%");
+        println!("{}", heatmap_compare_string(data, l1, l2, threshold / 100.0));
+
+        let (freqs1, freqs2) = (key_frequencies(data, l1), key_frequencies(data, l2));
+        let mut changed = (0..30)
+            .filter(|&i| (freqs2[i] - freqs1[i]).abs() * 100.0 > threshold)
+            .collect::<Vec<_>>();
+        changed.sort_by(|&i1, &i2| {
+            let change = |i: usize| (freqs2[i] - freqs1[i]).abs();
+            change(i2).partial_cmp(&change(i1)).unwrap()
+        });
+
+        if !changed.is_empty() {
+            println!();
+        }
+        for i in changed {
+            println!(
+                "row {} column {:<2} {} {:.2}% -> {} {:.2}% ({:+.2}%)",
+                i / 10 + 1,
+                i % 10 + 1,
+                data.convert_u8.from_single(l1.c(i)),
+                freqs1[i] * 100.0,
+                data.convert_u8.from_single(l2.c(i)),
+                freqs2[i] * 100.0,
+                (freqs2[i] - freqs1[i]) * 100.0
+            );
+        }
+        Ok(())
+    }
+
+    fn sample(&self, name: &str, amount: usize) {
+        let layout = match self.layout_by_name(name) {
+            Some(layout) => layout,
+            None => {
+                println!("{}", self.no_layout(name));
+                return;
+            }
+        };
+
+        if self.gen.data.samples.is_empty() {
+            println!(
+                "There are no sentence samples for {0}. Run 'load {0}' to create them.",
+                self.language
+            );
+            return;
+        }
+
+        println!("{name}, sfbs marked with ^, hand switches with ~:\n");
+        for sentence in self.gen.data.random_samples(amount) {
+            println!("{}\n", annotated_sample(&self.gen.data, layout, sentence));
+        }
+    }
+
+    fn respond(&mut self, line: &str) -> Result<bool, String> {
+        let args = shlex::split(line).ok_or("error: Invalid quoting")?;
+        let mut args = Options::new(args.iter().map(String::as_str));
+
+        match args.next_positional() {
+            Some("generate") | Some("gen") | Some("g") => match args.next_positional() {
+                Some("list") | Some("ls") => {
+                    let range = match args.next_positional() {
+                        Some(range_str) => parse_range(range_str, self.temp_generated.len()),
+                        None => Some(0..10),
+                    };
+                    match range {
+                        Some(range) => self.list_generated(range),
+                        None => print_error("generate list", &[O("range")]),
+                    }
+                }
+                Some("analyze") | Some("a") => {
+                    match args.next_positional().map(str::parse::<usize>) {
+                        Some(Ok(nr)) => {
+                            if let Some(layout) = self.get_nth(nr) {
+                                println!("#{nr}");
+                                self.analyze(&layout, false, false);
+                            }
+                        }
+                        _ => print_error("generate analyze", &[R("index")]),
+                    }
+                }
+                Some("diff") | Some("d") => {
+                    let nr1 = args.next_positional().map(str::parse::<usize>);
+                    let nr2 = args.next_positional().map(str::parse::<usize>);
+                    match (nr1, nr2) {
+                        (Some(Ok(nr1)), Some(Ok(nr2))) => self.diff_generated(nr1, nr2),
+                        _ => print_error("generate diff", &[R("index 1"), R("index 2")]),
+                    }
+                }
+                Some("save") | Some("s") => {
+                    match args.next_positional().map(str::parse::<usize>) {
+                        Some(Ok(nr)) => {
+                            if let Some(layout) = self.get_nth(nr) {
+                                let name = args.next_positional().map(str::to_string);
+                                self.save(layout, name)?;
+                            }
+                        }
+                        _ => print_error("generate save", &[R("index"), O("name")]),
+                    }
+                }
+                Some(count_str) => {
+                    if let Ok(count) = usize::from_str_radix(count_str, 10) {
+                        use getargs::Opt::*;
+
+                        let mut criteria = StopCriteria::default();
+                        loop {
+                            match args.next_opt() {
+                                Ok(Some(Short('m'))) | Ok(Some(Long("max-seconds"))) => {
+                                    let seconds = args.value().map_err(|e| e.to_string())?;
+                                    criteria.max_time = Some(parse_seconds(seconds)?);
+                                }
+                                Ok(Some(Short('t'))) | Ok(Some(Long("target-score"))) => {
+                                    let score = args.value().map_err(|e| e.to_string())?;
+                                    criteria.target_score = Some(
+                                        score
+                                            .parse()
+                                            .map_err(|_| format!("'{score}' isn't a score"))?,
+                                    );
+                                }
+                                Ok(Some(Short('p'))) | Ok(Some(Long("patience"))) => {
+                                    let patience = args.value().map_err(|e| e.to_string())?;
+                                    criteria.patience = Some(
+                                        patience
+                                            .parse()
+                                            .map_err(|_| format!("'{patience}' isn't an amount"))?,
+                                    );
+                                }
+                                Ok(Some(opt)) => {
+                                    return Err(format!("error: unknown option {opt:?}"))
+                                }
+                                Ok(None) => break,
+                                Err(e) => return Err(e.to_string()),
+                            }
+                        }
+
+                        self.check_scores()?;
+                        println!("generating {} layouts...", count_str);
+                        self.temp_generated = generate_n(&self.gen, count, &criteria);
+                        self.temp_parent = None;
+
+                        if count > 10 {
+                            println!("use 'generate list 10..20' to see more of them");
+                        }
+                    } else {
+                        print_error("generate", &[R("amount")]);
+                    }
+                }
+                None => print_error("generate", &[R("amount")]),
+            },
+            Some("improve") | Some("i") => {
+                use getargs::Opt::*;
+
+                let mut keep = Vec::new();
+                let mut temperature = None;
+                let mut positionals = Vec::new();
+                loop {
+                    match args.next_opt() {
+                        Ok(Some(Short('k'))) | Ok(Some(Long("keep"))) => {
+                            let value = args.value().map_err(|e| e.to_string())?;
+                            keep.push(PinTemplate::try_from(value)?);
+                        }
+                        Ok(Some(Short('t'))) | Ok(Some(Long("temperature"))) => {
+                            let value = args.value().map_err(|e| e.to_string())?;
+                            match value.parse::<f64>() {
+                                Ok(t) if t >= 0.0 => temperature = Some(t),
+                                _ => return Err(format!("'{value}' isn't a temperature")),
+                            }
+                        }
+                        Ok(Some(opt)) => return Err(format!("error: unknown option {opt:?}")),
+                        Ok(None) => match args.next_positional() {
+                            Some(positional) => positionals.push(positional),
+                            None => break,
+                        },
+                        Err(e) => return Err(e.to_string()),
+                    }
+                }
+
+                match positionals[..] {
+                    [name, amount_str, ..] => match usize::from_str_radix(amount_str, 10) {
+                        Ok(amount) => {
+                            if let Err(e) = self.improve(name, amount, &keep, temperature) {
+                                println!("{e}");
+                            }
+                        }
+                        Err(_) => print_error(
+                            "improve",
+                            &[R("name"), R("amount"), A("keep"), A("temperature")],
+                        ),
+                    },
+                    [_] => print_error(
+                        "improve",
+                        &[R("name"), R("amount"), A("keep"), A("temperature")],
+                    ),
+                    [] => {}
+                }
+            }
+            Some("rank") => {
+                use getargs::Opt::*;
+
+                let mut verbose = false;
+                let mut tag = None;
+                let mut by = None;
+                let mut group = false;
+
+                loop {
+                    match args.next_opt() {
+                        Ok(Some(Short('v'))) | Ok(Some(Long("verbose"))) => verbose = true,
+                        Ok(Some(Short('g'))) | Ok(Some(Long("group"))) => group = true,
+                        Ok(Some(Short('t'))) | Ok(Some(Long("tag"))) => {
+                            tag = Some(args.value().map_err(|e| e.to_string())?);
+                        }
+                        Ok(Some(Short('b'))) | Ok(Some(Long("by"))) => {
+                            by = Some(args.value().map_err(|e| e.to_string())?);
+                        }
+                        Ok(Some(opt)) => return Err(format!("error: unknown option {opt:?}")),
+                        Ok(None) => break,
+                        Err(e) => return Err(e.to_string()),
+                    }
+                }
+                match by {
+                    Some(metric) => self.print_rank_by(metric, tag, group)?,
+                    None => self.rank(verbose, tag, group),
+                }
+            }
+            Some("analyze") | Some("layout") | Some("a") => {
+                use getargs::Opt::*;
+
+                if let Some(name_or_nr) = args.next_positional() {
+                    let mut full = false;
+                    let mut renormalize = false;
+
+                    loop {
+                        match args.next_opt() {
+                            Ok(Some(Short('f'))) | Ok(Some(Long("full"))) => full = true,
+                            Ok(Some(Short('r'))) | Ok(Some(Long("renormalize"))) => {
+                                renormalize = true
+                            }
+                            Ok(Some(opt)) => return Err(format!("error: unknown option {opt:?}")),
+                            Ok(None) => break,
+                            Err(e) => return Err(e.to_string()),
+                        }
+                    }
+                    if let Ok(nr) = usize::from_str_radix(name_or_nr, 10) {
+                        if let Some(layout) = self.get_nth(nr) {
+                            self.analyze(&layout, full, renormalize);
+                        }
+                    } else {
+                        self.analyze_name(name_or_nr, full, renormalize);
+                    }
+                } else {
+                    print_error(
+                        "analyze",
+                        &[R("name or number"), A("full"), A("renormalize")],
+                    );
+                }
+            }
+            Some("compare") | Some("c") | Some("comp") | Some("cmopare") | Some("comprae") => {
+                use getargs::Opt::*;
+
+                if matches!(args.next_opt(), Ok(Some(Short('t'))) | Ok(Some(Long("tag")))) {
+                    self.compare_tagged(args.value().map_err(|e| e.to_string())?)?;
+                } else if let Some(layout1) = args.next_positional() {
+                    if let Some(layout2) = args.next_positional() {
+                        let retraining = matches!(
+                            args.next_opt(),
+                            Ok(Some(Short('r'))) | Ok(Some(Long("retraining")))
+                        );
+                        self.compare_name(layout1, layout2, retraining);
+                    } else {
+                        print_error("compare", &[R("layout 1"), R("layout 2"), A("retraining")]);
+                    }
+                }
+            }
+            Some("family") => match args.next_positional() {
+                Some(family) => self.family_summary(family)?,
+                None => print_error("family", &[R("family or layout")]),
+            },
+            Some(command @ ("tag" | "untag")) => {
+                let remove = command == "untag";
+                match args.next_positional() {
+                    Some(name) => {
+                        let mut tags = Vec::new();
+                        while let Some(tag) = args.next_positional() {
+                            tags.push(tag);
+                        }
+                        self.tag(name, &tags, remove)?;
+                    }
+                    None => print_error(command, &[R("name"), O("tags...")]),
+                }
+            }
+            Some(command @ ("delete" | "archive")) => {
+                use getargs::Opt::*;
+
+                let archive = command == "archive";
+                let names = match args.next_opt() {
+                    Ok(Some(Short('t'))) | Ok(Some(Long("tag"))) => {
+                        let tag = args.value().map_err(|e| e.to_string())?;
+                        let names = self.tagged(tag);
+                        if names.is_empty() {
+                            return Err(format!("no layouts are tagged '{tag}'"));
+                        }
+                        names
+                    }
+                    _ => {
+                        let mut names = Vec::new();
+                        while let Some(name) = args.next_positional() {
+                            names.push(name.to_string());
+                        }
+                        names
+                    }
+                };
+
+                if names.is_empty() {
+                    print_error(command, &[R("names"), A("tag")]);
+                } else {
+                    self.remove_layouts(&names, archive)?;
+                }
+            }
+            Some("rename") => match (args.next_positional(), args.next_positional()) {
+                (Some(old), Some(new)) => self.rename(old, new)?,
+                _ => print_error("rename", &[R("name"), R("new name")]),
+            },
+            Some("percentile") | Some("pct") => match args.next_positional() {
+                Some(name) => match args.next_positional().map(str::parse::<usize>) {
+                    None => self.percentile(name, 1000)?,
+                    Some(Ok(amount)) => self.percentile(name, amount)?,
+                    Some(Err(_)) => print_error("percentile", &[R("name"), O("amount")]),
+                },
+                None => print_error("percentile", &[R("name"), O("amount")]),
+            },
+            Some("sensitivity") | Some("sens") => match args.next_positional() {
+                Some(name) => self.sensitivity(name)?,
+                None => print_error("sensitivity", &[R("name")]),
+            },
+            Some("verify") => {
+                use getargs::Opt::*;
+
+                let mut save = false;
+                let mut tolerance = 1e-6;
+                loop {
+                    match args.next_opt() {
+                        Ok(Some(Short('s'))) | Ok(Some(Long("save"))) => save = true,
+                        Ok(Some(Short('t'))) | Ok(Some(Long("tolerance"))) => {
+                            let value = args.value().map_err(|e| e.to_string())?;
+                            tolerance = value
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|t| *t >= 0.0)
+                                .ok_or_else(|| format!("error: '{value}' is not a tolerance"))?;
+                        }
+                        Ok(Some(opt)) => return Err(format!("error: unknown option {opt:?}")),
+                        Ok(None) => break,
+                        Err(e) => return Err(e.to_string()),
+                    }
+                }
+                self.verify(save, tolerance)?;
+            }
+            Some("fit-weights") | Some("fit") => {
+                use getargs::Opt::*;
+
+                let mut candidates = 400;
+                let mut positionals = Vec::new();
+                loop {
+                    match args.next_opt() {
+                        Ok(Some(Short('c'))) | Ok(Some(Long("candidates"))) => {
+                            let value = args.value().map_err(|e| e.to_string())?;
+                            candidates = value
+                                .parse::<usize>()
+                                .map_err(|_| format!("error: '{value}' is not an amount"))?;
+                        }
+                        Ok(Some(opt)) => return Err(format!("error: unknown option {opt:?}")),
+                        // 'sturdy > canary > qwerty' reads like the ranking it is
+                        Ok(None) => match args.next_positional() {
+                            Some(">") => {}
+                            Some(positional) => positionals.push(positional),
+                            None => break,
+                        },
+                        Err(e) => return Err(e.to_string()),
+                    }
+                }
+
+                match positionals[..] {
+                    [profile, ref names @ ..] if names.len() >= 2 => {
+                        self.fit_weights(profile, names, candidates)?
+                    }
+                    _ => print_error(
+                        "fit-weights",
+                        &[R("profile"), R("layouts"), A("candidates")],
+                    ),
+                }
+            }
+            Some("edit") | Some("e") => match args.next_positional() {
+                Some(name) => self.edit(name)?,
+                None => print_error("edit", &[R("name")]),
+            },
+            Some("mirror") => match args.next_positional() {
+                Some(name) => self.mirror(name)?,
+                None => print_error("mirror", &[R("name")]),
+            },
+            Some("diff") => {
+                match (args.next_positional(), args.next_positional()) {
+                    (Some(layout1), Some(layout2)) => self.diff_name(layout1, layout2),
+                    _ => print_error("diff", &[R("layout 1"), R("layout 2")]),
+                }
+            }
+            Some("sfbs") | Some("sfb") => {
+                if let Some(name) = args.next_positional() {
+                    if let Some(top_n_str) = args.next_positional() {
+                        if let Ok(top_n) = usize::from_str_radix(top_n_str, 10) {
+                            self.sfbs(name, top_n)
+                        } else {
+                            print_error("ngram", &[R("name"), O("top n")]);
+                        }
+                    } else {
+                        self.sfbs(name, 10);
+                    }
+                } else {
+                    print_error("ngram", &[R("name"), O("top n")]);
+                }
+            }
+            Some("sfs") => {
+                if let Some(name) = args.next_positional() {
+                    if let Some(top_n_str) = args.next_positional() {
+                        if let Ok(top_n) = usize::from_str_radix(top_n_str, 10) {
+                            self.sfs(name, top_n)
+                        } else {
+                            print_error("sfs", &[R("name"), O("top n")]);
+                        }
+                    } else {
+                        self.sfs(name, 10);
+                    }
+                } else {
+                    print_error("sfs", &[R("name"), O("top n")]);
+                }
+            }
+            Some("coverage") | Some("cov") => {
+                if let Some(name) = args.next_positional() {
+                    self.coverage(name);
+                } else {
+                    print_error("coverage", &[R("name")]);
+                }
+            }
+            Some("excluded") | Some("excl") => self.excluded(),
+            Some("corpus") => match (args.next_positional(), args.next_positional()) {
+                (Some("stats"), None) => self.corpus_stats(30),
+                (Some("stats"), Some(amount)) => match amount.parse::<usize>() {
+                    Ok(amount) => self.corpus_stats(amount),
+                    Err(_) => print_error("corpus stats", &[O("amount")]),
+                },
+                _ => print_error("corpus", &[R("stats"), O("amount")]),
+            },
+            Some("suggest") | Some("sug") => match args.next_positional() {
+                Some(name) => match args.next_positional().map(str::parse::<usize>) {
+                    None => self.suggest(name, 10)?,
+                    Some(Ok(count)) => self.suggest(name, count)?,
+                    Some(Err(_)) => print_error("suggest", &[R("name"), O("count")]),
+                },
+                None => print_error("suggest", &[R("name"), O("count")]),
+            },
+            Some("show-effort") | Some("effort") => self.show_effort(),
+            Some("show-config") => match args.next_positional() {
+                Some(name) => self.show_config(name)?,
+                None => print_error("show-config", &[R("name")]),
+            },
+            Some("keys") => match args.next_positional() {
+                Some(name) => self.keys(name)?,
+                None => print_error("keys", &[R("name")]),
+            },
+            Some("punctuation") | Some("punct") => match args.next_positional() {
+                Some(name) => self.punctuation(name)?,
+                None => print_error("punctuation", &[R("name")]),
+            },
+            Some("onehanded") | Some("onehand") | Some("oh") => {
+                if let Some(name) = args.next_positional() {
+                    self.one_handed(name);
+                } else {
+                    print_error("onehanded", &[R("name")]);
+                }
+            }
+            Some("replay") | Some("rp") => {
+                if let Some(name) = args.next_positional() {
+                    self.replay(name, args.next_positional());
+                } else {
+                    print_error("replay", &[R("name"), O("file")]);
+                }
+            }
+            Some("chart") => {
+                if let Some(metrics) = args.next_positional() {
+                    let mut names = Vec::new();
+                    while let Some(name) = args.next_positional() {
+                        names.push(name);
+                    }
 
-        match args.next_positional() {
-            Some("generate") | Some("gen") | Some("g") => {
-                if let Some(count_str) = args.next_positional() {
-                    if let Ok(count) = usize::from_str_radix(count_str, 10) {
-                        println!("generating {} layouts...", count_str);
-                        self.temp_generated = generate_n(&self.gen, count);
+                    if names.is_empty() {
+                        print_error("chart", &[R("metrics"), R("layouts")]);
                     } else {
-                        print_error("generate", &[R("amount")]);
+                        self.chart(metrics, &names)?;
                     }
+                } else {
+                    print_error("chart", &[R("metrics"), R("layouts")]);
                 }
             }
-            Some("improve") | Some("i") => {
+            Some("heatmap") | Some("hm") => {
+                use getargs::Opt::*;
+
                 if let Some(name) = args.next_positional() {
-                    if let Some(amount_str) = args.next_positional() {
-                        if let Ok(amount) = usize::from_str_radix(amount_str, 10) {
-                            if let Some(l) = self.layout_by_name(name) {
-                                self.temp_generated = generate_n_with_pins(&self.gen, amount, l.clone(), &self.pins);
-                            } else {
-                                println!("'{name}' does not exist!")
+                    let mut svg_path = None;
+                    let mut mode = HeatmapMode::Frequency;
+                    let mut finger_zones = false;
+
+                    loop {
+                        match args.next_opt() {
+                            Ok(Some(Short('s'))) | Ok(Some(Long("svg"))) => {
+                                svg_path = Some(args.value().map_err(|e| e.to_string())?);
                             }
-                        } else {
-                            print_error("improve", &[R("name"), R("amount")]);
+                            Ok(Some(Short('m'))) | Ok(Some(Long("mode"))) => {
+                                let value = args.value().map_err(|e| e.to_string())?;
+                                mode = HeatmapMode::try_from(value)?;
+                            }
+                            Ok(Some(Short('z'))) | Ok(Some(Long("zones"))) => finger_zones = true,
+                            Ok(Some(opt)) => return Err(format!("error: unknown option {opt:?}")),
+                            Ok(None) => break,
+                            Err(e) => return Err(e.to_string()),
                         }
                     }
+
+                    match (svg_path, self.layout_by_name(name)) {
+                        (Some(path), _) => self.heatmap_svg(name, path, mode, finger_zones)?,
+                        (None, Some(layout)) if mode == HeatmapMode::Frequency => {
+                            println!("{}", heatmap_string(&self.gen.data, layout))
+                        }
+                        (None, Some(layout)) => {
+                            let heat = self.gen.key_heat(layout, mode);
+                            let s = heatmap_string_with(&self.gen.data, layout, &heat, [0.0; 2]);
+                            println!("{s}");
+                            if mode == HeatmapMode::SwapGain {
+                                let gains = self.gen.swap_gains(layout);
+                                match gains.iter().copied().fold(0.0, f64::max) {
+                                    max if max > 0.0 => println!(
+                                        "the reddest keys gain up to {max:.3} with one swap"
+                                    ),
+                                    _ => println!("no single swap improves {name}"),
+                                }
+                            }
+                        }
+                        (None, None) => println!("{}", self.no_layout(name)),
+                    }
+                } else {
+                    print_error("heatmap", &[R("name"), A("svg"), A("mode"), A("zones")]);
                 }
             }
-            Some("rank") => self.rank(),
-            Some("analyze") | Some("layout") | Some("a") => {
-                if let Some(name_or_nr) = args.next_positional() {
-                    if let Ok(nr) = usize::from_str_radix(name_or_nr, 10) {
-                        if let Some(layout) = self.get_nth(nr) {
-                            self.analyze(&layout);
+            Some("keymap") | Some("remap") => {
+                use getargs::Opt::*;
+
+                let name = args.next_positional();
+                let tool = args.next_positional();
+                let mut base = RemapBase::Qwerty;
+                let mut path = None;
+
+                loop {
+                    match args.next_opt() {
+                        Ok(Some(Short('b'))) | Ok(Some(Long("base"))) => {
+                            let value = args.value().map_err(|e| e.to_string())?;
+                            base = RemapBase::try_from(value)?;
                         }
-                    } else {
-                        self.analyze_name(name_or_nr);
+                        Ok(Some(Short('o'))) | Ok(Some(Long("out"))) => {
+                            path = Some(args.value().map_err(|e| e.to_string())?);
+                        }
+                        Ok(Some(opt)) => return Err(format!("error: unknown option {opt:?}")),
+                        Ok(None) => break,
+                        Err(e) => return Err(e.to_string()),
                     }
-                } else {
-                    print_error("analyze", &[R("name or number")]);
+                }
+
+                match (name, tool) {
+                    (Some(name), Some(tool)) => {
+                        self.keymap(name, RemapTool::try_from(tool)?, base, path)?
+                    }
+                    _ => print_error(
+                        "keymap",
+                        &[R("name or number"), R("kmonad or kanata"), A("base"), A("out")],
+                    ),
                 }
             }
-            Some("compare") | Some("c") | Some("comp") | Some("cmopare") | Some("comprae") => {
-                if let Some(layout1) = args.next_positional() {
-                    if let Some(layout2) = args.next_positional() {
-                        self.compare_name(layout1, layout2);
-                    } else {
-                        print_error("compare", &[R("layout 1"), R("layout 2")]);
+            Some("heatmap-compare") | Some("hmc") => {
+                use getargs::Opt::*;
+
+                let mut threshold = 1.0;
+                let names = (args.next_positional(), args.next_positional());
+                loop {
+                    match args.next_opt() {
+                        Ok(Some(Short('t'))) | Ok(Some(Long("threshold"))) => {
+                            let value = args.value().map_err(|e| e.to_string())?;
+                            threshold = value
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|t| *t >= 0.0)
+                                .ok_or_else(|| format!("error: '{value}' is not a percentage"))?;
+                        }
+                        Ok(Some(opt)) => return Err(format!("error: unknown option {opt:?}")),
+                        Ok(None) => break,
+                        Err(e) => return Err(e.to_string()),
                     }
                 }
+
+                match names {
+                    (Some(name1), Some(name2)) => self.heatmap_compare(name1, name2, threshold)?,
+                    _ => print_error(
+                        "heatmap-compare",
+                        &[R("layout 1"), R("layout 2"), A("threshold")],
+                    ),
+                }
             }
-            Some("sfbs") | Some("sfb") => {
+            Some("export-stats") | Some("export") => {
+                use getargs::Opt::*;
+
+                let mut csv_path = None;
+                let mut with_generated = false;
+
+                loop {
+                    match args.next_opt() {
+                        Ok(Some(Short('c'))) | Ok(Some(Long("csv"))) => {
+                            csv_path = Some(args.value().map_err(|e| e.to_string())?);
+                        }
+                        Ok(Some(Short('g'))) | Ok(Some(Long("generated"))) => with_generated = true,
+                        Ok(Some(opt)) => return Err(format!("error: unknown option {opt:?}")),
+                        Ok(None) => break,
+                        Err(e) => return Err(e.to_string()),
+                    }
+                }
+
+                match csv_path {
+                    Some(path) => self.export_stats(path, with_generated)?,
+                    None => {
+                        return Err(
+                            "error: export-stats needs a file to write to, like --csv stats.csv"
+                                .to_string(),
+                        )
+                    }
+                }
+            }
+            Some("sample") | Some("preview") => {
                 if let Some(name) = args.next_positional() {
-                    if let Some(top_n_str) = args.next_positional() {
-                        if let Ok(top_n) = usize::from_str_radix(top_n_str, 10) {
-                            self.sfbs(name, top_n)
+                    if let Some(amount_str) = args.next_positional() {
+                        if let Ok(amount) = usize::from_str_radix(amount_str, 10) {
+                            self.sample(name, amount)
                         } else {
-                            print_error("ngram", &[R("name"), O("top n")]);
+                            print_error("sample", &[R("name"), O("amount")]);
                         }
                     } else {
-                        self.sfbs(name, 10);
+                        self.sample(name, 5);
                     }
                 } else {
-                    print_error("ngram", &[R("name"), O("top n")]);
+                    print_error("sample", &[R("name"), O("amount")]);
+                }
+            }
+            Some("schema") => {
+                use getargs::Opt::*;
+
+                if matches!(args.next_opt(), Ok(Some(Short('s'))) | Ok(Some(Long("save")))) {
+                    self.save_schemas()?;
+                } else if let Some(name) = args.next_positional() {
+                    match schema::schema(name) {
+                        Some(schema) => println!(
+                            "{}",
+                            serde_json::to_string_pretty(&schema).map_err(|e| e.to_string())?
+                        ),
+                        None => println!("there is no schema called '{name}'"),
+                    }
+                } else {
+                    println!("available schemas: {}", schema::SCHEMAS.join(", "));
                 }
             }
             Some("ngram") | Some("occ") | Some("n") => {
@@ -367,89 +2937,95 @@ impl Repl {
             }
             Some("load") => {
                 use getargs::Opt::*;
-                let opt1 = args.next_opt();
 
-                if matches!(opt1, Ok(Some(Short('a'))) | Ok(Some(Long("all")))) {
+                let (mut all, mut raw, mut max_bytes) = (false, false, None);
+                let mut positionals = Vec::new();
+                loop {
+                    match args.next_opt() {
+                        Ok(Some(Short('a'))) | Ok(Some(Long("all"))) => all = true,
+                        Ok(Some(Short('r'))) | Ok(Some(Long("raw"))) => raw = true,
+                        Ok(Some(Short('m'))) | Ok(Some(Long("max-bytes"))) => {
+                            let value = args.value().map_err(|e| e.to_string())?;
+                            max_bytes = Some(parse_bytes(value).ok_or_else(|| {
+                                format!("error: '{value}' is not an amount of bytes, like 500M")
+                            })?);
+                        }
+                        Ok(Some(opt)) => return Err(format!("error: unknown option {opt:?}")),
+                        Ok(None) => match args.next_positional() {
+                            Some(positional) => positionals.push(positional),
+                            None => break,
+                        },
+                        Err(e) => return Err(e.to_string()),
+                    }
+                }
+
+                if all {
                     for (language, config) in CorpusConfig::all() {
                         println!("loading data for language: {language}...");
-                        load_text::load_data(language.as_str(), config.translator())
+                        load_text::load_data(language.as_str(), config.translator(), max_bytes)
                             .map_err(|e| e.to_string())?;
                     }
-                } else if let Some(language) = args.next_positional() {
-                    let opt2 = args.next_opt();
-                    if matches!(opt1, Ok(Some(Short('r'))) | Ok(Some(Long("raw"))))
-                    || matches!(opt2, Ok(Some(Short('r'))) | Ok(Some(Long("raw")))) {
+                } else if let Some(&language) = positionals.first() {
+                    if raw {
                         println!("loading raw data for language: {language}...");
-                        load_text::load_raw(language);
+                        load_text::load_raw(language, max_bytes);
                     } else {
-                        let preferred_folder = args.next_positional();
+                        let preferred_folder = positionals.get(1).copied();
                         let translator = CorpusConfig::new_translator(language, preferred_folder);
                         let is_raw_translator = translator.is_raw;
 
                         println!("loading data for {language}...");
-                        load_text::load_data(language, translator)
+                        load_text::load_data(language, translator, max_bytes)
                             .map_err(|e| e.to_string())?;
 
                         if !is_raw_translator {
-                            let config = Config::new();
-                            if let Ok(generator) = LayoutGeneration::new(
-                                language,
-                                "static",
-                                Some(config)
-                            ) {
-                                self.language = language.to_string();
-                                self.gen = generator;
-                                self.saved = self.gen.load_layouts(
-                                    "static/layouts",
-                                    language
-                                ).expect("couldn't load layouts lol");
-
-                                println!(
-                                    "Set language to {}. Sfr: {:.2}%",
-                                    language, self.sfr_freq() * 100.0
-                                );
-                            } else {
-                                println!("Could not load data for {language}");
-                            }
+                            self.set_language(language)?;
                         }
                     }
                 } else {
                     print_error(
                         "load",
-                        &[R("language"), O("preferred_config_folder"), A("raw")]
+                        &[R("language"), O("preferred_config_folder"), A("raw"), A("max-bytes")]
                     );
                 }
             }
             Some("language") | Some("lanugage") | Some("langauge") | Some("lang") | Some("l") => {
                 match args.next_positional() {
-                    Some(language) => {
-                        let config = Config::new();
-                        if let Ok(generator) = LayoutGeneration::new(
-                            language,
-                            "static",
-                            Some(config)
-                        ) {
-                            self.language = language.to_string();
-                            self.gen = generator;
-                            self.saved = self.gen.load_layouts(
-                                "static/layouts",
-                                language
-                            ).expect("couldn't load layouts lol");
-
-                            println!(
-                                "Set language to {}. Sfr: {:.2}%",
-                                language, self.sfr_freq() * 100.0
-                            );
-                        } else {
-                            println!("Could not load data for {language}");
+                    Some(language) => self.set_language(language)?,
+                    None => println!("Current language: {}", self.language)
+                }
+            }
+            Some("combine") | Some("mix") => {
+                let mut shares = Vec::new();
+                while let Some(language) = args.next_positional() {
+                    match args.next_positional().map(str::parse::<f64>) {
+                        Some(Ok(share)) if share > 0.0 => shares.push((language, share)),
+                        _ => {
+                            shares.clear();
+                            break;
                         }
                     }
-                    None => println!("Current language: {}", self.language)
+                }
+
+                if shares.is_empty() {
+                    print_error("combine", &[R("language"), R("share"), O("language share...")]);
+                } else {
+                    self.combine(&shares)?;
                 }
             }
+            Some("by-language") | Some("bylang") => match args.next_positional() {
+                Some(name) => {
+                    let mut languages = Vec::new();
+                    while let Some(language) = args.next_positional() {
+                        languages.push(language);
+                    }
+                    self.by_language(name, &languages)?
+                }
+                None => print_error("by-language", &[R("name"), O("languages...")]),
+            },
             Some("languages") | Some("langs") => {
                 std::fs::read_dir("static/language_data")
-                    .unwrap()
+                    .map_err(|e| format!("could not read static/language_data: {e}"))?
                     .flatten()
                     .map(|p| p
                         .file_name()
@@ -460,30 +3036,67 @@ impl Repl {
                     .filter(|n| n != "test")
                     .for_each(|n| println!("{n}"))
             }
-            Some("reload") | Some("r") => {
-                let config = Config::new();
-                self.pins = config.pins.clone();
-
-                if let Ok(generator) = LayoutGeneration::new(
-                    self.language.as_str(),
-                    "static",
-                    Some(config)
-                ) {
-                    self.gen = generator;
-                    self.saved = self.gen.load_layouts(
-                        "static/layouts",
-                        self.language.as_str()
-                    ).expect("couldn't load layouts lol");
-                } else {
-                    println!("Could not load {}", self.language);
+            Some("reload") | Some("r") => self.reload()?,
+            Some("precision") => match args.next_positional() {
+                Some("auto") => match args.next_positional().map(str::parse::<f64>) {
+                    Some(Ok(tolerance)) => self.calibrate_precision(tolerance)?,
+                    Some(Err(_)) => {
+                        print_error("precision", &[O("amount or auto"), O("tolerance")])
+                    }
+                    None => self.calibrate_precision(CALIBRATION_TOLERANCE)?,
+                },
+                Some(amount) => match amount.parse::<usize>() {
+                    Ok(amount) => self.set_precision(amount)?,
+                    Err(_) => print_error("precision", &[O("amount or auto"), O("tolerance")]),
+                },
+                None => println!("Trigram precision: {}", self.gen.trigram_precision),
+            },
+            Some("session") => match (args.next_positional(), args.next_positional()) {
+                (Some("save"), Some(path)) => self.save_session(path)?,
+                (Some("load"), Some(path)) => self.load_session(path)?,
+                _ => print_error("session", &[R("save or load"), R("file")]),
+            },
+            Some("undo") | Some("u") => self.undo()?,
+            Some("redo") => self.redo()?,
+            Some("history") => match (args.next_positional(), args.next_positional()) {
+                (None, _) => self.show_history(),
+                (Some("save"), Some(path)) => {
+                    self.history.write_script(path)?;
+                    println!("saved {} commands to {path}", self.history.commands().count());
                 }
-            }
+                _ => print_error("history", &[O("save"), O("file")]),
+            },
+            Some("run") => match args.next_positional() {
+                Some(path) => return self.run_script(path),
+                None => print_error("run", &[R("file")]),
+            },
+            Some("profile") | Some("weights") => match args.next_positional() {
+                Some(profile) => {
+                    let previous = self.profile.replace(profile.to_string());
+                    if let Err(e) = self.reload() {
+                        self.profile = previous;
+                        return Err(e);
+                    }
+                    println!("Using the weights of profile '{profile}'");
+                    self.rank(false, None, false);
+                }
+                None => {
+                    let config = self.config()?;
+                    let current = config.profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+                    let profiles = std::iter::once(DEFAULT_PROFILE)
+                        .chain(config.profiles.iter().map(String::as_str));
+                    for profile in profiles {
+                        let marker = if profile == current { "*" } else { " " };
+                        println!("{marker} {profile}");
+                    }
+                }
+            },
             Some("save") | Some("s") => {
                 if let Some(n_str) = args.next_positional() {
                     if let Ok(nr) = usize::from_str_radix(n_str, 10) {
                         if let Some(layout) = self.get_nth(nr) {
                             let name = args.next_positional().map(str::to_string);
-                            self.save(layout, name).unwrap();
+                            self.save(layout, name)?;
                         }
                     } else {
                         print_error("save", &[R("index"), O("name")])
@@ -499,35 +3112,229 @@ impl Repl {
                     Some("generate") | Some("gen") | Some("g") => {
                         print_help(
                             "generate", 
-                            "(g, gen) Generate a number of layouts and shows the best 10, All layouts generated are accessible until reloading or quiting.",
-                            &[R("amount")]
+                            concat!(
+                                "(g, gen) Generate a number of layouts and shows the best 10, All layouts generated are accessible until reloading or quiting.\n",
+                                "The generated layouts can be browsed, sorted by score, with:\n",
+                                "    generate list [range]          Show the layouts in a range like 20..40, 20.. or ..40. A single\n",
+                                "                                   index shows the 10 layouts starting there.\n",
+                                "    generate analyze <index>       Show all stats of a generated layout\n",
+                                "    generate diff <index> <index>  Compare 2 generated layouts\n",
+                                "    generate save <index> [name]   Save a generated layout\n",
+                                "Generating can stop before the amount is done, which makes the amount the most that are generated:\n",
+                                "    --max-seconds <seconds>  Stop starting new layouts after this many seconds\n",
+                                "    --target-score <score>   Stop once a layout has at least this score\n",
+                                "    --patience <amount>      Stop once this many layouts in a row didn't beat the best score"
+                            ),
+                            &[R("amount"), A("max-seconds"), A("target-score"), A("patience")]
+                        )
+                    }
+                    Some("percentile") | Some("pct") => {
+                        print_help(
+                            "percentile",
+                            "(pct) Generate a number of layouts with the current language and weights, 1000 by default, and show where the score and stats of a layout fall among them, along with their median. A stat in the 20th percentile is higher than 20% of the generated layouts. The generated layouts are cached, and only generated again when the weights, language or amount change.",
+                            &[R("name"), O("amount")]
+                        )
+                    }
+                    Some("sensitivity") | Some("sens") => {
+                        print_help(
+                            "sensitivity",
+                            "(sens) Lowers and raises every weight by 10% and shows how much that changes the score of a layout and its rank among all layouts, starting with the weight the score depends on most. Score differences smaller than these changes depend more on the exact weights than on the layout.",
+                            &[R("name")]
+                        )
+                    }
+                    Some("fit-weights") | Some("fit") => {
+                        print_help(
+                            "fit-weights",
+                            "(fit) Searches for weights that rank layouts in the order you give them, best first, like 'fit-weights mine sturdy canary colemak-dh qwerty' or 'fit-weights mine sturdy > canary > qwerty'. Starting from the current weights, random variations of every weight that isn't 0 are tried, 400 by default or as many as --candidates <amount>. When they rank the layouts better, they are added to 'config.toml' as the profile [weights.<profile>], which 'profile <profile>' switches to.",
+                            &[R("profile"), R("layouts"), A("candidates")]
+                        )
+                    }
+                    Some("verify") => {
+                        print_help(
+                            "verify",
+                            "Scores qwerty, dvorak, colemak, colemak_dh, workman and semimak again and compares their score and stats with the ones stored for the language, to check that changing the weights, the language data or the scoring didn't change results you didn't expect to change. Values may differ by a fraction of 1e-6 by default, or of --tolerance <fraction>. Use --save to store the current ones, like after a change that is expected.",
+                            &[A("save"), A("tolerance")]
+                        )
+                    }
+                    Some("session") => {
+                        print_help(
+                            "session",
+                            "'session save <file>' writes the language, weight profile, pins, pin templates and generated layouts to a file, and 'session load <file>' picks up from there, so you can continue exploring later or share what you found. Loaded layouts are scored again, and when their scores are different, the weights or language data have changed since the session was saved.",
+                            &[R("save or load"), R("file")]
+                        )
+                    }
+                    Some("undo") | Some("u") => {
+                        print_help(
+                            "undo",
+                            "(u) Goes back to before the last command that changed the generated layouts, the language, the weight profile or the pins, like a 'generate', 'improve', 'edit' or 'profile' that wasn't what you wanted. Saving, renaming and deleting layout files can't be undone.",
+                            &[]
+                        )
+                    }
+                    Some("redo") => {
+                        print_help(
+                            "redo",
+                            "Does the last command 'undo' went back on again. Any other command that changes something makes it impossible to redo what was undone before it.",
+                            &[]
+                        )
+                    }
+                    Some("history") => {
+                        print_help(
+                            "history",
+                            "Shows the commands that changed the generated layouts, the language, the weight profile or the pins, and the ones that can be redone. 'history save <file>' writes them to a script, which 'run <file>' does again.",
+                            &[O("save"), O("file")]
+                        )
+                    }
+                    Some("run") => {
+                        print_help(
+                            "run",
+                            "Runs every command of a script one after the other, as if you typed them, and stops at the first one that fails. Empty lines and lines that start with # are skipped. 'history save <file>' writes such a script.",
+                            &[R("file")]
+                        )
+                    }
+                    Some("keymap") | Some("remap") => {
+                        print_help(
+                            "keymap",
+                            "(remap) Write a kmonad or kanata config that remaps a keyboard to a layout, or to a generated layout by its number, so you can try it without changing anything else. The keys are sent as the keys of the layout your system is set to, which is qwerty unless you pass --base colemak. kanata types characters that aren't on that layout as unicode, kmonad leaves their keys as they are. Without --out <file> the config is printed. kmonad also needs a defcfg with the input and output of your keyboard.",
+                            &[R("name or number"), R("kmonad or kanata"), A("base"), A("out")]
+                        )
+                    }
+                    Some("keys") => {
+                        print_help(
+                            "keys",
+                            "Show what lands on each of the 30 keys of a layout: the character, its frequency, the effort of the key, the wasted effort (frequency times effort, times 100) and the finger speed of the key with its share of the finger speed of its finger. Keys are sorted by wasted effort, so a common character on a bad key ends up at the top even when the total score looks fine.",
+                            &[R("name")]
+                        )
+                    }
+                    Some("punctuation") | Some("punct") => {
+                        print_help(
+                            "punctuation",
+                            "(punct) Show what every punctuation key of a layout costs in sfbs, dsfbs, scissors and lsbs with the keys around it. Which characters count as punctuation is set with 'punctuation' in 'config.toml', and with 'move_punctuation = false' they stay where they are when using 'improve'.",
+                            &[R("name")]
+                        )
+                    }
+                    Some("suggest") | Some("sug") => {
+                        print_help(
+                            "suggest",
+                            "(sug) Show the single swaps that would improve the score of a layout the most, 10 by default, with how much every stat changes. Nothing is swapped, and keys that are pinned, either in the repl or in the layout file, or punctuation with 'move_punctuation = false' are left where they are.",
+                            &[R("name"), O("count")]
+                        )
+                    }
+                    Some("show-effort") | Some("effort") => {
+                        print_help(
+                            "show-effort",
+                            "(effort) Show the effort of every key as the score uses it, which is the heatmap of the keyboard type times the 'heatmap' weight, with hand dominance, travel and 'avoid_keys' applied. Below it is the average finger speed distance of the same finger bigrams every key is part of, with the lateral penalty, finger speeds and hand dominance applied. Both go from white for the lowest value to red for the highest, so you can see what changing those weights does.",
+                            &[]
+                        )
+                    }
+                    Some("show-config") => {
+                        print_help(
+                            "show-config",
+                            "Show the language, trigram precision, profile and weights a layout was saved with, and which weights are different now. 'save' stores them as comments in the layout file, so scores of older layouts can be traced back to the weights that produced them.",
+                            &[R("name")]
+                        )
+                    }
+                    Some("corpus") => {
+                        print_help(
+                            "corpus stats",
+                            "Show what the corpus of the current language is made of, to check it before generating with it: the most common characters with how much of the corpus they cover together, the most common bigrams and trigrams, the entropy of each, and how much characters 1 to 4 apart have to do with each other, which drops quickly for prose. Shows the 30 most common of each by default.",
+                            &[R("stats"), O("amount")]
+                        )
+                    }
+                    Some("excluded") | Some("excl") => {
+                        print_help(
+                            "excluded",
+                            "(excl) Show the characters of the language that generation doesn't put on the 30 keys, with how common they are. Languages keep their most common characters up to a limit, and characters past it are listed as well.",
+                            &[]
+                        )
+                    }
+                    Some("edit") | Some("e") => {
+                        print_help(
+                            "edit",
+                            "(e) Edit a layout in the terminal. Move around with the arrow keys or hjkl, pick a key with space or enter and pick another one to swap them, and undo with u. The score, sfbs, dsfbs, rolls and alternates update after every swap, along with how much they changed. Quitting with q shows the edited layout next to the original, which can be saved with 'save 0 <name>'.",
+                            &[R("name")]
+                        )
+                    }
+                    Some("mirror") => {
+                        print_help(
+                            "mirror",
+                            "Compare a layout with its left and right half swapped. The mirrored layout can be saved with 'save 0 <name>' afterwards.",
+                            &[R("name")]
+                        )
+                    }
+                    Some("heatmap-compare") | Some("hmc") => {
+                        print_help(
+                            "heatmap-compare",
+                            "(hmc) Shows the heatmaps of two layouts next to each other, and a third grid with the keys of the second layout that are used more than 1% more often than the same key on the first one in red, and 1% less often in green, or --threshold <percent> instead of 1%. Below that, every key that changed that much is listed with its frequency on both layouts.",
+                            &[R("layout 1"), R("layout 2"), A("threshold")]
+                        )
+                    }
+                    Some("heatmap") | Some("hm") => {
+                        print_help(
+                            "heatmap",
+                            "(hm) Shows the heatmap of a layout. By default keys are colored by how often they are used, --mode sfb, fspeed or scissors colors them by how much of those they take part in instead, relative to the worst key. --mode gain colors them by how much the score goes up with the best single swap of that key, which shows where the weak spots of the layout are. With --svg <file> it is saved as an svg instead, placed like the keyboard_type in 'config.toml', and --zones outlines every key in the color of its finger.",
+                            &[R("name"), A("svg"), A("mode"), A("zones")]
                         )
                     }
                     Some("improve") | Some("i") => {
                         print_help(
                             "improve",
-                            "(i) Save the top <number> result that was generated.",
-                            &[R("name"), R("amount")]
+                            "(i) Generates <amount> layouts by improving an existing one, keeping pinned keys in place. '--keep <template>' moves a set of keys to where they are on qwerty and keeps them there, and can be used more than once. The templates are zxcv, shortcuts (qwas and zxcv) and punctuation (the ,./ on the bottom row). Templates in 'pin_templates' in 'config.toml' are always used. With '--temperature <t>', optimizing also makes random swaps that make the layout a little worse once no swap improves it anymore, which helps when so many keys are pinned that it gets stuck right away. A swap that lowers the score by d is made with a chance of e^(-d / t), and only the best layout that comes by is kept. It replaces 'temperature' in '[generation]' of 'config.toml' for this time. Keys that are '*' in the layout file are undecided: they get the most common characters that aren't on the layout yet, and only those move.",
+                            &[R("name"), R("amount"), A("keep"), A("temperature")]
                         )
                     }
                     Some("rank") => {
                         print_help(
                             "rank",
-                            "(sort) Rank all layouts in set language by score using values set from 'config.toml'",
-                            &[]
+                            "(sort) Rank all layouts in set language by score using values set from 'config.toml'. Next to the score is the typing speed a simple model of keystroke times estimates, which compares layouts without the weights. With --verbose, the author, link and other metadata in the layout files is shown as well. With --tag <tag>, only layouts with that tag are ranked. With --by <stat>, layouts are ranked by a stat like sfb or rolls from low to high instead. With --group, only the best layout of every family set with '# family:' in the layout files is shown, with how many variants it has.",
+                            &[A("verbose"), A("tag"), A("by"), A("group")]
+                        )
+                    }
+                    Some("family") => {
+                        print_help(
+                            "family",
+                            "Show the layouts of a family set with '# family:' in the layout files from best to worst, with the lowest and highest value of the most important stats and how far apart they are. Instead of a family, a layout of the family can be given.",
+                            &[R("family or layout")]
                         )
                     }
                     Some("analyze") | Some("layout") | Some("a") => {
                         print_help(
                             "analyze",
-                            "(a, layout) Show details of layout.",
-                            &[R("name or number")]
+                            "(a, layout) Show details of layout. With --full, the layout is also scored using every trigram instead of only the trigram_precision most common ones, showing how much the score changes. When a lot of trigrams have characters that aren't on the layout, a warning shows which characters they are. With --renormalize, the trigram stats leave those trigrams out, so they can be compared with layouts that have all characters. With references set in config.toml, it also shows how the layout differs from every one of them. A layout with a magic line, like 'magic @ ou', is analyzed as it is typed with that magic key.",
+                            &[R("name or number"), A("full"), A("renormalize")]
                         )
                     }
                     Some("compare") | Some("c") | Some("cmp") | Some("cmopare") | Some("comprae") => {
                         print_help(
                             "compare",
-                            "(c, cmp) Compare 2 layouts.",
+                            "(c, cmp) Compare 2 layouts. With --retraining, it also shows how much the score changes when switching from the first layout to the second, next to an estimate of how much has to be relearned: the share of keystrokes that move to another key, where keys that stay on the same finger or hand count for less. With --tag <tag>, the most important stats of every layout with that tag are shown in a table instead.",
+                            &[R("layout 1"), R("layout 2"), A("retraining"), A("tag")]
+                        )
+                    }
+                    Some("tag") | Some("untag") => {
+                        print_help(
+                            "tag",
+                            "Add tags to a layout, like 'tag semimak ergo rolls', which are stored in its file. Without tags, the tags it has are shown. 'untag' removes tags in the same way. Tags can be used with 'rank', 'compare', 'delete' and 'archive'.",
+                            &[R("name"), O("tags...")]
+                        )
+                    }
+                    Some("delete") | Some("archive") => {
+                        print_help(
+                            "delete",
+                            "Delete the files of one or more layouts. 'archive' moves them to an 'archive' directory next to them instead, where they aren't loaded anymore. With --tag <tag>, every layout with that tag is removed after asking for confirmation.",
+                            &[R("names"), A("tag")]
+                        )
+                    }
+                    Some("rename") => {
+                        print_help(
+                            "rename",
+                            "Rename the file of a layout. It keeps its tags, aliases and place in 'rank'.",
+                            &[R("name"), R("new name")]
+                        )
+                    }
+                    Some("diff") => {
+                        print_help(
+                            "diff",
+                            "Shows which keys are in a different position on 2 layouts. Moved keys are highlighted and keys that stayed are dimmed. Columns with the same keys count as shared, even if they moved or were reordered.",
                             &[R("layout 1"), R("layout 2")]
                         )
                     }
@@ -538,6 +3345,62 @@ impl Repl {
                             &[R("name"), O("top n")]
                         )
                     }
+                    Some("sfs") => {
+                        print_help(
+                            "sfs",
+                            "Shows the top n same finger skipgrams for a certain layout, with an _ for every key in between. These add up to the dsfb, dsfb2 and dsfb3 stats.",
+                            &[R("name"), O("top n")]
+                        )
+                    }
+                    Some("chart") => {
+                        print_help(
+                            "chart",
+                            "Saves an svg bar chart comparing layouts on comma separated metrics, like 'sfb,dsfb,rolls', to static/charts.",
+                            &[R("metrics"), R("layouts")]
+                        )
+                    }
+                    Some("coverage") | Some("cov") => {
+                        print_help(
+                            "coverage",
+                            "(cov) Shows how much of the corpus' characters, bigrams and trigrams can be typed with a layout's keys.",
+                            &[R("name")]
+                        )
+                    }
+                    Some("onehanded") | Some("onehand") | Some("oh") => {
+                        print_help(
+                            "onehanded",
+                            "(onehand, oh) Analyzes a layout as if all keys are typed with one hand, using the fingers set in 'config.toml'.",
+                            &[R("name")]
+                        )
+                    }
+                    Some("replay") | Some("rp") => {
+                        print_help(
+                            "replay",
+                            "(rp) Types out a text file, or the corpus' sentence samples if none is given, on a layout key by key and shows sfbs, scissors and same hand runs per 1000 keystrokes.",
+                            &[R("name"), O("file")]
+                        )
+                    }
+                    Some("export-stats") | Some("export") => {
+                        print_help(
+                            "export-stats",
+                            "(export) Writes the stats of every saved layout to a csv file with --csv <file>, with a row per layout and a column per stat. With --generated, the layouts that were generated or improved last are added as #0, #1 and so on.",
+                            &[A("csv"), A("generated")]
+                        )
+                    }
+                    Some("sample") | Some("preview") => {
+                        print_help(
+                            "sample",
+                            "(preview) Shows random sentences from the corpus with the sfbs and hand switches a layout has on them.",
+                            &[R("name"), O("amount")]
+                        )
+                    }
+                    Some("schema") => {
+                        print_help(
+                            "schema",
+                            "Prints the JSON Schema of an exported file, or lists the available ones. With --save, writes all of them to static/schemas.",
+                            &[O("name"), A("save")]
+                        )
+                    }
                     Some("ngram") | Some("occ") | Some("n") => {
                         print_help(
                             "ngram",
@@ -548,15 +3411,29 @@ impl Repl {
                     Some("load") => {
                         print_help(
                             "load",
-                            "Generates corpus for <language>. Will be include everything but spaces if the language is not known.",
-                            &[R("language"), O("preferred_config_folder"), A("raw")]
+                            "Generates corpus for <language>. Will be include everything but spaces if the language is not known. Text files are read a few MB at a time, so corpora of any size fit in memory. '--max-bytes 50M' only reads the first 50 MB of the corpus, which is much quicker when trying out corpus rules.",
+                            &[R("language"), O("preferred_config_folder"), A("raw"), A("max-bytes")]
                         )
                     }
                     Some("language") | Some("lanugage") | Some("langauge") | Some("lang") | Some("l") => {
                         print_help(
                             "language",
-                            "(l, lang) Sets a language to be used for analysis.",
-                            &[R("language")]
+                            "(l, lang) Sets a language to be used for analysis, without restarting. The layouts of static/layouts/<language> are loaded and scored again, and generated layouts that weren't saved are dropped. Without a language, shows the current one.",
+                            &[O("language")]
+                        )
+                    }
+                    Some("combine") | Some("mix") => {
+                        print_help(
+                            "combine",
+                            "(mix) Uses a weighted mix of several languages, like 'combine english 60 german 40'. Analyze shows stats per language as well.",
+                            &[R("language"), R("share"), O("language share...")]
+                        )
+                    }
+                    Some("by-language") | Some("bylang") => {
+                        print_help(
+                            "by-language",
+                            "(bylang) Shows how a layout ranks among the saved layouts on every language on its own, like 'by-language semimak english german french', from the language it ranks best on to the one it ranks worst on. Without languages, the ones of the current mix are used. Layouts that rank well for the mix of all of them but badly on a language are marked with a '!'.",
+                            &[R("name"), O("languages...")]
                         )
                     }
                     Some("languages") | Some("langs") => {
@@ -566,6 +3443,20 @@ impl Repl {
                             &[]
                         )
                     }
+                    Some("profile") | Some("weights") => {
+                        print_help(
+                            "profile",
+                            "(weights) Use the weights of a profile like [weights.prose] in 'config.toml' and rank all layouts with them. 'default' uses [weights] as is. Without a name, all profiles are listed.",
+                            &[O("name")]
+                        )
+                    }
+                    Some("precision") => {
+                        print_help(
+                            "precision",
+                            "Score a different amount of the most common trigrams, instead of trigram_precision from 'config.toml', until the next reload. Every layout is scored again right away. Without an amount, the current one is shown. With auto, the smallest amount is picked that ranks the saved layouts the same as every trigram does, except for at most tolerance percent of the pairs of layouts (1 by default), after showing how well every amount it tried ranks them.",
+                            &[O("amount or auto"), O("tolerance")]
+                        )
+                    }
                     Some("reload") | Some("r") => {
                         print_help(
                             "reload",
@@ -599,24 +3490,65 @@ impl Repl {
                         println!(concat!(
                             "commands:\n",
                             "    analyze      (a, layout) Show details of layout\n",
+                            "    archive      Move layouts out of the way without deleting them\n",
+                            "    by-language  (bylang) Show how a layout ranks on every language alone\n",
+                            "    chart        Save an svg bar chart comparing metrics of several layouts\n",
+                            "    combine      (mix) Use a weighted mix of several languages\n",
                             "    compare      (c, comp) Compare 2 layouts\n",
+                            "    corpus       Show the most common characters and ngrams of the corpus, and its entropy\n",
+                            "    coverage     (cov) Show how much of the corpus can be typed with a layout's keys\n",
+                            "    delete       Delete layouts, by name or by tag\n",
+                            "    diff         Show which keys are in a different position on 2 layouts\n",
+                            "    edit         (e) Swap keys of a layout by hand and see the stats change right away\n",
+                            "    excluded     (excl) Show the characters that don't fit on the board\n",
+                            "    export-stats (export) Write the stats of all layouts to a csv file\n",
+                            "    family       Show how the layouts of a family differ in score and stats\n",
+                            "    fit-weights  (fit) Find weights that rank layouts in your order and save them as a profile\n",
                             "    generate     (g, gen) Generate a number of layouts and shows the best 10, All layouts\n",
-                            "                     generated are accessible until reloading or quiting.\n",
+                            "                     generated are accessible until reloading or quiting, and can be browsed\n",
+                            "                     with 'generate list/analyze/diff/save'.\n",
+                            "    heatmap      (hm) Show the heatmap of a layout, or save it as an svg\n",
+                            "    heatmap-compare (hmc) Show the heatmaps of 2 layouts next to each other\n",
                             "    help         Print this message or the help of the given subcommand(s)\n",
+                            "    history      Show the commands that changed the session, or save them as a script\n",
                             "    improve      (i, optimize) Save the top <NR> result that was generated. Starts from 1, Takes\n",
                             "                     negative values\n",
+                            "    keymap       (remap) Write a kmonad or kanata config that remaps a keyboard to a layout\n",
+                            "    keys         Show what every key of a layout costs, sorted by wasted effort\n",
                             "    language     (l, lang) Set a language to be used for analysis. Loads corpus when not present\n",
                             "    languages    (langs) Show available languages\n",
                             "    load         Generates corpus for <language>. Will be exclude spaces from source if the\n",
                             "                     language isn't known\n",
+                            "    mirror       Compare a layout with its mirrored version\n",
                             "    ngram        (occ) Gives information about a certain ngram. for 2 letter ones, skipgram info\n",
                             "                     will be provided as well.\n",
+                            "    onehanded    (onehand, oh) Analyze a layout as if all keys are typed with one hand\n",
+                            "    percentile   (pct) Show how a layout compares to a baseline of generated layouts\n",
+                            "    precision    Score a different amount of trigrams until reloading\n",
+                            "    profile      (weights) Switch to another weight profile from 'config.toml'\n",
+                            "    punctuation  (punct) Show what the punctuation keys of a layout cost\n",
                             "    quit         (q) Quit the repl\n",
                             "    rank         (sort) Rank all layouts in set language by score using values set from\n",
                             "                     'config.toml'\n",
+                            "    redo         Do the last command that was undone again\n",
                             "    reload       (r) Reloads all data with the current language. Loses temporary layouts.\n",
+                            "    rename       Rename the file of a layout\n",
+                            "    replay       (rp) Type out a text on a layout and show stats per 1000 keystrokes\n",
+                            "    run          Run the commands of a script, like one saved with 'history save'\n",
+                            "    sample       (preview) Show corpus sentences annotated with sfbs and hand switches\n",
                             "    save         (s) Save the top <NR> result that was generated. Starts from 1 up to the number\n",
-                            "                     generated, Takes negative values\n"
+                            "                     generated, Takes negative values\n",
+                            "    schema       Print the JSON Schema of an exported file\n",
+                            "    sensitivity  (sens) Show how much the score of a layout depends on every weight\n",
+                            "    session      Save or load the language, weights, pins and generated layouts\n",
+                            "    sfs          Show the most common same finger skipgrams of a layout\n",
+                            "    show-config  Show the weights and language a layout was saved with\n",
+                            "    show-effort  (effort) Show the effort and finger speed distance of every key\n",
+                            "    suggest      (sug) Show the swaps that would improve a layout the most\n",
+                            "    tag          Add tags to a layout, which 'rank', 'compare', 'delete' and 'archive' can use\n",
+                            "    undo         (u) Go back to before the last command that changed the session\n",
+                            "    untag        Remove tags from a layout\n",
+                            "    verify       Check that well known layouts still have the stats they are stored with\n"
                         ));
                     }
                 }
@@ -628,3 +3560,120 @@ impl Repl {
         Ok(false)
     }
 }
+
+/// Shows what was ignored in `config.toml`, like keys that aren't known.
+fn print_warnings(config: &Config) {
+    for warning in config.warnings.iter() {
+        eprintln!("warning in config.toml: {warning}");
+    }
+}
+
+/// Caches the trigrams of `language` next to its json, so loading it reads them from there next
+/// time.
+fn cache_trigrams<P: AsRef<Path>>(base_path: P, language: &str) {
+    if let Err(e) = LanguageData::cache_trigrams(&FileSource::new(base_path), language) {
+        eprintln!("trigrams could not be cached: {e}");
+    }
+}
+
+/// Percentage of the pairs of layouts `precision auto` can rank differently than every trigram.
+const CALIBRATION_TOLERANCE: f64 = 1.0;
+
+/// Part of the trigrams that can be invalid before `analyze` warns about it.
+const INVALID_TRIGRAM_WARNING: f64 = 0.01;
+
+/// Stats `percentile` shows, next to the score.
+const PERCENTILE_STATS: [&str; 16] = [
+    "score",
+    "sfb",
+    "sfb_distance",
+    "dsfb",
+    "scissors",
+    "lsbs",
+    "row_skips",
+    "fspeed",
+    "travel",
+    "alternates",
+    "inrolls",
+    "outrolls",
+    "onehands",
+    "redirects",
+    "bad_redirects",
+    "sfts",
+];
+
+/// Parses an amount of bytes like `1000`, `500K`, `64M` or `2G`, where a K is 1024 bytes.
+fn parse_bytes(bytes_str: &str) -> Option<u64> {
+    let bytes_str = bytes_str.trim().to_uppercase();
+    let bytes_str = bytes_str.strip_suffix('B').unwrap_or(&bytes_str);
+    let (number, unit) = match bytes_str.char_indices().last()? {
+        (i, 'K') => (&bytes_str[..i], 1 << 10),
+        (i, 'M') => (&bytes_str[..i], 1 << 20),
+        (i, 'G') => (&bytes_str[..i], 1 << 30),
+        _ => (bytes_str, 1),
+    };
+    number.trim().parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// Parses a range of generated layouts like `20..40`, `20..` or `..40`. A single index is the 10
+/// layouts starting there.
+fn parse_range(range_str: &str, len: usize) -> Option<Range<usize>> {
+    match range_str.split_once("..") {
+        Some((start, end)) => {
+            let start = if start.is_empty() { 0 } else { start.parse().ok()? };
+            let end = if end.is_empty() { len } else { end.parse().ok()? };
+            Some(start..end.max(start))
+        }
+        None => {
+            let start = range_str.parse().ok()?;
+            Some(start..start + 10)
+        }
+    }
+}
+
+/// Parses a number of seconds to generate for, which can have decimals but can't be negative.
+pub fn parse_seconds(seconds: &str) -> Result<Duration, String> {
+    match seconds.parse::<f64>() {
+        Ok(s) if s.is_finite() && s >= 0.0 => Ok(Duration::from_secs_f64(s)),
+        _ => Err(format!("'{seconds}' isn't a number of seconds")),
+    }
+}
+
+/// What `rank --group` shows after a layout that is the best of a family with other layouts.
+fn family_note(family: &str, members: usize) -> String {
+    match members {
+        0 | 1 => String::new(),
+        2 => format!(" (best of {family}, 1 other variant)"),
+        _ => format!(" (best of {family}, {} other variants)", members - 1),
+    }
+}
+
+/// Columns of the tables `compare --tag` and `family` show, see `table_values`.
+const TABLE_COLUMNS: [&str; 9] = [
+    "score", "sfb", "dsfb", "scissors", "lsbs", "inrolls", "outrolls", "alt", "redir",
+];
+
+/// Score and the stats in `TABLE_COLUMNS` of `layout` scored by `gen`, with the stats in percent.
+fn table_values(gen: &LayoutGeneration, layout: &FastLayout) -> [f64; 9] {
+    let stats = gen.get_layout_stats(layout);
+    let ts = &stats.trigram_stats;
+    [
+        layout.score,
+        stats.sfb * 100.0,
+        stats.dsfb * 100.0,
+        stats.scissors * 100.0,
+        stats.lsbs * 100.0,
+        ts.inrolls * 100.0,
+        ts.outrolls * 100.0,
+        ts.alternates * 100.0,
+        ts.redirects * 100.0,
+    ]
+}
+
+fn print_table_row(name: &str, values: [String; 9]) {
+    print!("{name:<16}");
+    for value in values {
+        print!("{value:>10}");
+    }
+    println!();
+}
@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// A generated layout in a [`Session`], with the score it had when the session was saved.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionLayout {
+    /// The 30 keys of the layout, row by row.
+    pub layout: String,
+    pub score: f64,
+}
+
+/// Everything `session save` writes to resume exploring layouts later, or to let someone else
+/// see the same generated layouts with the same language and weights.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Session {
+    pub language: String,
+    /// Languages and their shares when `combine` was used, empty otherwise.
+    #[serde(default)]
+    pub combination: Vec<(String, f64)>,
+    /// Weight profile, or `None` for the one in `config.toml`.
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Positions pinned in the repl.
+    #[serde(default)]
+    pub pins: Vec<usize>,
+    /// Names of the pin templates that were used.
+    #[serde(default)]
+    pub pin_templates: Vec<String>,
+    /// Layout the generated layouts were improved from.
+    #[serde(default)]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub generated: Vec<SessionLayout>,
+}
+
+impl Session {
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {e}", path.display()))?;
+        serde_json::from_str(&s).map_err(|e| format!("{} is not a session: {e}", path.display()))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("could not write {}: {e}", path.display()))
+    }
+}
@@ -1,9 +1,11 @@
 use std::io::Write;
 
-use oxeylyzer_core::generate::LayoutGeneration;
+use oxeylyzer_core::generate::{Comparison, GenerationProgress, LayoutGeneration, StopCriteria};
+use oxeylyzer_core::graphemes::nfc;
 use oxeylyzer_core::language_data::LanguageData;
 use oxeylyzer_core::layout::*;
 use oxeylyzer_core::rayon::iter::ParallelIterator;
+use oxeylyzer_core::utility::{is_left_hand, TotalScore};
 
 use ansi_rgb::{rgb, Colorable};
 use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
@@ -15,18 +17,67 @@ pub fn readline() -> Result<String, String> {
     std::io::stdin()
         .read_line(&mut buf)
         .map_err(|e| e.to_string())?;
-    Ok(buf)
+    Ok(nfc(&buf).into_owned())
 }
 
 pub fn heatmap_heat(data: &LanguageData, c: u8) -> String {
-    let complement = 215.0 - *data.characters.get(c as usize).unwrap_or_else(|| &0.0) * 1720.0;
-    let complement = complement.max(0.0) as u8;
-    let heat = rgb(215, complement, complement);
-    let c = data.convert_u8.from_single(c);
-    format!("{}", c.to_string().fg(heat))
+    let freq = *data.characters.get(c as usize).unwrap_or(&0.0);
+    heat_colored(data, c, freq * 8.0)
+}
+
+/// `c` colored from white for a heat of 0 to red for a heat of 1.
+pub fn heat_colored(data: &LanguageData, c: u8, heat: f64) -> String {
+    heat_text(&data.convert_u8.from_single(c).to_string(), heat)
+}
+
+/// `text` colored from white for a heat of 0 to red for a heat of 1.
+pub fn heat_text(text: &str, heat: f64) -> String {
+    let complement = (215.0 - heat * 215.0).clamp(0.0, 215.0) as u8;
+    format!("{}", text.fg(rgb(215, complement, complement)))
+}
+
+/// A value for every key in the shape of a layout, colored from white for the lowest value to
+/// red for the highest.
+pub fn value_grid_string(values: &[f64; 30]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let mut res = String::new();
+
+    for (i, &value) in values.iter().enumerate() {
+        if i % 10 == 0 && i > 0 {
+            res.push('\n');
+        }
+        if (i + 5) % 10 == 0 {
+            res.push(' ');
+        }
+        let heat = if max > min {
+            (value - min) / (max - min)
+        } else {
+            0.0
+        };
+        res.push_str(&heat_text(&format!("{value:>6.2}"), heat));
+    }
+    res
 }
 
 pub fn heatmap_string(data: &LanguageData, layout: &FastLayout) -> String {
+    let heat = layout
+        .matrix
+        .map(|c| *data.characters.get(c as usize).unwrap_or(&0.0) * 8.0);
+    let thumb_heat = layout
+        .thumbs
+        .map(|c| *data.characters.get(c as usize).unwrap_or(&0.0) * 8.0);
+    heatmap_string_with(data, layout, &heat, thumb_heat)
+}
+
+/// The layout with every key colored by its own heat from 0 to 1, like
+/// [`LayoutGeneration::key_heat`] returns.
+pub fn heatmap_string_with(
+    data: &LanguageData,
+    layout: &FastLayout,
+    heat: &[f64; 30],
+    thumb_heat: [f64; 2],
+) -> String {
     let mut print_str = String::new();
 
     for (i, c) in layout.matrix.iter().enumerate() {
@@ -36,13 +87,198 @@ pub fn heatmap_string(data: &LanguageData, layout: &FastLayout) -> String {
         if (i + 5) % 10 == 0 {
             print_str.push(' ');
         }
-        print_str.push_str(heatmap_heat(data, *c).as_str());
+        print_str.push_str(heat_colored(data, *c, heat[i]).as_str());
         print_str.push(' ');
     }
 
+    if layout.thumbs != [NO_THUMB; 2] {
+        print_str.push_str("\n        ");
+        for (i, &c) in layout.thumbs.iter().enumerate() {
+            if c == NO_THUMB {
+                print_str.push('_');
+            } else {
+                print_str.push_str(heat_colored(data, c, thumb_heat[i]).as_str());
+            }
+            print_str.push_str(if i == 0 { "  " } else { " " });
+        }
+    }
+
     print_str
 }
 
+/// Frequency of the character on every key of `layout`.
+pub fn key_frequencies(data: &LanguageData, layout: &FastLayout) -> [f64; 30] {
+    layout
+        .matrix
+        .map(|c| *data.characters.get(c as usize).unwrap_or(&0.0))
+}
+
+/// The heatmaps of `layout1` and `layout2` next to each other, followed by a grid with the keys of
+/// `layout2` that are used more than `threshold` more often than the same key of `layout1` in red,
+/// the ones used that much less often in green and the others dimmed.
+pub fn heatmap_compare_string(
+    data: &LanguageData,
+    layout1: &FastLayout,
+    layout2: &FastLayout,
+    threshold: f64,
+) -> String {
+    let freqs1 = key_frequencies(data, layout1);
+    let freqs2 = key_frequencies(data, layout2);
+    let mut print_str = String::new();
+
+    for row in 0..3 {
+        if row > 0 {
+            print_str.push('\n');
+        }
+        for (layout, freqs) in [(layout1, &freqs1), (layout2, &freqs2)] {
+            for i in (row * 10)..(row * 10 + 10) {
+                if i % 10 == 5 {
+                    print_str.push(' ');
+                }
+                print_str.push_str(&heat_colored(data, layout.c(i), freqs[i] * 8.0));
+                print_str.push(' ');
+            }
+            print_str.push_str("   ");
+        }
+        for i in (row * 10)..(row * 10 + 10) {
+            if i % 10 == 5 {
+                print_str.push(' ');
+            }
+            let change = freqs2[i] - freqs1[i];
+            let color = if change > threshold {
+                rgb(215, 60, 60)
+            } else if change < -threshold {
+                rgb(60, 180, 60)
+            } else {
+                rgb(100, 100, 100)
+            };
+            let key = data.convert_u8.from_single(layout2.c(i)).to_string();
+            print_str.push_str(&format!("{}", key.fg(color)));
+            print_str.push(' ');
+        }
+    }
+
+    print_str
+}
+
+/// Shows `layout` with the keys that are somewhere else in `other` highlighted, and the keys that
+/// stayed in the same position dimmed.
+pub fn diff_string(data: &LanguageData, layout: &FastLayout, other: &FastLayout) -> String {
+    let moved = layout.moved_keys(other);
+    let mut print_str = String::new();
+
+    for (i, c) in layout.matrix.iter().enumerate() {
+        if i % 10 == 0 && i > 0 {
+            print_str.push('\n');
+        }
+        if (i + 5) % 10 == 0 {
+            print_str.push(' ');
+        }
+        let color = if moved[i] {
+            rgb(255, 170, 0)
+        } else {
+            rgb(100, 100, 100)
+        };
+        let key = data.convert_u8.from_single(*c).to_string();
+        print_str.push_str(&format!("{}", key.fg(color)));
+        print_str.push(' ');
+    }
+
+    print_str
+}
+
+/// Shows a sentence with its sfbs highlighted, and a line below it marking sfbs with `^` and
+/// keys typed with the other hand than the previous key with `~`.
+pub fn annotated_sample(data: &LanguageData, layout: &FastLayout, sentence: &str) -> String {
+    let chars = sentence.chars().collect::<Vec<_>>();
+    let keys = chars
+        .iter()
+        .map(|c| {
+            let lower = c.to_lowercase().next().unwrap_or(*c);
+            let u = data.convert_u8.to_single_lossy(lower);
+            match layout.char_to_finger.get(u as usize) {
+                Some(&finger) if finger != usize::MAX => Some((u, finger)),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let is_sfb = |i: usize| match (keys[i - 1], keys[i]) {
+        (Some((c1, f1)), Some((c2, f2))) => c1 != c2 && f1 == f2,
+        _ => false,
+    };
+    let is_alternate = |i: usize| match (keys[i - 1], keys[i]) {
+        (Some((_, f1)), Some((_, f2))) => is_left_hand(f1) != is_left_hand(f2),
+        _ => false,
+    };
+
+    let mut text = String::new();
+    let mut marks = String::new();
+    let (mut sfbs, mut alternates) = (0usize, 0usize);
+
+    for (i, c) in chars.iter().enumerate() {
+        let sfb_before = i > 0 && is_sfb(i);
+        let sfb_after = i + 1 < chars.len() && is_sfb(i + 1);
+
+        if sfb_before {
+            sfbs += 1;
+        }
+
+        if sfb_before || sfb_after {
+            text.push_str(&format!("{}", c.to_string().fg(rgb(215, 60, 60))));
+            marks.push('^');
+        } else if i > 0 && is_alternate(i) {
+            alternates += 1;
+            text.push(*c);
+            marks.push('~');
+        } else {
+            text.push(*c);
+            marks.push(' ');
+        }
+    }
+
+    format!("{text}\n{marks}\nsfbs: {sfbs}, hand switches: {alternates}")
+}
+
+/// How a generated layout is listed: its number, score and heatmap, followed by the constraints
+/// it meets if there are any.
+pub fn generated_string(gen: &LayoutGeneration, nr: usize, layout: &FastLayout) -> String {
+    let mut res = format!(
+        "#{}, score: {:.5}\n{}",
+        nr,
+        layout.score,
+        heatmap_string(&gen.data, layout)
+    );
+
+    let checked = gen.check_constraints(layout);
+    if !checked.is_empty() {
+        let met = checked.iter().filter(|(_, _, met)| *met).count();
+        let details = checked
+            .iter()
+            .map(|(constraint, value, met)| {
+                let cmp = if *met { "<=" } else { ">" };
+                format!("{} {value:.3} {cmp} {}", constraint.metric, constraint.max)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        res.push_str(&format!(
+            "\nconstraints: {met} of {} met ({details})",
+            checked.len()
+        ));
+    }
+    res
+}
+
+/// Sorts `layouts` from best to worst and keeps only the best of those with the same columns,
+/// like mirrored ones, so the listing shows layouts that are actually different.
+fn best_unique(layouts: &mut Vec<FastLayout>) {
+    let runs = layouts.len();
+    layouts.sort_by_key(|layout| std::cmp::Reverse(TotalScore(layout.score)));
+    dedup_columns(layouts);
+    println!("{} unique layouts out of {runs} runs", layouts.len());
+}
+
 pub fn generate_n_with_pins(
     gen: &LayoutGeneration,
     amount: usize,
@@ -72,47 +308,265 @@ pub fn generate_n_with_pins(
         start.elapsed().as_secs()
     );
 
-    layouts.sort_by(|l1, l2| l2.score.partial_cmp(&l1.score).unwrap());
+    best_unique(&mut layouts);
 
     for (i, layout) in layouts.iter().enumerate().take(10) {
-        let printable = heatmap_string(&gen.data, layout);
-        println!("#{}, score: {:.5}\n{}", i, layout.score, printable);
+        println!("{}", generated_string(gen, i, layout));
     }
+    print_language_results(gen, &layouts[..layouts.len().min(10)]);
 
     layouts
 }
 
-pub fn generate_n(gen: &LayoutGeneration, amount: usize) -> Vec<FastLayout> {
+pub fn generate_n(
+    gen: &LayoutGeneration,
+    amount: usize,
+    criteria: &StopCriteria,
+) -> Vec<FastLayout> {
     if amount == 0 {
         return Vec::new();
     }
 
     let start = std::time::Instant::now();
 
-    let pb = ProgressBar::new(amount as u64);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("[{elapsed_precise}] [{wide_bar:.white/white}] [eta: {eta:>3}] - {per_sec:>11} {pos:>6}/{len}")
-        .expect("couldn't initialize the progress bar template")
-        .progress_chars("=>-"));
-
-    let mut layouts = gen
-        .generate_n_iter(amount)
-        .progress_with(pb)
-        .collect::<Vec<_>>();
+    let mut layouts = generate_with_progress(gen, amount, criteria);
 
     println!(
         "optimizing {} variants took: {} seconds",
-        amount,
+        layouts.len(),
         start.elapsed().as_secs()
     );
 
-    layouts.sort_by(|l1, l2| l2.score.partial_cmp(&l1.score).unwrap());
+    best_unique(&mut layouts);
 
     for (i, layout) in layouts.iter().enumerate().take(10) {
-        let printable = heatmap_string(&gen.data, layout);
-        println!("#{}, score: {:.5}\n{}", i, layout.score, printable);
+        println!("{}", generated_string(gen, i, layout));
+    }
+    print_language_results(gen, &layouts[..layouts.len().min(10)]);
+
+    layouts
+}
+
+/// The stats of 2 layouts next to each other, like `compare` shows them.
+pub fn comparison_string(comparison: &Comparison) -> String {
+    let [s1, s2] = &comparison.stats;
+    let (ts1, ts2) = (&s1.trigram_stats, &s2.trigram_stats);
+    format!(
+        concat!(
+            "Sfb:               {: <11} Sfb:               {:.3}%\n",
+            "Sfb Distance:      {: <11} Sfb Distance:      {:.3}\n",
+            "Dsfb:              {: <11} Dsfb:              {:.3}%\n",
+            "Dsfb2:             {: <11} Dsfb2:             {:.3}%\n",
+            "Dsfb3:             {: <11} Dsfb3:             {:.3}%\n",
+            "Finger Speed:      {: <11} Finger Speed:      {:.3}\n",
+            "Scissors           {: <11} Scissors:          {:.3}%\n",
+            "Lsbs               {: <11} Lsbs:              {:.3}%\n",
+            "Row Skips          {: <11} Row Skips:         {:.3}%\n",
+            "Travel:            {: <11} Travel:            {:.3}\n\n",
+            "Inrolls:           {: <11} Inrolls:           {:.2}%\n",
+            "Outrolls:          {: <11} Outrolls:          {:.2}%\n",
+            "Total Rolls:       {: <11} Total Rolls:       {:.2}%\n",
+            "Home Row Rolls:    {: <11} Home Row Rolls:    {:.2}%\n",
+            "Adjacent Rolls:    {: <11} Adjacent Rolls:    {:.2}%\n",
+            "Onehands:          {: <11} Onehands:          {:.3}%\n\n",
+            "Alternates:        {: <11} Alternates:        {:.2}%\n",
+            "Alternates Sfs:    {: <11} Alternates Sfs:    {:.2}%\n",
+            "Total Alternates:  {: <11} Total Alternates:  {:.2}%\n\n",
+            "Redirects:         {: <11} Redirects:         {:.3}%\n",
+            "Redirects Sfs:     {: <11} Redirects Sfs:     {:.3}%\n",
+            "Bad Redirects:     {: <11} Bad Redirects:     {:.3}%\n",
+            "Bad Redirects Sfs: {: <11} Bad Redirects Sfs: {:.3}%\n",
+            "Total Redirects:   {: <11} Total Redirects:   {:.3}%\n\n",
+            "Bad Sfbs:          {: <11} Bad Sfbs:          {:.3}%\n",
+            "Sft:               {: <11} Sft:               {:.3}%\n\n",
+            "Score:             {: <11} Score:             {:.3}\n"
+        ),
+        format!("{:.3}%", s1.sfb * 100.0),
+        s2.sfb * 100.0,
+        format!("{:.3}", s1.sfb_distance * 100.0),
+        s2.sfb_distance * 100.0,
+        format!("{:.3}%", s1.dsfb * 100.0),
+        s2.dsfb * 100.0,
+        format!("{:.3}%", s1.dsfb2 * 100.0),
+        s2.dsfb2 * 100.0,
+        format!("{:.3}%", s1.dsfb3 * 100.0),
+        s2.dsfb3 * 100.0,
+        format!("{:.3}", s1.fspeed * 10.0),
+        s2.fspeed * 10.0,
+        format!("{:.3}%", s1.scissors * 100.0),
+        s2.scissors * 100.0,
+        format!("{:.3}%", s1.lsbs * 100.0),
+        s2.lsbs * 100.0,
+        format!("{:.3}%", s1.row_skips * 100.0),
+        s2.row_skips * 100.0,
+        format!("{:.3}", s1.travel),
+        s2.travel,
+        format!("{:.2}%", ts1.inrolls * 100.0),
+        ts2.inrolls * 100.0,
+        format!("{:.2}%", ts1.outrolls * 100.0),
+        ts2.outrolls * 100.0,
+        format!("{:.2}%", (ts1.inrolls + ts1.outrolls) * 100.0),
+        (ts2.inrolls + ts2.outrolls) * 100.0,
+        format!("{:.2}%", ts1.home_row_rolls() * 100.0),
+        ts2.home_row_rolls() * 100.0,
+        format!("{:.2}%", ts1.adjacent_rolls * 100.0),
+        ts2.adjacent_rolls * 100.0,
+        format!("{:.3}%", ts1.onehands * 100.0),
+        ts2.onehands * 100.0,
+        format!("{:.2}%", ts1.alternates * 100.0),
+        ts2.alternates * 100.0,
+        format!("{:.2}%", ts1.alternates_sfs * 100.0),
+        ts2.alternates_sfs * 100.0,
+        format!("{:.2}%", (ts1.alternates + ts1.alternates_sfs) * 100.0),
+        (ts2.alternates + ts2.alternates_sfs) * 100.0,
+        format!("{:.3}%", ts1.redirects * 100.0),
+        ts2.redirects * 100.0,
+        format!("{:.3}%", ts1.redirects_sfs * 100.0),
+        ts2.redirects_sfs * 100.0,
+        format!("{:.3}%", ts1.bad_redirects * 100.0),
+        ts2.bad_redirects * 100.0,
+        format!("{:.3}%", ts1.bad_redirects_sfs * 100.0),
+        ts2.bad_redirects_sfs * 100.0,
+        format!(
+            "{:.3}%",
+            (ts1.redirects + ts1.redirects_sfs + ts1.bad_redirects + ts1.bad_redirects_sfs) * 100.0
+        ),
+        (ts2.redirects + ts2.redirects_sfs + ts2.bad_redirects + ts2.bad_redirects_sfs) * 100.0,
+        format!("{:.3}%", ts1.bad_sfbs * 100.0),
+        ts2.bad_sfbs * 100.0,
+        format!("{:.3}%", ts1.sfts * 100.0),
+        ts2.sfts * 100.0,
+        format!("{:.3}", comparison.scores[0]),
+        comparison.scores[1]
+    )
+}
+
+/// What switching layouts gains and how much has to be relearned for it.
+pub fn retraining_string(from_name: &str, to_name: &str, comparison: &Comparison) -> String {
+    let cost = &comparison.retraining;
+    format!(
+        "from {from_name} to {to_name}: score {:+.3}, relearning {:.2}% of keystrokes\n\
+        {} keys move, {} of them stay on the same finger and {} on the same hand, which count for \
+        less",
+        comparison.score_gain(),
+        cost.cost * 100.0,
+        cost.moved,
+        cost.same_finger,
+        cost.same_hand
+    )
+}
+
+/// When `gen` mixes several languages, how each of `layouts` scores and ranks on every language
+/// alone. Layouts that rank well for the mix but badly for a language are marked with a `!`.
+pub fn print_language_results(gen: &LayoutGeneration, layouts: &[FastLayout]) {
+    if gen.languages.is_empty() {
+        return;
     }
 
+    let results = gen.language_results(layouts);
+    println!(
+        "\nper language, with the rank among these {} layouts:",
+        layouts.len()
+    );
+    for (i, results) in results.iter().enumerate() {
+        let scores = results
+            .iter()
+            .map(|r| {
+                let mark = if r.lopsided { " !" } else { "" };
+                format!(
+                    "{} {:.5} (#{}, sfb {:.3}%){mark}",
+                    r.language,
+                    r.score,
+                    r.rank,
+                    r.stats.sfb * 100.0
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("#{i}: {scores}");
+    }
+    if results.iter().flatten().any(|r| r.lopsided) {
+        println!("! good for the mix, but among the worst of these for that language");
+    }
+}
+
+/// Generates `amount` layouts while showing a progress bar, in the order they finished. When
+/// `criteria` stop it before that, it says why.
+pub fn generate_with_progress(
+    gen: &LayoutGeneration,
+    amount: usize,
+    criteria: &StopCriteria,
+) -> Vec<FastLayout> {
+    if !criteria.is_empty() {
+        return generate_until_with_progress(gen, amount, criteria);
+    }
+
+    let pb = ProgressBar::new(amount as u64);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("[{elapsed_precise}] [{wide_bar:.white/white}] [eta: {eta:>3}] - {per_sec:>11} {pos:>6}/{len}")
+        .expect("couldn't initialize the progress bar template")
+        .progress_chars("=>-"));
+
+    if gen.islands > 1 {
+        let layouts = gen.generate_islands(amount, || pb.inc(1));
+        pb.finish();
+        layouts
+    } else {
+        gen.generate_n_iter(amount)
+            .progress_with(pb)
+            .collect::<Vec<_>>()
+    }
+}
+
+/// With a time limit, the progress bar fills up over that time and shows how much of it is left.
+/// Otherwise a spinner shows how many layouts are done.
+fn generate_until_with_progress(
+    gen: &LayoutGeneration,
+    amount: usize,
+    criteria: &StopCriteria,
+) -> Vec<FastLayout> {
+    let progress = GenerationProgress::new(*criteria);
+    let pb = match criteria.max_time {
+        Some(max_time) => {
+            let pb = ProgressBar::new(max_time.as_millis() as u64);
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("[{elapsed_precise}] [{wide_bar:.white/white}] {msg}")
+                    .expect("couldn't initialize the progress bar template")
+                    .progress_chars("=>-"),
+            );
+            pb
+        }
+        None => {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(
+                ProgressStyle::default_spinner()
+                    .template("[{elapsed_precise}] {spinner} {msg}")
+                    .expect("couldn't initialize the progress bar template"),
+            );
+            pb
+        }
+    };
+
+    let layouts = gen.generate_until(amount, &progress, || {
+        let best = progress.best_score().unwrap_or(f64::NAN);
+        let done = format!("{} layouts, best score {best:.5}", progress.done());
+        match progress.remaining() {
+            Some(remaining) => {
+                pb.set_position(progress.elapsed().as_millis() as u64);
+                pb.set_message(format!("{}s left - {done}", remaining.as_secs()));
+            }
+            None => {
+                pb.set_message(done);
+                pb.tick();
+            }
+        }
+    });
+    pb.finish_and_clear();
+
+    if let Some(reason) = progress.stop_reason() {
+        println!("stopped after {} layouts: {reason}", layouts.len());
+    }
     layouts
 }
 